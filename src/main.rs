@@ -37,9 +37,21 @@ use crate::{
         },
         network::{
             server as ManifoldNetworkServer,
-            worker::Worker as ManifoldNetworkWorker,
+            worker::{
+                Worker as ManifoldNetworkWorker,
+                WorkerCaches,
+            },
+            connection_manager as ManifoldConnectionManager,
+            request::RequestTracker,
+            peer_score::PeerScores,
+            emulation::EmulationConfig,
+            identity::NodeIdentity,
+            stats::NetworkStats,
+            capture::{self, CaptureWriter},
         },
         api::Server as ManifoldApiServer,
+        light_client::LightClient,
+        swap::SwapWallet,
         miner::{
             self as ManifoldMiner,
             worker::Worker as ManifoldMinerWorker,
@@ -49,7 +61,17 @@ use crate::{
         confirmation::Confirmation,
         verifier::{
             self as Verifier,
-        }
+        },
+        fraudproof::bundle::FraudProofBundle,
+        validator::stats::ValidationStats,
+        audit::{ShardLedger, AuditReport},
+        sharding::{
+            ShardMap,
+            ShardLoad,
+            TablePolicy,
+            reshard::{ReshardPlan, StateHandoff, Checkpoint},
+            rotation::Rotation,
+        },
     },
     types::hash::{H256},
 };
@@ -70,6 +92,7 @@ use std::{
     sync::{Arc, Mutex},
     num::ParseIntError,
     convert::TryInto,
+    collections::HashMap,
 };
 use env_logger::Env;
 
@@ -253,6 +276,18 @@ fn run_manifoldchain() {
     (@arg shard_num:
             --shardNum [INT]
             "Sets the number of shards")
+    (@arg shard_policy:
+            --shardPolicy [SPEC]
+            "Sets the policy mapping the addresses to the shards: modulo, consistent, ranges:PREFIX=SHARD,... or table:FILE")
+    (@arg reshard:
+            --reshard [SPEC]
+            "Ends the epoch at a height, then the network restarts with a number of shards: HEIGHT:SHARDS[:POLICY]")
+    (@arg checkpoint:
+            --checkpoint [DIR]
+            "Starts the next epoch from the checkpoints of the directory, the shard of the node is reassigned")
+    (@arg rotate:
+            --rotate [EPOCH_LEN]
+            "Reassigns the nodes to the shards every EPOCH_LEN blocks by the inclusive-block beacon")
     (@arg shard_size:
             --shardSize [INT]
             "Sets the size of shards")
@@ -268,9 +303,60 @@ fn run_manifoldchain() {
     (@arg inclusive_diff:
             --iDiff [STR]
             "Sets the difficulty of mining an inclusive block")
+    (@arg shard_diffs:
+            --shardDiffs [STR]
+            "Sets the difficulties of mining a block in every shard, separated by commas")
     (@arg domestic_ratio:
             --domesticRatio [FLOAT]
             "The ratio of the domestic txs")
+    (@arg target_peers:
+            --targetPeers [INT]
+            "Sets the number of peers to keep within the own shard")
+    (@arg min_foreign_peers:
+            --minForeignPeers [INT]
+            "Sets the minimal number of peers to keep in every other shard")
+    (@arg full_blocks:
+            --fullBlocks
+            "Relays full blocks instead of compact blocks")
+    (@arg network_delay:
+            --networkDelay [INT]
+            "Sets the propagation delay (us) of every connection")
+    (@arg emulation:
+            --emulation [FILE]
+            "Emulates the delays, bandwidths and drop rates in the config file of an experiment")
+    (@arg identity:
+            --identity [FILE]
+            "Sets the file of the identity key, which is generated if it does not exist")
+    (@arg capture:
+            --capture [FILE]
+            "Writes every inbound and outbound P2P frame to the capture log")
+    (@arg replay:
+            --replay [FILE]
+            "Feeds the inbound frames of a capture log to a single network worker offline, then exits")
+    (@arg light:
+            --light [SHARDS]
+            "Runs a light client following the headers of the comma-separated shards, the own shard by default")
+    (@arg fraud_proof_dir:
+            --fraudProofDir [DIR]
+            "Exports every generated or accepted fraud proof with the headers it depends on into the directory")
+    (@arg verify_threads:
+            --verifyThreads [INT]
+            "Sets the number of threads checking the transactions of a block, one per cpu by default")
+    (@subcommand verify =>
+            (about: "Verifies an exported fraud proof offline, without running a node")
+            (@arg file: +required "Sets the file of the exported fraud proof"))
+    (@subcommand audit =>
+            (about: "Audits the conservation of the coins across the shards served by the nodes")
+            (@arg api_addr: +required +multiple "Sets the API addresses of the nodes, one per shard at least"))
+    (@subcommand shard_table =>
+            (about: "Clusters the addresses trading together into the same shard, and writes the assignment table")
+            (@arg trades: +required "Sets the file of the trades, a json list of [sender, receiver, count]")
+            (@arg shard_num: +required "Sets the number of shards")
+            (@arg table: +required "Sets the file the table is written to"))
+    (@subcommand reshard =>
+            (about: "Builds the checkpoints of the next epoch from the states of the shards at the end of the epoch")
+            (@arg dir: +required "Sets the directory the checkpoints are written to")
+            (@arg api_addr: +required +multiple "Sets the API addresses of the nodes, one per shard at least"))
     )
     .get_matches();
 
@@ -279,6 +365,118 @@ fn run_manifoldchain() {
     //let verbosity = matches.occurrences_of("verbose") as usize;
     //stderrlog::new().verbosity(verbosity).init().unwrap();
 
+    // verify an exported fraud proof and exit, the exit code tells whether it holds
+    if let Some(verify_matches) = matches.subcommand_matches("verify") {
+        let path = verify_matches.value_of("file").unwrap();
+        let bundle = FraudProofBundle::load(path)
+            .unwrap_or_else(|e| {
+                error!("Error loading the fraud proof: {}", e);
+                process::exit(1);
+            });
+        let block_hash = bundle.fraud_proof.get_invalid_block();
+        match bundle.verify() {
+            Ok(true) => {
+                println!("valid fraud proof against block {} of shard {}",
+                    block_hash, bundle.fraud_proof.get_shard_id());
+                process::exit(0);
+            }
+            Ok(false) => {
+                println!("invalid fraud proof against block {} of shard {}",
+                    block_hash, bundle.fraud_proof.get_shard_id());
+                process::exit(2);
+            }
+            Err(e) => {
+                error!("Error verifying the fraud proof: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // audit the supply of the shards served by the nodes and exit, the exit code tells
+    // whether a discrepancy was found
+    if let Some(audit_matches) = matches.subcommand_matches("audit") {
+        let mut ledgers: Vec<ShardLedger> = vec![];
+        for api_addr in audit_matches.values_of("api_addr").unwrap() {
+            match ShardLedger::fetch(api_addr) {
+                Ok(node_ledgers) => ledgers.extend(node_ledgers),
+                Err(e) => {
+                    error!("Error fetching the ledgers: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        let report = AuditReport::audit(&ShardLedger::merge(ledgers));
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        match report.is_clean() {
+            true => process::exit(0),
+            false => process::exit(2),
+        }
+    }
+
+    // build a locality-aware table from the trades and exit, reporting the cross-shard
+    // load of the trades with and without it
+    if let Some(table_matches) = matches.subcommand_matches("shard_table") {
+        let trades = ShardLoad::load_trades(table_matches.value_of("trades").unwrap())
+            .unwrap_or_else(|e| {
+                error!("Error loading the trades: {}", e);
+                process::exit(1);
+            });
+        let shard_num = table_matches
+            .value_of("shard_num")
+            .unwrap()
+            .parse::<usize>()
+            .ok()
+            .filter(|shard_num| *shard_num > 0)
+            .unwrap_or_else(|| {
+                error!("Error parsing the number of shards");
+                process::exit(1);
+            });
+        let path = table_matches.value_of("table").unwrap();
+        let table = TablePolicy::cluster(&trades, shard_num);
+        if let Err(e) = TablePolicy::save(&table, path) {
+            error!("Error saving the table: {}", e);
+            process::exit(1);
+        }
+        let mut config = ManifoldConfiguration::new();
+        config.shard_num = shard_num;
+        for spec in [String::from("modulo"), format!("table:{}", path)] {
+            config.shard_map = ShardMap::from_spec(&spec, shard_num).unwrap();
+            let load = ShardLoad::from_trades(&trades, &config);
+            println!("{}: {} domestic, {} cross-shard, domestic ratio {:.3}, txs per shard {:?}",
+                spec, load.domestic, load.cross, load.domestic_ratio, load.shard_txs);
+        }
+        process::exit(0);
+    }
+
+    // build the checkpoints of the next epoch from the handoffs of the shards and exit
+    if let Some(reshard_matches) = matches.subcommand_matches("reshard") {
+        let mut handoffs: Vec<StateHandoff> = vec![];
+        for api_addr in reshard_matches.values_of("api_addr").unwrap() {
+            match StateHandoff::fetch(api_addr) {
+                Ok(handoff) => handoffs.push(handoff),
+                Err(e) => {
+                    error!("Error fetching the handoff: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        let checkpoints = Checkpoint::build(&handoffs)
+            .unwrap_or_else(|e| {
+                error!("Error building the checkpoints: {}", e);
+                process::exit(1);
+            });
+        let dir = reshard_matches.value_of("dir").unwrap();
+        if let Err(e) = Checkpoint::save(&checkpoints, dir) {
+            error!("Error saving the checkpoints: {}", e);
+            process::exit(1);
+        }
+        for checkpoint in checkpoints.iter() {
+            println!("shard {}: {} utxos, state root {}",
+                checkpoint.shard_id, checkpoint.utxos.len(), checkpoint.state_root);
+        }
+        process::exit(0);
+    }
+
     // parse p2p server address
     let p2p_addr = matches
         .value_of("peer_addr")
@@ -392,14 +590,148 @@ fn run_manifoldchain() {
     let inclusive_diff_hash: H256 = inclusive_diff_bytes.into();
     config.difficulty = exclusive_diff_hash;
     config.thredshold = inclusive_diff_hash;
-    config.shard_id = shard_id as usize;
+    config.set_shard_id(shard_id as usize);
     config.node_id = node_id as usize;
     config.exper_number = exper_number as usize;
     config.shard_num = shard_num as usize;
+    //the parents in other shards are checked against the difficulties of their shards
+    if let Some(diffs) = matches.value_of("shard_diffs") {
+        for diff in diffs.split(',') {
+            let diff_bytes: [u8; 32] = match decode_hex(diff) {
+                Ok(bytes) if bytes.len() == 32 => bytes.try_into().unwrap(),
+                _ => {
+                    error!("Error parsing the shard difficulty {}", diff);
+                    process::exit(1);
+                }
+            };
+            config.shard_diffs.push(diff_bytes.into());
+        }
+        if config.shard_diffs.len() != config.shard_num ||
+            config.shard_diffs[shard_id as usize] != config.difficulty {
+            error!("The shard difficulties do not match the number of shards or eDiff");
+            process::exit(1);
+        }
+    }
+    if let Some(spec) = matches.value_of("shard_policy") {
+        config.shard_map = ShardMap::from_spec(spec, config.shard_num)
+            .unwrap_or_else(|e| {
+                error!("Error parsing the shard policy: {}", e);
+                process::exit(1);
+            });
+    }
     config.shard_size = shard_size as usize;
     config.block_size = block_size as usize;
     config.k = confirmation_depth as usize;
+    //the own shard of the next epoch starts from the state of its checkpoint
+    let initial_state = match matches.value_of("checkpoint") {
+        Some(dir) => {
+            let checkpoints = Checkpoint::load_all(dir)
+                .unwrap_or_else(|e| {
+                    error!("Error loading the checkpoints: {}", e);
+                    process::exit(1);
+                });
+            let state = Checkpoint::apply(&checkpoints, &mut config)
+                .unwrap_or_else(|e| {
+                    error!("Error applying the checkpoints: {}", e);
+                    process::exit(1);
+                });
+            info!("Node moved to shard {} of {} by the checkpoints", config.shard_id(), config.shard_num);
+            state
+        }
+        None => HashMap::new(),
+    };
+    if let Some(spec) = matches.value_of("reshard") {
+        let plan = ReshardPlan::from_spec(spec, &config)
+            .unwrap_or_else(|e| {
+                error!("Error parsing the resharding: {}", e);
+                process::exit(1);
+            });
+        config.reshard = Some(plan);
+    }
+    if let Some(epoch_len) = matches.value_of("rotate") {
+        config.rotation_epoch = epoch_len
+            .parse::<usize>()
+            .unwrap_or_else(|e| {
+                error!("Error parsing the rotation epoch: {}", e);
+                process::exit(1);
+            });
+        //the beacon deals the shard_num * shard_size node ids over the shards
+        if config.node_id >= config.shard_num * config.shard_size {
+            error!("The node id {} is out of the {} nodes of the rotation",
+                config.node_id, config.shard_num * config.shard_size);
+            process::exit(1);
+        }
+    }
     config.domestic_tx_ratio = domestic_ratio as f64;
+    if let Some(target_peers) = matches.value_of("target_peers") {
+        config.target_peers = target_peers
+            .parse::<usize>()
+            .unwrap_or_else(|e| {
+                error!("Error parsing the target peers: {}", e);
+                process::exit(1);
+            });
+    }
+    if let Some(min_foreign_peers) = matches.value_of("min_foreign_peers") {
+        config.min_foreign_peers = min_foreign_peers
+            .parse::<usize>()
+            .unwrap_or_else(|e| {
+                error!("Error parsing the minimal foreign peers: {}", e);
+                process::exit(1);
+            });
+    }
+    if let Some(verify_threads) = matches.value_of("verify_threads") {
+        config.verify_threads = verify_threads
+            .parse::<usize>()
+            .unwrap_or_else(|e| {
+                error!("Error parsing the verify threads: {}", e);
+                process::exit(1);
+            });
+    }
+    if matches.is_present("full_blocks") {
+        config.compact_blocks = false;
+    }
+    if let Some(network_delay) = matches.value_of("network_delay") {
+        config.network_delay = network_delay
+            .parse::<usize>()
+            .unwrap_or_else(|e| {
+                error!("Error parsing the network delay: {}", e);
+                process::exit(1);
+            });
+    }
+    if let Some(path) = matches.value_of("emulation") {
+        let emulation = EmulationConfig::load(path)
+            .unwrap_or_else(|e| {
+                error!("Error loading the network emulation: {}", e);
+                process::exit(1);
+            });
+        config.emulation = Some(emulation);
+    }
+    if matches.is_present("light") {
+        config.light_client = true;
+        config.light_shards = match matches.value_of("light") {
+            Some(shards) => shards
+                .split(',')
+                .map(|shard| shard.trim().parse::<usize>())
+                .collect::<Result<Vec<usize>, ParseIntError>>()
+                .unwrap_or_else(|e| {
+                    error!("Error parsing the light client shards: {}", e);
+                    process::exit(1);
+                }),
+            None => vec![config.shard_id()],
+        };
+        if let Some(shard) = config.light_shards.iter().find(|shard| **shard >= config.shard_num) {
+            error!("Error parsing the light client shards: no shard {}", shard);
+            process::exit(1);
+        }
+    }
+    if let Some(dir) = matches.value_of("fraud_proof_dir") {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|e| {
+                error!("Error creating the fraud proof directory: {}", e);
+                process::exit(1);
+            });
+        config.fraud_proof_dir = Some(dir.to_string());
+    }
     let shard_id = format!("{:x}", shard_id);
     info!("configuration: {:?}", config);
 
@@ -407,7 +739,10 @@ fn run_manifoldchain() {
     let chains: Vec<Arc<Mutex<ManifoldBlockchain>>> = (0..config.shard_num)
         .into_iter()
         .map(|i| {
-            let blockchain = ManifoldBlockchain::new(&config, i);
+            let blockchain = match i == config.shard_id() {
+                true => ManifoldBlockchain::with_state(&config, i, initial_state.clone()),
+                false => ManifoldBlockchain::new(&config, i),
+            };
             Arc::new(Mutex::new(blockchain))
         })
         .collect();
@@ -431,9 +766,47 @@ fn run_manifoldchain() {
 
     let tx_generator_handle = ManifoldGenerator::new_handle(&tx_generator_sender);
 
+    // load the ban list, the misbehaving peers are scored and banned by the server
+    let peer_scores = PeerScores::new(&config);
+
+    // load the identity key, which authenticates this node to its peers
+    let identity_path = matches
+        .value_of("identity")
+        .map(|path| path.to_string())
+        .unwrap_or(format!("./keys/exper_{}/node_{}.pk8", config.exper_number, config.node_id));
+    let identity = NodeIdentity::load_or_generate(&identity_path)
+        .unwrap_or_else(|e| {
+            error!("Error loading the identity key: {}", e);
+            process::exit(1);
+        });
+    info!("Node identity {}", identity.peer_id());
+
+    // the traffic statistics, every frame is also written to the capture log if any
+    let capture_writer = matches
+        .value_of("capture")
+        .map(|path| CaptureWriter::create(path)
+            .unwrap_or_else(|e| {
+                error!("Error creating the capture log: {}", e);
+                process::exit(1);
+            })
+        );
+    let network_stats = NetworkStats::new(capture_writer);
+    let replay_sink = msg_tx.clone();
+    // a replay only feeds the captured frames to the worker, there are no peers
+    let is_replay = matches.is_present("replay");
+
     // start the p2p server
-    let (server_ctx, server) = ManifoldNetworkServer::new(p2p_addr, msg_tx, &tx_generator_handle, config.shard_id).unwrap();
-    server_ctx.start().unwrap();
+    let (server_ctx, server) = ManifoldNetworkServer::new(p2p_addr, msg_tx, &tx_generator_handle, &multichain, &config, &peer_scores, &identity, &network_stats).unwrap();
+    match is_replay {
+        true => server_ctx.start_offline(),
+        false => server_ctx.start().unwrap(),
+    }
+
+    // start the request tracker, which retries the unanswered requests
+    let request_tracker = RequestTracker::new(&server, &config);
+    if !is_replay {
+        request_tracker.start();
+    }
     
     // start the worker
     let p2p_workers = matches
@@ -444,6 +817,28 @@ fn run_manifoldchain() {
             error!("Error parsing P2P workers: {}", e);
             process::exit(1);
         });
+    // a replay is handled by one worker so that the frames keep their order
+    let p2p_workers = match is_replay {
+        true => 1,
+        false => p2p_workers,
+    };
+    // the blocks and txs rejected by the workers and the miner
+    let validation_stats = ValidationStats::new();
+    // the bounded caches of the workers, the orphans are retried by the cache monitor
+    let worker_caches = WorkerCaches::new(&config, &request_tracker);
+    if !is_replay {
+        worker_caches.start();
+    }
+    // a light client follows the headers only, the blocks are left to the full nodes
+    let light_client = match config.light_client {
+        true => Some(LightClient::new(&config, &request_tracker)),
+        false => None,
+    };
+    // with the committee rotation, the node moves to the shard drawn every epoch
+    let rotation = match config.rotation_epoch > 0 && !config.light_client {
+        true => Some(Rotation::new(&multichain, &mempool, &server, &request_tracker, &config)),
+        false => None,
+    };
     let worker_ctx = ManifoldNetworkWorker::new(
         p2p_workers,
         msg_rx,
@@ -452,11 +847,33 @@ fn run_manifoldchain() {
         &mempool,
         &config,
         &confirmation,
+        &request_tracker,
+        &worker_caches,
+        &network_stats,
+        &validation_stats,
+        light_client.as_ref(),
+        rotation.as_ref(),
     );
     worker_ctx.start();
 
+    // replay the capture log without any peer, and print the statistics of the replay
+    if let Some(path) = matches.value_of("replay") {
+        let fed = capture::replay(path, &replay_sink)
+            .unwrap_or_else(|e| {
+                error!("Error replaying the capture log: {}", e);
+                process::exit(1);
+            });
+        while !replay_sink.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        info!("Replayed {} frames from {}", fed, path);
+        println!("{}", serde_json::to_string_pretty(&network_stats.get_report()).unwrap());
+        process::exit(0);
+    }
+
     // start the miner
-    let (miner_ctx, miner, finished_block_chan) = ManifoldMiner::new(&multichain, &mempool, &config);
+    let (miner_ctx, miner, finished_block_chan) = ManifoldMiner::new(&multichain, &mempool, &config, &validation_stats);
     let miner_worker_ctx = ManifoldMinerWorker::new(
         &server, 
         finished_block_chan, 
@@ -464,47 +881,54 @@ fn run_manifoldchain() {
         &confirmation,
         &config,
     );
-    miner_ctx.start();
-    miner_worker_ctx.start();
+    if !config.light_client {
+        miner_ctx.start();
+        miner_worker_ctx.start();
+    }
+
 
+    //start the connection manager, which discovers the peers beyond the known ones
+    let (connection_manager_ctx, connection_manager) = ManifoldConnectionManager::new(
+        &server, 
+        p2p_addr, 
+        &config
+    );
+    connection_manager_ctx.start();
 
     //start the sample monitor
-    let verifier_ctx = Verifier::new(&multichain, &server, &config);
-    verifier_ctx.start();
+    let verifier_ctx = Verifier::new(&multichain, &request_tracker, &config);
+    if !config.light_client {
+        verifier_ctx.start();
+    }
+
+    //start the header sync of the light client
+    if let Some(light_client) = &light_client {
+        light_client.start();
+    }
+
+    //start the shard rotation
+    if let Some(rotation) = &rotation {
+        rotation.start();
+    }
 
     
-    // connect to known peers
+    // connect to known peers, they serve as the seeds of peer discovery and are
+    // redialed by the connection manager whenever the connection is lost
     if let Some(known_peers) = matches.values_of("known_peer") {
-        let known_peers: Vec<String> = known_peers.map(|x| x.to_owned()).collect();
-        let server = server.clone();
-        thread::spawn(move || {
-            for peer in known_peers {
-                loop {
-                    let addr = match peer.parse::<net::SocketAddr>() {
-                        Ok(x) => x,
-                        Err(e) => {
-                            error!("Error parsing peer address {}: {}", &peer, e);
-                            break;
-                        }
-                    };
-                    match server.connect(addr) {
-                        Ok(_) => {
-                            info!("Connected to outgoing peer {}", &addr);
-                            break;
-                        }
-                        Err(e) => {
-                            error!(
-                                "Error connecting to peer {}, retrying in one second: {}",
-                                addr, e
-                            );
-                            thread::sleep(time::Duration::from_millis(1000));
-                            continue;
-                        }
-                    }
+        for peer in known_peers {
+            match peer.parse::<net::SocketAddr>() {
+                Ok(addr) => connection_manager.add_persistent(addr),
+                Err(e) => {
+                    error!("Error parsing peer address {}: {}", &peer, e);
                 }
             }
-        });
+        }
+    }
 
+    //start the swap wallet, which claims and refunds the swaps of its user
+    let swap_wallet = SwapWallet::new(&multichain, &mempool, &server, &config);
+    if !config.light_client {
+        swap_wallet.start();
     }
 
     //start the transaction generator
@@ -515,13 +939,24 @@ fn run_manifoldchain() {
         &config, 
         api_port
     );
-    generator_ctx.start();
+    if !config.light_client {
+        generator_ctx.start();
+    }
 
     // start the API server
     ManifoldApiServer::start(
         api_addr,
         &miner,
         &server,
+        &connection_manager,
+        &request_tracker,
+        &peer_scores,
+        &worker_caches,
+        &network_stats,
+        &validation_stats,
+        &light_client,
+        &rotation,
+        &swap_wallet,
         &multichain,
         &tx_generator_handle,
         &mempool,
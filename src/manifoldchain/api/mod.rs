@@ -2,10 +2,25 @@ use serde::Serialize;
 use crate::{
     manifoldchain::{
         multichain::Multichain,
+        blockchain::Blockchain,
         miner::Handle as MinerHandle,
         network::{
             server::Handle as NetworkServerHandle,
             message::Message,
+            server::PeerStatus as NetworkPeerStatus,
+            connection_manager::{
+                Handle as ConnectionManagerHandle,
+                PersistentPeerStatus,
+            },
+            request::RequestTracker,
+            peer_score::{
+                PeerScores,
+                BanEntry,
+                PeerScoreStatus,
+            },
+            worker::WorkerCaches,
+            stats::NetworkStats,
+            identity::parse_peer_id,
         },
         transaction::{
             generator::Handle as GeneratorHandle,
@@ -16,14 +31,22 @@ use crate::{
         validator::{
             Validator,
             CrossUtxoStatus,
+            stats::ValidationStats,
         },
         configuration::Configuration,
+        light_client::LightClient,
+        fraudproof::bundle::FraudProofBundle,
+        audit::{ShardLedger, AuditReport},
+        sharding::{ShardLoad, reshard::StateHandoff, rotation::Rotation},
+        swap::{SwapWallet, SwapRole},
     },
     types::{
         hash::{
             H256,
             Hashable,
-        }
+        },
+        sparse_merkle::SparseMerkleProof,
+        address::Address,
     },
 };
 
@@ -44,6 +67,15 @@ pub struct Server {
     handle: HTTPServer,
     miner: MinerHandle,
     network: NetworkServerHandle,
+    connection_manager: ConnectionManagerHandle,
+    requests: RequestTracker,
+    peer_scores: PeerScores,
+    worker_caches: WorkerCaches,
+    network_stats: NetworkStats,
+    validation_stats: ValidationStats,
+    light_client: Option<LightClient>,
+    rotation: Option<Rotation>,
+    swap_wallet: SwapWallet,
     multichain: Multichain,
     generator: GeneratorHandle,
     mempool: Arc<Mutex<Mempool>>,
@@ -56,6 +88,27 @@ struct ApiResponse {
     message: String,
 }
 
+#[derive(Serialize)]
+struct PeerList {
+    connected: Vec<NetworkPeerStatus>,
+    persistent: Vec<PersistentPeerStatus>,
+}
+
+#[derive(Serialize)]
+struct UtxoProof {
+    block_hash: H256, //the block whose state root the proof is against
+    state_root: H256,
+    key: H256,
+    value: Option<H256>, //none if the utxo is not in the state
+    proof: SparseMerkleProof,
+}
+
+#[derive(Serialize)]
+struct BanList {
+    bans: Vec<BanEntry>,
+    scores: Vec<PeerScoreStatus>,
+}
+
 macro_rules! respond_result {
     ( $req:expr, $success:expr, $message:expr ) => {{
         let content_type = "Content-Type: application/json".parse::<Header>().unwrap();
@@ -82,6 +135,15 @@ impl Server {
         addr: std::net::SocketAddr,
         miner: &MinerHandle,
         network: &NetworkServerHandle,
+        connection_manager: &ConnectionManagerHandle,
+        requests: &RequestTracker,
+        peer_scores: &PeerScores,
+        worker_caches: &WorkerCaches,
+        network_stats: &NetworkStats,
+        validation_stats: &ValidationStats,
+        light_client: &Option<LightClient>,
+        rotation: &Option<Rotation>,
+        swap_wallet: &SwapWallet,
         multichain: &Multichain,
         generator: &GeneratorHandle,
         mempool: &Arc<Mutex<Mempool>>,
@@ -92,6 +154,15 @@ impl Server {
             handle,
             miner: miner.clone(),
             network: network.clone(),
+            connection_manager: connection_manager.clone(),
+            requests: requests.clone(),
+            peer_scores: peer_scores.clone(),
+            worker_caches: worker_caches.clone(),
+            network_stats: network_stats.clone(),
+            validation_stats: validation_stats.clone(),
+            light_client: light_client.clone(),
+            rotation: rotation.clone(),
+            swap_wallet: swap_wallet.clone(),
             multichain: multichain.clone(),
             generator: generator.clone(),
             mempool: Arc::clone(mempool),
@@ -103,6 +174,15 @@ impl Server {
                 for req in server.handle.incoming_requests() {
                     let miner = server.miner.clone();
                     let network = server.network.clone();
+                    let connection_manager = server.connection_manager.clone();
+                    let requests = server.requests.clone();
+                    let peer_scores = server.peer_scores.clone();
+                    let worker_caches = server.worker_caches.clone();
+                    let network_stats = server.network_stats.clone();
+                    let validation_stats = server.validation_stats.clone();
+                    let light_client = server.light_client.clone();
+                    let rotation = server.rotation.clone();
+                    let swap_wallet = server.swap_wallet.clone();
                     let multichain = server.multichain.clone();
                     let generator = server.generator.clone();
                     let mempool = Arc::clone(&server.mempool);
@@ -188,8 +268,256 @@ impl Server {
                                 network.broadcast(Message::Ping(String::from("Test ping")));
                                 respond_result!(req, true, "ok");
                             }
+                            "/network/peers" => {
+                                let peers = PeerList {
+                                    connected: network.get_peer_status(),
+                                    persistent: connection_manager.get_persistent_peers(),
+                                };
+                                respond_json!(req, peers);
+                            }
+                            "/network/requests" => {
+                                respond_json!(req, requests.get_stats());
+                            }
+                            "/network/caches" => {
+                                respond_json!(req, worker_caches.get_stats());
+                            }
+                            "/network/stats" => {
+                                respond_json!(req, network_stats.get_report());
+                            }
+                            "/validation/errors" => {
+                                respond_json!(req, validation_stats.get_report());
+                            }
+                            "/light/status" => {
+                                match &light_client {
+                                    Some(light_client) => {
+                                        respond_json!(req, light_client.get_status());
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "not a light client");
+                                    }
+                                }
+                            }
+                            "/light/payment" => {
+                                let light_client = match &light_client {
+                                    Some(light_client) => light_client,
+                                    None => {
+                                        respond_result!(req, false, "not a light client");
+                                        return;
+                                    }
+                                };
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let tx_hash = match params.get("tx").map(|tx| hex::decode(tx)) {
+                                    Some(Ok(bytes)) if bytes.len() == 32 => {
+                                        let bytes: [u8; 32] = bytes.try_into().unwrap();
+                                        H256::from(bytes)
+                                    }
+                                    Some(_) => {
+                                        respond_result!(req, false, "error parsing tx");
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing tx");
+                                        return;
+                                    }
+                                };
+                                //a verified payment is served from the synced headers,
+                                //otherwise its proof is requested from the shard
+                                if let Some(status) = light_client.get_payment(&tx_hash) {
+                                    respond_json!(req, status);
+                                    return;
+                                }
+                                let shard_id = match params.get("shard").map(|v| v.parse::<usize>()) {
+                                    Some(Ok(shard_id)) if config.light_shards.contains(&shard_id) => shard_id,
+                                    Some(_) => {
+                                        respond_result!(req, false, "shard not followed");
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing shard");
+                                        return;
+                                    }
+                                };
+                                light_client.request_tx_proof(tx_hash, shard_id);
+                                respond_result!(req, false, "proof requested, try again later");
+                            }
+                            "/network/connect" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let addr = match params.get("addr") {
+                                    Some(v) => v,
+                                    None => {
+                                        respond_result!(req, false, "missing addr");
+                                        return;
+                                    }
+                                };
+                                let addr = match addr.parse::<std::net::SocketAddr>() {
+                                    Ok(v) => v,
+                                    Err(e) => {
+                                        respond_result!(
+                                            req,
+                                            false,
+                                            format!("error parsing addr: {}", e)
+                                        );
+                                        return;
+                                    }
+                                };
+                                match connection_manager.connect(addr) {
+                                    Ok(_) => {
+                                        respond_result!(req, true, "ok");
+                                    }
+                                    Err(e) => {
+                                        respond_result!(
+                                            req,
+                                            false,
+                                            format!("error connecting to {}, will retry: {}", addr, e)
+                                        );
+                                    }
+                                }
+                            }
+                            "/network/disconnect" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let addr = match params.get("addr") {
+                                    Some(v) => v,
+                                    None => {
+                                        respond_result!(req, false, "missing addr");
+                                        return;
+                                    }
+                                };
+                                let addr = match addr.parse::<std::net::SocketAddr>() {
+                                    Ok(v) => v,
+                                    Err(e) => {
+                                        respond_result!(
+                                            req,
+                                            false,
+                                            format!("error parsing addr: {}", e)
+                                        );
+                                        return;
+                                    }
+                                };
+                                if connection_manager.disconnect(addr) {
+                                    respond_result!(req, true, "ok");
+                                } else {
+                                    respond_result!(req, false, "peer not connected");
+                                }
+                            }
+                            "/network/bans" => {
+                                let bans = BanList {
+                                    bans: peer_scores.get_bans(),
+                                    scores: peer_scores.get_scores(),
+                                };
+                                respond_json!(req, bans);
+                            }
+                            "/network/ban" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                //a peer is banned by its identity, an addr is resolved through
+                                //the connected peers
+                                let peers = network.get_peer_status();
+                                let (identity, addr) = match (params.get("identity"), params.get("addr")) {
+                                    (Some(identity), _) => match parse_peer_id(identity) {
+                                        Ok(identity) => {
+                                            let addr = peers
+                                                .iter()
+                                                .find(|peer| peer.identity == identity)
+                                                .map(|peer| peer.listen_addr)
+                                                .unwrap_or_else(|| std::net::SocketAddr::from(([0, 0, 0, 0], 0)));
+                                            (identity, addr)
+                                        }
+                                        Err(e) => {
+                                            respond_result!(
+                                                req,
+                                                false,
+                                                format!("error parsing identity: {}", e)
+                                            );
+                                            return;
+                                        }
+                                    },
+                                    (None, Some(addr)) => match addr.parse::<std::net::SocketAddr>() {
+                                        Ok(addr) => match peers
+                                            .iter()
+                                            .find(|peer| peer.addr == addr || peer.listen_addr == addr)
+                                        {
+                                            Some(peer) => (peer.identity, peer.listen_addr),
+                                            None => {
+                                                respond_result!(
+                                                    req,
+                                                    false,
+                                                    "peer not connected, ban it by identity"
+                                                );
+                                                return;
+                                            }
+                                        },
+                                        Err(e) => {
+                                            respond_result!(
+                                                req,
+                                                false,
+                                                format!("error parsing addr: {}", e)
+                                            );
+                                            return;
+                                        }
+                                    },
+                                    (None, None) => {
+                                        respond_result!(req, false, "missing identity or addr");
+                                        return;
+                                    }
+                                };
+                                let permanent = match params.get("permanent") {
+                                    Some(v) => v == "true",
+                                    None => false,
+                                };
+                                peer_scores.ban(identity, addr, String::from("banned by api"), permanent);
+                                for peer in peers.iter().filter(|peer| peer.identity == identity) {
+                                    connection_manager.disconnect(peer.listen_addr);
+                                }
+                                respond_result!(req, true, "ok");
+                            }
+                            "/network/unban" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let identity = match (params.get("identity"), params.get("addr")) {
+                                    (Some(identity), _) => match parse_peer_id(identity) {
+                                        Ok(identity) => identity,
+                                        Err(e) => {
+                                            respond_result!(
+                                                req,
+                                                false,
+                                                format!("error parsing identity: {}", e)
+                                            );
+                                            return;
+                                        }
+                                    },
+                                    (None, Some(addr)) => match addr.parse::<std::net::SocketAddr>() {
+                                        Ok(addr) => match peer_scores.get_banned_identity(&addr) {
+                                            Some(identity) => identity,
+                                            None => {
+                                                respond_result!(req, false, "peer not banned");
+                                                return;
+                                            }
+                                        },
+                                        Err(e) => {
+                                            respond_result!(
+                                                req,
+                                                false,
+                                                format!("error parsing addr: {}", e)
+                                            );
+                                            return;
+                                        }
+                                    },
+                                    (None, None) => {
+                                        respond_result!(req, false, "missing identity or addr");
+                                        return;
+                                    }
+                                };
+                                if peer_scores.unban(&identity) {
+                                    respond_result!(req, true, "ok");
+                                } else {
+                                    respond_result!(req, false, "peer not banned");
+                                }
+                            }
                             "/blockchain/log" => {
-                                multichain.log_to_file_with_shard(config.shard_id);
+                                multichain.log_to_file_with_shard(config.shard_id());
                                 respond_result!(req, true, "ok");
                             }
                             "/blockchain/longest-chain" => {
@@ -277,6 +605,190 @@ impl Server {
                                 // unimplemented!()
                                 respond_result!(req, false, "unimplemented!");
                             }
+                            "/blockchain/supply" => {
+                                match multichain.check_supply() {
+                                    Ok(supply) => {
+                                        respond_json!(req, supply);
+                                    }
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                    }
+                                }
+                            }
+                            "/audit/report" => {
+                                let ledgers = ShardLedger::from_multichain(&multichain);
+                                respond_json!(req, AuditReport::audit(&ledgers));
+                            }
+                            "/audit/ledgers" => {
+                                respond_json!(req, ShardLedger::from_multichain(&multichain));
+                            }
+                            "/sharding/load" => {
+                                //the txs of the own shard in the longest chain
+                                let txs: Vec<Transaction> = multichain
+                                    .all_blocks_in_longest_chain_with_shard(config.shard_id())
+                                    .iter()
+                                    .filter_map(|blk_hash| multichain.get_block_by_shard(blk_hash, config.shard_id()))
+                                    .filter_map(|block| block.get_txs_ref().cloned())
+                                    .flatten()
+                                    .collect();
+                                respond_json!(req, ShardLoad::from_txs(&txs, &config));
+                            }
+                            "/reshard/handoff" => {
+                                match StateHandoff::from_multichain(&multichain) {
+                                    Ok(handoff) => respond_json!(req, handoff),
+                                    Err(e) => respond_result!(req, false, e),
+                                }
+                            }
+                            "/rotation/status" => {
+                                match &rotation {
+                                    Some(rotation) => {
+                                        respond_json!(req, rotation.get_status());
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "the shard rotation is off");
+                                    }
+                                }
+                            }
+                            "/swap/wallet" => {
+                                respond_json!(req, swap_wallet.get_status());
+                            }
+                            "/swap/mint" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let value = match params.get("value").map(|v| v.parse::<u64>()) {
+                                    Some(Ok(value)) => value,
+                                    Some(Err(e)) => {
+                                        respond_result!(req, false, format!("error parsing value: {}", e));
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing value");
+                                        return;
+                                    }
+                                };
+                                match swap_wallet.mint(value) {
+                                    Ok(tx) => {
+                                        respond_result!(req, true, tx.hash());
+                                    }
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                    }
+                                }
+                            }
+                            "/swap/create" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let role = match params.get("role").map(|v| v.as_str()) {
+                                    Some("initiator") => SwapRole::Initiator,
+                                    Some("responder") => SwapRole::Responder,
+                                    Some(_) => {
+                                        respond_result!(req, false, "error parsing role");
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing role");
+                                        return;
+                                    }
+                                };
+                                let mut values: Vec<u64> = vec![];
+                                for name in ["value", "counter_value", "timeout"] {
+                                    match params.get(name).map(|v| v.parse::<u64>()) {
+                                        Some(Ok(value)) => values.push(value),
+                                        Some(Err(e)) => {
+                                            respond_result!(req, false, format!("error parsing {}: {}", name, e));
+                                            return;
+                                        }
+                                        None => {
+                                            respond_result!(req, false, format!("missing {}", name));
+                                            return;
+                                        }
+                                    }
+                                }
+                                let counter_addr = match params.get("counter_addr").map(|v| v.parse::<Address>()) {
+                                    Some(Ok(addr)) => addr,
+                                    Some(Err(e)) => {
+                                        respond_result!(req, false, e);
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing counter_addr");
+                                        return;
+                                    }
+                                };
+                                let counter_key = match params.get("counter_key").map(|key| hex::decode(key)) {
+                                    Some(Ok(key)) => key,
+                                    Some(Err(e)) => {
+                                        respond_result!(req, false, format!("error parsing counter_key: {}", e));
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing counter_key");
+                                        return;
+                                    }
+                                };
+                                let secret_hash = match Self::parse_hash(&params, "secret_hash") {
+                                    Ok(secret_hash) => secret_hash,
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                        return;
+                                    }
+                                };
+                                match swap_wallet.create(
+                                    role,
+                                    secret_hash,
+                                    values[0],
+                                    counter_addr,
+                                    counter_key,
+                                    values[1],
+                                    values[2],
+                                ) {
+                                    Ok(entry) => {
+                                        respond_json!(req, entry);
+                                    }
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                    }
+                                }
+                            }
+                            "/swap/status" | "/swap/fund" | "/swap/claim" | "/swap/refund" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let id = match Self::parse_hash(&params, "id") {
+                                    Ok(Some(id)) => id,
+                                    Ok(None) => {
+                                        respond_result!(req, false, "missing id");
+                                        return;
+                                    }
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                        return;
+                                    }
+                                };
+                                let res = match url.path() {
+                                    "/swap/fund" => swap_wallet.fund(&id),
+                                    "/swap/claim" => swap_wallet.claim(&id),
+                                    "/swap/refund" => swap_wallet.refund(&id),
+                                    _ => {
+                                        match swap_wallet.get_swap(&id) {
+                                            Some(entry) => {
+                                                respond_json!(req, entry);
+                                            }
+                                            None => {
+                                                respond_result!(req, false, "swap not found");
+                                            }
+                                        }
+                                        return;
+                                    }
+                                };
+                                match res {
+                                    Ok(tx) => {
+                                        respond_result!(req, true, tx.hash());
+                                    }
+                                    Err(e) => {
+                                        respond_result!(req, false, e);
+                                    }
+                                }
+                            }
                             "/blockchain/available-utxo" => {
                                 let params = url.query_pairs();
                                 let params: HashMap<_, _> = params.into_owned().collect();
@@ -288,7 +800,7 @@ impl Server {
                                         return;
                                     }
                                 };
-                                let user = match user.parse::<String>() {
+                                let user = match user.parse::<Address>() {
                                     Ok(v) => v,
                                     Err(e) => {
                                         info!("error passing user");
@@ -300,7 +812,6 @@ impl Server {
                                         return;
                                     }
                                 };
-                                let user: H256 = user.into();
                                 let utxos = Self::get_available_utxo(
                                     &multichain,
                                     &validator,
@@ -309,6 +820,96 @@ impl Server {
                                 );
                                 respond_json!(req, utxos);
                             }
+                            "/blockchain/utxo-proof" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let tx_hash = match params.get("tx").map(|tx| hex::decode(tx)) {
+                                    Some(Ok(bytes)) if bytes.len() == 32 => {
+                                        let bytes: [u8; 32] = bytes.try_into().unwrap();
+                                        H256::from(bytes)
+                                    }
+                                    Some(_) => {
+                                        respond_result!(req, false, "error parsing tx");
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing tx");
+                                        return;
+                                    }
+                                };
+                                let index = match params.get("index").map(|v| v.parse::<u32>()) {
+                                    Some(Ok(index)) => index,
+                                    Some(Err(e)) => {
+                                        respond_result!(req, false, format!("error parsing index: {}", e));
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing index");
+                                        return;
+                                    }
+                                };
+                                //the proof is against the state of the longest chain
+                                let tip = multichain.get_longest_chain_hash();
+                                let block = match multichain
+                                    .get_state_block_with_shard(&tip, config.shard_id()) {
+                                    Some(block) => block,
+                                    None => {
+                                        respond_result!(req, false, "state not found");
+                                        return;
+                                    }
+                                };
+                                let block_hash = block.hash();
+                                let state = match multichain.get_state(&block_hash) {
+                                    Some(state) => state,
+                                    None => {
+                                        respond_result!(req, false, "state not found");
+                                        return;
+                                    }
+                                };
+                                let tree = Blockchain::get_state_tree(&state);
+                                let key = Blockchain::get_utxo_key(&(tx_hash, index));
+                                respond_json!(req, UtxoProof {
+                                    block_hash,
+                                    state_root: block.get_state_root(),
+                                    value: tree.get(&key),
+                                    proof: tree.proof(&key),
+                                    key,
+                                });
+                            }
+                            "/fraudproof/list" => {
+                                let fp_hashes: Vec<String> = worker_caches
+                                    .get_fraud_proof_hashes()
+                                    .into_iter()
+                                    .map(|hash| hash.to_string())
+                                    .collect();
+                                respond_json!(req, fp_hashes);
+                            }
+                            "/fraudproof/export" => {
+                                let params = url.query_pairs();
+                                let params: HashMap<_, _> = params.into_owned().collect();
+                                let fp_hash = match params.get("hash").map(|hash| hex::decode(hash)) {
+                                    Some(Ok(bytes)) if bytes.len() == 32 => {
+                                        let bytes: [u8; 32] = bytes.try_into().unwrap();
+                                        H256::from(bytes)
+                                    }
+                                    Some(_) => {
+                                        respond_result!(req, false, "error parsing hash");
+                                        return;
+                                    }
+                                    None => {
+                                        respond_result!(req, false, "missing hash");
+                                        return;
+                                    }
+                                };
+                                let fp = match worker_caches.get_fraud_proof(&fp_hash) {
+                                    Some(fp) => fp,
+                                    None => {
+                                        respond_result!(req, false, "fraud proof not found");
+                                        return;
+                                    }
+                                };
+                                respond_json!(req, FraudProofBundle::export(&fp, &multichain, &config));
+                            }
                             _ => {
                                 info!("invalid HTTP request");
                                 let content_type =
@@ -331,16 +932,28 @@ impl Server {
         info!("API server listening at {}", &addr);
     }
 
+    //an optional hash given in hex
+    fn parse_hash(params: &HashMap<String, String>, name: &str) -> Result<Option<H256>, String> {
+        match params.get(name).map(|hash| hex::decode(hash)) {
+            Some(Ok(bytes)) if bytes.len() == 32 => {
+                let bytes: [u8; 32] = bytes.try_into().unwrap();
+                Ok(Some(H256::from(bytes)))
+            }
+            Some(_) => Err(format!("error parsing {}", name)),
+            None => Ok(None),
+        }
+    }
+
     fn get_available_utxo(
         multichain: &Multichain, 
         validator: &Validator, 
         config: &Configuration, 
-        payer: &H256
+        payer: &Address
     ) -> Vec<(Transaction, u32)> {
         if Validator::get_shard_id(
             payer,
-            config.shard_num,
-        ) != config.shard_id {
+            config,
+        ) != config.shard_id() {
             return vec![];
         }
 
@@ -362,7 +975,7 @@ impl Server {
                                 for input in tx.inputs.iter() {
                                     let ori_shard_id = Validator::get_shard_id(
                                         &input.sender_addr,
-                                        config.shard_num
+                                        &config
                                     );
                                     match validator.validate_cross_utxo(
                                         tx,
@@ -383,7 +996,7 @@ impl Server {
                                  for output in tx.outputs.iter() {
                                     let ori_shard_id = Validator::get_shard_id(
                                         &output.receiver_addr,
-                                        config.shard_num
+                                        &config
                                     );
                                     match validator.validate_cross_utxo(
                                         tx,
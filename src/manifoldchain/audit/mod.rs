@@ -0,0 +1,399 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        blockchain::supply::Supply,
+        configuration::Configuration,
+        multichain::Multichain,
+        sharding::ShardMap,
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+};
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+//the txs of the longest chain of a shard, in chain order. Only the txs of the
+//audited shards are known, the node keeps the headers of the other shards
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShardLedger {
+    pub shard_id: usize,
+    pub shard_num: usize,
+    #[serde(default = "default_shard_policy")]
+    pub shard_policy: String, //the spec of the shard map of the node
+    pub tip: H256,
+    pub height: usize,
+    pub audited: bool,
+    pub txs: Vec<(H256, Transaction)>, //block_hash, tx
+    pub state_value: Option<u64>, //the coins of the utxo state at the tip, if known
+    #[serde(default)]
+    pub migrated: u64, //the coins of the genesis state after a resharding, if known
+}
+
+fn default_shard_policy() -> String {
+    String::from("modulo")
+}
+
+impl ShardLedger {
+    /// Walk the longest chain of every shard of the multichain
+    pub fn from_multichain(multichain: &Multichain) -> Vec<ShardLedger> {
+        let shard_num = multichain.config.shard_num;
+        let shard_policy = multichain.config.shard_map.get_spec().to_string();
+        (0..shard_num)
+            .map(|shard_id| {
+                let path = multichain.all_blocks_in_longest_chain_with_shard(shard_id);
+                let mut txs: Vec<(H256, Transaction)> = vec![];
+                //the genesis block has no txs
+                let mut full = true;
+                for blk_hash in path.iter().skip(1) {
+                    let block = multichain.get_block_by_shard(blk_hash, shard_id);
+                    match block.as_ref().and_then(|block| block.get_txs_ref()) {
+                        Some(blk_txs) => {
+                            for tx in blk_txs.iter() {
+                                txs.push((blk_hash.clone(), tx.clone()));
+                            }
+                        }
+                        None => full = false,
+                    }
+                }
+                let tip = path.last().unwrap().clone();
+                let own_shard = shard_id == multichain.config.shard_id();
+                let state_value = match own_shard {
+                    true => multichain
+                        .get_state(&tip)
+                        .and_then(|state| Supply::get_state_value(&state)),
+                    false => None,
+                };
+                //only the node of the shard holds the state its genesis block commits to
+                let migrated = match own_shard {
+                    true => multichain
+                        .get_state(&path[0])
+                        .and_then(|state| Supply::get_state_value(&state))
+                        .unwrap_or(0),
+                    false => 0,
+                };
+                ShardLedger {
+                    shard_id,
+                    shard_num,
+                    shard_policy: shard_policy.clone(),
+                    tip,
+                    height: path.len() - 1,
+                    audited: own_shard || (path.len() > 1 && full),
+                    txs,
+                    state_value,
+                    migrated,
+                }
+            })
+            .collect()
+    }
+
+    /// Fetch the ledgers of a node from its API server
+    pub fn fetch(api_addr: &str) -> Result<Vec<ShardLedger>, String> {
+        let url = format!("http://{}/audit/ledgers", api_addr);
+        reqwest::blocking::get(&url)
+            .and_then(|resp| resp.json::<Vec<ShardLedger>>())
+            .map_err(|e| format!("error fetching {}: {}", url, e))
+    }
+
+    /// Keep one ledger per shard, the longest audited one if any
+    pub fn merge(ledgers: Vec<ShardLedger>) -> Vec<ShardLedger> {
+        let mut best: HashMap<usize, ShardLedger> = HashMap::new();
+        for ledger in ledgers {
+            let better = match best.get(&ledger.shard_id) {
+                Some(current) => (ledger.audited, ledger.height) > (current.audited, current.height),
+                None => true,
+            };
+            if better {
+                best.insert(ledger.shard_id, ledger);
+            }
+        }
+        let mut merged: Vec<ShardLedger> = best.into_values().collect();
+        merged.sort_by_key(|ledger| ledger.shard_id);
+        merged
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Discrepancy {
+    pub kind: &'static str,
+    pub shard_id: usize,
+    pub block_hash: H256,
+    pub tx_hash: Option<H256>,
+    pub message: String,
+}
+
+//a cross-shard tx whose inputs are locked in a shard but neither accepted nor rejected yet
+#[derive(Serialize, Debug, Clone)]
+pub struct InFlight {
+    pub shard_id: usize,
+    pub block_hash: H256,
+    pub tx_hash: H256,
+    pub value: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ShardAudit {
+    pub shard_id: usize,
+    pub tip: H256,
+    pub height: usize,
+    pub audited: bool,
+    pub supply: Supply,
+    pub state_value: Option<u64>,
+    pub in_flight: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditReport {
+    pub shards: Vec<ShardAudit>,
+    pub issued: u64,
+    pub in_flight: Vec<InFlight>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+//where the versions of a cross-shard tx are found, by shard
+#[derive(Default)]
+struct CrossTx {
+    tx: Transaction,
+    inputs: BTreeMap<usize, (H256, H256)>, //shard_id -> block_hash, tx_hash
+    outputs: BTreeMap<usize, (H256, H256)>,
+    accepts: BTreeMap<usize, (H256, H256)>,
+    rejects: BTreeMap<usize, (H256, H256)>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    fn record(
+        &mut self,
+        kind: &'static str,
+        shard_id: usize,
+        location: &(H256, H256),
+        message: String,
+    ) {
+        self.discrepancies.push(Discrepancy {
+            kind,
+            shard_id,
+            block_hash: location.0.clone(),
+            tx_hash: Some(location.1.clone()),
+            message,
+        });
+    }
+
+    /// Audit the ledgers of the shards: the supply of each shard against its state, and
+    /// every cross-shard tx from the lock of its inputs to their acceptance or refund
+    pub fn audit(ledgers: &Vec<ShardLedger>) -> AuditReport {
+        let mut report = AuditReport {
+            shards: vec![],
+            issued: 0,
+            in_flight: vec![],
+            discrepancies: vec![],
+        };
+        let audited: HashSet<usize> = ledgers
+            .iter()
+            .filter(|ledger| ledger.audited)
+            .map(|ledger| ledger.shard_id)
+            .collect();
+        let mut cross_txs: HashMap<H256, CrossTx> = HashMap::new();
+        let mut cross_order: Vec<H256> = vec![];
+        //the addresses are mapped to the shards as the nodes do
+        let mut config = Configuration::new();
+        config.shard_num = ledgers[0].shard_num;
+        match ShardMap::from_spec(&ledgers[0].shard_policy, config.shard_num) {
+            Ok(shard_map) => config.shard_map = shard_map,
+            Err(e) => report.discrepancies.push(Discrepancy {
+                kind: "unknown_policy",
+                shard_id: ledgers[0].shard_id,
+                block_hash: ledgers[0].tip.clone(),
+                tx_hash: None,
+                message: e,
+            }),
+        }
+
+        for ledger in ledgers.iter() {
+            let shard_id = ledger.shard_id;
+            let mut supply = Supply {
+                migrated: ledger.migrated,
+                ..Default::default()
+            };
+            let mut seen: HashSet<H256> = HashSet::new();
+            for (blk_hash, tx) in ledger.txs.iter() {
+                let tx_hash = tx.hash();
+                let location = (blk_hash.clone(), tx_hash.clone());
+                if tx.flag == TxFlag::Empty {
+                    continue;
+                }
+                if !seen.insert(tx_hash.clone()) {
+                    report.record("duplicate_tx", shard_id, &location,
+                        format!("tx {} appears twice in shard {}", tx_hash, shard_id));
+                }
+                if let Err(e) = supply.apply_tx(tx, shard_id, &config) {
+                    report.record("overflow", shard_id, &location, e);
+                }
+                if tx.flag == TxFlag::Initial {
+                    continue;
+                }
+                match (tx.get_input_value(), tx.get_output_value()) {
+                    (Some(input_value), Some(output_value)) if input_value == output_value => {}
+                    (input_value, output_value) => {
+                        report.record("unbalanced_tx", shard_id, &location,
+                            format!("tx {} spends {:?} coins with inputs of {:?} coins",
+                                tx_hash, output_value, input_value));
+                    }
+                }
+                if tx.flag == TxFlag::Domestic {
+                    continue;
+                }
+
+                //the versions of a cross-shard tx only differ by their flags
+                let cross_hash = tx.get_related_hash(TxFlag::Input);
+                let cross_tx = cross_txs.entry(cross_hash.clone()).or_insert_with(|| {
+                    cross_order.push(cross_hash.clone());
+                    CrossTx {
+                        tx: tx.clone(),
+                        ..Default::default()
+                    }
+                });
+                let involved = match tx.flag {
+                    TxFlag::Output => tx.outputs
+                        .iter()
+                        .any(|output| Validator::get_shard_id(&output.receiver_addr, &config) == shard_id),
+                    _ => tx.inputs
+                        .iter()
+                        .any(|input| Validator::get_shard_id(&input.sender_addr, &config) == shard_id),
+                };
+                if !involved {
+                    report.record("wrong_shard", shard_id, &location,
+                        format!("{} tx {} does not belong to shard {}",
+                            tx.flag.to_string(), tx_hash, shard_id));
+                    continue;
+                }
+                let versions = match tx.flag {
+                    TxFlag::Input => &mut cross_tx.inputs,
+                    TxFlag::Output => &mut cross_tx.outputs,
+                    TxFlag::Accept => &mut cross_tx.accepts,
+                    _ => &mut cross_tx.rejects,
+                };
+                versions.insert(shard_id, location);
+            }
+
+            if let Some(state_value) = ledger.state_value {
+                if supply.get_total() != Some(state_value) {
+                    report.discrepancies.push(Discrepancy {
+                        kind: "state_mismatch",
+                        shard_id,
+                        block_hash: ledger.tip.clone(),
+                        tx_hash: None,
+                        message: format!("state of shard {} holds {} coins, expected {:?}",
+                            shard_id, state_value, supply.get_total()),
+                    });
+                }
+            }
+            if ledger.audited {
+                match report.issued.checked_add(supply.minted).and_then(|sum| sum.checked_add(supply.migrated)) {
+                    Some(issued) => report.issued = issued,
+                    None => report.discrepancies.push(Discrepancy {
+                        kind: "overflow",
+                        shard_id,
+                        block_hash: ledger.tip.clone(),
+                        tx_hash: None,
+                        message: String::from("issued coins overflow"),
+                    }),
+                }
+            }
+            report.shards.push(ShardAudit {
+                shard_id,
+                tip: ledger.tip.clone(),
+                height: ledger.height,
+                audited: ledger.audited,
+                supply,
+                state_value: ledger.state_value,
+                in_flight: 0,
+            });
+        }
+
+        for cross_hash in cross_order.iter() {
+            let cross_tx = cross_txs.get(cross_hash).unwrap();
+            report.audit_cross_tx(cross_tx, &audited, &config);
+        }
+        for in_flight in report.in_flight.iter() {
+            if let Some(shard) = report.shards
+                .iter_mut()
+                .find(|shard| shard.shard_id == in_flight.shard_id) {
+                shard.in_flight = shard.in_flight.saturating_add(in_flight.value);
+            }
+        }
+        report
+    }
+
+    fn audit_cross_tx(&mut self, cross_tx: &CrossTx, audited: &HashSet<usize>, config: &Configuration) {
+        let tx = &cross_tx.tx;
+        let mut input_shards: Vec<usize> = tx.inputs
+            .iter()
+            .map(|input| Validator::get_shard_id(&input.sender_addr, config))
+            .collect();
+        input_shards.sort();
+        input_shards.dedup();
+        let mut output_shards: Vec<usize> = tx.outputs
+            .iter()
+            .map(|output| Validator::get_shard_id(&output.receiver_addr, config))
+            .collect();
+        output_shards.sort();
+        output_shards.dedup();
+
+        for shard_id in input_shards.iter().filter(|shard_id| audited.contains(shard_id)) {
+            let accept = cross_tx.accepts.get(shard_id);
+            let reject = cross_tx.rejects.get(shard_id);
+            match cross_tx.inputs.get(shard_id) {
+                Some(input) => {
+                    if accept.is_none() && reject.is_none() {
+                        let value = tx.inputs
+                            .iter()
+                            .filter(|input| {
+                                Validator::get_shard_id(&input.sender_addr, config) == *shard_id
+                            })
+                            .fold(0u64, |sum, input| sum.saturating_add(input.value));
+                        self.in_flight.push(InFlight {
+                            shard_id: *shard_id,
+                            block_hash: input.0.clone(),
+                            tx_hash: input.1.clone(),
+                            value,
+                        });
+                    }
+                }
+                None => {
+                    //the coins are delivered or refunded without being locked
+                    if let Some(location) = reject.or(accept) {
+                        self.record("settled_without_input", *shard_id, location,
+                            format!("tx {} settled in shard {} without its input-tx",
+                                location.1, shard_id));
+                    } else if let Some((output_shard, location)) = cross_tx.outputs.iter().next() {
+                        self.record("output_without_input", *output_shard, location,
+                            format!("output-tx {} without its input-tx in shard {}",
+                                location.1, shard_id));
+                    }
+                }
+            }
+            if let (Some(_), Some(location)) = (accept, reject) {
+                self.record("accepted_and_rejected", *shard_id, location,
+                    format!("tx {} both accepted and rejected in shard {}", location.1, shard_id));
+            }
+            if let Some(location) = accept {
+                for output_shard in output_shards.iter() {
+                    if audited.contains(output_shard) && !cross_tx.outputs.contains_key(output_shard) {
+                        self.record("accepted_undelivered", *shard_id, location,
+                            format!("tx {} accepted in shard {} but not delivered to shard {}",
+                                location.1, shard_id, output_shard));
+                    }
+                }
+            }
+        }
+        //refunded coins which were delivered exist twice
+        if !cross_tx.outputs.is_empty() {
+            for (shard_id, location) in cross_tx.rejects.iter() {
+                self.record("refunded_and_delivered", *shard_id, location,
+                    format!("tx {} refunded in shard {} after its delivery", location.1, shard_id));
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        block::{
+            Info,
+            Content,
+            exclusive_block::ExclusiveBlock,
+            inclusive_block::InclusiveBlock,
+            transaction_block::TransactionBlock,
+            versa_block::{
+                VersaBlock,
+                ExclusiveFullBlock,
+                InclusiveFullBlock,
+            },
+        },
+        transaction::Transaction,
+        testimony::Testimony,
+        mempool::Mempool,
+    },
+};
+use serde::{Serialize, Deserialize};
+use std::time::SystemTime;
+
+//the first 8 bytes of the transaction hash, a collision is caught by the merkle root
+//check after the reconstruction and the full block is requested instead
+pub type ShortId = u64;
+
+pub fn short_id(hash: &H256) -> ShortId {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash.as_ref()[0..8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CompactHeader {
+    Exclusive(ExclusiveBlock),
+    Inclusive(InclusiveBlock),
+}
+
+//a full block carrying the consensus header and the ids of its content only
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompactBlock {
+    pub header: CompactHeader,
+    pub shard_id: u32,
+    pub short_ids: Vec<ShortId>, //in the order of the transactions in the block
+    pub tmy_hashes: Vec<H256>, //in the order of the testimonies in the block
+}
+
+//the missing content of a compact block, indexed by the position in the block
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockTxnsRequest {
+    pub block_hash: H256,
+    pub is_exclusive: bool,
+    pub tx_indexes: Vec<u32>,
+    pub tmy_indexes: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockTxns {
+    pub block_hash: H256,
+    pub is_exclusive: bool,
+    pub txs: Vec<(u32, Transaction)>,
+    pub tmys: Vec<(u32, Testimony)>,
+}
+
+impl Hashable for CompactBlock {
+    fn hash(&self) -> H256 {
+        match &self.header {
+            CompactHeader::Exclusive(ex_block) => ex_block.hash(),
+            CompactHeader::Inclusive(in_block) => in_block.hash(),
+        }
+    }
+}
+
+impl CompactBlock {
+    //only full blocks have a compact form
+    pub fn from_full_block(block: &VersaBlock) -> Option<Self> {
+        let (header, tx_block) = match block {
+            VersaBlock::ExFullBlock(ex_full_block) => (
+                CompactHeader::Exclusive(ex_full_block.ex_block.clone()),
+                &ex_full_block.tx_block,
+            ),
+            VersaBlock::InFullBlock(in_full_block) => (
+                CompactHeader::Inclusive(in_full_block.in_block.clone()),
+                &in_full_block.tx_block,
+            ),
+            _ => return None,
+        };
+        let short_ids: Vec<ShortId> = tx_block
+            .get_txs_ref()
+            .iter()
+            .map(|tx| short_id(&tx.hash()))
+            .collect();
+        let tmy_hashes: Vec<H256> = tx_block
+            .get_tmys_ref()
+            .iter()
+            .map(|tmy| tmy.hash())
+            .collect();
+        Some(CompactBlock {
+            header,
+            shard_id: tx_block.get_shard_id() as u32,
+            short_ids,
+            tmy_hashes,
+        })
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        match self.header {
+            CompactHeader::Exclusive(_) => true,
+            CompactHeader::Inclusive(_) => false,
+        }
+    }
+
+    pub fn get_tx_merkle_root(&self) -> H256 {
+        match &self.header {
+            CompactHeader::Exclusive(ex_block) => ex_block.get_tx_merkle_root(),
+            CompactHeader::Inclusive(in_block) => in_block.get_tx_merkle_root(),
+        }
+    }
+
+    pub fn get_testimony_merkle_root(&self) -> H256 {
+        match &self.header {
+            CompactHeader::Exclusive(ex_block) => ex_block.get_testimony_merkle_root(),
+            CompactHeader::Inclusive(in_block) => in_block.get_testimony_merkle_root(),
+        }
+    }
+}
+
+impl BlockTxns {
+    //collect the requested content from the full block
+    pub fn from_full_block(block: &VersaBlock, request: &BlockTxnsRequest) -> Option<Self> {
+        let tx_block = match block {
+            VersaBlock::ExFullBlock(ex_full_block) => &ex_full_block.tx_block,
+            VersaBlock::InFullBlock(in_full_block) => &in_full_block.tx_block,
+            _ => return None,
+        };
+        let txs_ref = tx_block.get_txs_ref();
+        let tmys_ref = tx_block.get_tmys_ref();
+        let txs: Vec<(u32, Transaction)> = request.tx_indexes
+            .iter()
+            .filter_map(|i| txs_ref.get(*i as usize).map(|tx| (*i, tx.clone())))
+            .collect();
+        let tmys: Vec<(u32, Testimony)> = request.tmy_indexes
+            .iter()
+            .filter_map(|i| tmys_ref.get(*i as usize).map(|tmy| (*i, tmy.clone())))
+            .collect();
+        Some(BlockTxns {
+            block_hash: request.block_hash,
+            is_exclusive: request.is_exclusive,
+            txs,
+            tmys,
+        })
+    }
+}
+
+//a compact block whose content is being collected
+#[derive(Debug, Clone)]
+pub struct PartialBlock {
+    compact: CompactBlock,
+    txs: Vec<Option<Transaction>>,
+    tmys: Vec<Option<Testimony>>,
+    pub received_at: SystemTime,
+}
+
+impl PartialBlock {
+    //fill in the content already in the mempool
+    pub fn new(compact: CompactBlock, mempool: &Mempool) -> Self {
+        let txs: Vec<Option<Transaction>> = compact.short_ids
+            .iter()
+            .map(|id| mempool.get_tx_by_short_id(*id))
+            .collect();
+        let tmys: Vec<Option<Testimony>> = compact.tmy_hashes
+            .iter()
+            .map(|hash| mempool.find_testimony(hash))
+            .collect();
+        PartialBlock {
+            compact,
+            txs,
+            tmys,
+            received_at: SystemTime::now(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.txs.iter().all(|tx| tx.is_some()) && self.tmys.iter().all(|tmy| tmy.is_some())
+    }
+
+    pub fn get_missing(&self) -> BlockTxnsRequest {
+        let tx_indexes: Vec<u32> = self.txs
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(i, _)| i as u32)
+            .collect();
+        let tmy_indexes: Vec<u32> = self.tmys
+            .iter()
+            .enumerate()
+            .filter(|(_, tmy)| tmy.is_none())
+            .map(|(i, _)| i as u32)
+            .collect();
+        BlockTxnsRequest {
+            block_hash: self.compact.hash(),
+            is_exclusive: self.compact.is_exclusive(),
+            tx_indexes,
+            tmy_indexes,
+        }
+    }
+
+    //the content must match the ids in the compact block
+    pub fn fill(&mut self, block_txns: BlockTxns) {
+        for (i, tx) in block_txns.txs {
+            let i = i as usize;
+            if i < self.txs.len() && short_id(&tx.hash()) == self.compact.short_ids[i] {
+                self.txs[i] = Some(tx);
+            }
+        }
+        for (i, tmy) in block_txns.tmys {
+            let i = i as usize;
+            if i < self.tmys.len() && tmy.hash() == self.compact.tmy_hashes[i] {
+                self.tmys[i] = Some(tmy);
+            }
+        }
+    }
+
+    //return None if the content is incomplete or the merkle roots do not match
+    pub fn build(&self) -> Option<VersaBlock> {
+        if !self.is_complete() {
+            return None;
+        }
+        let txs: Vec<Transaction> = self.txs.iter().flatten().cloned().collect();
+        let tmys: Vec<Testimony> = self.tmys.iter().flatten().cloned().collect();
+        let tx_block = TransactionBlock::new(self.compact.shard_id as usize, txs, tmys);
+        if tx_block.get_tx_merkle_root() != self.compact.get_tx_merkle_root() ||
+            tx_block.get_testimony_merkle_root() != self.compact.get_testimony_merkle_root() {
+            return None;
+        }
+        match &self.compact.header {
+            CompactHeader::Exclusive(ex_block) => Some(VersaBlock::ExFullBlock(
+                ExclusiveFullBlock::create(ex_block.clone(), tx_block)
+            )),
+            CompactHeader::Inclusive(in_block) => Some(VersaBlock::InFullBlock(
+                InclusiveFullBlock::create(in_block.clone(), tx_block)
+            )),
+        }
+    }
+}
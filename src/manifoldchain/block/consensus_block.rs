@@ -1,6 +1,7 @@
 use crate::{
     types::{
         hash::{H256, Hashable},
+        sparse_merkle::SparseMerkleTree,
     },
     manifoldchain::{
         block::{
@@ -15,12 +16,13 @@ use crate::{
 use serde::{Serialize, Deserialize};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub struct ConsensusBlock {
     basic: BlockHeader,
     testimony_merkle_root: H256,
     inter_parent_merkle_root: H256,
     global_parent_merkle_root: H256,
+    state_root: H256,
 }
 
 
@@ -60,6 +62,7 @@ impl Info for ConsensusBlock {
         basic_info.push(self.testimony_merkle_root.clone());
         basic_info.push(self.inter_parent_merkle_root.clone());
         basic_info.push(self.global_parent_merkle_root.clone());
+        basic_info.push(self.state_root.clone());
         basic_info
     }
 }
@@ -71,6 +74,7 @@ impl Default for ConsensusBlock {
             testimony_merkle_root: H256::default(),
             inter_parent_merkle_root: H256::default(),
             global_parent_merkle_root: H256::default(),
+            state_root: SparseMerkleTree::empty_root(),
         }
     }
 }
@@ -86,13 +90,14 @@ impl ConsensusBlock {
             basic,
             testimony_merkle_root,
             inter_parent_merkle_root,
-            global_parent_merkle_root
+            global_parent_merkle_root,
+            state_root: SparseMerkleTree::empty_root(),
         }
     }
 
     pub fn get_mem_size() -> usize {
         BlockHeader::get_mem_size() 
-            + H256::get_mem_size() * 3
+            + H256::get_mem_size() * 4
     }
 
     pub fn generate(
@@ -117,11 +122,7 @@ impl ConsensusBlock {
 
         let inter_parent_merkle_root = H256::multi_hash(&inter_parents);
 
-        let chains: Vec<H256> = global_parents
-            .into_iter()
-            .map(|x| H256::multi_hash(&x.0))
-            .collect();
-        let global_parent_merkle_root = H256::multi_hash(&chains);
+        let global_parent_merkle_root = Self::get_global_parents_root(&global_parents);
 
         let block_header = BlockHeader::create(
             parent,
@@ -143,6 +144,21 @@ impl ConsensusBlock {
     }
 
 
+    //the shard of each chain is committed along with its blocks, so that the parents
+    //of an inclusive block can not be moved to another shard
+    pub fn get_global_parents_root(global_parents: &[(Vec<H256>, usize)]) -> H256 {
+        let chains: Vec<H256> = global_parents
+            .iter()
+            .map(|x| {
+                let shard_hash: H256 = ring::digest::digest(
+                    &ring::digest::SHA256, format!("{}", x.1).as_bytes()
+                ).into();
+                H256::chash(&shard_hash, &H256::multi_hash(&x.0))
+            })
+            .collect();
+        H256::multi_hash(&chains)
+    }
+
     pub fn get_testimony_merkle_root(&self) -> H256 {
         self.testimony_merkle_root.clone()
     }
@@ -155,6 +171,15 @@ impl ConsensusBlock {
         self.global_parent_merkle_root.clone()
     }
 
+    //the root of the utxo state after applying the block on its verified parent
+    pub fn get_state_root(&self) -> H256 {
+        self.state_root.clone()
+    }
+
+    pub fn set_state_root(&mut self, state_root: &H256) {
+        self.state_root = state_root.clone();
+    }
+
     pub fn get_verified_parent(&self) -> H256 {
         self.get_parent()
     }
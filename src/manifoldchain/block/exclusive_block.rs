@@ -8,6 +8,7 @@ use crate::{
             consensus_block::ConsensusBlock,
             transaction_block::TransactionBlock,
         },
+        configuration::Configuration,
         transaction::Transaction,
         testimony::Testimony,
     }
@@ -79,6 +80,33 @@ impl ExclusiveBlock {
         }
     }
 
+    //the genesis block of the shard, the same on every node
+    pub fn genesis(shard_id: usize) -> Self {
+        Self::genesis_with_state(shard_id, &H256::default())
+    }
+
+    //the genesis block of a shard starting from a checkpointed state
+    pub fn genesis_with_state(shard_id: usize, state_root: &H256) -> Self {
+        let mut cons_block = ConsensusBlock::default();
+        cons_block.set_shard_id(shard_id);
+        cons_block.set_state_root(state_root);
+        let cons_hash = cons_block.hash();
+        Self::create(
+            cons_block,
+            cons_hash,
+            vec![],
+        )
+    }
+
+    //the genesis block of the shard in the network of the configuration, which
+    //commits to the state of its checkpoint after a resharding
+    pub fn genesis_of(shard_id: usize, config: &Configuration) -> Self {
+        match config.genesis_roots.get(shard_id) {
+            Some(state_root) => Self::genesis_with_state(shard_id, state_root),
+            None => Self::genesis(shard_id),
+        }
+    }
+
     pub fn get_mem_size(&self) -> usize {
         ConsensusBlock::get_mem_size()
             + H256::get_mem_size() * (self.inter_parents.len() + 1)
@@ -117,6 +145,10 @@ impl ExclusiveBlock {
         self.cons_block.get_testimony_merkle_root()
     }
 
+    pub fn get_state_root(&self) -> H256 {
+        self.cons_block.get_state_root()
+    }
+
     pub fn get_inter_parent_merkle_root(&self) -> H256 {
         self.cons_block.get_inter_parent_merkle_root()
     }
@@ -18,7 +18,7 @@ use std::{
     collections::HashMap,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub struct InclusiveBlock {
     cons_block: ConsensusBlock,
     hash_val: H256,
@@ -136,6 +136,10 @@ impl InclusiveBlock {
         self.cons_block.get_testimony_merkle_root()
     }
 
+    pub fn get_state_root(&self) -> H256 {
+        self.cons_block.get_state_root()
+    }
+
 
     pub fn get_inter_parent_merkle_root(&self) -> H256 {
         self.cons_block.get_inter_parent_merkle_root()
@@ -155,13 +159,8 @@ impl InclusiveBlock {
             return false;
         }
 
-        let chains: Vec<H256> = self.global_parents
-            .clone()
-            .into_iter()
-            .map(|x| H256::multi_hash(&x.0))
-            .collect();
         if self.cons_block.get_global_parent_merkle_root() != 
-            H256::multi_hash(&chains) {
+            ConsensusBlock::get_global_parents_root(&self.get_global_parents()) {
             return false;
         }
         
@@ -195,6 +194,22 @@ impl InclusiveBlock {
         chains
     }
 
+    //every parent with the shard it is claimed in, the inter parents being the
+    //parents in the shard of the block
+    pub fn get_all_parents(&self) -> Vec<(H256, usize)> {
+        let shard_id = self.get_shard_id();
+        let mut parents: Vec<(H256, usize)> = self.inter_parents
+            .iter()
+            .map(|x| (x.clone(), shard_id))
+            .collect();
+        for item in self.global_parents.iter() {
+            for parent in item.0.iter() {
+                parents.push((parent.clone(), item.1 as usize));
+            }
+        }
+        parents
+    }
+
     pub fn get_verified_parent(&self) -> H256 {
         self.get_parent()
     }
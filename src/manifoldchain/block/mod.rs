@@ -3,6 +3,7 @@ pub mod inclusive_block;
 pub mod transaction_block;
 pub mod versa_block;
 pub mod consensus_block;
+pub mod compact_block;
 
 use serde::{Serialize, Deserialize};
 use crate::{
@@ -14,7 +15,7 @@ use crate::{
 };
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub struct BlockHeader {
     parent: H256,
     nonce: u32,
@@ -72,6 +72,9 @@ impl TransactionBlock {
         }
         res
     }
+    pub fn get_tmys_ref(&self) -> &Vec<Testimony> {
+        &self.testimonys.data
+    }
     pub fn get_tx_merkle_proof(&self, tx_index: usize) -> Vec<H256> {
         self.txs.get_tx_merkle_proof(tx_index)
     }
@@ -31,7 +31,7 @@ pub struct InclusiveFullBlock {
     pub tx_block: TransactionBlock
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum VersaBlock {   
     ExBlock(ExclusiveBlock),
     InBlock(InclusiveBlock),
@@ -246,6 +246,24 @@ impl VersaBlock {
         }
     }
 
+    pub fn get_state_root(&self) -> H256 {
+        match self {
+            VersaBlock::ExBlock(ex_block) => ex_block.get_state_root(),
+            VersaBlock::InBlock(in_block) => in_block.get_state_root(),
+            VersaBlock::ExFullBlock(ex_full_block) => ex_full_block.get_state_root(),
+            VersaBlock::InFullBlock(in_full_block) => in_full_block.get_state_root(),
+        }
+    }
+
+    //the block without its transactions, as synced by the light clients
+    pub fn to_header(&self) -> VersaBlock {
+        match self {
+            VersaBlock::ExFullBlock(ex_full_block) => VersaBlock::ExBlock(ex_full_block.get_exclusive_block()),
+            VersaBlock::InFullBlock(in_full_block) => VersaBlock::InBlock(in_full_block.get_inclusive_block()),
+            header => header.clone(),
+        }
+    }
+
 }
 
 impl ExclusiveFullBlock {
@@ -291,6 +309,9 @@ impl ExclusiveFullBlock {
     pub fn get_testimony_merkle_root(&self) -> H256 {
         self.ex_block.get_testimony_merkle_root()
     }
+    pub fn get_state_root(&self) -> H256 {
+        self.ex_block.get_state_root()
+    }
     pub fn get_tmys(&self) -> HashMap<H256, Testimony> {
         self.tx_block.get_tmys()
     }
@@ -374,6 +395,9 @@ impl InclusiveFullBlock {
     pub fn get_testimony_merkle_root(&self) -> H256 {
         self.in_block.get_testimony_merkle_root()
     } 
+    pub fn get_state_root(&self) -> H256 {
+        self.in_block.get_state_root()
+    }
     pub fn get_inter_parent_merkle_root(&self) -> H256 {
         self.in_block.get_inter_parent_merkle_root()
     }
@@ -1,6 +1,9 @@
+pub mod supply;
+
 use crate::{
     types::{
         hash::{H256, Hashable},
+        sparse_merkle::SparseMerkleTree,
     },
     manifoldchain::{
         block::{
@@ -87,6 +90,7 @@ pub struct Blockchain {
     //multiple blocks
     //states: HashMap<H256, State>, //block_hash -> static state
     states: Database<State>,
+    tip_state: Option<(H256, State)>, //the state of the longest chain, kept for the lookups of incoming txs
     leaves: Vec<H256>,
     unverified_blocks: HashMap<(H256, usize), bool>,
     //to facilitate the insertion of two same blocks with the same parent
@@ -385,29 +389,13 @@ impl Node {
 impl Blockchain {
     /// Create a new blockchain, only containing the genesis block
     pub fn new(config: &Configuration, shard_id: usize) -> Self {
-        //create genesis block
-        let (ex_blk, tx_blk) = ExclusiveBlock::generate(
-            H256::default(), //verified_parent
-            shard_id, //shard_id
-            0, //nonce
-            H256::default(), //difficulty
-            vec![], //txs
-            vec![], //tmys
-            vec![], //inter_parents
-            vec![], //global_parents
-        );
-
-        let mut cons_blk = ConsensusBlock::default();
-        cons_blk.set_shard_id(shard_id);
-        let cons_hash = cons_blk.hash();
-        let ex_blk = ExclusiveBlock::create(
-            cons_blk,
-            cons_hash,
-            vec![],
-        );
-
+        Self::with_state(config, shard_id, HashMap::new())
+    }
 
-        
+    /// Create a new blockchain whose genesis block holds the state of a checkpoint
+    pub fn with_state(config: &Configuration, shard_id: usize, initial_state: State) -> Self {
+        //create genesis block
+        let ex_blk = ExclusiveBlock::genesis_of(shard_id, config);
         let genesis_hash = ex_blk.hash();
         let genesis_block = VersaBlock::ExBlock(ex_blk);
 
@@ -433,8 +421,7 @@ impl Blockchain {
         let mut hash2ver_status: HashMap<H256, VerStatus> = HashMap::new();
         hash2ver_status.insert(genesis_hash.clone(), VerStatus::Verified);
 
-        //intitialize a empty state to the genesis block
-        let initial_state: State = HashMap::new();
+        //intitialize the state of the genesis block
         //let mut states: HashMap<H256, State> = HashMap::new();
         let mut states: Database<State> = 
             Database::<State>::new(format!("{:?}/blockchain/states", now));
@@ -449,6 +436,7 @@ impl Blockchain {
             root,
             tx_map: HashMap::new(),
             states,
+            tip_state: None,
             unverified_blocks: HashMap::new(),
             dp_map: HashMap::new(),
             longest_chain_hash,
@@ -470,6 +458,9 @@ impl Blockchain {
         self.hash2node.remove(hash);
         self.hash2ver_status.remove(hash);
         self.states.remove(hash);
+        if self.tip_state.as_ref().map_or(false, |(tip, _)| tip == hash) {
+            self.tip_state = None;
+        }
         //self.tx_map.retain(|_, val| *hash != val.0);
     }
 
@@ -816,7 +807,7 @@ impl Blockchain {
             match block {
                 VersaBlock::ExBlock(_) 
                     => {
-                        if shard_id == self.config.shard_id {
+                        if shard_id == self.config.shard_id() {
                             self.hash2ver_status.insert(blk_hash.clone(), VerStatus::Verified);
                         } else {
                             self.hash2ver_status.insert(blk_hash.clone(), VerStatus::Unverified);
@@ -825,7 +816,7 @@ impl Blockchain {
                     }
                 VersaBlock::InBlock(_) 
                     => {
-                        if shard_id == self.config.shard_id {
+                        if shard_id == self.config.shard_id() {
                             self.hash2ver_status.insert(blk_hash.clone(), VerStatus::Verified);
                         } else {
                             self.hash2ver_status.insert(blk_hash.clone(), VerStatus::Unverified);
@@ -938,29 +929,58 @@ impl Blockchain {
     }
 
     fn update_state(&self, tx: &Transaction, state: &mut State, tmys: &HashMap<H256, Testimony>) {
+        Self::apply_tx(tx, state, tmys, self.shard_id, &self.config);
+    }
+
+    /// Apply the transaction to the utxo state of the shard
+    pub fn apply_tx(
+        tx: &Transaction,
+        state: &mut State,
+        tmys: &HashMap<H256, Testimony>,
+        shard_id: usize,
+        config: &Configuration,
+    ) {
+        let tx_hash = tx.hash();
+        let (spent, created) = Self::get_state_changes(tx, shard_id, config);
+        for key in spent {
+            state.remove(&key);
+        }
+        //the utxos created by an output-tx or a reject-tx are unstable, they depend on
+        //the corresponding testimony
+        let tmy = match tx.flag {
+            TxFlag::Output | TxFlag::Reject => Some(tmys.get(&tx_hash).unwrap().clone()),
+            _ => None,
+        };
+        for key in created {
+            state.insert(key, (tx.clone(), tmy.clone()));
+        }
+    }
+
+    /// The utxos of the shard spent and created by the transaction
+    pub fn get_state_changes(
+        tx: &Transaction,
+        shard_id: usize,
+        config: &Configuration,
+    ) -> (Vec<(H256, u32)>, Vec<(H256, u32)>) {
         let tx_hash = tx.hash();
+        let mut spent: Vec<(H256, u32)> = vec![];
+        let mut created: Vec<(H256, u32)> = vec![];
         match tx.flag {
             TxFlag::Empty => {}
             TxFlag::Initial => {
                 //For an initial tx, it does not consume any utxos
-                state.insert((tx_hash.clone(), 0), (tx.clone(), None));
+                created.push((tx_hash.clone(), 0));
             }
             TxFlag::Domestic => {
                 //For an domestic tx, all inputs and outputs corresponds to the current
                 //shard
                 //remove all inputs from current state
                 for input in tx.inputs.iter() {
-                    state.remove(&(
-                        input.tx_hash.clone(),
-                        input.index
-                    ));
+                    spent.push((input.tx_hash.clone(), input.index));
                 }
                 //add all outputs to state
                 for j in 0..tx.outputs.len() {
-                    state.insert(
-                        (tx_hash.clone(), j as u32),
-                        (tx.clone(), None)
-                    );
+                    created.push((tx_hash.clone(), j as u32));
                 }
             }
             TxFlag::Input => {
@@ -970,32 +990,25 @@ impl Blockchain {
                     //skip inputs not corresponding to the current shard
                     if Validator::get_shard_id(
                         &input.sender_addr,
-                        self.config.shard_num
-                    ) != self.config.shard_id {
+                        config
+                    ) != shard_id {
                         continue;
                     }
-                    state.remove(&(
-                        input.tx_hash.clone(),
-                        input.index
-                    ));
+                    spent.push((input.tx_hash.clone(), input.index));
                 }
             }
             TxFlag::Output => {
                 //For a valid output-tx, we dont care the inputs,
                 //we only add the corresponding utxos specified by the outputs to state
-                //but this state is unstable, it depends on the corresponding testimony
                 for j in 0..tx.outputs.len() {
                     let output = &tx.outputs[j];
                     if Validator::get_shard_id(
                         &output.receiver_addr,
-                        self.config.shard_num
-                    ) != self.config.shard_id {
+                        config
+                    ) != shard_id {
                         continue;
                     }
-                    state.insert(
-                        (tx_hash.clone(), j as u32),
-                        (tx.clone(), Some(tmys.get(&tx_hash).unwrap().clone()))
-                    );
+                    created.push((tx_hash.clone(), j as u32));
                 }
             }
             TxFlag::Accept => {
@@ -1014,19 +1027,101 @@ impl Blockchain {
                     let input = &tx.inputs[j];
                     if Validator::get_shard_id(
                         &input.sender_addr,
-                        self.config.shard_num
-                    ) != self.config.shard_id {
+                        config
+                    ) != shard_id {
                         continue;
                     }
                     //reinsert the utxo to state
-                    state.insert(
-                        (tx_hash.clone(), j as u32),
-                        (tx.clone(), Some(tmys.get(&tx_hash).unwrap().clone()))
-                    );
+                    created.push((tx_hash.clone(), j as u32));
                 }
-                
             }
         }
+        (spent, created)
+    }
+
+    /// The key of a utxo in the state tree
+    pub fn get_utxo_key(utxo: &(H256, u32)) -> H256 {
+        let mut bytes: [u8; 36] = [0; 36];
+        bytes[0..32].copy_from_slice(&utxo.0.0);
+        bytes[32..36].copy_from_slice(&utxo.1.to_be_bytes());
+        ring::digest::digest(&ring::digest::SHA256, &bytes).into()
+    }
+
+    /// The value of a utxo in the state tree, the hash of the output it refers to,
+    /// or of the returned input for a reject-tx
+    pub fn get_utxo_value(tx: &Transaction, index: u32) -> H256 {
+        let index = index as usize;
+        match tx.flag {
+            TxFlag::Reject => tx.inputs.get(index).map(|input| input.hash()),
+            _ => tx.outputs.get(index).map(|output| output.hash()),
+        }.unwrap_or_default()
+    }
+
+    /// The leaves of the state tree, utxo key -> utxo value
+    pub fn get_state_leaves(state: &State) -> HashMap<H256, H256> {
+        state
+            .iter()
+            .map(|(utxo, (tx, _))| (Self::get_utxo_key(utxo), Self::get_utxo_value(tx, utxo.1)))
+            .collect()
+    }
+
+    /// The sparse merkle tree over the utxos of the state
+    pub fn get_state_tree(state: &State) -> SparseMerkleTree {
+        let leaves: Vec<(H256, H256)> = Self::get_state_leaves(state)
+            .into_iter()
+            .collect();
+        SparseMerkleTree::new(&leaves)
+    }
+
+    /// The updates of the state tree made by the transactions in order,
+    /// (utxo key, new value), none if the utxo is spent
+    pub fn get_state_updates(
+        txs: &[Transaction],
+        shard_id: usize,
+        config: &Configuration,
+    ) -> Vec<(H256, Option<H256>)> {
+        let mut updates: Vec<(H256, Option<H256>)> = vec![];
+        for tx in txs.iter() {
+            let (spent, created) = Self::get_state_changes(tx, shard_id, config);
+            for utxo in spent.iter() {
+                updates.push((Self::get_utxo_key(utxo), None));
+            }
+            for utxo in created.iter() {
+                updates.push((Self::get_utxo_key(utxo), Some(Self::get_utxo_value(tx, utxo.1))));
+            }
+        }
+        updates
+    }
+
+    /// The state root committed by a block with the transactions on top of the state
+    pub fn get_next_state_root(
+        state: &State,
+        txs: &[Transaction],
+        shard_id: usize,
+        config: &Configuration,
+    ) -> H256 {
+        let mut leaves = Self::get_state_leaves(state);
+        for (key, value) in Self::get_state_updates(txs, shard_id, config) {
+            match value {
+                Some(value) => leaves.insert(key, value),
+                None => leaves.remove(&key),
+            };
+        }
+        let leaves: Vec<(H256, H256)> = leaves.into_iter().collect();
+        SparseMerkleTree::new(&leaves).root()
+    }
+
+    /// The closest block of the shard ending with the block, whose state root commits
+    /// to the utxo state of the chain at the block
+    pub fn get_state_block(&self, hash: &H256) -> Option<VersaBlock> {
+        let path = self.all_blocks_end_with_block(hash)?;
+        for blk_hash in path.iter().rev() {
+            let block = self.get_block(blk_hash)?;
+            if block.get_shard_id() == self.shard_id {
+                return Some(block);
+            }
+        }
+        None
     }
 
     /// Get the last block's hash of the longest chain
@@ -1034,6 +1129,11 @@ impl Blockchain {
         self.longest_chain_hash.clone()
     }
 
+    /// Get the hash of the genesis block
+    pub fn genesis(&self) -> H256 {
+        self.root.val.clone()
+    }
+
     /// Get all blocks' hashes of the longest chain, ordered from genesis to the tip
     pub fn all_blocks_in_longest_chain(&self) -> Vec<H256> {
         Node::get_path(&self.root, &self.longest_chain_hash)
@@ -1069,6 +1169,28 @@ impl Blockchain {
         self.states.into_map()
     }
 
+    //get the static state after the block
+    pub fn get_state(&self, hash: &H256) -> Option<State> {
+        self.states.get(hash)
+    }
+
+    /// The tx whose output the utxo is in the state of the longest chain, the state is
+    /// loaded once per tip rather than once per lookup
+    pub fn get_utxo_in_longest_chain(&mut self, utxo: &(H256, u32)) -> Option<Transaction> {
+        let is_stale = match &self.tip_state {
+            Some((tip, _)) => *tip != self.longest_chain_hash,
+            None => true,
+        };
+        if is_stale {
+            let state = self.states.get(&self.longest_chain_hash)?;
+            self.tip_state = Some((self.longest_chain_hash.clone(), state));
+        }
+        self.tip_state
+            .as_ref()
+            .and_then(|(_, state)| state.get(utxo))
+            .map(|(tx, _)| tx.clone())
+    }
+
 
     //get the unverified leaves
     pub fn get_leaves(&self) -> Vec<H256> {
@@ -1256,6 +1378,59 @@ impl Blockchain {
         }
     }
 
+    /// Import the state of a block synced from the nodes of the shard, then recompute
+    /// the states of its descendants: the full blocks apply their txs, the headers
+    /// inherit the state of their parents. The block and its ancestors are verified
+    pub fn import_state(&mut self, hash: &H256, state: State) -> Result<(), String> {
+        let path = self.all_blocks_end_with_block(hash)
+            .ok_or(format!("block {} not found", hash))?;
+        let node = Node::get_node_by_hash(&self.root, hash)
+            .ok_or(format!("block {} not found", hash))?;
+        for blk_hash in path.iter() {
+            self.hash2ver_status.insert(blk_hash.clone(), VerStatus::Verified);
+        }
+        self.unverified_blocks.retain(|key, _| !path.contains(&key.0));
+        let _ = self.states.insert(hash.clone(), state.clone());
+        self.tip_state = None;
+        self.update_descendant_states(&node, &state);
+
+        let (longest_verified_hash, height) = Node::get_longest_verified_fork(
+            &self.root,
+            &self.hash2ver_status
+        ).unwrap();
+        self.longest_verified_chain_hash = longest_verified_hash;
+        self.verified_height = height;
+        self.leaves = Node::get_leaves_start_from(
+            &self.root,
+            &self.longest_verified_chain_hash
+        ).unwrap();
+        Ok(())
+    }
+
+    fn update_descendant_states(&mut self, node: &Box<Node>, state: &State) {
+        for child in node.children.iter() {
+            let mut child_state = state.clone();
+            if let Some(block) = self.get_block(&child.val) {
+                if let (Some(txs), Some(tmys)) = (block.get_txs_ref(), block.get_tmys()) {
+                    for tx in txs.iter() {
+                        self.update_state(tx, &mut child_state, &tmys);
+                    }
+                }
+            }
+            let _ = self.states.insert(child.val.clone(), child_state.clone());
+            self.update_descendant_states(child, &child_state);
+        }
+    }
+
+    /// The height of the block including the tx on the chain ending with the block
+    pub fn get_tx_height(&self, tx_hash: &H256, tip: &H256) -> Option<usize> {
+        let locations = self.tx_map.get(tx_hash)?;
+        let path = self.all_blocks_end_with_block(tip)?;
+        locations
+            .iter()
+            .find_map(|(blk_hash, _)| path.iter().position(|hash| hash == blk_hash))
+    }
+
     pub fn get_block_height(&self, block_hash: &H256) -> Option<usize> {
         match self.hash2node.get(block_hash) {
             Some(node) => {
@@ -1352,12 +1527,12 @@ impl Blockchain {
                         write!(output, "input tx {:?} packaged at {} confirmed at {} ", tx_hash, package_time, confirmed_time);
                         //write!(output, "input shards: ");
                         //for input_tx in tx.inputs {
-                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         //write!(output, "output shards: ");
                         //for output_tx in tx.outputs {
-                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         write!(output, "\n");
@@ -1366,12 +1541,12 @@ impl Blockchain {
                         write!(output, "output tx {:?} packaged at {} confirmed at {} ", tx_hash, package_time, confirmed_time)?;
                         //write!(output, "input shards: ")?;
                         //for input_tx in &tx.inputs {
-                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         //write!(output, "output shards: ");
                         //for output_tx in &tx.outputs {
-                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         let mut corr_input_tx = tx.clone();
@@ -1382,12 +1557,12 @@ impl Blockchain {
                         write!(output, "accept tx {:?} packaged at {} confirmed at {} ", tx_hash, package_time, confirmed_time);
                         //write!(output, "input shards: ");
                         //for input_tx in &tx.inputs {
-                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         //write!(output, "output shards: ");
                         //for output_tx in &tx.outputs {
-                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         let mut corr_output_tx = tx.clone();
@@ -1398,12 +1573,12 @@ impl Blockchain {
                         write!(output, "reject tx {:?} packaged at {} confirmed at {} ", tx_hash, package_time, confirmed_time)?;
                         //write!(output, "input shards: ")?;
                         //for input_tx in &tx.inputs {
-                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&input_tx.sender_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         //write!(output, "output shards: ");
                         //for output_tx in &tx.outputs {
-                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, self.config.shard_num);
+                        //    let shard_id = Validator::get_shard_id(&output_tx.receiver_addr, &self.config);
                         //    write!(output, "{} ", shard_id)?;
                         //}
                         let mut corr_output_tx = tx.clone();
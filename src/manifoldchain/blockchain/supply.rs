@@ -0,0 +1,157 @@
+use crate::{
+    types::hash::H256,
+    manifoldchain::{
+        blockchain::{Blockchain, State},
+        configuration::Configuration,
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+};
+use serde::{Serialize, Deserialize};
+
+//the coins of a shard, by the way they enter or leave it. The utxos of the shard
+//always hold migrated + minted + received + refunded - sent coins
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct Supply {
+    pub minted: u64, //created by initial-txs
+    pub sent: u64, //locked by input-txs, on their way to other shards
+    pub received: u64, //created by output-txs
+    pub refunded: u64, //returned by reject-txs
+    #[serde(default)]
+    pub migrated: u64, //held by the genesis state after a resharding
+}
+
+impl Supply {
+    /// Account the coins the transaction moves in or out of the shard, following
+    /// the state changes of Blockchain::get_state_changes
+    pub fn apply_tx(
+        &mut self,
+        tx: &Transaction,
+        shard_id: usize,
+        config: &Configuration,
+    ) -> Result<(), String> {
+        let overflow = || format!("supply of shard {} overflows", shard_id);
+        match tx.flag {
+            TxFlag::Initial => {
+                let value = tx.outputs.get(0).map_or(0, |output| output.value);
+                self.minted = self.minted.checked_add(value).ok_or_else(overflow)?;
+            }
+            TxFlag::Input => {
+                for input in tx.inputs.iter() {
+                    if Validator::get_shard_id(&input.sender_addr, config) != shard_id {
+                        continue;
+                    }
+                    self.sent = self.sent.checked_add(input.value).ok_or_else(overflow)?;
+                }
+            }
+            TxFlag::Output => {
+                for output in tx.outputs.iter() {
+                    if Validator::get_shard_id(&output.receiver_addr, config) != shard_id {
+                        continue;
+                    }
+                    self.received = self.received.checked_add(output.value).ok_or_else(overflow)?;
+                }
+            }
+            TxFlag::Reject => {
+                for input in tx.inputs.iter() {
+                    if Validator::get_shard_id(&input.sender_addr, config) != shard_id {
+                        continue;
+                    }
+                    self.refunded = self.refunded.checked_add(input.value).ok_or_else(overflow)?;
+                }
+            }
+            //a domestic-tx moves coins inside the shard, an accept-tx moves none
+            TxFlag::Domestic | TxFlag::Accept | TxFlag::Empty => {}
+        }
+        Ok(())
+    }
+
+    /// The coins the utxos of the shard should hold, none if more coins were sent
+    /// than the shard ever had
+    pub fn get_total(&self) -> Option<u64> {
+        self.migrated
+            .checked_add(self.minted)?
+            .checked_add(self.received)?
+            .checked_add(self.refunded)?
+            .checked_sub(self.sent)
+    }
+
+    /// The coins held by the utxos of a state
+    pub fn get_state_value(state: &State) -> Option<u64> {
+        state
+            .iter()
+            .try_fold(0u64, |sum, (key, (tx, _))| {
+                let index = key.1 as usize;
+                //the utxo of a reject-tx is specified by its inputs
+                let value = match tx.flag {
+                    TxFlag::Reject => tx.inputs.get(index)?.value,
+                    _ => tx.outputs.get(index)?.value,
+                };
+                sum.checked_add(value)
+            })
+    }
+
+    /// Check the coins of the state against the supply of the shard
+    pub fn check_state(&self, state: &State) -> Result<(), String> {
+        let expected = self.get_total()
+            .ok_or(String::from("more coins sent than supplied"))?;
+        let actual = Self::get_state_value(state)
+            .ok_or(String::from("coins of the state overflow"))?;
+        if expected != actual {
+            return Err(format!("state holds {} coins, expected {}", actual, expected));
+        }
+        Ok(())
+    }
+
+    /// Check the conservation of the coins across the shards: the coins received and
+    /// refunded never exceed the coins sent, the rest is still on the way
+    pub fn check_conservation(supplies: &Vec<Supply>) -> Result<u64, String> {
+        let mut sent: u64 = 0;
+        let mut arrived: u64 = 0;
+        for supply in supplies.iter() {
+            sent = sent.checked_add(supply.sent)
+                .ok_or(String::from("sent coins overflow"))?;
+            arrived = arrived.checked_add(supply.received)
+                .and_then(|sum| sum.checked_add(supply.refunded))
+                .ok_or(String::from("arrived coins overflow"))?;
+        }
+        sent.checked_sub(arrived)
+            .ok_or(format!("{} coins arrived but only {} sent", arrived, sent))
+    }
+}
+
+impl Blockchain {
+    /// The supply of the shard along the chain ending with the block
+    pub fn get_supply(&self, hash: &H256) -> Result<Supply, String> {
+        let path = self.all_blocks_end_with_block(hash)
+            .ok_or(format!("block {} not found", hash))?;
+        let mut supply = Supply::default();
+        //the genesis state is migrated from the shards of the previous epoch
+        if let Some(state) = path.first().and_then(|genesis| self.get_state(genesis)) {
+            supply.migrated = Supply::get_state_value(&state)
+                .ok_or(String::from("coins of the genesis state overflow"))?;
+        }
+        for blk_hash in path.iter() {
+            //the exclusive and inclusive blocks inherit the state of their parents
+            let block = self.get_block(blk_hash)
+                .ok_or(format!("block {} not found", blk_hash))?;
+            if let Some(txs) = block.get_txs_ref() {
+                for tx in txs.iter() {
+                    supply.apply_tx(tx, self.shard_id, &self.config)?;
+                }
+            }
+        }
+        Ok(supply)
+    }
+
+    /// The supply of the shard at the tip of the longest chain, checked against
+    /// the coins of its state
+    pub fn check_supply(&self) -> Result<Supply, String> {
+        let tip = self.tip();
+        let supply = self.get_supply(&tip)?;
+        let state = self.get_state(&tip)
+            .ok_or(format!("state of block {} not found", tip))?;
+        supply.check_state(&state)?;
+        Ok(supply)
+    }
+}
@@ -1,25 +1,58 @@
-use crate::types::hash::H256;
+use crate::{
+    types::hash::H256,
+    manifoldchain::{
+        network::emulation::EmulationConfig,
+        sharding::{ShardMap, reshard::ReshardPlan, rotation::ShardRole},
+    },
+};
 
 
 #[derive(Debug, Default, Clone)]
 pub struct Configuration {
     pub difficulty: H256,
     pub thredshold: H256,
+    pub shard_diffs: Vec<H256>, //the exclusive difficulty of every shard, empty when they all share the difficulty
     pub block_size: usize,
     pub k: usize,
-    pub initial_balance: usize,
+    pub initial_balance: u64,
     pub initial_utxo_num: usize,
     pub user_size: usize,
     pub num_tx_recv: usize, //the number of receivers of a transaction when generating txs
-    pub shard_id: usize,
+    pub role: ShardRole, //the shard of the node, shared by all its components
     pub node_id: usize,
     pub max_shard_num: usize,
     pub shard_num: usize,
     pub shard_size: usize,
     pub tx_merkle_proof_len: usize,
-    pub network_delay: usize,
+    pub network_delay: usize, //us, the propagation delay of every connection without an emulation config
     pub exper_number: usize,
     pub domestic_tx_ratio: f64,
+    pub target_peers: usize, //the number of peers to keep within the own shard
+    pub min_foreign_peers: usize, //the minimal number of peers to keep in every other shard
+    pub request_timeout: u64, //ms, before a request is sent to another peer
+    pub max_request_retries: usize,
+    pub ban_threshold: u32, //the misbehavior score at which a peer is banned
+    pub ban_duration: u64, //s, the duration of a temporary ban
+    pub max_bans: u32, //the number of bans after which a peer is banned permanently
+    pub max_unsolicited_txs: usize, //known or rejected unsolicited txs per minute, before a peer is considered flooding
+    pub compact_blocks: bool, //relay full blocks as compact blocks within the shard
+    pub emulation: Option<EmulationConfig>, //the emulated network conditions of the outgoing traffic
+    pub max_orphans: usize, //the number of blocks waiting for their parents
+    pub orphan_retry_interval: u64, //ms, before the missing parent is requested again, doubled every retry
+    pub max_orphan_retries: usize, //the number of retries before an orphan is evicted
+    pub max_fraud_proofs: usize, //the number of fraud proofs kept by the network workers
+    pub max_samples: usize, //the number of sample indexes kept by the network workers
+    pub cache_ttl: u64, //s, the lifetime of the cached fraud proofs and samples
+    pub light_client: bool, //follow only the block headers, without mining or validating blocks
+    pub light_shards: Vec<usize>, //the shards whose headers are followed by a light client
+    pub fraud_proof_dir: Option<String>, //the directory the generated and accepted fraud proofs are exported to
+    pub max_exported_fraud_proofs: usize, //the number of proofs the export directory holds
+    pub verify_threads: usize, //the number of threads checking the txs of a block, 0 for one per cpu
+    pub max_sig_cache: usize, //the number of verified signatures kept by the validator
+    pub shard_map: ShardMap, //the policy mapping the addresses to the shards
+    pub reshard: Option<ReshardPlan>, //the end of the epoch, after which the shards are rebuilt
+    pub genesis_roots: Vec<H256>, //the state roots the shards start from after a resharding
+    pub rotation_epoch: usize, //the blocks per epoch of the committee rotation, 0 for a fixed shard
 }
 
 impl Configuration {
@@ -41,13 +74,14 @@ impl Configuration {
         Configuration {
             difficulty: H256::default(),
             thredshold: H256::default(), //spliting exclusive block and inclusive block
+            shard_diffs: vec![],
             block_size: 2048,
             k: 6,
             initial_balance: 1000,
             initial_utxo_num: 3,
             user_size: 3,
             num_tx_recv: 3,
-            shard_id: 0,
+            role: ShardRole::default(),
             node_id: 0,
             max_shard_num: 256,
             shard_num: 0,
@@ -56,6 +90,50 @@ impl Configuration {
             network_delay: 0,
             exper_number: 0,
             domestic_tx_ratio: 0.7,
+            target_peers: 8,
+            min_foreign_peers: 2,
+            request_timeout: 5000,
+            max_request_retries: 3,
+            ban_threshold: 100,
+            ban_duration: 3600,
+            max_bans: 3,
+            max_unsolicited_txs: 100000,
+            compact_blocks: true,
+            emulation: None,
+            max_orphans: 1024,
+            orphan_retry_interval: 2000,
+            max_orphan_retries: 5,
+            max_fraud_proofs: 4096,
+            max_samples: 65536,
+            cache_ttl: 3600,
+            light_client: false,
+            light_shards: vec![],
+            fraud_proof_dir: None,
+            max_exported_fraud_proofs: 1024,
+            verify_threads: 0,
+            max_sig_cache: 65536,
+            shard_map: ShardMap::default(),
+            reshard: None,
+            genesis_roots: vec![],
+            rotation_epoch: 0,
         }
     }
+
+    /// The shard the node mines on
+    pub fn shard_id(&self) -> usize {
+        self.role.get_shard_id()
+    }
+
+    /// The difficulty the blocks of the shard are mined at
+    pub fn get_difficulty(&self, shard_id: usize) -> H256 {
+        match self.shard_diffs.get(shard_id) {
+            Some(difficulty) => difficulty.clone(),
+            None => self.difficulty.clone(),
+        }
+    }
+
+    /// Move the node and all the components sharing its configuration to the shard
+    pub fn set_shard_id(&mut self, shard_id: usize) {
+        self.role.set_shard_id(shard_id);
+    }
 }
@@ -143,7 +143,7 @@ impl Confirmation {
                 for input in tx.inputs.iter() {
                     let input_shard_id = Validator::get_shard_id(
                         &input.sender_addr, 
-                        self.config.shard_num
+                        &self.config
                     );
                     let tmy_unit = tmy.get_tmy_unit(&input.hash()).unwrap();
                     let originate_block = tmy_unit.get_ori_blk_hash();
@@ -172,7 +172,7 @@ impl Confirmation {
                 for output in tx.outputs.iter() {
                     let output_shard_id = Validator::get_shard_id(
                         &output.receiver_addr,
-                        self.config.shard_num
+                        &self.config
                     );
                     let tmy_unit = tmy.get_tmy_unit(&output.hash()).unwrap();
                     let originate_block = tmy_unit.get_ori_blk_hash();
@@ -241,8 +241,8 @@ impl Confirmation {
         for output in tx.outputs.iter() {
             if Validator::get_shard_id(
                 &output.receiver_addr,
-                self.config.shard_num
-            ) == self.config.shard_id {
+                &self.config
+            ) == self.config.shard_id() {
                 let tmy_unit = TestimonyUnit::create(
                     output.hash(),
                     block_hash.clone(),
@@ -378,7 +378,7 @@ impl Confirmation {
                             let tmy_unit = tmy.get_tmy_unit(&input.hash()).unwrap();
                             let ori_shard_id = Validator::get_shard_id(
                                 &input.sender_addr, 
-                                self.config.shard_num
+                                &self.config
                             );
                             let ori_block_hash = tmy_unit.get_ori_blk_hash();
                             input_ori_blocks.insert(
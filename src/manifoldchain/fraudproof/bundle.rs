@@ -0,0 +1,294 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        block::{
+            Info,
+            consensus_block::ConsensusBlock,
+            exclusive_block::ExclusiveBlock,
+            versa_block::VersaBlock,
+        },
+        blockchain::VerStatus,
+        configuration::Configuration,
+        fraudproof::FraudProof,
+        sharding::ShardMap,
+        validator::{ChainView, ProofCheck, Validator},
+    },
+};
+use serde::{Serialize, Deserialize};
+use std::fs;
+
+//a fraud proof with the headers it depends on, so that it can be verified offline
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FraudProofBundle {
+    pub fraud_proof: FraudProof,
+    //the parameters of the network the proof is checked with
+    pub shard_num: usize,
+    #[serde(default = "default_shard_policy")]
+    pub shard_policy: String, //the spec of the shard map
+    #[serde(default)]
+    pub genesis_roots: Vec<H256>, //the states the shards start from after a resharding
+    pub block_size: usize,
+    pub difficulty: H256,
+    pub thredshold: H256,
+    #[serde(default)]
+    pub shard_diffs: Vec<H256>, //the difficulty of every shard, if they differ
+    pub blocks: Vec<BundleBlock>,
+}
+
+fn default_shard_policy() -> String {
+    String::from("modulo")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BundleBlock {
+    pub shard_id: u32,
+    pub header: VersaBlock,
+    //the parent of the block in the chain of the shard, if it is exported as well
+    pub parent: Option<H256>,
+    //the chain of the shard as seen by the exporting node
+    pub in_longest_chain: bool,
+    pub confirmed: bool,
+}
+
+impl FraudProofBundle {
+    pub fn export(fraud_proof: &FraudProof, view: &dyn ChainView, config: &Configuration) -> Self {
+        let mut bundle = FraudProofBundle {
+            fraud_proof: fraud_proof.clone(),
+            shard_num: config.shard_num,
+            shard_policy: config.shard_map.get_spec().to_string(),
+            genesis_roots: config.genesis_roots.clone(),
+            block_size: config.block_size,
+            difficulty: config.difficulty.clone(),
+            thredshold: config.thredshold.clone(),
+            shard_diffs: config.shard_diffs.clone(),
+            blocks: vec![],
+        };
+        bundle.add_dependencies(fraud_proof, view, config);
+        bundle
+    }
+
+    fn add_dependencies(&mut self, fraud_proof: &FraudProof, view: &dyn ChainView, config: &Configuration) {
+        let block_hash = fraud_proof.get_invalid_block();
+        let shard_id = fraud_proof.get_shard_id();
+        match fraud_proof {
+            //the conflict block is an ancestor of the invalid block
+            FraudProof::DoubleSpending(fp) => {
+                self.add_path(Some(&fp.conflict_block_hash), &block_hash, shard_id, view);
+            }
+            FraudProof::UnequalCoins(fp) if fp.conflict_tx.is_some() => {
+                self.add_path(Some(&fp.conflict_block_hash), &block_hash, shard_id, view);
+            }
+            FraudProof::WrongSignature(fp) => {
+                //an absolute timelock is checked with the height of the invalid block,
+                //counted from the genesis block
+                let absolute = fp.invalid_tx.inputs
+                    .iter()
+                    .filter(|input| input.tx_hash == fp.conflict_tx.hash())
+                    .filter_map(|input| fp.conflict_tx.outputs.get(input.index as usize))
+                    .any(|output| output.lock
+                        .as_ref()
+                        .map_or(false, |lock| lock.has_absolute_timelock()));
+                let ancestor = match absolute {
+                    true => ExclusiveBlock::genesis_of(shard_id, config).hash(),
+                    false => fp.conflict_block_hash.clone(),
+                };
+                self.add_path(Some(&ancestor), &block_hash, shard_id, view);
+            }
+            //the blocks the testimony refers to in the other shards
+            FraudProof::WrongTestimony(fp) => {
+                self.add_path(None, &block_hash, shard_id, view);
+                for input in fp.invalid_tx.inputs.iter() {
+                    let input_shard_id = Validator::get_shard_id(
+                        &input.sender_addr,
+                        config
+                    );
+                    if input_shard_id == shard_id {
+                        continue;
+                    }
+                    if let Some(ori_block_hash) = fp.invalid_tmy.get_ori_blk_hash(input.hash()) {
+                        self.add_path(None, &ori_block_hash, input_shard_id, view);
+                    }
+                }
+            }
+            //the blocks from the parent back to the one committing its state
+            FraudProof::WrongStateRoot(fp) => {
+                self.add_path(None, &block_hash, shard_id, view);
+                if let Some(state_block) = view.get_state_block_with_shard(&fp.parent_hash, shard_id) {
+                    self.add_path(Some(&state_block.hash()), &fp.parent_hash, shard_id, view);
+                }
+            }
+            FraudProof::InvalidParent(fp) => {
+                if let Some(parent_proof) = &fp.parent_proof {
+                    self.add_dependencies(parent_proof, view, config);
+                }
+            }
+            _ => self.add_path(None, &block_hash, shard_id, view),
+        }
+    }
+
+    //add the headers of the chain from the ancestor, or only the block, to the block
+    fn add_path(
+        &mut self,
+        ancestor: Option<&H256>,
+        block_hash: &H256,
+        shard_id: usize,
+        view: &dyn ChainView
+    ) {
+        let path = match view.all_blocks_end_with_block_with_shard(block_hash, shard_id) {
+            Some(path) => path,
+            None => return,
+        };
+        let start = match ancestor {
+            Some(ancestor) => match path.iter().position(|hash| hash == ancestor) {
+                Some(start) => start,
+                None => path.len() - 1,
+            },
+            None => path.len() - 1,
+        };
+        for i in start..path.len() {
+            let parent = match i > start {
+                true => Some(path[i - 1].clone()),
+                false => None,
+            };
+            let index = self.blocks
+                .iter()
+                .position(|blk| blk.header.hash() == path[i] && blk.shard_id == shard_id as u32);
+            if let Some(index) = index {
+                if parent.is_some() {
+                    self.blocks[index].parent = parent;
+                }
+                continue;
+            }
+            let header = match view.get_block_by_shard(&path[i], shard_id) {
+                Some(block) => block.to_header(),
+                None => return,
+            };
+            self.blocks.push(BundleBlock {
+                shard_id: shard_id as u32,
+                header,
+                parent,
+                in_longest_chain: view.is_block_in_longest_chain(shard_id, &path[i]),
+                confirmed: view.is_block_confirmed(shard_id, &path[i]),
+            });
+        }
+    }
+
+    fn get_bundle_block(&self, hash: &H256, shard_id: usize) -> Option<&BundleBlock> {
+        self.blocks
+            .iter()
+            .find(|blk| blk.shard_id == shard_id as u32 && blk.header.hash() == *hash)
+    }
+
+    //the parents of the header in the chain of the shard
+    fn get_parents(header: &VersaBlock, shard_id: usize) -> Vec<H256> {
+        match header.get_global_parents() {
+            Some(global_parents) if header.get_shard_id() != shard_id => global_parents
+                .into_iter()
+                .filter(|(_, parent_shard_id)| *parent_shard_id == shard_id)
+                .flat_map(|(parents, _)| parents)
+                .collect(),
+            _ => header.get_inter_parents(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("fail to encode the fraud proof: {}", e))?;
+        fs::write(path, content)
+            .map_err(|e| format!("fail to write {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("fail to read {}: {}", path, e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("fail to parse {}: {}", path, e))
+    }
+
+    //check the headers, then the proof against them with the checks of the validator
+    pub fn verify(&self) -> Result<bool, String> {
+        for blk in self.blocks.iter() {
+            if !blk.header.verify_hash() {
+                return Err(format!("header {:?} does not match its hash", blk.header.hash()));
+            }
+            if blk.shard_id as usize >= self.shard_num {
+                return Err(format!("header {:?} in no shard", blk.header.hash()));
+            }
+        }
+        let mut config = Configuration::new();
+        config.shard_num = self.shard_num;
+        config.shard_map = ShardMap::from_spec(&self.shard_policy, self.shard_num)?;
+        config.genesis_roots = self.genesis_roots.clone();
+        config.block_size = self.block_size;
+        config.difficulty = self.difficulty.clone();
+        config.thredshold = self.thredshold.clone();
+        config.shard_diffs = self.shard_diffs.clone();
+        Ok(Validator::check_fraud_proof(self, &config, &self.fraud_proof) == ProofCheck::Valid)
+    }
+}
+
+impl ChainView for FraudProofBundle {
+    fn get_block_by_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock> {
+        self.get_bundle_block(hash, shard_id)
+            .map(|blk| blk.header.clone())
+    }
+
+    //the fault is judged on the blocks alone, whatever the exporting node made of them
+    fn get_verify_status_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VerStatus> {
+        self.get_bundle_block(hash, shard_id)
+            .map(|_| VerStatus::Unverified)
+    }
+
+    //the exported part of the chain ending with the block, each link checked
+    //against the parents of the header
+    fn all_blocks_end_with_block_with_shard(&self, hash: &H256, shard_id: usize)
+        -> Option<Vec<H256>> {
+        let mut blk = self.get_bundle_block(hash, shard_id)?;
+        let mut path: Vec<H256> = vec![hash.clone()];
+        while let Some(parent) = blk.parent.as_ref() {
+            if path.contains(parent) ||
+                !Self::get_parents(&blk.header, shard_id).contains(parent) {
+                break;
+            }
+            blk = match self.get_bundle_block(parent, shard_id) {
+                Some(parent_blk) => parent_blk,
+                None => break,
+            };
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn get_consensus_block_by_shard(&self, shard_id: usize, hash: &H256) -> Option<ConsensusBlock> {
+        match self.get_block_by_shard(hash, shard_id)? {
+            VersaBlock::ExBlock(ex_block) => Some(ex_block.get_cons_block()),
+            VersaBlock::InBlock(in_block) => Some(in_block.get_cons_block()),
+            VersaBlock::ExFullBlock(ex_full_block) => Some(ex_full_block.get_exclusive_block().get_cons_block()),
+            VersaBlock::InFullBlock(in_full_block) => Some(in_full_block.get_inclusive_block().get_cons_block()),
+        }
+    }
+
+    fn get_state_block_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock> {
+        let path = self.all_blocks_end_with_block_with_shard(hash, shard_id)?;
+        for blk_hash in path.iter().rev() {
+            let block = self.get_block_by_shard(blk_hash, shard_id)?;
+            if block.get_shard_id() == shard_id {
+                return Some(block);
+            }
+        }
+        None
+    }
+
+    fn is_block_in_longest_chain(&self, shard_id: usize, hash: &H256) -> bool {
+        self.get_bundle_block(hash, shard_id)
+            .map(|blk| blk.in_longest_chain)
+            .unwrap_or(false)
+    }
+
+    fn is_block_confirmed(&self, shard_id: usize, hash: &H256) -> bool {
+        self.get_bundle_block(hash, shard_id)
+            .map(|blk| blk.confirmed)
+            .unwrap_or(false)
+    }
+}
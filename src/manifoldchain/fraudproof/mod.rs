@@ -1,8 +1,16 @@
+pub mod bundle;
+
 use crate::{
     types::{
-        hash::{H256, Hashable}
+        hash::{H256, Hashable},
+        sparse_merkle::SparseMerkleProof,
     },
     manifoldchain::{
+        block::{
+            Info,
+            consensus_block::ConsensusBlock,
+            inclusive_block::InclusiveBlock,
+        },
         transaction::Transaction,
         testimony::Testimony,
     }
@@ -18,7 +26,10 @@ pub enum FraudProof {
     WrongSignature(WrongSignatureProof),
     TestimonyLost(TestimonyLostProof),
     WrongTestimony(WrongTestimonyProof),
-    UnsolvedFault,
+    WrongStateRoot(WrongStateRootProof),
+    InvalidParent(InvalidParentProof),
+    WrongParentShard(WrongParentShardProof),
+    WrongParentRoot(WrongParentRootProof),
 }
 
 impl Hashable for FraudProof {
@@ -31,7 +42,10 @@ impl Hashable for FraudProof {
             FraudProof::WrongSignature(fp) => fp.hash(),
             FraudProof::TestimonyLost(fp) => fp.hash(),
             FraudProof::WrongTestimony(fp) => fp.hash(),
-            FraudProof::UnsolvedFault => H256::default(),
+            FraudProof::WrongStateRoot(fp) => fp.hash(),
+            FraudProof::InvalidParent(fp) => fp.hash(),
+            FraudProof::WrongParentShard(fp) => fp.hash(),
+            FraudProof::WrongParentRoot(fp) => fp.hash(),
         }
     }
 }
@@ -46,7 +60,10 @@ impl FraudProof {
             FraudProof::WrongSignature(fp) => fp.invalid_block_hash.clone(),
             FraudProof::TestimonyLost(fp) => fp.block_hash.clone(),
             FraudProof::WrongTestimony(fp) => fp.block_hash.clone(),
-            FraudProof::UnsolvedFault => H256::default(),
+            FraudProof::WrongStateRoot(fp) => fp.block_hash.clone(),
+            FraudProof::InvalidParent(fp) => fp.block.hash(),
+            FraudProof::WrongParentShard(fp) => fp.block.hash(),
+            FraudProof::WrongParentRoot(fp) => fp.block.hash(),
         }
     }
 
@@ -59,7 +76,10 @@ impl FraudProof {
             FraudProof::WrongSignature(fp) => fp.shard_id.clone() as usize,
             FraudProof::TestimonyLost(fp) => fp.shard_id.clone() as usize,
             FraudProof::WrongTestimony(fp) => fp.shard_id.clone() as usize,
-            FraudProof::UnsolvedFault => 0,
+            FraudProof::WrongStateRoot(fp) => fp.shard_id.clone() as usize,
+            FraudProof::InvalidParent(fp) => fp.shard_id.clone() as usize,
+            FraudProof::WrongParentShard(fp) => fp.shard_id.clone() as usize,
+            FraudProof::WrongParentRoot(fp) => fp.shard_id.clone() as usize,
         }
     }
 
@@ -72,7 +92,11 @@ impl FraudProof {
             FraudProof::WrongSignature(fp) => fp.invalid_tx.clone(),
             FraudProof::TestimonyLost(fp) => fp.invalid_tx.clone(),
             FraudProof::WrongTestimony(fp) => fp.invalid_tx.clone(),
-            FraudProof::UnsolvedFault => Transaction::default(),
+            //the whole block is invalid rather than one of its transactions
+            FraudProof::WrongStateRoot(_) |
+            FraudProof::InvalidParent(_) |
+            FraudProof::WrongParentShard(_) |
+            FraudProof::WrongParentRoot(_) => Transaction::default(),
         }       
     }
 
@@ -85,7 +109,10 @@ impl FraudProof {
             FraudProof::WrongSignature(fp) => fp.invalid_tx_merkle_proof.clone(),
             FraudProof::TestimonyLost(fp) => fp.invalid_tx_merkle_proof.clone(),
             FraudProof::WrongTestimony(fp) => fp.invalid_tx_merkle_proof.clone(),
-            FraudProof::UnsolvedFault => vec![],
+            FraudProof::WrongStateRoot(_) |
+            FraudProof::InvalidParent(_) |
+            FraudProof::WrongParentShard(_) |
+            FraudProof::WrongParentRoot(_) => vec![],
         }
     }
 
@@ -98,7 +125,10 @@ impl FraudProof {
             FraudProof::WrongSignature(fp) => fp.invalid_index.clone() as usize,
             FraudProof::TestimonyLost(fp) => fp.invalid_index.clone() as usize,
             FraudProof::WrongTestimony(fp) => fp.invalid_index.clone() as usize,
-            FraudProof::UnsolvedFault => 0,
+            FraudProof::WrongStateRoot(_) |
+            FraudProof::InvalidParent(_) |
+            FraudProof::WrongParentShard(_) |
+            FraudProof::WrongParentRoot(_) => 0,
         }
     }
 }
@@ -206,6 +236,16 @@ pub struct UnequalCoinsProof {
     pub invalid_tx: Transaction,
     pub invalid_tx_merkle_proof: Vec<H256>,
     pub invalid_index: u32,
+
+    //the spent tx, if an input claims another value than the output it spends
+    #[serde(default)]
+    pub conflict_tx: Option<Transaction>,
+    #[serde(default)]
+    pub conflict_block_hash: H256,
+    #[serde(default)]
+    pub conflict_tx_merkle_proof: Vec<H256>,
+    #[serde(default)]
+    pub conflict_index: u32,
 }
 
 impl Hashable for UnequalCoinsProof {
@@ -222,6 +262,15 @@ impl Hashable for UnequalCoinsProof {
         ];
 
         tmp_vec.extend(self.invalid_tx_merkle_proof.clone());
+        if let Some(conflict_tx) = self.conflict_tx.as_ref() {
+            let str = format!("{}", self.conflict_index);
+            tmp_vec.push(ring::digest::digest(
+                &ring::digest::SHA256, str.as_bytes()
+            ).into());
+            tmp_vec.push(self.conflict_block_hash.clone());
+            tmp_vec.push(conflict_tx.hash());
+            tmp_vec.extend(self.conflict_tx_merkle_proof.clone());
+        }
         H256::multi_hash(&tmp_vec)
     }
 }
@@ -328,3 +377,131 @@ impl Hashable for WrongTestimonyProof {
     }
 }
 
+//the state root committed by the block does not follow from the state of its parent.
+//state_proofs replay the updates of the txs on the parent's state root, each one
+//against the root left by the previous one
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub struct WrongStateRootProof {
+    pub shard_id: u32,
+    pub block_hash: H256,
+    pub parent_hash: H256,
+
+    pub txs: Vec<Transaction>,
+    pub state_proofs: Vec<SparseMerkleProof>,
+}
+
+impl Hashable for WrongStateRootProof {
+    fn hash(&self) -> H256 {
+        let str = format!("{}{}", self.shard_id, self.state_proofs.len());
+        let str_hash: H256 = ring::digest::digest(
+            &ring::digest::SHA256, str.as_bytes()
+        ).into();
+
+        let mut tmp_vec: Vec<H256> = vec![
+            str_hash,
+            self.block_hash.clone(),
+            self.parent_hash.clone(),
+        ];
+
+        tmp_vec.extend(self.txs.iter().map(|tx| tx.hash()));
+        for proof in self.state_proofs.iter() {
+            tmp_vec.extend(proof.siblings.clone());
+        }
+        H256::multi_hash(&tmp_vec)
+    }
+}
+
+//the inclusive block carries the parents it was relayed with, the evidence below is
+//checked against them without the chains of the other shards
+
+//a parent of the block is not a valid block of its shard: either its hash can not be
+//the hash of any block (it is not the genesis and misses the PoW), or parent_proof
+//proves the parent itself invalid, which is why it was pruned
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub struct InvalidParentProof {
+    pub shard_id: u32,
+    pub block: InclusiveBlock,
+
+    pub parent_hash: H256,
+    pub parent_shard_id: u32,
+    pub parent_proof: Option<Box<FraudProof>>,
+}
+
+impl Hashable for InvalidParentProof {
+    fn hash(&self) -> H256 {
+        let str = format!("{}{}", self.shard_id, self.parent_shard_id);
+        let str_hash: H256 = ring::digest::digest(
+            &ring::digest::SHA256, str.as_bytes()
+        ).into();
+
+        let parent_proof_hash = match &self.parent_proof {
+            Some(fp) => fp.hash(),
+            None => H256::default(),
+        };
+        let tmp_vec: Vec<H256> = vec![
+            str_hash,
+            self.block.hash(),
+            self.parent_hash.clone(),
+            parent_proof_hash,
+        ];
+        H256::multi_hash(&tmp_vec)
+    }
+}
+
+//a parent of the block in parent_shard_id is an exclusive block of another shard,
+//as shown by its header
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub struct WrongParentShardProof {
+    pub shard_id: u32,
+    pub block: InclusiveBlock,
+
+    pub parent_shard_id: u32,
+    pub parent_header: ConsensusBlock,
+}
+
+impl Hashable for WrongParentShardProof {
+    fn hash(&self) -> H256 {
+        let str = format!(
+            "{}{}{}",
+            self.shard_id,
+            self.parent_shard_id,
+            self.parent_header.get_shard_id()
+        );
+        let str_hash: H256 = ring::digest::digest(
+            &ring::digest::SHA256, str.as_bytes()
+        ).into();
+
+        let tmp_vec: Vec<H256> = vec![
+            str_hash,
+            self.block.hash(),
+            self.parent_header.hash(),
+        ];
+        H256::multi_hash(&tmp_vec)
+    }
+}
+
+//the inter parents or the global parents of the block do not match the roots
+//committed by its header
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub struct WrongParentRootProof {
+    pub shard_id: u32,
+    pub block: InclusiveBlock,
+}
+
+impl Hashable for WrongParentRootProof {
+    fn hash(&self) -> H256 {
+        let str = format!("{}", self.shard_id);
+        let str_hash: H256 = ring::digest::digest(
+            &ring::digest::SHA256, str.as_bytes()
+        ).into();
+
+        //the parents as relayed, with their shards
+        let tmp_vec: Vec<H256> = vec![
+            str_hash,
+            self.block.hash(),
+            H256::multi_hash(&self.block.get_inter_parents()),
+            ConsensusBlock::get_global_parents_root(&self.block.get_global_parents()),
+        ];
+        H256::multi_hash(&tmp_vec)
+    }
+}
@@ -0,0 +1,496 @@
+use crate::{
+    types::{
+        hash::{H256, Hashable},
+        merkle::MerkleTree,
+    },
+    manifoldchain::{
+        block::{
+            Info,
+            consensus_block::ConsensusBlock,
+            exclusive_block::ExclusiveBlock,
+            versa_block::VersaBlock,
+        },
+        configuration::Configuration,
+        network::{
+            message::Message,
+            request::RequestTracker,
+        },
+        testimony::{Testimony, TestimonyUnit},
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+};
+use log::{debug, info};
+use serde::{Serialize, Deserialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+    time,
+};
+
+//the number of headers in one Headers message
+pub const MAX_HEADERS: usize = 512;
+
+//the interval between two rounds of header sync
+const HEADER_SYNC_INTERVAL: u64 = 2000; //ms
+
+//the inclusion of a transaction in a block of the shard, served by the full nodes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxProof {
+    pub tx: Transaction,
+    pub block_hash: H256,
+    pub tx_index: u32,
+    pub tx_merkle_proof: Vec<H256>,
+    //the testimony of a cross-shard transaction, proving its inputs in the other shards
+    pub testimony: Option<Testimony>,
+}
+
+struct HeaderEntry {
+    header: ConsensusBlock,
+    parent: H256,
+    height: usize,
+}
+
+//the headers of one shard, with their parent links checked
+pub struct HeaderChain {
+    shard_id: usize,
+    headers: HashMap<H256, HeaderEntry>,
+    genesis: H256,
+    tip: H256,
+    height: usize,
+}
+
+impl HeaderChain {
+    pub fn new(shard_id: usize, config: &Configuration) -> Self {
+        let genesis = ExclusiveBlock::genesis_of(shard_id, config);
+        let genesis_hash = genesis.hash();
+        let mut headers: HashMap<H256, HeaderEntry> = HashMap::new();
+        headers.insert(genesis_hash, HeaderEntry {
+            header: genesis.get_cons_block(),
+            parent: H256::default(),
+            height: 0,
+        });
+        HeaderChain {
+            shard_id,
+            headers,
+            genesis: genesis_hash,
+            tip: genesis_hash,
+            height: 0,
+        }
+    }
+
+    //the parents of the header in this shard, an inclusive block of another shard
+    //extends this shard through its global parents
+    fn get_parents(&self, header: &VersaBlock) -> Result<Vec<H256>, String> {
+        match header {
+            VersaBlock::ExBlock(ex_block) => {
+                if ex_block.get_shard_id() != self.shard_id {
+                    return Err(format!(
+                        "exclusive block of shard {} in shard {}",
+                        ex_block.get_shard_id(),
+                        self.shard_id
+                    ));
+                }
+                Ok(ex_block.get_inter_parents())
+            }
+            VersaBlock::InBlock(in_block) => {
+                if in_block.get_shard_id() == self.shard_id {
+                    return Ok(in_block.get_inter_parents());
+                }
+                Ok(in_block
+                    .get_global_parents()
+                    .into_iter()
+                    .filter(|(_, shard_id)| *shard_id == self.shard_id)
+                    .flat_map(|(parents, _)| parents)
+                    .collect())
+            }
+            _ => Err(String::from("full block instead of a header")),
+        }
+    }
+
+    //check the format and the PoW of the header, independent of the chain
+    pub fn check_header(header: &VersaBlock, config: &Configuration) -> Result<(), String> {
+        let format_ok = match header {
+            VersaBlock::ExBlock(ex_block) => ex_block.verify_format(),
+            VersaBlock::InBlock(in_block) => in_block.verify_format(),
+            _ => return Err(String::from("full block instead of a header")),
+        };
+        if !format_ok {
+            return Err(String::from("the hash or the parents do not match the header"));
+        }
+        let hash = header.hash();
+        let difficulty = config.get_difficulty(header.get_shard_id());
+        if header.get_difficulty() != difficulty || hash > difficulty {
+            return Err(format!("invalid PoW of header {:?}", hash));
+        }
+        if let VersaBlock::InBlock(_) = header {
+            if hash > config.thredshold {
+                return Err(format!("inclusive header {:?} above the threshold", hash));
+            }
+        }
+        Ok(())
+    }
+
+    //insert a checked header, return false if it is known or its parents are not
+    pub fn insert(&mut self, header: &VersaBlock) -> Result<bool, String> {
+        let hash = header.hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(false);
+        }
+        //follow the highest known parent
+        let parent = self.get_parents(header)?
+            .into_iter()
+            .filter_map(|parent| self.headers
+                .get(&parent)
+                .map(|entry| (parent, entry.height))
+            )
+            .max_by_key(|(_, height)| *height);
+        let (parent, parent_height) = match parent {
+            Some(parent) => parent,
+            None => {
+                debug!("Header {:?} of shard {}: parent not found", hash, self.shard_id);
+                return Ok(false);
+            }
+        };
+        let cons_block = match header {
+            VersaBlock::ExBlock(ex_block) => ex_block.get_cons_block(),
+            VersaBlock::InBlock(in_block) => in_block.get_cons_block(),
+            _ => unreachable!(),
+        };
+        let height = parent_height + 1;
+        self.headers.insert(hash, HeaderEntry {
+            header: cons_block,
+            parent,
+            height,
+        });
+        if height > self.height {
+            self.tip = hash;
+            self.height = height;
+        }
+        Ok(true)
+    }
+
+    //the hashes from the tip back to the genesis, dense at the tip and sparse below,
+    //the first one known by the full node is where its headers start
+    pub fn locator(&self) -> Vec<H256> {
+        let mut locator: Vec<H256> = vec![];
+        let mut hash = self.tip;
+        let mut step: usize = 1;
+        loop {
+            locator.push(hash);
+            if hash == self.genesis {
+                break;
+            }
+            for _ in 0..step {
+                if hash == self.genesis {
+                    break;
+                }
+                hash = self.headers[&hash].parent;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+        }
+        locator
+    }
+
+    pub fn get_header(&self, hash: &H256) -> Option<ConsensusBlock> {
+        self.headers.get(hash).map(|entry| entry.header.clone())
+    }
+
+    pub fn get_height(&self, hash: &H256) -> Option<usize> {
+        self.headers.get(hash).map(|entry| entry.height)
+    }
+
+    pub fn is_in_longest_chain(&self, hash: &H256) -> bool {
+        let height = match self.headers.get(hash) {
+            Some(entry) => entry.height,
+            None => return false,
+        };
+        let mut curr = self.tip;
+        while self.headers[&curr].height > height {
+            curr = self.headers[&curr].parent;
+        }
+        curr == *hash
+    }
+
+    //the number of headers on top of the block in the longest chain
+    pub fn get_depth(&self, hash: &H256) -> Option<usize> {
+        match self.is_in_longest_chain(hash) {
+            true => Some(self.height - self.headers[hash].height),
+            false => None,
+        }
+    }
+
+    pub fn tip(&self) -> (H256, usize) {
+        (self.tip, self.height)
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HeaderChainStatus {
+    pub shard_id: usize,
+    pub tip: H256,
+    pub height: usize,
+    pub headers: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PaymentStatus {
+    pub tx_hash: H256,
+    pub shard_id: usize,
+    pub block_hash: H256,
+    pub depth: Option<usize>, //None if the block is not in the longest chain any more
+    pub confirmed: bool,
+    pub testimony_units: usize, //the verified testimony units of a cross-shard transaction
+}
+
+struct VerifiedPayment {
+    shard_id: usize,
+    block_hash: H256,
+    testimony_units: usize,
+}
+
+struct LightState {
+    chains: HashMap<usize, HeaderChain>,
+    payments: HashMap<H256, VerifiedPayment>, //tx_hash -> the verified inclusion
+}
+
+//A light client syncs only the headers of the followed shards from the full
+//nodes, and verifies the payments through the Merkle proofs against them.
+#[derive(Clone)]
+pub struct LightClient {
+    inner: Arc<Mutex<LightState>>,
+    requests: RequestTracker,
+    config: Configuration,
+}
+
+//the transaction whose inclusion the testimony units prove, it is the previous
+//stage of the cross-shard transaction
+fn originating_tx(tx: &Transaction) -> Option<Transaction> {
+    let flag = match tx.flag {
+        TxFlag::Output => TxFlag::Input,
+        TxFlag::Accept | TxFlag::Reject => TxFlag::Output,
+        _ => return None,
+    };
+    let mut ori_tx = tx.clone();
+    ori_tx.flag = flag;
+    Some(ori_tx)
+}
+
+impl LightState {
+    //verify every unit of the testimony whose originate shard is followed, return
+    //the number of verified units
+    fn verify_testimony(
+        &self,
+        tx: &Transaction,
+        tmy: &Testimony,
+        config: &Configuration
+    ) -> Result<usize, String> {
+        if tmy.get_tx_hash() != tx.hash() {
+            return Err(String::from("the testimony is not for the transaction"));
+        }
+        let ori_tx = match originating_tx(tx) {
+            Some(ori_tx) => ori_tx,
+            None => return Err(String::from("the transaction has no testimony")),
+        };
+        //the shard of every unit, by the hash of the input or output it testifies
+        let unit_shards: HashMap<H256, usize> = match ori_tx.flag {
+            TxFlag::Input => ori_tx.inputs
+                .iter()
+                .map(|input| (input.hash(), Validator::get_shard_id(&input.sender_addr, &config)))
+                .collect(),
+            _ => ori_tx.outputs
+                .iter()
+                .map(|output| (output.hash(), Validator::get_shard_id(&output.receiver_addr, &config)))
+                .collect(),
+        };
+        let units: Vec<(TestimonyUnit, usize)> = unit_shards
+            .iter()
+            .filter_map(|(hash, shard_id)| tmy.get_tmy_unit(hash).map(|unit| (unit, *shard_id)))
+            .collect();
+        if units.len() != tmy.get_tmy_units().len() {
+            return Err(String::from("the testimony unit is not for any input or output"));
+        }
+        let ori_tx_hash = ori_tx.hash();
+        let mut verified: usize = 0;
+        for (unit, shard_id) in units {
+            let chain = match self.chains.get(&shard_id) {
+                Some(chain) => chain,
+                None => continue,
+            };
+            let header = match chain.get_header(&unit.get_ori_blk_hash()) {
+                Some(header) => header,
+                None => return Err(format!(
+                    "originate block {:?} unknown in shard {}",
+                    unit.get_ori_blk_hash(),
+                    shard_id
+                )),
+            };
+            if !MerkleTree::<Transaction>::check_proof(
+                &header.get_tx_merkle_root(),
+                &ori_tx_hash,
+                &unit.get_tx_merkle_proof(),
+                unit.get_tx_index(),
+                config.block_size,
+            ) {
+                return Err(String::from("invalid Merkle proof in the testimony"));
+            }
+            verified += 1;
+        }
+        Ok(verified)
+    }
+}
+
+impl LightClient {
+    pub fn new(config: &Configuration, requests: &RequestTracker) -> Self {
+        let chains: HashMap<usize, HeaderChain> = config.light_shards
+            .iter()
+            .map(|shard_id| (*shard_id, HeaderChain::new(*shard_id, config)))
+            .collect();
+        LightClient {
+            inner: Arc::new(Mutex::new(LightState {
+                chains,
+                payments: HashMap::new(),
+            })),
+            requests: requests.clone(),
+            config: config.clone(),
+        }
+    }
+
+    //ask a full node of every followed shard for the headers after the local tip
+    pub fn start(&self) {
+        let light_client = self.clone();
+        thread::Builder::new()
+            .name("Header-Sync".to_string())
+            .spawn(move || loop {
+                for (shard_id, locator) in light_client.get_locators() {
+                    light_client.requests.request_from_shard(
+                        Message::GetHeaders((locator, shard_id as u32)),
+                        shard_id,
+                    );
+                }
+                thread::sleep(time::Duration::from_millis(HEADER_SYNC_INTERVAL));
+            })
+            .unwrap();
+        info!("Header sync started for shards {:?}", self.config.light_shards);
+    }
+
+    pub fn get_locators(&self) -> Vec<(usize, Vec<H256>)> {
+        let inner = self.inner.lock().unwrap();
+        inner.chains
+            .iter()
+            .map(|(shard_id, chain)| (*shard_id, chain.locator()))
+            .collect()
+    }
+
+    //insert the headers in order, return the number of new ones, or an error if
+    //any of them is invalid
+    pub fn insert_headers(&self, headers: &[VersaBlock], shard_id: usize) -> Result<usize, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let chain = match inner.chains.get_mut(&shard_id) {
+            Some(chain) => chain,
+            None => return Err(format!("shard {} is not followed", shard_id)),
+        };
+        let mut inserted: usize = 0;
+        for header in headers.iter() {
+            HeaderChain::check_header(header, &self.config)?;
+            if chain.insert(header)? {
+                inserted += 1;
+            }
+        }
+        if inserted > 0 {
+            let (tip, height) = chain.tip();
+            info!("Light client: tip of shard {} is {:?} at height {}", shard_id, tip, height);
+        }
+        Ok(inserted)
+    }
+
+    //ask a full node of the shard for the inclusion proof of the transaction
+    pub fn request_tx_proof(&self, tx_hash: H256, shard_id: usize) {
+        self.requests.request_from_shard(
+            Message::GetTxProof((tx_hash, shard_id as u32)),
+            shard_id,
+        );
+    }
+
+    //check the proof against the synced headers and remember the payment, return
+    //an error if the proof is invalid
+    pub fn handle_tx_proof(&self, proof: &TxProof, shard_id: usize) -> Result<(), String> {
+        let mut inner = self.inner.lock().unwrap();
+        let header = match inner.chains.get(&shard_id) {
+            Some(chain) => match chain.get_header(&proof.block_hash) {
+                Some(header) => header,
+                //the header may come later, the proof is requested again then
+                None => {
+                    debug!("Tx proof for unknown block {:?}", proof.block_hash);
+                    return Ok(());
+                }
+            },
+            None => return Err(format!("shard {} is not followed", shard_id)),
+        };
+        let tx_hash = proof.tx.hash();
+        if !MerkleTree::<Transaction>::check_proof(
+            &header.get_tx_merkle_root(),
+            &tx_hash,
+            &proof.tx_merkle_proof,
+            proof.tx_index as usize,
+            self.config.block_size,
+        ) {
+            return Err(format!("invalid Merkle proof of tx {:?}", tx_hash));
+        }
+        let testimony_units = match &proof.testimony {
+            Some(tmy) => inner.verify_testimony(&proof.tx, tmy, &self.config)?,
+            None => 0,
+        };
+        info!("Light client: tx {:?} included in block {:?} of shard {}", tx_hash, proof.block_hash, shard_id);
+        inner.payments.insert(tx_hash, VerifiedPayment {
+            shard_id,
+            block_hash: proof.block_hash,
+            testimony_units,
+        });
+        Ok(())
+    }
+
+    //verify the testimony of a cross-shard transaction against the synced headers
+    pub fn verify_testimony(&self, tx: &Transaction, tmy: &Testimony) -> Result<usize, String> {
+        self.inner.lock().unwrap().verify_testimony(tx, tmy, &self.config)
+    }
+
+    pub fn get_payment(&self, tx_hash: &H256) -> Option<PaymentStatus> {
+        let inner = self.inner.lock().unwrap();
+        let payment = inner.payments.get(tx_hash)?;
+        let depth = inner.chains[&payment.shard_id].get_depth(&payment.block_hash);
+        Some(PaymentStatus {
+            tx_hash: *tx_hash,
+            shard_id: payment.shard_id,
+            block_hash: payment.block_hash,
+            depth,
+            confirmed: depth.map(|depth| depth >= self.config.k).unwrap_or(false),
+            testimony_units: payment.testimony_units,
+        })
+    }
+
+    pub fn get_status(&self) -> Vec<HeaderChainStatus> {
+        let inner = self.inner.lock().unwrap();
+        let mut status: Vec<HeaderChainStatus> = inner.chains
+            .iter()
+            .map(|(shard_id, chain)| {
+                let (tip, height) = chain.tip();
+                HeaderChainStatus {
+                    shard_id: *shard_id,
+                    tip,
+                    height,
+                    headers: chain.len(),
+                }
+            })
+            .collect();
+        status.sort_by_key(|chain| chain.shard_id);
+        status
+    }
+}
@@ -10,12 +10,16 @@ use crate::{
         },
         database::Database,
         configuration::Configuration,
+        block::compact_block::{ShortId, short_id},
     },
 };
 use std::collections::{VecDeque, HashMap};
 use log::{info, debug};
 use std::time::{SystemTime};
 
+//the number of popped txs kept for the reconstruction of compact blocks
+const MAX_POPPED_TXS: usize = 8192;
+
 pub struct Mempool {
     //txs_map: HashMap<H256, Transaction>, //the key is the hash of the tx, while value is the
     txs_map: Database<Transaction>,
@@ -25,6 +29,13 @@ pub struct Mempool {
     //exact value of tx
     txs_queue: VecDeque<H256>,
     tx2tmy: HashMap<H256, H256>,
+    //short id -> tx hash, for both the txs in the mempool and the popped ones
+    short_ids: HashMap<ShortId, H256>,
+    //the txs (and their testimonies) packed into the block being mined are popped from
+    //the mempool, but blocks from other miners usually contain them as well
+    popped_txs: HashMap<H256, Transaction>,
+    popped_tmys: HashMap<H256, Testimony>,
+    popped_queue: VecDeque<(H256, Option<H256>)>,
 }
 
 
@@ -40,6 +51,10 @@ impl Mempool {
             testimony_map,
             txs_queue: VecDeque::new(),
             tx2tmy: HashMap::new(),
+            short_ids: HashMap::new(),
+            popped_txs: HashMap::new(),
+            popped_tmys: HashMap::new(),
+            popped_queue: VecDeque::new(),
         }
     }
 
@@ -61,6 +76,7 @@ impl Mempool {
             false
         } else {
             let _ = self.txs_map.insert(hash.clone(), tx.clone());
+            self.short_ids.insert(short_id(&hash), hash.clone());
             if tx.flag == TxFlag::Initial {
                 self.txs_queue.push_back(hash); 
             } else {
@@ -113,6 +129,8 @@ impl Mempool {
         for tx_hash in tx_hashs.iter() {
             self.txs_map.remove(&tx_hash);
             self.txs_queue.retain(|x| x != tx_hash);
+            self.popped_txs.remove(tx_hash);
+            self.short_ids.remove(&short_id(tx_hash));
         }
         true
     }
@@ -127,17 +145,57 @@ impl Mempool {
             let tx = self.txs_map.get(&tx_hash).unwrap().clone();
             self.txs_map.remove(&tx_hash);
             
-            if let Some(tmy_hash) = self.tx2tmy.get(&tx_hash) {
+            if let Some(tmy_hash) = self.tx2tmy.get(&tx_hash).cloned() {
                 let tmy = self.testimony_map.get(&tmy_hash).unwrap().clone();
                 self.testimony_map.remove(&tmy_hash);
                 self.tx2tmy.remove(&tx_hash);
+                self.keep_popped(&tx, Some(&tmy));
                 (Some(tx), Some(tmy.clone()))
             } else {
+                self.keep_popped(&tx, None);
                 (Some(tx), None)
             }
         }
     }
 
+    fn keep_popped(&mut self, tx: &Transaction, tmy: Option<&Testimony>) {
+        let tx_hash = tx.hash();
+        self.popped_txs.insert(tx_hash.clone(), tx.clone());
+        let tmy_hash = tmy.map(|tmy| {
+            let tmy_hash = tmy.hash();
+            self.popped_tmys.insert(tmy_hash.clone(), tmy.clone());
+            tmy_hash
+        });
+        self.popped_queue.push_back((tx_hash, tmy_hash));
+        while self.popped_queue.len() > MAX_POPPED_TXS {
+            let (old_tx_hash, old_tmy_hash) = self.popped_queue.pop_front().unwrap();
+            self.popped_txs.remove(&old_tx_hash);
+            if !self.txs_map.contains_key(&old_tx_hash) {
+                self.short_ids.remove(&short_id(&old_tx_hash));
+            }
+            if let Some(old_tmy_hash) = old_tmy_hash {
+                self.popped_tmys.remove(&old_tmy_hash);
+            }
+        }
+    }
+
+    //find the tx in the mempool or among the recently popped ones
+    pub fn get_tx_by_short_id(&self, id: ShortId) -> Option<Transaction> {
+        let tx_hash = self.short_ids.get(&id)?;
+        match self.get_tx(tx_hash) {
+            Some(tx) => Some(tx),
+            None => self.popped_txs.get(tx_hash).cloned(),
+        }
+    }
+
+    //find the testimony in the mempool or among the recently popped ones
+    pub fn find_testimony(&self, tmy_hash: &H256) -> Option<Testimony> {
+        match self.get_testimony(tmy_hash) {
+            Some(tmy) => Some(tmy),
+            None => self.popped_tmys.get(tmy_hash).cloned(),
+        }
+    }
+
     pub fn get_all_tx_hash(&self) -> Vec<H256> {
         let mut res: Vec<H256> = Vec::new();
         for (_, val) in self.txs_map.iter() {
@@ -34,9 +34,12 @@ use crate::{
             }
         },
         multichain::Multichain,
+        blockchain::Blockchain,
         transaction::{Transaction, TxFlag},
         validator::{
             Validator,
+            error::ValidationError,
+            stats::{ValidationStats, FROM_MINER},
         },
         configuration::Configuration,
         mempool::Mempool,
@@ -48,6 +51,9 @@ use crate::{
 };
 use rand::Rng;
 
+//the interval between two checks of the synced state while rotating to a new shard
+const ROTATION_PAUSE: u64 = 100; //ms
+
 enum ControlSignal {
     Start(u64), // the number controls the lambda of interval between block generation
     Update, // update the block in mining, it may due to new blockchain tip or new transaction
@@ -68,6 +74,7 @@ pub struct Context {
     multichain: Multichain,
     mempool: Arc<Mutex<Mempool>>,
     validator: Validator,
+    validation_stats: ValidationStats,
     config: Configuration,
 }
 
@@ -79,7 +86,8 @@ pub struct Handle {
 
 pub fn new(multichain: &Multichain, 
     mempool: &Arc<Mutex<Mempool>>, 
-    config: &Configuration) -> (Context, Handle, Receiver<MinerMessage>) 
+    config: &Configuration,
+    validation_stats: &ValidationStats) -> (Context, Handle, Receiver<MinerMessage>) 
 {
     let (signal_chan_sender, signal_chan_receiver) = unbounded();
     let (finished_block_sender, finished_block_receiver) = unbounded();
@@ -93,6 +101,7 @@ pub fn new(multichain: &Multichain,
         multichain: multichain.clone(),
         mempool: Arc::clone(mempool),
         validator,
+        validation_stats: validation_stats.clone(),
         config: config.clone()
     };
 
@@ -164,8 +173,8 @@ impl Context {
         global_parents: Vec<(Vec<H256>, usize)>) -> (ConsensusBlock, TransactionBlock) 
 
     {
-        let shard_id = self.config.shard_id;
-        let difficulty = self.config.difficulty.clone();
+        let shard_id = self.config.shard_id();
+        let difficulty = self.config.get_difficulty(shard_id);
         let nonce: usize = rand::thread_rng().gen();
 
         ConsensusBlock::generate(
@@ -304,7 +313,11 @@ impl Context {
                     let interval = time::Duration::from_micros(i as u64);
                     thread::sleep(interval);
                 }
- 
+                //a node rotating to a new shard mines once it has the state of the shard
+                if self.config.role.is_syncing() {
+                    thread::sleep(time::Duration::from_millis(ROTATION_PAUSE));
+                    continue;
+                }
 
                 let verified_parent = self.multichain.get_longest_verified_fork();
                 let inter_parents = self.multichain.get_inter_unverified_forks();
@@ -316,9 +329,6 @@ impl Context {
                     .map(|item| H256::multi_hash(&item.0))
                     .collect();
                 let curr_global_parents = H256::multi_hash(&tmp_global_parents);
-                let last_blk_hash = self.multichain.get_longest_chain_hash();
-                //assert_eq!(verified_parent, last_blk_hash);
-                //assert_eq!(inter_parents[0], last_blk_hash);
                 //check if parents have been change
                 if verified_parent != pre_verified_parent ||
                     curr_inter_parents != pre_inter_parents ||
@@ -355,9 +365,11 @@ impl Context {
                     
                     
                     //parent state
-
+                    //the blocks between the verified parent and the tip are not from this
+                    //shard, they share the state of the verified parent, which the new block
+                    //commits to
                     let last_state = states
-                        .get(&last_blk_hash)
+                        .get(&verified_parent)
                         .unwrap();
                     //package txs into block
                     let mut counter = 0;
@@ -367,7 +379,8 @@ impl Context {
                     let mut invalid_txs: Vec<Transaction> = Vec::new();
                     let mut invalid_tmys: Vec<Testimony> = Vec::new();
                     let mut set: HashMap<H256, bool> = HashMap::new();
-                    let mut err_types: Vec<String> = vec![];
+                    let mut err_types: Vec<ValidationError> = vec![];
+                    let height = self.validator.get_block_height(&inter_parents);
                     while counter < self.config.block_size {
                         let (possible_tx, possible_tmy) = self.mempool
                             .lock()
@@ -375,6 +388,20 @@ impl Context {
                             .pop_one_tx();
                         match possible_tx {
                             Some(tx) => {
+                                //the txs closed by the end of the epoch wait for the next one
+                                if let Some(plan) = self.config.reshard.as_ref() {
+                                    if !plan.allows(&tx, height) {
+                                        err_types.push(ValidationError::EpochClosed {
+                                            tx_hash: tx.hash(),
+                                            height,
+                                        });
+                                        invalid_txs.push(tx);
+                                        if let Some(tmy) = possible_tmy {
+                                            invalid_tmys.push(tmy);
+                                        }
+                                        continue;
+                                    }
+                                }
                                 if tx.flag == TxFlag::Initial {
                                     counter += 1;
                                     txs.push(tx);
@@ -382,11 +409,11 @@ impl Context {
                                 }
                                 //check if it is in the chain
                                 if let Some(_) = self.multichain.get_tx_in_longest_chain(&tx.hash()) {
+                                    err_types.push(ValidationError::KnownTx { tx_hash: tx.hash() });
                                     invalid_txs.push(tx);
                                     if let Some(tmy) = possible_tmy {
                                         invalid_tmys.push(tmy);
                                     }
-                                    err_types.push(String::from("in the longest chain"));
                                     continue;
                                 }
                                 //check whether all the units in collected
@@ -395,17 +422,22 @@ impl Context {
                                     tx.flag == TxFlag::Reject {
                                     if let Some(tmy) = possible_tmy.clone() {
                                         if !self.check_complete_testimony(&tx, &tmy) {
+                                            err_types.push(ValidationError::IncompleteTestimony {
+                                                tx_hash: tx.hash(),
+                                            });
                                             invalid_txs.push(tx);
                                             invalid_tmys.push(tmy);
-                                            err_types.push(String::from("incomplete tmy"));
                                             continue;
                                         }
                                     } else {
+                                        err_types.push(ValidationError::MissingTestimony {
+                                            tx_hash: tx.hash(),
+                                            proof: None,
+                                        });
                                         invalid_txs.push(tx);
                                         if let Some(tmy) = possible_tmy {
                                             invalid_tmys.push(tmy);
                                         }
-                                        err_types.push(String::from("missing tmy"));
                                         continue;
                                     }
                                 }
@@ -415,13 +447,13 @@ impl Context {
                                 match self.validator.check_tx_from_state(
                                     &tx, 
                                     possible_tmy.clone(), 
-                                    &last_blk_hash,
+                                    &verified_parent,
                                     last_state,
                                 )
                                 {
                                     Ok(_) => {} 
-                                    Err(fp) => {
-                                        err_types.push(String::from("invalid state"));
+                                    Err(err) => {
+                                        err_types.push(err);
                                         invalid_txs.push(tx);
                                         if let Some(tmy) = possible_tmy {
                                             invalid_tmys.push(tmy);
@@ -433,10 +465,15 @@ impl Context {
                                 let mut is_inside_double_spent = false;
                                 if tx.flag == TxFlag::Input ||
                                     tx.flag == TxFlag::Domestic {
-                                    for input in tx.inputs.iter() {
+                                    for (input_index, input) in tx.inputs.iter().enumerate() {
                                         match set.get(&input.hash()) {
                                             Some(_) => {
                                                 is_inside_double_spent = true;
+                                                err_types.push(ValidationError::DoubleSpending {
+                                                    tx_hash: tx.hash(),
+                                                    input_index,
+                                                    proof: None,
+                                                });
                                                 break;
                                             }
                                             None => {
@@ -455,7 +492,6 @@ impl Context {
                                     if let Some(tmy) = possible_tmy.clone() {
                                         invalid_tmys.push(tmy);
                                     }
-                                    err_types.push(String::from("inside double spending"));
                                     continue;
                                 }
                                 counter += 1;
@@ -475,6 +511,9 @@ impl Context {
                     for tmy in invalid_tmys {
                         self.mempool.lock().unwrap().add_testimony(tmy);
                     }
+                    for err in err_types.iter() {
+                        self.validation_stats.record(FROM_MINER, err);
+                    }
                     if counter < self.config.block_size {
 //                        //put all txs and tmys back to mempool
 //                        for tx in txs {
@@ -506,15 +545,22 @@ impl Context {
 //                    );
                     //debug: only one parent in each shard
                     //let mut supposed_global_parents = global_parents.clone();
-                    //supposed_global_parents.retain(|x| x.1 != self.config.shard_id );
-                    //supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id));
-                    let (cons_block, tx_block) = self.create_consensus_block(
+                    //supposed_global_parents.retain(|x| x.1 != self.config.shard_id() );
+                    //supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id()));
+                    let state_root = Blockchain::get_next_state_root(
+                        last_state,
+                        &txs,
+                        self.config.shard_id(),
+                        &self.config
+                    );
+                    let (mut cons_block, tx_block) = self.create_consensus_block(
                         txs,
                         tmys,
                         verified_parent.clone(),
                         inter_parents.clone(),
                         global_parents.clone()
                     );
+                    cons_block.set_state_root(&state_root);
                     //info!("mines a block with parent {:?} of state size: {}", last_blk_hash, last_state.len());
                     //update related information
                     pre_verified_parent = verified_parent.clone();
@@ -527,17 +573,17 @@ impl Context {
                 let nonce: usize = rand::thread_rng().gen();
                 let hash_val = self.PoW(&mut pre_cons_block, nonce);
                 //info!("block hash: {:?}", hash_val);
-                let ex_diff = self.config.difficulty;
+                let ex_diff = self.config.get_difficulty(self.config.shard_id());
                 let in_diff = self.config.thredshold;
                 //debug: only one parent in each shard
                 //let mut supposed_global_parents = global_parents.clone();
-                //supposed_global_parents.retain(|x| x.1 != self.config.shard_id );
-                //supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id));
+                //supposed_global_parents.retain(|x| x.1 != self.config.shard_id() );
+                //supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id()));
                 if hash_val <= ex_diff {
                     let mut final_block: VersaBlock = VersaBlock::default();
                     if hash_val > in_diff {
                         //generate an exclusive block
-                        info!("mine an exclusive block {:?} in shard {}", hash_val, self.config.shard_id);
+                        info!("mine an exclusive block {:?} in shard {}", hash_val, self.config.shard_id());
                         let ex_block = ExclusiveBlock::create(
                             pre_cons_block.clone(),
                             hash_val,
@@ -558,7 +604,7 @@ impl Context {
                         //    .into_iter()
                         //    .map(|x| (x.0.clone(), x.1 as u32))
                         //    .collect();
-                        info!("mine an inclusive block {} in shard {}", hash_val, self.config.shard_id);
+                        info!("mine an inclusive block {} in shard {}", hash_val, self.config.shard_id());
                         let in_block = InclusiveBlock::create(
                             pre_cons_block.clone(),
                             hash_val,
@@ -587,7 +633,7 @@ impl Context {
                             for output in tx.outputs.iter() {
                                 let output_shard_id = Validator::get_shard_id(
                                     &output.receiver_addr, 
-                                    self.config.shard_num
+                                    &self.config
                                 );
                                 output_shards.push(output_shard_id);
                             }
@@ -595,8 +641,8 @@ impl Context {
                                 tx,
                                 &final_block,
                                 tx_index,
-                                self.config.shard_id,
-                                self.config.shard_num,
+                                self.config.shard_id(),
+                                &self.config,
                                 true
                             ).unwrap();
                             let mut output_tx = tx.clone();
@@ -628,7 +674,7 @@ impl Context {
                                     }
                                 }
                                 //add the current node's mempool
-                                if shard == self.config.shard_id {
+                                if shard == self.config.shard_id() {
                                     self.mempool.lock().unwrap().add_testimony(tmy.clone());
                                     self.mempool.lock().unwrap().insert_tx(output_tx.clone());
                                 }
@@ -637,7 +683,7 @@ impl Context {
                              for input in tx.inputs.iter() {
                                 let input_shard_id = Validator::get_shard_id(
                                     &input.sender_addr, 
-                                    self.config.shard_num
+                                    &self.config
                                 );
                                 output_shards.push(input_shard_id);
                             }
@@ -645,8 +691,8 @@ impl Context {
                                 tx,
                                 &final_block,
                                 tx_index,
-                                self.config.shard_id,
-                                self.config.shard_num,
+                                self.config.shard_id(),
+                                &self.config,
                                 true
                             ).unwrap();
                             for shard in output_shards {
@@ -660,7 +706,7 @@ impl Context {
                                         new_tmys.insert(shard, vec![tmy.clone()]);
                                     }
                                 }
-                                if shard == self.config.shard_id {
+                                if shard == self.config.shard_id() {
                                     self.mempool.lock().unwrap().add_testimony(tmy.clone());
                                 }
                             }                           
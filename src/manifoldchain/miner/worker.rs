@@ -9,7 +9,8 @@ use crate::{
                 VersaBlock,
                 ExclusiveFullBlock,
                 InclusiveFullBlock,
-            }
+            },
+            compact_block::CompactBlock,
         },
         network::{
             server::Handle as ServerHandle,
@@ -123,6 +124,23 @@ impl Worker {
         }
     }
 
+    //the peers in the shard rebuild the compact block from their mempool
+    fn broadcast_full_block(&self, block: VersaBlock) {
+        let shard_id = self.config.shard_id();
+        let msg = match (self.config.compact_blocks, block) {
+            (true, block) => Message::CompactBlocks((
+                CompactBlock::from_full_block(&block).into_iter().collect(),
+                shard_id as u32
+            )),
+            (false, VersaBlock::ExFullBlock(ex_full_block)) 
+                => Message::ExFullBlocks((vec![ex_full_block], shard_id as u32)),
+            (false, VersaBlock::InFullBlock(in_full_block)) 
+                => Message::InFullBlocks((vec![in_full_block], shard_id as u32)),
+            _ => return,
+        };
+        self.server.broadcast_with_shard(msg, shard_id);
+    }
+
     fn worker_loop(&mut self) {
         loop {
             let message = self.finished_block_chan
@@ -139,14 +157,14 @@ impl Worker {
                         match self.multichain.insert_block_with_parent(
                             VersaBlock::ExFullBlock(ex_full_block.clone()),
                             &parent,
-                            self.config.shard_id,
+                            self.config.shard_id(),
                         ) {
                             Ok(confirmation_info) => {
                                 successful_insertion = true;
                                 self.handle_confirmation(
                                     VersaBlock::ExFullBlock(ex_full_block.clone()),
                                     confirmation_info,
-                                    self.config.shard_id,
+                                    self.config.shard_id(),
                                 );
                             }
                             Err(e) => {
@@ -159,17 +177,10 @@ impl Worker {
                         self.server.broadcast(
                                 Message::ExBlocks((
                                     new_ex_blocks, 
-                                    self.config.shard_id as u32
+                                    self.config.shard_id() as u32
                                 ))
                             );
-                        let new_blocks: Vec<ExclusiveFullBlock> = vec![ex_full_block];
-                        self.server.broadcast_with_shard(
-                                Message::ExFullBlocks((
-                                    new_blocks, 
-                                    self.config.shard_id as u32
-                                )), 
-                                self.config.shard_id
-                            );       
+                        self.broadcast_full_block(VersaBlock::ExFullBlock(ex_full_block));
                     }
                 }
                 MinerMessage::InFullBlock(in_full_block) => {
@@ -179,7 +190,7 @@ impl Worker {
                     for (inter_parents, shard_id) in global_parents {
                         info!("inter_parents size: {}", inter_parents.len());
                         for parent in inter_parents {
-                            let inserted_block = match (shard_id == self.config.shard_id) {
+                            let inserted_block = match (shard_id == self.config.shard_id()) {
                                 true => VersaBlock::InFullBlock(in_full_block.clone()),
                                 false => VersaBlock::InBlock(in_block.clone()),
                             };
@@ -207,17 +218,10 @@ impl Worker {
                         self.server.broadcast(
                             Message::InBlocks((
                                 new_in_blocks, 
-                                self.config.shard_id as u32
+                                self.config.shard_id() as u32
                             ))
                         );
-                        let new_blocks: Vec<InclusiveFullBlock> = vec![in_full_block];
-                        self.server.broadcast_with_shard(
-                            Message::InFullBlocks((
-                                new_blocks, 
-                                self.config.shard_id as u32
-                            )),
-                            self.config.shard_id
-                        );
+                        self.broadcast_full_block(VersaBlock::InFullBlock(in_full_block));
                     }
                 }
                 MinerMessage::Testimonies(tmys) => {
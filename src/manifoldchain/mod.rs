@@ -13,3 +13,7 @@ pub mod fraudproof;
 pub mod confirmation;
 pub mod database;
 pub mod verifier;
+pub mod light_client;
+pub mod audit;
+pub mod swap;
+pub mod sharding;
@@ -4,6 +4,7 @@ use crate::{
             Blockchain,
             State,
             VerStatus,
+            supply::Supply,
         },
         configuration::Configuration,
         block::{
@@ -14,7 +15,7 @@ use crate::{
         transaction::Transaction,
     },
     types::{
-        hash::H256,
+        hash::{H256, Hashable},
     }
 };
 use std::{
@@ -61,7 +62,7 @@ impl Multichain {
     //    -> Result<Option<(VersaBlock, usize)>, String> 
     //{
     //    self.chains
-    //        .get(self.config.shard_id)
+    //        .get(self.config.shard_id())
     //        .unwrap()
     //        .lock()
     //        .unwrap()
@@ -104,15 +105,33 @@ impl Multichain {
 
     pub fn get_longest_chain_hash(&self) -> H256 {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
             .tip()
     }
+    //(tip hash, height) of the longest chain of every shard, indexed by shard_id
+    pub fn get_tips(&self) -> Vec<(H256, usize)> {
+        self.chains
+            .iter()
+            .map(|chain| {
+                let chain = chain.lock().unwrap();
+                (chain.tip(), chain.height)
+            })
+            .collect()
+    }
+    //the chain id commits to the genesis blocks of all shards
+    pub fn get_chain_id(&self) -> H256 {
+        let genesis_hashes: Vec<H256> = self.chains
+            .iter()
+            .map(|chain| chain.lock().unwrap().genesis())
+            .collect();
+        H256::multi_hash(&genesis_hashes)
+    }
     pub fn all_blocks_in_longest_chain(&self) -> Vec<H256> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -129,7 +148,7 @@ impl Multichain {
     }
     pub fn all_blocks_end_with_block(&self, hash: &H256) -> Option<Vec<H256>> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -147,7 +166,7 @@ impl Multichain {
     }
     pub fn get_block(&self, hash: &H256) -> Option<VersaBlock> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -165,16 +184,58 @@ impl Multichain {
     }
     pub fn get_states(&self) -> HashMap<H256, State> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
             .get_states()
     }
+    pub fn check_supply(&self) -> Result<Supply, String> {
+        self.chains
+            .get(self.config.shard_id())
+            .unwrap()
+            .lock()
+            .unwrap()
+            .check_supply()
+    }
+    pub fn get_state_block_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock> {
+        self.chains
+            .get(shard_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get_state_block(hash)
+    }
+    pub fn import_state_with_shard(&self, hash: &H256, shard_id: usize, state: State)
+        -> Result<(), String>
+    {
+        self.chains
+            .get(shard_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .import_state(hash, state)
+    }
+    pub fn get_utxo_in_longest_chain(&self, utxo: &(H256, u32)) -> Option<Transaction> {
+        self.chains
+            .get(self.config.shard_id())
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get_utxo_in_longest_chain(utxo)
+    }
+    pub fn get_state(&self, hash: &H256) -> Option<State> {
+        self.chains
+            .get(self.config.shard_id())
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get_state(hash)
+    }
     
     pub fn get_leaves(&self) -> Vec<H256> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -185,7 +246,7 @@ impl Multichain {
         tx_hash: &H256) -> Option<Transaction> 
     {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -270,7 +331,7 @@ impl Multichain {
     }
     pub fn get_inter_unverified_forks(&self) -> Vec<H256> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -293,7 +354,7 @@ impl Multichain {
     }
     pub fn get_longest_verified_fork(&self) -> H256 {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -319,7 +380,7 @@ impl Multichain {
 
     pub fn get_block_with_tx(&self, tx_hash: &H256) -> Option<(VersaBlock, usize)> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -337,6 +398,14 @@ impl Multichain {
             .verify_block(block)
     }
 
+    pub fn get_tx_height(&self, tx_hash: &H256, tip: &H256) -> Option<usize> {
+        self.chains
+            .get(self.config.shard_id())
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get_tx_height(tx_hash, tip)
+    }
     pub fn get_block_height_with_shard(&self, block_hash: &H256, shard_id: usize) 
         -> Option<usize> 
     {
@@ -350,7 +419,7 @@ impl Multichain {
 
     pub fn get_all_txs_in_longest_chain(&self) -> Vec<Transaction> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -380,7 +449,7 @@ impl Multichain {
     }
     pub fn all_blocks_in_longest_chain_with_time(&self) -> Vec<(H256, String)> {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
@@ -389,7 +458,7 @@ impl Multichain {
 
     pub fn get_forking_rate(&self) -> f64 {
         self.chains
-            .get(self.config.shard_id)
+            .get(self.config.shard_id())
             .unwrap()
             .lock()
             .unwrap()
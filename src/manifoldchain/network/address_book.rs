@@ -0,0 +1,165 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+//the maximal number of addresses returned in one Addr message
+pub const MAX_ADDR_PER_MSG: usize = 1000;
+//the maximal number of new addresses taken from one Addr message
+pub const MAX_NEW_ADDR_PER_MSG: usize = 32;
+//the maximal number of addresses kept for one shard
+pub const MAX_ADDR_PER_SHARD: usize = 256;
+//the maximal number of gossiped addresses kept from one source
+pub const MAX_ADDR_PER_SOURCE: usize = 64;
+//a gossiped address not seen for ADDR_TTL is dropped
+pub const ADDR_TTL: u64 = 3 * 3600; //s
+
+#[derive(Debug, Clone)]
+pub struct AddrInfo {
+    pub shard_id: usize,
+    pub last_seen: SystemTime,
+    pub source: Option<IpAddr>, //the peer which gossiped the address, none if learned from a handshake
+}
+
+//The address book records the listening address of every node we have heard
+//of, keyed by the shard the node belongs to. Entries are learned from the
+//handshake of connected peers and from Addr gossip. Gossip is bounded per
+//message, per source and per shard, so that one peer cannot fill the book.
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    addrs: HashMap<SocketAddr, AddrInfo>, //listening addr -> info
+    by_shard: HashMap<usize, Vec<SocketAddr>>, //shard_id -> listening addrs
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self {
+            addrs: HashMap::new(),
+            by_shard: HashMap::new(),
+        }
+    }
+
+    //insert or refresh the address of a peer which finished the handshake, return
+    //true if the address is new
+    pub fn add(&mut self, addr: SocketAddr, shard_id: usize) -> bool {
+        match self.addrs.get_mut(&addr) {
+            Some(info) => {
+                info.last_seen = SystemTime::now();
+                info.source = None;
+                if info.shard_id != shard_id {
+                    //the node announces a new shard, move it
+                    let old_shard = info.shard_id;
+                    info.shard_id = shard_id;
+                    if let Some(addrs) = self.by_shard.get_mut(&old_shard) {
+                        addrs.retain(|x| *x != addr);
+                    }
+                    self.by_shard.entry(shard_id).or_insert_with(Vec::new).push(addr);
+                }
+                false
+            }
+            None => {
+                //a full shard makes room by dropping its oldest entry, gossiped ones first
+                if self.count_shard(shard_id) >= MAX_ADDR_PER_SHARD {
+                    let oldest = self.get_by_shard(shard_id)
+                        .into_iter()
+                        .min_by_key(|x| {
+                            let info = &self.addrs[x];
+                            (info.source.is_none(), info.last_seen)
+                        });
+                    if let Some(oldest) = oldest {
+                        self.remove(&oldest);
+                    }
+                }
+                self.insert(addr, shard_id, None);
+                true
+            }
+        }
+    }
+
+    //take the new addresses gossiped by the source, return the number of them taken;
+    //known addresses are left as they are, since only a handshake proves them
+    pub fn add_gossip(&mut self, source: IpAddr, addrs: Vec<(SocketAddr, usize)>) -> usize {
+        self.evict_stale(SystemTime::now());
+        let mut from_source = self.addrs
+            .values()
+            .filter(|info| info.source == Some(source))
+            .count();
+        let mut added: usize = 0;
+        for (addr, shard_id) in addrs {
+            if added >= MAX_NEW_ADDR_PER_MSG || from_source >= MAX_ADDR_PER_SOURCE {
+                break;
+            }
+            if self.addrs.contains_key(&addr) || self.count_shard(shard_id) >= MAX_ADDR_PER_SHARD {
+                continue;
+            }
+            self.insert(addr, shard_id, Some(source));
+            added += 1;
+            from_source += 1;
+        }
+        added
+    }
+
+    //drop the gossiped addresses not seen for ADDR_TTL, return the number dropped
+    pub fn evict_stale(&mut self, now: SystemTime) -> usize {
+        let ttl = Duration::from_secs(ADDR_TTL);
+        let stale: Vec<SocketAddr> = self.addrs
+            .iter()
+            .filter(|(_, info)| {
+                info.source.is_some() &&
+                    now.duration_since(info.last_seen).map(|d| d > ttl).unwrap_or(false)
+            })
+            .map(|(addr, _)| addr.clone())
+            .collect();
+        for addr in stale.iter() {
+            self.remove(addr);
+        }
+        stale.len()
+    }
+
+    fn insert(&mut self, addr: SocketAddr, shard_id: usize, source: Option<IpAddr>) {
+        self.addrs.insert(addr, AddrInfo {
+            shard_id,
+            last_seen: SystemTime::now(),
+            source,
+        });
+        self.by_shard.entry(shard_id).or_insert_with(Vec::new).push(addr);
+    }
+
+    fn count_shard(&self, shard_id: usize) -> usize {
+        self.by_shard.get(&shard_id).map(|addrs| addrs.len()).unwrap_or(0)
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        if let Some(info) = self.addrs.remove(addr) {
+            if let Some(addrs) = self.by_shard.get_mut(&info.shard_id) {
+                addrs.retain(|x| x != addr);
+            }
+        }
+    }
+
+    pub fn get_shard(&self, addr: &SocketAddr) -> Option<usize> {
+        self.addrs.get(addr).map(|info| info.shard_id)
+    }
+
+    pub fn get_by_shard(&self, shard_id: usize) -> Vec<SocketAddr> {
+        match self.by_shard.get(&shard_id) {
+            Some(addrs) => addrs.clone(),
+            None => vec![],
+        }
+    }
+
+    //return the most recently seen addresses, at most MAX_ADDR_PER_MSG of them
+    pub fn get_all(&self) -> Vec<(SocketAddr, usize)> {
+        let mut all: Vec<(&SocketAddr, &AddrInfo)> = self.addrs.iter().collect();
+        all.sort_by(|a, b| b.1.last_seen.cmp(&a.1.last_seen));
+        all.into_iter()
+            .take(MAX_ADDR_PER_MSG)
+            .map(|(addr, info)| (addr.clone(), info.shard_id))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+}
@@ -0,0 +1,231 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CacheStats {
+    pub name: String,
+    pub size: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub evicted: u64, //dropped to make room, or given up on
+    pub expired: u64, //dropped after the ttl
+}
+
+struct CacheEntry<V> {
+    value: V,
+    inserted_at: Instant,
+    tick: u64, //the last use, the entry with the smallest tick is evicted first
+}
+
+//a map with at most capacity entries, which drops the least recently used entry when
+//it is full and the entries older than the ttl
+pub struct Cache<K, V> {
+    name: String,
+    capacity: usize,
+    ttl: Option<Duration>,
+    entries: HashMap<K, CacheEntry<V>>,
+    lru: BTreeMap<u64, K>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl<K: Eq + Hash + Clone, V> Cache<K, V> {
+    pub fn new(name: &str, capacity: usize, ttl: Option<Duration>) -> Self {
+        Cache {
+            name: name.to_string(),
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry<V>, now: Instant) -> bool {
+        match self.ttl {
+            Some(ttl) => now.duration_since(entry.inserted_at) > ttl,
+            None => false,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    //drop the entry if it is expired, return whether it is still in the cache
+    fn check_expiry(&mut self, key: &K) -> bool {
+        let now = Instant::now();
+        let expired = match self.entries.get(key) {
+            Some(entry) => self.is_expired(entry, now),
+            None => return false,
+        };
+        if expired {
+            self.remove(key);
+            self.stats.expired += 1;
+        }
+        !expired
+    }
+
+    //look up the entry and mark it as recently used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        if !self.check_expiry(key) {
+            self.stats.misses += 1;
+            return None;
+        }
+        self.stats.hits += 1;
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key).unwrap();
+        self.lru.remove(&entry.tick);
+        self.lru.insert(tick, key.clone());
+        entry.tick = tick;
+        Some(&entry.value)
+    }
+
+    //look up the entry without counting it as a use
+    pub fn peek_mut(&mut self, key: &K) -> Option<&mut V> {
+        match self.check_expiry(key) {
+            true => self.entries.get_mut(key).map(|entry| &mut entry.value),
+            false => None,
+        }
+    }
+
+    pub fn contains_key(&mut self, key: &K) -> bool {
+        self.check_expiry(key)
+    }
+
+    //insert or replace the entry, evicting the least recently used one if it is full
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.remove(&key);
+        if old.is_none() {
+            while self.entries.len() >= self.capacity {
+                let oldest = match self.lru.iter().next() {
+                    Some((_, oldest)) => oldest.clone(),
+                    None => break,
+                };
+                self.remove(&oldest);
+                self.stats.evicted += 1;
+            }
+            if self.capacity == 0 {
+                return None;
+            }
+        }
+        let tick = self.next_tick();
+        self.lru.insert(tick, key.clone());
+        self.entries.insert(key, CacheEntry {
+            value,
+            inserted_at: Instant::now(),
+            tick,
+        });
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.lru.remove(&entry.tick);
+        Some(entry.value)
+    }
+
+    //remove the entry which is given up on
+    pub fn evict(&mut self, key: &K) -> Option<V> {
+        let value = self.remove(key);
+        if value.is_some() {
+            self.stats.evicted += 1;
+        }
+        value
+    }
+
+    //drop all expired entries, return the number of them
+    pub fn purge_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<K> = self.entries
+            .iter()
+            .filter(|(_, entry)| self.is_expired(entry, now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired.iter() {
+            self.remove(key);
+        }
+        self.stats.expired += expired.len() as u64;
+        expired.len()
+    }
+
+    pub fn keys(&self) -> Vec<K> {
+        self.entries.keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let lookups = self.stats.hits + self.stats.misses;
+        CacheStats {
+            name: self.name.clone(),
+            size: self.entries.len(),
+            capacity: self.capacity,
+            hit_rate: match lookups {
+                0 => 0.0,
+                lookups => self.stats.hits as f64 / lookups as f64,
+            },
+            ..self.stats.clone()
+        }
+    }
+}
+
+//a cache shared by the worker threads
+pub struct SharedCache<K, V> {
+    inner: Arc<Mutex<Cache<K, V>>>,
+}
+
+impl<K, V> Clone for SharedCache<K, V> {
+    fn clone(&self) -> Self {
+        SharedCache {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SharedCache<K, V> {
+    pub fn new(name: &str, capacity: usize, ttl: Option<Duration>) -> Self {
+        SharedCache {
+            inner: Arc::new(Mutex::new(Cache::new(name, capacity, ttl))),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.inner.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.lock().unwrap().contains_key(key)
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.inner.lock().unwrap().insert(key, value)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.inner.lock().unwrap().remove(key)
+    }
+
+    pub fn purge_expired(&self) -> usize {
+        self.inner.lock().unwrap().purge_expired()
+    }
+
+    pub fn keys(&self) -> Vec<K> {
+        self.inner.lock().unwrap().keys()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats()
+    }
+}
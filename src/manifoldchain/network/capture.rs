@@ -0,0 +1,128 @@
+use crate::manifoldchain::network::{
+    peer,
+    frame::MAX_FRAME_SIZE,
+};
+use log::info;
+use serde::{Serialize, Deserialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CaptureDirection {
+    Inbound,
+    Outbound,
+}
+
+//one frame in the capture log, the log is a sequence of [record size: u32][bincode record]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CaptureRecord {
+    pub timestamp: u64, //us since the unix epoch
+    pub direction: CaptureDirection,
+    pub peer: SocketAddr, //the connection addr of the peer
+    pub body: Vec<u8>, //the body of the frame, [type id][bincode payload]
+}
+
+impl CaptureRecord {
+    pub fn new(direction: CaptureDirection, peer: SocketAddr, body: Vec<u8>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        CaptureRecord {
+            timestamp,
+            direction,
+            peer,
+            body,
+        }
+    }
+}
+
+pub struct CaptureWriter {
+    writer: BufWriter<File>,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("fail to create the capture log {}: {}", path, e))?;
+        info!("Capture the P2P traffic to {}", path);
+        Ok(CaptureWriter {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    //the record is flushed right away so that the log survives a killed node
+    pub fn write(&mut self, record: &CaptureRecord) -> Result<(), String> {
+        let buffer = bincode::serialize(record).map_err(|e| e.to_string())?;
+        self.writer
+            .write_all(&(buffer.len() as u32).to_be_bytes())
+            .and_then(|_| self.writer.write_all(&buffer))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub struct CaptureReader {
+    reader: BufReader<File>,
+}
+
+impl CaptureReader {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("fail to open the capture log {}: {}", path, e))?;
+        Ok(CaptureReader {
+            reader: BufReader::new(file),
+        })
+    }
+
+    //return None at the end of the log
+    pub fn read(&mut self) -> Result<Option<CaptureRecord>, String> {
+        let mut size_buffer = [0u8; 4];
+        match self.reader.read_exact(&mut size_buffer) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        }
+        let size = u32::from_be_bytes(size_buffer);
+        if size > MAX_FRAME_SIZE + 1024 {
+            return Err(format!("capture record of {} bytes exceeds the limit", size));
+        }
+        let mut buffer = vec![0u8; size as usize];
+        self.reader
+            .read_exact(&mut buffer)
+            .map_err(|e| format!("truncated capture record: {}", e))?;
+        bincode::deserialize(&buffer)
+            .map(Some)
+            .map_err(|e| format!("fail to parse the capture record: {}", e))
+    }
+}
+
+//feed the inbound frames of the capture to the network worker, as if they came from
+//the recorded peers, return the number of frames fed
+pub fn replay(
+    path: &str,
+    msg_sink: &smol::channel::Sender<(Vec<u8>, peer::Handle)>,
+) -> Result<usize, String> {
+    let mut reader = CaptureReader::open(path)?;
+    //the responses of the worker to the recorded peers are dropped
+    let mut handles: HashMap<SocketAddr, peer::Handle> = HashMap::new();
+    let mut fed: usize = 0;
+    while let Some(record) = reader.read()? {
+        if record.direction != CaptureDirection::Inbound {
+            continue;
+        }
+        let handle = handles
+            .entry(record.peer)
+            .or_insert_with(|| peer::Handle::detached(record.peer))
+            .clone();
+        smol::block_on(msg_sink.send((record.body, handle)))
+            .map_err(|e| format!("the network worker is gone: {}", e))?;
+        fed += 1;
+    }
+    Ok(fed)
+}
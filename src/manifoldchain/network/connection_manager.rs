@@ -0,0 +1,350 @@
+use log::{info, debug};
+use serde::Serialize;
+use std::{
+    time::{self, SystemTime},
+    thread,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    collections::{HashMap, HashSet},
+};
+use crate::manifoldchain::{
+    configuration::Configuration,
+    network::{
+        server::Handle as ServerHandle,
+        message::Message,
+    },
+};
+use rand::seq::SliceRandom;
+
+//the interval between two rounds of peer maintenance
+const MAINTAIN_INTERVAL: u64 = 1000; //ms
+//discover new peers every DISCOVERY_ROUNDS rounds of maintenance
+const DISCOVERY_ROUNDS: usize = 5;
+//the backoff before redialing grows from BASE_BACKOFF to MAX_BACKOFF
+const BASE_BACKOFF: u64 = 1; //s
+const MAX_BACKOFF: u64 = 64; //s
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Connected,
+    Connecting,
+    Backoff,
+}
+
+//a peer which is redialed whenever the connection is lost
+#[derive(Debug, Clone)]
+struct PersistentPeer {
+    state: ConnectionState,
+    failures: u32,
+    next_retry: SystemTime,
+    connected_since: Option<SystemTime>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PersistentPeerStatus {
+    pub addr: SocketAddr,
+    pub state: ConnectionState,
+    pub failures: u32,
+    pub uptime: u64, //in seconds
+    pub next_retry: u64, //in seconds from now
+}
+
+//the backoff of a discovered (non-persistent) address
+#[derive(Debug, Clone)]
+struct DialBackoff {
+    failures: u32,
+    next_retry: SystemTime,
+}
+
+pub struct Context {
+    server: ServerHandle,
+    config: Configuration,
+    addr: SocketAddr,
+    persistent: Arc<Mutex<HashMap<SocketAddr, PersistentPeer>>>,
+    backoffs: HashMap<SocketAddr, DialBackoff>,
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    server: ServerHandle,
+    persistent: Arc<Mutex<HashMap<SocketAddr, PersistentPeer>>>,
+}
+
+pub fn new(
+    server: &ServerHandle,
+    addr: SocketAddr,
+    config: &Configuration,
+) -> (Context, Handle) {
+    let persistent = Arc::new(Mutex::new(HashMap::new()));
+    let ctx = Context {
+        server: server.clone(),
+        config: config.clone(),
+        addr,
+        persistent: Arc::clone(&persistent),
+        backoffs: HashMap::new(),
+    };
+    let handle = Handle {
+        server: server.clone(),
+        persistent,
+    };
+    (ctx, handle)
+}
+
+fn backoff(failures: u32) -> time::Duration {
+    let secs = BASE_BACKOFF
+        .checked_shl(failures)
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF);
+    time::Duration::from_secs(secs)
+}
+
+impl Handle {
+    //keep a connection to the peer, redialing it with backoff once it is lost
+    pub fn add_persistent(&self, addr: SocketAddr) {
+        self.persistent
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_insert(PersistentPeer {
+                state: ConnectionState::Connecting,
+                failures: 0,
+                next_retry: SystemTime::now(),
+                connected_since: None,
+            });
+    }
+
+    pub fn remove_persistent(&self, addr: &SocketAddr) {
+        self.persistent.lock().unwrap().remove(addr);
+    }
+
+    //dial the peer immediately and keep it as a persistent peer
+    pub fn connect(&self, addr: SocketAddr) -> std::io::Result<()> {
+        self.add_persistent(addr);
+        let res = self.server.connect(addr);
+        if let Some(peer) = self.persistent.lock().unwrap().get_mut(&addr) {
+            match &res {
+                Ok(_) => {
+                    peer.state = ConnectionState::Connected;
+                    peer.failures = 0;
+                    peer.connected_since = Some(SystemTime::now());
+                }
+                Err(_) => {
+                    peer.state = ConnectionState::Backoff;
+                    peer.failures += 1;
+                    peer.next_retry = SystemTime::now() + backoff(peer.failures);
+                }
+            }
+        }
+        res.map(|_| ())
+    }
+
+    //close the connection and stop redialing the peer
+    pub fn disconnect(&self, addr: SocketAddr) -> bool {
+        self.remove_persistent(&addr);
+        self.server.disconnect(addr)
+    }
+
+    pub fn get_persistent_peers(&self) -> Vec<PersistentPeerStatus> {
+        let now = SystemTime::now();
+        self.persistent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(addr, peer)| PersistentPeerStatus {
+                addr: addr.clone(),
+                state: peer.state.clone(),
+                failures: peer.failures,
+                uptime: peer.connected_since
+                    .and_then(|t| now.duration_since(t).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                next_retry: peer.next_retry
+                    .duration_since(now)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+            })
+            .collect()
+    }
+}
+
+impl Context {
+    pub fn start(mut self) {
+        thread::Builder::new()
+            .name("Connection-Manager".to_string())
+            .spawn(move || {
+                self.maintain_peers();
+            })
+            .unwrap();
+        info!("Connection manager started");
+    }
+
+    //the number of peers to keep in the given shard
+    fn target_peers(&self, shard_id: usize) -> usize {
+        if shard_id == self.config.shard_id() {
+            self.config.target_peers
+        } else {
+            self.config.min_foreign_peers
+        }
+    }
+
+    /// The shards short of their target, with the number of peers missing in each
+    pub fn get_missing_peers(&self, connected: &Vec<(SocketAddr, usize)>) -> Vec<(usize, usize)> {
+        let mut peer_counts: HashMap<usize, usize> = HashMap::new();
+        for (_, shard_id) in connected.iter() {
+            *peer_counts.entry(*shard_id).or_insert(0) += 1;
+        }
+        (0..self.config.shard_num)
+            .filter_map(|shard_id| {
+                let target = self.target_peers(shard_id);
+                let count = peer_counts.get(&shard_id).cloned().unwrap_or(0);
+                match count < target {
+                    true => Some((shard_id, target - count)),
+                    false => None,
+                }
+            })
+            .collect()
+    }
+
+    fn maintain_peers(&mut self) {
+        let mut round: usize = 0;
+        loop {
+            let interval = time::Duration::from_millis(MAINTAIN_INTERVAL);
+            thread::sleep(interval);
+
+            let connected = self.server.get_connected_peers();
+            let connected_addrs: HashSet<SocketAddr> = connected
+                .iter()
+                .map(|(addr, _)| addr.clone())
+                .collect();
+            self.redial_persistent_peers(&connected_addrs);
+
+            round += 1;
+            if round % DISCOVERY_ROUNDS == 0 {
+                self.discover_peers(&connected, &connected_addrs);
+            }
+        }
+    }
+
+    fn redial_persistent_peers(&mut self, connected_addrs: &HashSet<SocketAddr>) {
+        let now = SystemTime::now();
+        let mut to_dial: Vec<SocketAddr> = vec![];
+        {
+            let mut persistent = self.persistent.lock().unwrap();
+            for (addr, peer) in persistent.iter_mut() {
+                if connected_addrs.contains(addr) {
+                    if peer.state != ConnectionState::Connected {
+                        peer.state = ConnectionState::Connected;
+                        peer.failures = 0;
+                        peer.connected_since = Some(now);
+                    }
+                    continue;
+                }
+                if peer.state == ConnectionState::Connected {
+                    //the connection is lost, redial it right away
+                    info!("Persistent peer {} is lost", addr);
+                    peer.state = ConnectionState::Connecting;
+                    peer.connected_since = None;
+                    peer.next_retry = now;
+                }
+                if peer.next_retry <= now {
+                    to_dial.push(addr.clone());
+                }
+            }
+        }
+        //dial without holding the lock, since connecting may take a while
+        for addr in to_dial {
+            let res = self.server.connect(addr);
+            let mut persistent = self.persistent.lock().unwrap();
+            let peer = match persistent.get_mut(&addr) {
+                Some(peer) => peer,
+                None => continue,
+            };
+            match res {
+                Ok(_) => {
+                    info!("Connected to persistent peer {}", addr);
+                    peer.state = ConnectionState::Connected;
+                    peer.failures = 0;
+                    peer.connected_since = Some(SystemTime::now());
+                }
+                Err(e) => {
+                    peer.state = ConnectionState::Backoff;
+                    peer.failures += 1;
+                    let wait = backoff(peer.failures);
+                    peer.next_retry = SystemTime::now() + wait;
+                    debug!(
+                        "Error connecting to persistent peer {}, retrying in {:?}: {}",
+                        addr, wait, e
+                    );
+                }
+            }
+        }
+    }
+
+    fn discover_peers(
+        &mut self,
+        connected: &Vec<(SocketAddr, usize)>,
+        connected_addrs: &HashSet<SocketAddr>,
+    ) {
+        let now = SystemTime::now();
+        let known_addrs = self.server.get_addrs();
+        let missing = self.get_missing_peers(connected);
+        let is_short = !missing.is_empty();
+        for (shard_id, need) in missing {
+            let mut candidates: Vec<SocketAddr> = known_addrs
+                .iter()
+                .filter(|(addr, id)| {
+                    *id == shard_id &&
+                        *addr != self.addr &&
+                        !connected_addrs.contains(addr) &&
+                        self.backoffs
+                            .get(addr)
+                            .map(|b| b.next_retry <= now)
+                            .unwrap_or(true)
+                })
+                .map(|(addr, _)| addr.clone())
+                .collect();
+            candidates.shuffle(&mut rand::thread_rng());
+            let mut candidates = candidates.into_iter();
+            let mut need = need;
+            while need > 0 {
+                //dial the missing peers at once, each dial is bounded by the handshake timeout
+                let dials: Vec<(SocketAddr, _)> = candidates
+                    .by_ref()
+                    .take(need)
+                    .map(|addr| (addr, self.server.start_connect(addr)))
+                    .collect();
+                if dials.is_empty() {
+                    break;
+                }
+                for (addr, dial) in dials {
+                    let res = smol::block_on(dial).unwrap_or_else(|_| Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        "the server dropped the connection",
+                    )));
+                    match res {
+                        Ok(_) => {
+                            info!("Connected to peer {} in shard {}", addr, shard_id);
+                            self.backoffs.remove(&addr);
+                            need -= 1;
+                        }
+                        Err(e) => {
+                            let entry = self.backoffs
+                                .entry(addr)
+                                .or_insert(DialBackoff {
+                                    failures: 0,
+                                    next_retry: now,
+                                });
+                            entry.failures += 1;
+                            entry.next_retry = SystemTime::now() + backoff(entry.failures);
+                            debug!("Error connecting to peer {}: {}", addr, e);
+                        }
+                    }
+                }
+            }
+        }
+        //ask the neighbours for more addresses if some shard is not covered
+        if is_short {
+            self.server.broadcast(Message::GetAddr);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::manifoldchain::configuration::Configuration;
+use futures::{channel::mpsc, sink::SinkExt, stream::StreamExt};
+use log::info;
+use rand::Rng;
+use serde::Deserialize;
+use smol::{Executor, Timer};
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+//the bucket holds at most BURST_INTERVAL ms of traffic
+const BURST_INTERVAL: u64 = 50; //ms
+//the burst of the htb classes in scripts/network_simulation
+const MIN_BURST: f64 = 1540.0; //bytes
+
+//the network conditions of an experiment, in the format of scripts/expers/exper_*/config.json,
+//so the config of the experiment can be passed as it is, the other fields are ignored
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct EmulationConfig {
+    #[serde(default)]
+    pub propagation_delay: u64, //ms, shared by all connections
+    #[serde(default)]
+    pub bandwidths: Vec<Vec<u64>>, //kbps, [shard][node index], 0 for unlimited
+    #[serde(default)]
+    pub delays: Vec<Vec<u64>>, //ms, [from shard][to shard], overrides propagation_delay
+    #[serde(default)]
+    pub drop_rates: Vec<Vec<f64>>, //the probability to drop a message, [from shard][to shard]
+}
+
+impl EmulationConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("fail to read {}: {}", path, e))?;
+        let emulation: EmulationConfig = serde_json::from_str(&content)
+            .map_err(|e| format!("fail to parse {}: {}", path, e))?;
+        for rates in emulation.drop_rates.iter() {
+            if rates.iter().any(|rate| !(0.0..=1.0).contains(rate)) {
+                return Err(format!("drop rates in {} must be within [0, 1]", path));
+            }
+        }
+        Ok(emulation)
+    }
+}
+
+//a token bucket which may go into debt, so that a frame larger than the bucket
+//is sent after the time it takes to transmit it
+struct TokenBucket {
+    rate: f64, //bytes per second
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(kbps: u64) -> Self {
+        let rate = kbps as f64 * 1000.0 / 8.0;
+        let capacity = (rate * BURST_INTERVAL as f64 / 1000.0).max(MIN_BURST);
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    //take the bytes out of the bucket, return how long to wait before sending them
+    fn consume(&mut self, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+        self.tokens -= bytes as f64;
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens / self.rate),
+            false => Duration::from_secs(0),
+        }
+    }
+}
+
+//shapes the outgoing traffic of this node in the writer tasks of the server, in place
+//of the tc scripts in scripts/network_simulation
+#[derive(Clone)]
+pub struct NetworkEmulator {
+    emulation: EmulationConfig,
+    shard_id: usize,
+    default_delay: Duration,
+    //the egress bandwidth of this node is shared by all its peers
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+}
+
+impl NetworkEmulator {
+    //return None if neither config.emulation nor config.network_delay is set
+    pub fn new(config: &Configuration) -> Option<Self> {
+        let emulation = match &config.emulation {
+            Some(emulation) => emulation.clone(),
+            None if config.network_delay != 0 => EmulationConfig::default(),
+            None => return None,
+        };
+        let default_delay = match emulation.propagation_delay {
+            0 => Duration::from_micros(config.network_delay as u64),
+            delay => Duration::from_millis(delay),
+        };
+        let node_index = match config.shard_size {
+            0 => 0,
+            shard_size => config.node_id % shard_size,
+        };
+        let kbps = emulation.bandwidths
+            .get(config.shard_id())
+            .and_then(|bandwidths| bandwidths.get(node_index))
+            .cloned()
+            .unwrap_or(0);
+        let bucket = match kbps {
+            0 => None,
+            kbps => Some(Arc::new(Mutex::new(TokenBucket::new(kbps)))),
+        };
+        info!(
+            "Network emulation: delay {:?}, bandwidth {} kbps",
+            default_delay,
+            kbps
+        );
+        Some(NetworkEmulator {
+            emulation,
+            shard_id: config.shard_id(),
+            default_delay,
+            bucket,
+        })
+    }
+
+    pub fn get_delay(&self, peer_shard: usize) -> Duration {
+        self.emulation.delays
+            .get(self.shard_id)
+            .and_then(|delays| delays.get(peer_shard))
+            .map(|delay| Duration::from_millis(*delay))
+            .unwrap_or(self.default_delay)
+    }
+
+    pub fn get_drop_rate(&self, peer_shard: usize) -> f64 {
+        self.emulation.drop_rates
+            .get(self.shard_id)
+            .and_then(|rates| rates.get(peer_shard))
+            .cloned()
+            .unwrap_or(0.0)
+    }
+
+    //the time to wait before the frame leaves this node
+    fn throttle(&self, bytes: usize) -> Duration {
+        match &self.bucket {
+            Some(bucket) => bucket.lock().unwrap().consume(bytes),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    //put the frames to a peer in the given shard through the emulated link, the frames
+    //are dropped, throttled by the bandwidth, and then delayed, in their original order
+    pub fn shape(
+        &self,
+        mut frames: mpsc::UnboundedReceiver<Vec<u8>>,
+        peer_shard: usize,
+        ex: &Executor<'_>,
+    ) -> mpsc::UnboundedReceiver<Vec<u8>> {
+        let (mut delay_sender, mut delay_receiver) = mpsc::unbounded::<(Instant, Vec<u8>)>();
+        let (mut shaped_sender, shaped_receiver) = mpsc::unbounded();
+        let delay = self.get_delay(peer_shard);
+        let drop_rate = self.get_drop_rate(peer_shard);
+        let emulator = self.clone();
+        // the transmission, one frame at a time
+        ex.spawn(async move {
+            while let Some(frame) = frames.next().await {
+                if drop_rate > 0.0 && rand::thread_rng().gen_bool(drop_rate) {
+                    continue;
+                }
+                let wait = emulator.throttle(frame.len());
+                if wait > Duration::from_secs(0) {
+                    Timer::after(wait).await;
+                }
+                if delay_sender.send((Instant::now() + delay, frame)).await.is_err() {
+                    break;
+                }
+            }
+        })
+            .detach();
+        // the propagation, the frames in flight do not wait for each other
+        ex.spawn(async move {
+            while let Some((arrival, frame)) = delay_receiver.next().await {
+                Timer::at(arrival).await;
+                if shaped_sender.send(frame).await.is_err() {
+                    break;
+                }
+            }
+        })
+            .detach();
+        shaped_receiver
+    }
+}
@@ -0,0 +1,93 @@
+use crate::manifoldchain::network::message::{Message, NUM_MESSAGE_TYPES};
+use ring::digest;
+
+//a frame on the wire is [body size: u32][checksum: u32][body], and the body is
+//[type id: u8][bincode payload], the checksum is the first 4 bytes of the sha256 of the body
+pub const FRAME_HEADER_SIZE: usize = 8;
+//a full block with 2048 transactions and their testimonies takes a few MB
+pub const MAX_FRAME_SIZE: u32 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameHeader {
+    pub size: u32,
+    pub checksum: u32,
+}
+
+pub fn checksum(body: &[u8]) -> u32 {
+    let digest = digest::digest(&digest::SHA256, body);
+    u32::from_be_bytes(digest.as_ref()[0..4].try_into().unwrap())
+}
+
+//encode the message into a whole frame, including the header
+pub fn encode(msg: &Message) -> Result<Vec<u8>, String> {
+    let payload = bincode::config()
+        .limit(MAX_FRAME_SIZE as u64)
+        .serialize(msg)
+        .map_err(|e| format!("fail to serialize the message: {}", e))?;
+    let size = payload.len() + 1;
+    if size > MAX_FRAME_SIZE as usize {
+        return Err(format!("message of {} bytes exceeds the frame limit", size));
+    }
+    let mut body: Vec<u8> = Vec::with_capacity(size);
+    body.push(msg.type_id());
+    body.extend_from_slice(&payload);
+
+    let mut frame: Vec<u8> = Vec::with_capacity(FRAME_HEADER_SIZE + size);
+    frame.extend_from_slice(&(size as u32).to_be_bytes());
+    frame.extend_from_slice(&checksum(&body).to_be_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+//check the size before the body is read, so that a peer cannot make us allocate
+//an arbitrary amount of memory
+pub fn decode_header(header: &[u8; FRAME_HEADER_SIZE]) -> Result<FrameHeader, String> {
+    let size = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let checksum = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    if size == 0 {
+        return Err(String::from("empty frame"));
+    }
+    if size > MAX_FRAME_SIZE {
+        return Err(format!("frame of {} bytes exceeds the limit", size));
+    }
+    Ok(FrameHeader {
+        size,
+        checksum,
+    })
+}
+
+pub fn verify_checksum(header: &FrameHeader, body: &[u8]) -> Result<(), String> {
+    if body.len() != header.size as usize {
+        return Err(format!("expect {} bytes, get {}", header.size, body.len()));
+    }
+    let actual = checksum(body);
+    if actual != header.checksum {
+        return Err(format!(
+            "checksum mismatch: expect {:#x}, get {:#x}",
+            header.checksum, actual
+        ));
+    }
+    Ok(())
+}
+
+//decode the body of a frame whose checksum has been verified
+pub fn decode(body: &[u8]) -> Result<Message, String> {
+    let (type_id, payload) = match body.split_first() {
+        Some((type_id, payload)) => (*type_id, payload),
+        None => return Err(String::from("empty frame")),
+    };
+    if type_id >= NUM_MESSAGE_TYPES {
+        return Err(format!("unknown message type {}", type_id));
+    }
+    let msg: Message = bincode::config()
+        .limit(MAX_FRAME_SIZE as u64)
+        .deserialize(payload)
+        .map_err(|e| format!("fail to deserialize message of type {}: {}", type_id, e))?;
+    if msg.type_id() != type_id {
+        return Err(format!(
+            "message type mismatch: expect {}, get {}",
+            type_id, msg.type_id()
+        ));
+    }
+    Ok(msg)
+}
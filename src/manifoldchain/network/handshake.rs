@@ -0,0 +1,183 @@
+use serde::{Serialize, Deserialize};
+use crate::{
+    types::hash::H256,
+    manifoldchain::{
+        configuration::Configuration,
+        multichain::Multichain,
+        network::{
+            peer::Direction,
+            identity::{self, NodeIdentity, PeerId},
+            secure::{self, Sealer, Opener, SEALED_HEADER_SIZE, TAG_LEN, EPHEMERAL_KEY_SIZE},
+        },
+    },
+};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::{
+    io,
+    net::SocketAddr,
+};
+
+//the version of the P2P protocol spoken by this node
+pub const PROTOCOL_VERSION: u32 = 2;
+//the oldest version this node still talks to, version 1 had no encryption
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+//the handshake carries one tip per shard, so it is small
+pub const MAX_HANDSHAKE_SIZE: u32 = 64 * 1024;
+
+//capability flags announced in the handshake
+pub const CAP_FULL_NODE: u32 = 1;
+pub const CAP_DA_SAMPLER: u32 = 1 << 1;
+pub const CAP_LIGHT_CLIENT: u32 = 1 << 2;
+
+//the first message exchanged over a new connection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    pub magic: u32,
+    pub chain_id: H256,
+    pub version: u32,
+    pub node_id: u64,
+    pub identity: PeerId, //the ed25519 public key which signs the key exchange
+    pub listen_addr: SocketAddr,
+    pub shard_id: u32,
+    pub tips: Vec<(H256, u64)>, //(tip hash, height), indexed by shard_id
+    pub capabilities: u32,
+}
+
+//the handshake is sent over the encrypted channel, along with the signature of the
+//key exchange by the identity key of the sender
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SignedHandshake {
+    handshake: Handshake,
+    signature: Vec<u8>,
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+//the network magic separates the experiments running on the same host
+pub fn network_magic(exper_number: usize) -> u32 {
+    0x4d430000 ^ (exper_number as u32)
+}
+
+impl Handshake {
+    pub fn new(
+        multichain: &Multichain,
+        config: &Configuration,
+        identity: &NodeIdentity,
+        listen_addr: SocketAddr,
+        capabilities: u32,
+    ) -> Self {
+        let tips: Vec<(H256, u64)> = multichain
+            .get_tips()
+            .into_iter()
+            .map(|(hash, height)| (hash, height as u64))
+            .collect();
+        Handshake {
+            magic: network_magic(config.exper_number),
+            chain_id: multichain.get_chain_id(),
+            version: PROTOCOL_VERSION,
+            node_id: config.node_id as u64,
+            identity: identity.peer_id(),
+            listen_addr,
+            shard_id: config.shard_id() as u32,
+            tips,
+            capabilities,
+        }
+    }
+
+    pub fn has_capability(&self, capability: u32) -> bool {
+        self.capabilities & capability != 0
+    }
+
+    //check whether the remote handshake is compatible with the local one
+    pub fn check_compatible(&self, remote: &Handshake) -> Result<(), String> {
+        if remote.magic != self.magic {
+            return Err(format!(
+                "network magic mismatch: expect {:#x}, get {:#x}",
+                self.magic, remote.magic
+            ));
+        }
+        if remote.chain_id != self.chain_id {
+            return Err(format!(
+                "chain id mismatch: expect {:?}, get {:?}",
+                self.chain_id, remote.chain_id
+            ));
+        }
+        if remote.version < MIN_PROTOCOL_VERSION {
+            return Err(format!(
+                "protocol version {} is older than {}",
+                remote.version, MIN_PROTOCOL_VERSION
+            ));
+        }
+        if remote.tips.len() != self.tips.len() ||
+            remote.shard_id as usize >= self.tips.len() {
+            return Err(format!(
+                "shard mismatch: {} shards and shard id {}",
+                remote.tips.len(), remote.shard_id
+            ));
+        }
+        if remote.identity == self.identity ||
+            (remote.node_id == self.node_id && remote.listen_addr == self.listen_addr) {
+            return Err(String::from("connecting to itself"));
+        }
+        Ok(())
+    }
+
+    //exchange the x25519 ephemeral keys, then the signed handshakes over the encrypted
+    //channel, return the authenticated remote handshake and the ciphers of the connection
+    pub async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: &mut S,
+        identity: &NodeIdentity,
+        direction: Direction,
+    ) -> io::Result<(Handshake, Sealer, Opener)> {
+        let (private_key, local_public) = secure::ephemeral_key().map_err(invalid_data)?;
+        stream.write_all(&local_public).await?;
+        stream.flush().await?;
+        let mut remote_public = [0u8; EPHEMERAL_KEY_SIZE];
+        stream.read_exact(&mut remote_public).await?;
+        let transcript = secure::transcript(&local_public, &remote_public, direction);
+        let (mut sealer, mut opener) = secure::derive_ciphers(
+            private_key,
+            &remote_public,
+            &transcript,
+            direction,
+        ).map_err(invalid_data)?;
+
+        let signed = SignedHandshake {
+            handshake: self.clone(),
+            signature: identity.sign(&secure::signing_payload(direction, &transcript)),
+        };
+        let buffer = bincode::serialize(&signed).map_err(invalid_data)?;
+        stream.write_all(&sealer.seal(&buffer).map_err(invalid_data)?).await?;
+        stream.flush().await?;
+
+        let mut size_buffer = [0u8; SEALED_HEADER_SIZE];
+        stream.read_exact(&mut size_buffer).await?;
+        let size = secure::Opener::decode_size(&size_buffer, MAX_HANDSHAKE_SIZE + TAG_LEN as u32)
+            .map_err(invalid_data)?;
+        let mut buffer = vec![0u8; size];
+        stream.read_exact(&mut buffer).await?;
+        let buffer = opener.open(&size_buffer, &mut buffer).map_err(invalid_data)?;
+        let remote: SignedHandshake = bincode::config()
+            .limit(MAX_HANDSHAKE_SIZE as u64)
+            .deserialize(&buffer)
+            .map_err(invalid_data)?;
+        let remote_direction = match direction {
+            Direction::Outgoing => Direction::Incoming,
+            Direction::Incoming => Direction::Outgoing,
+        };
+        if !identity::verify(
+            &remote.handshake.identity,
+            &secure::signing_payload(remote_direction, &transcript),
+            &remote.signature,
+        ) {
+            return Err(invalid_data(format!(
+                "invalid signature of the key exchange by {}",
+                remote.handshake.identity
+            )));
+        }
+        Ok((remote.handshake, sealer, opener))
+    }
+}
@@ -0,0 +1,86 @@
+use crate::types::hash::H256;
+use log::info;
+use ring::{
+    rand::SystemRandom,
+    signature::{self, Ed25519KeyPair, KeyPair},
+};
+use std::{
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+//the ed25519 public key of a node, which identifies the node across addresses
+pub type PeerId = H256;
+
+//the long-term identity key of this node, it signs the key exchange of every connection
+#[derive(Clone)]
+pub struct NodeIdentity {
+    key_pair: Arc<Ed25519KeyPair>,
+    peer_id: PeerId,
+}
+
+impl NodeIdentity {
+    //load the pkcs8 key at the path, or generate one and save it there
+    pub fn load_or_generate(path: &str) -> Result<Self, String> {
+        let path = Path::new(path);
+        let pkcs8_bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                let rng = SystemRandom::new();
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng)
+                    .map_err(|_| String::from("fail to generate the identity key"))?;
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)
+                        .map_err(|e| format!("fail to create {:?}: {}", dir, e))?;
+                }
+                fs::write(path, pkcs8.as_ref())
+                    .map_err(|e| format!("fail to save the identity key to {:?}: {}", path, e))?;
+                info!("Generate a new identity key at {:?}", path);
+                pkcs8.as_ref().to_vec()
+            }
+        };
+        let key_pair = Ed25519KeyPair::from_pkcs8(&pkcs8_bytes)
+            .map_err(|e| format!("invalid identity key {:?}: {}", path, e))?;
+        Ok(Self::from_key_pair(key_pair))
+    }
+
+    //an identity which is not persisted
+    pub fn random() -> Self {
+        Self::from_key_pair(crate::types::key_pair::random())
+    }
+
+    fn from_key_pair(key_pair: Ed25519KeyPair) -> Self {
+        let mut public_key = [0u8; 32];
+        public_key.copy_from_slice(key_pair.public_key().as_ref());
+        NodeIdentity {
+            key_pair: Arc::new(key_pair),
+            peer_id: public_key.into(),
+        }
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(msg).as_ref().to_vec()
+    }
+}
+
+pub fn verify(peer_id: &PeerId, msg: &[u8], sig: &[u8]) -> bool {
+    signature::UnparsedPublicKey::new(&signature::ED25519, peer_id.as_ref())
+        .verify(msg, sig)
+        .is_ok()
+}
+
+//parse the hex encoded public key of a node
+pub fn parse_peer_id(s: &str) -> Result<PeerId, String> {
+    let bytes = hex::decode(s).map_err(|e| e.to_string())?;
+    if bytes.len() != 32 {
+        return Err(format!("expected 32 bytes, got {}", bytes.len()));
+    }
+    let mut peer_id = [0u8; 32];
+    peer_id.copy_from_slice(&bytes);
+    Ok(PeerId::from(peer_id))
+}
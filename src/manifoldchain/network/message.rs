@@ -14,12 +14,19 @@ use crate::{
                 VersaHash,
                 ExclusiveFullBlock,
                 InclusiveFullBlock,
-            }
+            },
+            compact_block::{
+                CompactBlock,
+                BlockTxnsRequest,
+                BlockTxns,
+            },
         },
         transaction::Transaction,
         testimony::Testimony,
         fraudproof::FraudProof,
         network::worker::{SampleIndex, Sample},
+        light_client::TxProof,
+        sharding::rotation::StateSnapshot,
     }
 };
 
@@ -55,6 +62,11 @@ pub enum Message {
     NewInFullBlockHash((Vec<H256>, u32)),
     GetInFullBlocks((Vec<H256>, u32)),
     InFullBlocks((Vec<InclusiveFullBlock>, u32)),
+    //Compact full blocks, the content is rebuilt from the mempool
+    GetCompactBlocks((Vec<H256>, u32)),
+    CompactBlocks((Vec<CompactBlock>, u32)),
+    GetBlockTxns((BlockTxnsRequest, u32)),
+    BlockTxns((BlockTxns, u32)),
     //FraudProof
     NewFraudProofHash(Vec<H256>),
     GetFraudProofs(Vec<H256>),
@@ -66,4 +78,91 @@ pub enum Message {
     //key: block_hash, tx_index, value: (sample_index, sample) 
     //missing block
     NewMissBlockHash((Vec<H256>, u32)),
+    //Peer discovery
+    GetAddr,
+    Addr(Vec<(std::net::SocketAddr, u32)>), //(listening addr, shard_id)
+    //Light client
+    GetHeaders((Vec<H256>, u32)), //(locator, shard_id)
+    Headers((Vec<VersaBlock>, u32)), //exclusive and inclusive blocks only
+    GetTxProof((H256, u32)), //(tx_hash, shard_id)
+    TxProof((TxProof, u32)),
+    //Committee rotation
+    GetSnapshot((H256, u32)), //(block_hash, shard_id)
+    Snapshot((StateSnapshot, u32)),
+    Rotate(u32), //the sender moves to the shard
+}
+
+//the number of message types, the type ids are 0..NUM_MESSAGE_TYPES
+pub const NUM_MESSAGE_TYPES: u8 = 40;
+
+//the names of the message types, indexed by the type id
+pub const MESSAGE_TYPE_NAMES: [&str; NUM_MESSAGE_TYPES as usize] = [
+    "Ping", "Pong",
+    "NewTransactionHash", "GetTransactions", "Transactions",
+    "NewTestimonyHash", "GetTestimonies", "Testimonies",
+    "NewExBlockHash", "GetExBlocks", "ExBlocks",
+    "NewInBlockHash", "GetInBlocks", "InBlocks",
+    "NewExFullBlockHash", "GetExFullBlocks", "ExFullBlocks",
+    "NewInFullBlockHash", "GetInFullBlocks", "InFullBlocks",
+    "GetCompactBlocks", "CompactBlocks", "GetBlockTxns", "BlockTxns",
+    "NewFraudProofHash", "GetFraudProofs", "FraudProofs",
+    "NewSamples", "GetSamples", "Samples",
+    "NewMissBlockHash", "GetAddr", "Addr",
+    "GetHeaders", "Headers", "GetTxProof", "TxProof",
+    "GetSnapshot", "Snapshot", "Rotate",
+];
+
+pub fn type_name(type_id: u8) -> &'static str {
+    MESSAGE_TYPE_NAMES
+        .get(type_id as usize)
+        .cloned()
+        .unwrap_or("Unknown")
+}
+
+impl Message {
+    //the type id which prefixes the message on the wire, in the order of the variants
+    pub fn type_id(&self) -> u8 {
+        match self {
+            Message::Ping(_) => 0,
+            Message::Pong(_) => 1,
+            Message::NewTransactionHash(_) => 2,
+            Message::GetTransactions(_) => 3,
+            Message::Transactions(_) => 4,
+            Message::NewTestimonyHash(_) => 5,
+            Message::GetTestimonies(_) => 6,
+            Message::Testimonies(_) => 7,
+            Message::NewExBlockHash(_) => 8,
+            Message::GetExBlocks(_) => 9,
+            Message::ExBlocks(_) => 10,
+            Message::NewInBlockHash(_) => 11,
+            Message::GetInBlocks(_) => 12,
+            Message::InBlocks(_) => 13,
+            Message::NewExFullBlockHash(_) => 14,
+            Message::GetExFullBlocks(_) => 15,
+            Message::ExFullBlocks(_) => 16,
+            Message::NewInFullBlockHash(_) => 17,
+            Message::GetInFullBlocks(_) => 18,
+            Message::InFullBlocks(_) => 19,
+            Message::GetCompactBlocks(_) => 20,
+            Message::CompactBlocks(_) => 21,
+            Message::GetBlockTxns(_) => 22,
+            Message::BlockTxns(_) => 23,
+            Message::NewFraudProofHash(_) => 24,
+            Message::GetFraudProofs(_) => 25,
+            Message::FraudProofs(_) => 26,
+            Message::NewSamples(_) => 27,
+            Message::GetSamples(_) => 28,
+            Message::Samples(_) => 29,
+            Message::NewMissBlockHash(_) => 30,
+            Message::GetAddr => 31,
+            Message::Addr(_) => 32,
+            Message::GetHeaders(_) => 33,
+            Message::Headers(_) => 34,
+            Message::GetTxProof(_) => 35,
+            Message::TxProof(_) => 36,
+            Message::GetSnapshot(_) => 37,
+            Message::Snapshot(_) => 38,
+            Message::Rotate(_) => 39,
+        }
+    }
 }
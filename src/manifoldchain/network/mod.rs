@@ -1,4 +1,17 @@
+pub mod address_book;
+pub mod cache;
+pub mod capture;
+pub mod connection_manager;
+pub mod emulation;
+pub mod frame;
+pub mod handshake;
+pub mod identity;
 pub mod message;
+pub mod orphan_pool;
 pub mod peer;
+pub mod peer_score;
+pub mod request;
+pub mod secure;
 pub mod server;
+pub mod stats;
 pub mod worker;
@@ -0,0 +1,110 @@
+use crate::{
+    types::hash::H256,
+    manifoldchain::{
+        block::versa_block::VersaBlock,
+        configuration::Configuration,
+        network::{
+            cache::{Cache, CacheStats},
+            message::Message,
+            request::RequestTracker,
+        },
+    },
+};
+use log::debug;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+//the retries of an orphan never wait longer than this
+const MAX_BACKOFF: u64 = 60_000; //ms
+
+struct Orphan {
+    block: VersaBlock,
+    shard_id: usize, //the shard of the missing parent
+    retries: usize,
+    next_retry: Instant,
+}
+
+//the blocks whose parent is missing, keyed by the hash of the parent. The parent is
+//requested again with an exponential backoff, and the orphan is evicted after
+//config.max_orphan_retries retries.
+#[derive(Clone)]
+pub struct OrphanPool {
+    inner: Arc<Mutex<Cache<H256, Orphan>>>,
+    requests: RequestTracker,
+    retry_interval: u64, //ms
+    max_retries: usize,
+}
+
+impl OrphanPool {
+    pub fn new(config: &Configuration, requests: &RequestTracker) -> Self {
+        OrphanPool {
+            inner: Arc::new(Mutex::new(Cache::new("orphans", config.max_orphans, None))),
+            requests: requests.clone(),
+            retry_interval: config.orphan_retry_interval,
+            max_retries: config.max_orphan_retries,
+        }
+    }
+
+    fn backoff(&self, retries: usize) -> Duration {
+        let backoff = self.retry_interval.saturating_mul(1 << retries.min(16));
+        Duration::from_millis(backoff.min(MAX_BACKOFF))
+    }
+
+    //keep the block until its parent arrives, the first request for the parent is
+    //sent by the caller
+    pub fn insert(&self, parent_hash: H256, shard_id: usize, block: VersaBlock) {
+        let next_retry = Instant::now() + self.backoff(0);
+        self.inner.lock().unwrap().insert(parent_hash, Orphan {
+            block,
+            shard_id,
+            retries: 0,
+            next_retry,
+        });
+    }
+
+    //take out the orphan waiting for the parent
+    pub fn take(&self, parent_hash: &H256) -> Option<VersaBlock> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.get(parent_hash).is_some() {
+            true => inner.remove(parent_hash).map(|orphan| orphan.block),
+            false => None,
+        }
+    }
+
+    //request the parents which are due again, and give up on the orphans which
+    //have been retried too many times
+    pub fn retry_orphans(&self) {
+        let now = Instant::now();
+        let mut missing: HashMap<usize, Vec<H256>> = HashMap::new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            for parent_hash in inner.keys() {
+                let orphan = inner.peek_mut(&parent_hash).unwrap();
+                if orphan.next_retry > now {
+                    continue;
+                }
+                if orphan.retries >= self.max_retries {
+                    debug!("Evict the orphan of parent {:?} after {} retries", parent_hash, orphan.retries);
+                    inner.evict(&parent_hash);
+                    continue;
+                }
+                orphan.retries += 1;
+                orphan.next_retry = now + self.backoff(orphan.retries);
+                missing.entry(orphan.shard_id).or_insert_with(Vec::new).push(parent_hash);
+            }
+        }
+        for (shard_id, hashs) in missing {
+            self.requests.request_from_shard(
+                Message::NewMissBlockHash((hashs, shard_id as u32)),
+                shard_id,
+            );
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats()
+    }
+}
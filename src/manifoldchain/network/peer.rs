@@ -1,21 +1,27 @@
-use super::message::Message;
+use super::{
+    message::Message,
+    frame,
+    identity::PeerId,
+};
 use futures::{channel::mpsc, sink::SinkExt};
-use log::trace;
+use log::{trace, warn};
 use smol::Async;
 
 pub fn new(
     stream: &Async<std::net::TcpStream>,
+    identity: PeerId,
 ) -> std::io::Result<(mpsc::UnboundedReceiver<Vec<u8>>, Handle)> {
     let (write_sender, write_receiver) = mpsc::unbounded();
     let addr = stream.get_ref().peer_addr()?;
     let handle = Handle {
         write_queue: write_sender,
         addr,
+        identity,
     };
     Ok((write_receiver, handle))
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Direction {
     Incoming,
     Outgoing,
@@ -25,6 +31,7 @@ pub enum Direction {
 pub struct Handle {
     addr: std::net::SocketAddr,
     write_queue: mpsc::UnboundedSender<Vec<u8>>,
+    identity: PeerId, //authenticated in the handshake, every message is attributable to it
 }
 
 #[cfg(any(test,test_utilities))]
@@ -34,7 +41,13 @@ pub struct TestReceiver {
 
 impl Handle {
     pub fn write(&mut self, msg: Message) {
-        let buffer = bincode::serialize(&msg).unwrap();
+        let buffer = match frame::encode(&msg) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                warn!("Fail to send message to {}: {}", self.addr, e);
+                return;
+            }
+        };
         smol::block_on(async move {
             if self.write_queue.send(buffer).await.is_err() {
                 trace!("Trying to send to disconnected peer");
@@ -46,12 +59,27 @@ impl Handle {
         &self.addr
     }
 
+    pub fn identity(&self) -> &PeerId {
+        &self.identity
+    }
+
+    //a handle which is not backed by a connection, the messages written to it are dropped
+    pub fn detached(addr: std::net::SocketAddr) -> Handle {
+        let (write_queue, _) = mpsc::unbounded();
+        Handle {
+            addr,
+            write_queue,
+            identity: PeerId::default(),
+        }
+    }
+
     #[cfg(any(test,test_utilities))]
     pub fn test_handle() -> (Handle, TestReceiver) {
         let (s,r) = mpsc::unbounded();
         (Handle {
             addr: std::net::SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)), 12321),
             write_queue: s,
+            identity: PeerId::default(),
         },
         TestReceiver {
             r
@@ -63,7 +91,7 @@ impl Handle {
 impl TestReceiver {
     pub fn recv(&mut self) -> Message {
         let bytes = smol::block_on(futures::stream::StreamExt::next(&mut self.r)).unwrap();
-        let msg: Message = bincode::deserialize(&bytes).unwrap();
+        let msg: Message = frame::decode(&bytes[frame::FRAME_HEADER_SIZE..]).unwrap();
         msg
     }
 }
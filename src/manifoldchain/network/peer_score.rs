@@ -0,0 +1,355 @@
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    collections::HashMap,
+};
+use crate::manifoldchain::{
+    configuration::Configuration,
+    network::identity::PeerId,
+};
+
+//the score of a peer is reduced by one point every SCORE_DECAY seconds
+const SCORE_DECAY: u64 = 60; //s
+//unsolicited transactions are counted within windows of FLOOD_WINDOW seconds
+const FLOOD_WINDOW: u64 = 60; //s
+
+//the misbehaviors of a peer, reported by the network worker
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Misbehavior {
+    InvalidBlock,          //a block failing validate_block
+    InvalidFraudProof,     //a fraud proof verify_fraud_proof finds invalid
+    InvalidTransaction,    //a transaction failing validate_tx
+    TransactionFlood,      //too many unsolicited transactions
+    MalformedMessage,      //a frame which cannot be decoded
+    InvalidTxProof,        //a transaction proof failing against the synced headers
+    InvalidSnapshot,       //a state snapshot not matching the state root of its block
+    UnsolicitedTransactions(usize), //unsolicited transactions which are known or rejected
+}
+
+impl Misbehavior {
+    //the penalty added to the score of the peer, a peer is banned once its score
+    //reaches config.ban_threshold
+    pub fn penalty(&self) -> u32 {
+        match self {
+            Misbehavior::InvalidBlock => 50,
+            Misbehavior::InvalidFraudProof => 50,
+            Misbehavior::TransactionFlood => 20,
+            Misbehavior::MalformedMessage => 50,
+            Misbehavior::InvalidTxProof => 50,
+            Misbehavior::InvalidSnapshot => 50,
+            //a transaction may be invalid only because of a conflicting one in the mempool
+            Misbehavior::InvalidTransaction => 2,
+            //unsolicited transactions are fine unless they turn into a flood
+            Misbehavior::UnsolicitedTransactions(_) => 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BanEntry {
+    pub identity: PeerId,
+    pub addr: SocketAddr, //the last known listening addr of the peer
+    pub reason: String,
+    pub ban_count: u32,
+    pub until: Option<u64>, //unix time in seconds, None for a permanent ban
+}
+
+impl BanEntry {
+    fn is_active(&self, now: u64) -> bool {
+        match self.until {
+            Some(until) => until > now,
+            None => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerScoreStatus {
+    pub identity: PeerId,
+    pub addr: SocketAddr,
+    pub score: u32,
+    pub last_misbehavior: Option<Misbehavior>,
+}
+
+#[derive(Debug, Clone)]
+struct PeerScore {
+    addr: SocketAddr,
+    score: u32,
+    last_update: u64,
+    last_misbehavior: Option<Misbehavior>,
+    flood_window: u64,
+    unsolicited_txs: usize,
+}
+
+#[derive(Default)]
+struct Scores {
+    scores: HashMap<PeerId, PeerScore>,
+    //expired bans are kept so that repeated offenders get banned permanently
+    bans: HashMap<PeerId, BanEntry>,
+}
+
+//what is written to disk, so that the reputation of a peer survives restarts
+#[derive(Serialize, Deserialize, Default)]
+struct SavedScores {
+    bans: Vec<BanEntry>,
+    scores: Vec<PeerScoreStatus>,
+}
+
+//the misbehavior scores and the ban list, keyed by the handshake identity of the peer
+//so that a peer cannot shed its reputation by reconnecting from another addr
+#[derive(Clone)]
+pub struct PeerScores {
+    inner: Arc<Mutex<Scores>>,
+    path: Option<PathBuf>,
+    ban_threshold: u32,
+    ban_duration: u64,
+    max_bans: u32,
+    max_unsolicited_txs: usize,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl PeerScores {
+    //load the bans and scores persisted by the previous run of this node
+    pub fn new(config: &Configuration) -> Self {
+        let path = PathBuf::from(format!(
+            "./log/exper_{}/bans_{}.json",
+            config.exper_number,
+            config.node_id
+        ));
+        let scores = Self::new_in_memory(config);
+        let scores = PeerScores {
+            path: Some(path.clone()),
+            ..scores
+        };
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_json::from_str::<SavedScores>(&content) {
+                Ok(saved) => {
+                    info!("Load {} bans and {} scores from {:?}", saved.bans.len(), saved.scores.len(), path);
+                    let now = now_secs();
+                    let mut inner = scores.inner.lock().unwrap();
+                    for ban in saved.bans {
+                        inner.bans.insert(ban.identity, ban);
+                    }
+                    for status in saved.scores {
+                        inner.scores.insert(status.identity, PeerScore {
+                            addr: status.addr,
+                            score: status.score,
+                            last_update: now,
+                            last_misbehavior: status.last_misbehavior,
+                            flood_window: now,
+                            unsolicited_txs: 0,
+                        });
+                    }
+                }
+                Err(e) => warn!("Fail to parse the ban list {:?}: {}", path, e),
+            }
+        }
+        scores
+    }
+
+    //a ban list which is never written to disk
+    pub fn new_in_memory(config: &Configuration) -> Self {
+        PeerScores {
+            inner: Arc::new(Mutex::new(Scores::default())),
+            path: None,
+            ban_threshold: config.ban_threshold,
+            ban_duration: config.ban_duration,
+            max_bans: config.max_bans,
+            max_unsolicited_txs: config.max_unsolicited_txs,
+        }
+    }
+
+    //add the penalty of the misbehavior to the peer, return true if the peer gets banned
+    pub fn penalize(&self, identity: PeerId, addr: SocketAddr, misbehavior: Misbehavior) -> bool {
+        let now = now_secs();
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.scores
+            .entry(identity)
+            .or_insert(PeerScore {
+                addr,
+                score: 0,
+                last_update: now,
+                last_misbehavior: None,
+                flood_window: now,
+                unsolicited_txs: 0,
+            });
+        entry.addr = addr;
+        //the old misbehaviors are forgiven gradually
+        let decay = now.saturating_sub(entry.last_update) / SCORE_DECAY;
+        if decay > 0 {
+            entry.score = entry.score.saturating_sub(decay as u32);
+            entry.last_update += decay * SCORE_DECAY;
+        }
+
+        let mut misbehavior = misbehavior;
+        if let Misbehavior::UnsolicitedTransactions(num) = misbehavior {
+            if now >= entry.flood_window + FLOOD_WINDOW {
+                entry.flood_window = now;
+                entry.unsolicited_txs = 0;
+            }
+            entry.unsolicited_txs += num;
+            if entry.unsolicited_txs <= self.max_unsolicited_txs {
+                return false;
+            }
+            entry.unsolicited_txs = 0;
+            misbehavior = Misbehavior::TransactionFlood;
+        }
+        let penalty = misbehavior.penalty();
+        if penalty == 0 {
+            return false;
+        }
+        entry.score = entry.score.saturating_add(penalty);
+        entry.last_misbehavior = Some(misbehavior.clone());
+        warn!("Peer {} ({}) misbehaves: {:?}, score {}", addr, identity, misbehavior, entry.score);
+        if entry.score < self.ban_threshold {
+            drop(inner);
+            self.save();
+            return false;
+        }
+        inner.scores.remove(&identity);
+        drop(inner);
+        self.ban(identity, addr, format!("{:?}", misbehavior), false);
+        true
+    }
+
+    //ban the peer for config.ban_duration seconds, or forever if it has been banned
+    //config.max_bans times
+    pub fn ban(&self, identity: PeerId, addr: SocketAddr, reason: String, permanent: bool) {
+        let now = now_secs();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let ban_count = inner.bans
+                .get(&identity)
+                .map(|ban| ban.ban_count)
+                .unwrap_or(0) + 1;
+            let until = match permanent || ban_count >= self.max_bans {
+                true => None,
+                false => Some(now + self.ban_duration),
+            };
+            match until {
+                Some(until) => info!("Ban peer {} ({}) for {}s: {}", addr, identity, until - now, reason),
+                None => info!("Ban peer {} ({}) permanently: {}", addr, identity, reason),
+            }
+            inner.bans.insert(identity, BanEntry {
+                identity,
+                addr,
+                reason,
+                ban_count,
+                until,
+            });
+        }
+        self.save();
+    }
+
+    //lift the ban, return false if the peer is not banned
+    pub fn unban(&self, identity: &PeerId) -> bool {
+        let is_banned = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.scores.remove(identity);
+            inner.bans.remove(identity).is_some()
+        };
+        if is_banned {
+            self.save();
+        }
+        is_banned
+    }
+
+    pub fn is_banned(&self, identity: &PeerId) -> bool {
+        let now = now_secs();
+        self.inner
+            .lock()
+            .unwrap()
+            .bans
+            .get(identity)
+            .map(|ban| ban.is_active(now))
+            .unwrap_or(false)
+    }
+
+    //whether a banned peer was last seen on the addr, only a hint to skip dialing it
+    //since the identity behind an addr is known after the handshake
+    pub fn is_addr_banned(&self, addr: &SocketAddr) -> bool {
+        let now = now_secs();
+        self.inner
+            .lock()
+            .unwrap()
+            .bans
+            .values()
+            .any(|ban| ban.addr == *addr && ban.is_active(now))
+    }
+
+    //the identity of the banned peer last seen on the addr
+    pub fn get_banned_identity(&self, addr: &SocketAddr) -> Option<PeerId> {
+        self.inner
+            .lock()
+            .unwrap()
+            .bans
+            .values()
+            .find(|ban| ban.addr == *addr)
+            .map(|ban| ban.identity)
+    }
+
+    //the active bans
+    pub fn get_bans(&self) -> Vec<BanEntry> {
+        let now = now_secs();
+        self.inner
+            .lock()
+            .unwrap()
+            .bans
+            .values()
+            .filter(|ban| ban.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_scores(&self) -> Vec<PeerScoreStatus> {
+        self.inner
+            .lock()
+            .unwrap()
+            .scores
+            .iter()
+            .map(|(identity, score)| PeerScoreStatus {
+                identity: *identity,
+                addr: score.addr,
+                score: score.score,
+                last_misbehavior: score.last_misbehavior.clone(),
+            })
+            .collect()
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let saved = SavedScores {
+            bans: self.inner
+                .lock()
+                .unwrap()
+                .bans
+                .values()
+                .cloned()
+                .collect(),
+            scores: self.get_scores(),
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let res = serde_json::to_string_pretty(&saved)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(path, content).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            warn!("Fail to save the ban list to {:?}: {}", path, e);
+        }
+    }
+}
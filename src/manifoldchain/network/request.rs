@@ -0,0 +1,496 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        configuration::Configuration,
+        network::{
+            message::Message,
+            peer,
+            server::Handle as ServerHandle,
+            worker::SampleIndex,
+        },
+    },
+};
+use log::{info, debug, warn};
+use serde::Serialize;
+use rand::seq::SliceRandom;
+use std::{
+    net::SocketAddr,
+    time::{self, SystemTime},
+    thread,
+    sync::{Arc, Mutex},
+    collections::HashMap,
+};
+
+//the interval between two scans of the outstanding requests
+const MONITOR_INTERVAL: u64 = 500; //ms
+
+pub type RequestId = u64;
+
+//one requested item, identified by the hash of the data it asks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestItem {
+    Transaction(H256),
+    Testimony(H256),
+    ExBlock(H256),
+    InBlock(H256),
+    ExFullBlock(H256),
+    InFullBlock(H256),
+    CompactBlock(H256),
+    MissingBlock(H256),
+    FraudProof(H256),
+    Sample(SampleIndex),
+    TxProof(H256),
+    Snapshot(H256),
+}
+
+#[derive(Debug, Clone)]
+struct OutstandingRequest {
+    id: RequestId,
+    shard_id: Option<usize>, //None if any peer can serve the request
+    request_shard_id: u32, //the shard id in the request message, reused by the retries
+    peer: SocketAddr,
+    tried: Vec<SocketAddr>,
+    sent_at: SystemTime,
+    retries: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RequestStats {
+    pub outstanding: usize,
+    pub sent: u64,
+    pub completed: u64,
+    pub timed_out: u64,
+    pub retried: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct Requests {
+    next_id: RequestId,
+    outstanding: HashMap<RequestItem, OutstandingRequest>,
+    stats: RequestStats,
+}
+
+//Track the Get* requests sent to peers. A request which is not answered
+//within the timeout is sent again to another peer in the right shard.
+#[derive(Clone)]
+pub struct RequestTracker {
+    inner: Arc<Mutex<Requests>>,
+    server: ServerHandle,
+    timeout: time::Duration,
+    max_retries: usize,
+}
+
+impl RequestItem {
+    //split a request message into the items it asks for
+    pub fn from_request(msg: &Message) -> Vec<(RequestItem, Option<usize>)> {
+        match msg {
+            Message::GetTransactions((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::Transaction(*h), Some(*shard_id as usize)))
+                .collect(),
+            Message::GetTestimonies((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::Testimony(*h), Some(*shard_id as usize)))
+                .collect(),
+            Message::GetExBlocks((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::ExBlock(*h), Some(*shard_id as usize)))
+                .collect(),
+            //inclusive blocks are relayed to every shard
+            Message::GetInBlocks((hashs, _)) => hashs
+                .iter()
+                .map(|h| (RequestItem::InBlock(*h), None))
+                .collect(),
+            Message::GetExFullBlocks((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::ExFullBlock(*h), Some(*shard_id as usize)))
+                .collect(),
+            Message::GetInFullBlocks((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::InFullBlock(*h), Some(*shard_id as usize)))
+                .collect(),
+            Message::GetCompactBlocks((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::CompactBlock(*h), Some(*shard_id as usize)))
+                .collect(),
+            //fall back to the full block if the missing content does not come
+            Message::GetBlockTxns((request, shard_id)) => match request.is_exclusive {
+                true => vec![(RequestItem::ExFullBlock(request.block_hash), Some(*shard_id as usize))],
+                false => vec![(RequestItem::InFullBlock(request.block_hash), Some(*shard_id as usize))],
+            },
+            Message::NewMissBlockHash((hashs, shard_id)) => hashs
+                .iter()
+                .map(|h| (RequestItem::MissingBlock(*h), Some(*shard_id as usize)))
+                .collect(),
+            Message::GetFraudProofs(hashs) => hashs
+                .iter()
+                .map(|h| (RequestItem::FraudProof(*h), None))
+                .collect(),
+            Message::GetSamples(indexs) => indexs
+                .iter()
+                .map(|idx| (RequestItem::Sample(*idx), Some(idx.2 as usize)))
+                .collect(),
+            Message::GetTxProof((tx_hash, shard_id)) =>
+                vec![(RequestItem::TxProof(*tx_hash), Some(*shard_id as usize))],
+            Message::GetSnapshot((block_hash, shard_id)) =>
+                vec![(RequestItem::Snapshot(*block_hash), Some(*shard_id as usize))],
+            _ => vec![],
+        }
+    }
+
+    //the shard id carried by a request message, which may differ from the shard of the
+    //peers serving it, e.g. inclusive blocks are asked for with the shard they are
+    //inserted into
+    pub fn request_shard_id(msg: &Message) -> u32 {
+        match msg {
+            Message::GetTransactions((_, shard_id)) |
+            Message::GetTestimonies((_, shard_id)) |
+            Message::GetExBlocks((_, shard_id)) |
+            Message::GetInBlocks((_, shard_id)) |
+            Message::GetExFullBlocks((_, shard_id)) |
+            Message::GetInFullBlocks((_, shard_id)) |
+            Message::GetCompactBlocks((_, shard_id)) |
+            Message::GetBlockTxns((_, shard_id)) |
+            Message::NewMissBlockHash((_, shard_id)) |
+            Message::GetTxProof((_, shard_id)) |
+            Message::GetSnapshot((_, shard_id)) => *shard_id,
+            //the samples carry their own shard ids and the fraud proofs have none
+            _ => 0,
+        }
+    }
+
+    //the items answered by a response message
+    pub fn from_response(msg: &Message) -> Vec<RequestItem> {
+        match msg {
+            Message::Transactions((txs, _)) => txs
+                .iter()
+                .map(|tx| RequestItem::Transaction(tx.hash()))
+                .collect(),
+            Message::Testimonies((tmys, _)) => tmys
+                .iter()
+                .map(|tmy| RequestItem::Testimony(tmy.hash()))
+                .collect(),
+            Message::ExBlocks((blks, _)) => blks
+                .iter()
+                .flat_map(|blk| {
+                    let hash = blk.hash();
+                    vec![RequestItem::ExBlock(hash), RequestItem::MissingBlock(hash)]
+                })
+                .collect(),
+            Message::InBlocks((blks, _)) => blks
+                .iter()
+                .flat_map(|blk| {
+                    let hash = blk.hash();
+                    vec![RequestItem::InBlock(hash), RequestItem::MissingBlock(hash)]
+                })
+                .collect(),
+            Message::ExFullBlocks((blks, _)) => blks
+                .iter()
+                .flat_map(|blk| {
+                    let hash = blk.hash();
+                    vec![
+                        RequestItem::ExFullBlock(hash),
+                        RequestItem::CompactBlock(hash),
+                        RequestItem::MissingBlock(hash),
+                    ]
+                })
+                .collect(),
+            Message::InFullBlocks((blks, _)) => blks
+                .iter()
+                .flat_map(|blk| {
+                    let hash = blk.hash();
+                    vec![
+                        RequestItem::InFullBlock(hash),
+                        RequestItem::CompactBlock(hash),
+                        RequestItem::MissingBlock(hash),
+                    ]
+                })
+                .collect(),
+            Message::CompactBlocks((blks, _)) => blks
+                .iter()
+                .map(|blk| RequestItem::CompactBlock(blk.hash()))
+                .collect(),
+            Message::BlockTxns((block_txns, _)) => match block_txns.is_exclusive {
+                true => vec![RequestItem::ExFullBlock(block_txns.block_hash)],
+                false => vec![RequestItem::InFullBlock(block_txns.block_hash)],
+            },
+            Message::FraudProofs(fps) => fps
+                .iter()
+                .map(|fp| RequestItem::FraudProof(fp.hash()))
+                .collect(),
+            Message::Samples(samples) => samples
+                .iter()
+                .map(|(idx, _)| RequestItem::Sample(*idx))
+                .collect(),
+            Message::TxProof((proof, _)) => vec![RequestItem::TxProof(proof.tx.hash())],
+            Message::Snapshot((snapshot, _)) => vec![RequestItem::Snapshot(snapshot.block_hash)],
+            _ => vec![],
+        }
+    }
+
+    //group the items into request messages, one message per kind
+    pub fn into_requests(items: Vec<RequestItem>, shard_id: u32) -> Vec<Message> {
+        let mut txs: Vec<H256> = vec![];
+        let mut tmys: Vec<H256> = vec![];
+        let mut ex_blks: Vec<H256> = vec![];
+        let mut in_blks: Vec<H256> = vec![];
+        let mut ex_full_blks: Vec<H256> = vec![];
+        let mut in_full_blks: Vec<H256> = vec![];
+        let mut compact_blks: Vec<H256> = vec![];
+        let mut miss_blks: Vec<H256> = vec![];
+        let mut fps: Vec<H256> = vec![];
+        let mut samples: Vec<SampleIndex> = vec![];
+        let mut tx_proofs: Vec<H256> = vec![];
+        let mut snapshots: Vec<H256> = vec![];
+        for item in items {
+            match item {
+                RequestItem::Transaction(h) => txs.push(h),
+                RequestItem::Testimony(h) => tmys.push(h),
+                RequestItem::ExBlock(h) => ex_blks.push(h),
+                RequestItem::InBlock(h) => in_blks.push(h),
+                RequestItem::ExFullBlock(h) => ex_full_blks.push(h),
+                RequestItem::InFullBlock(h) => in_full_blks.push(h),
+                RequestItem::CompactBlock(h) => compact_blks.push(h),
+                RequestItem::MissingBlock(h) => miss_blks.push(h),
+                RequestItem::FraudProof(h) => fps.push(h),
+                RequestItem::Sample(idx) => samples.push(idx),
+                RequestItem::TxProof(h) => tx_proofs.push(h),
+                RequestItem::Snapshot(h) => snapshots.push(h),
+            }
+        }
+        let mut msgs: Vec<Message> = vec![];
+        if !txs.is_empty() {
+            msgs.push(Message::GetTransactions((txs, shard_id)));
+        }
+        if !tmys.is_empty() {
+            msgs.push(Message::GetTestimonies((tmys, shard_id)));
+        }
+        if !ex_blks.is_empty() {
+            msgs.push(Message::GetExBlocks((ex_blks, shard_id)));
+        }
+        if !in_blks.is_empty() {
+            msgs.push(Message::GetInBlocks((in_blks, shard_id)));
+        }
+        if !ex_full_blks.is_empty() {
+            msgs.push(Message::GetExFullBlocks((ex_full_blks, shard_id)));
+        }
+        if !in_full_blks.is_empty() {
+            msgs.push(Message::GetInFullBlocks((in_full_blks, shard_id)));
+        }
+        if !compact_blks.is_empty() {
+            msgs.push(Message::GetCompactBlocks((compact_blks, shard_id)));
+        }
+        if !miss_blks.is_empty() {
+            msgs.push(Message::NewMissBlockHash((miss_blks, shard_id)));
+        }
+        if !fps.is_empty() {
+            msgs.push(Message::GetFraudProofs(fps));
+        }
+        if !samples.is_empty() {
+            msgs.push(Message::GetSamples(samples));
+        }
+        for tx_hash in tx_proofs {
+            msgs.push(Message::GetTxProof((tx_hash, shard_id)));
+        }
+        for block_hash in snapshots {
+            msgs.push(Message::GetSnapshot((block_hash, shard_id)));
+        }
+        msgs
+    }
+}
+
+impl RequestTracker {
+    pub fn new(server: &ServerHandle, config: &Configuration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Requests::default())),
+            server: server.clone(),
+            timeout: time::Duration::from_millis(config.request_timeout),
+            max_retries: config.max_request_retries,
+        }
+    }
+
+    //register the items of the request which are not in flight yet, and
+    //return the request message restricted to them
+    fn track(&self, msg: Message, peer: SocketAddr) -> Vec<Message> {
+        let items = RequestItem::from_request(&msg);
+        if items.is_empty() {
+            //not a request, send it as it is
+            return vec![msg];
+        }
+        let request_shard_id = RequestItem::request_shard_id(&msg);
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let now = SystemTime::now();
+        let mut new_items: Vec<RequestItem> = vec![];
+        for (item, shard_id) in items {
+            if inner.outstanding.contains_key(&item) {
+                continue;
+            }
+            inner.outstanding.insert(item, OutstandingRequest {
+                id,
+                shard_id,
+                request_shard_id,
+                peer,
+                tried: vec![peer],
+                sent_at: now,
+                retries: 0,
+            });
+            new_items.push(item);
+        }
+        if new_items.is_empty() {
+            return vec![];
+        }
+        inner.stats.sent += 1;
+        RequestItem::into_requests(new_items, request_shard_id)
+    }
+
+    //send a request to the given peer
+    pub fn request(&self, peer: &mut peer::Handle, msg: Message) {
+        for msg in self.track(msg, peer.addr().clone()) {
+            peer.write(msg);
+        }
+    }
+
+    //send a request to a random peer of the shard
+    pub fn request_from_shard(&self, msg: Message, shard_id: usize) {
+        let mut peers = self.server.get_peers_by_shard(shard_id);
+        peers.shuffle(&mut rand::thread_rng());
+        match peers.first() {
+            Some(peer) => {
+                for msg in self.track(msg, peer.clone()) {
+                    self.server.send(peer.clone(), msg);
+                }
+            }
+            None => debug!("No peer in shard {} to serve the request", shard_id),
+        }
+    }
+
+    //mark the items answered by the response as completed, return the number of them
+    pub fn complete(&self, msg: &Message) -> usize {
+        let items = RequestItem::from_response(msg);
+        if items.is_empty() {
+            return 0;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let mut completed: usize = 0;
+        for item in items {
+            if inner.outstanding.remove(&item).is_some() {
+                completed += 1;
+            }
+        }
+        inner.stats.completed += completed as u64;
+        completed
+    }
+
+    pub fn get_stats(&self) -> RequestStats {
+        let inner = self.inner.lock().unwrap();
+        let mut stats = inner.stats.clone();
+        stats.outstanding = inner.outstanding.len();
+        stats
+    }
+
+    pub fn start(&self) {
+        let tracker = self.clone();
+        thread::Builder::new()
+            .name("Request-Monitor".to_string())
+            .spawn(move || {
+                tracker.monitor_requests();
+            })
+            .unwrap();
+        info!("Request monitor started");
+    }
+
+    fn monitor_requests(&self) {
+        loop {
+            thread::sleep(time::Duration::from_millis(MONITOR_INTERVAL));
+            let now = SystemTime::now();
+            //take out the timed out requests
+            let expired: Vec<(RequestItem, OutstandingRequest)> = {
+                let mut inner = self.inner.lock().unwrap();
+                let timeout = self.timeout;
+                let expired_items: Vec<RequestItem> = inner.outstanding
+                    .iter()
+                    .filter(|(_, req)| {
+                        now.duration_since(req.sent_at)
+                            .map(|d| d > timeout)
+                            .unwrap_or(false)
+                    })
+                    .map(|(item, _)| *item)
+                    .collect();
+                inner.stats.timed_out += expired_items.len() as u64;
+                expired_items
+                    .into_iter()
+                    .map(|item| {
+                        let req = inner.outstanding.remove(&item).unwrap();
+                        (item, req)
+                    })
+                    .collect()
+            };
+            if expired.is_empty() {
+                continue;
+            }
+            //retry every item against a peer which has not been tried
+            let mut retries: HashMap<(SocketAddr, Option<usize>, u32), Vec<(RequestItem, OutstandingRequest)>>
+                = HashMap::new();
+            let mut failed: u64 = 0;
+            for (item, req) in expired {
+                if req.retries >= self.max_retries {
+                    debug!(
+                        "Request {} for {:?} to {} failed after {} retries", 
+                        req.id, item, req.peer, req.retries
+                    );
+                    failed += 1;
+                    continue;
+                }
+                let mut candidates: Vec<SocketAddr> = match req.shard_id {
+                    Some(shard_id) => self.server.get_peers_by_shard(shard_id),
+                    None => self.server.get_peers(),
+                };
+                candidates.retain(|addr| !req.tried.contains(addr));
+                candidates.shuffle(&mut rand::thread_rng());
+                match candidates.first() {
+                    Some(peer) => {
+                        retries
+                            .entry((peer.clone(), req.shard_id, req.request_shard_id))
+                            .or_insert_with(Vec::new)
+                            .push((item, req));
+                    }
+                    None => {
+                        debug!("Request {} for {:?} failed: no other peer", req.id, item);
+                        failed += 1;
+                    }
+                }
+            }
+            let mut inner = self.inner.lock().unwrap();
+            inner.stats.failed += failed;
+            if failed > 0 {
+                warn!("{} requests failed", failed);
+            }
+            for ((peer, shard_id, request_shard_id), reqs) in retries {
+                let id = inner.next_id;
+                inner.next_id += 1;
+                let mut items: Vec<RequestItem> = vec![];
+                for (item, req) in reqs {
+                    let mut tried = req.tried.clone();
+                    tried.push(peer);
+                    inner.outstanding.insert(item, OutstandingRequest {
+                        id,
+                        shard_id,
+                        request_shard_id,
+                        peer,
+                        tried,
+                        sent_at: SystemTime::now(),
+                        retries: req.retries + 1,
+                    });
+                    items.push(item);
+                }
+                inner.stats.retried += 1;
+                for msg in RequestItem::into_requests(items, request_shard_id) {
+                    self.server.send(peer, msg);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::manifoldchain::network::{
+    peer::Direction,
+    frame::{FRAME_HEADER_SIZE, MAX_FRAME_SIZE},
+};
+use ring::{
+    aead,
+    agreement,
+    hkdf,
+    rand::SystemRandom,
+};
+
+//a sealed frame on the wire is [sealed size: u32][ciphertext][tag], the size is
+//authenticated as the associated data
+pub const SEALED_HEADER_SIZE: usize = 4;
+pub const TAG_LEN: usize = 16;
+pub const MAX_SEALED_SIZE: u32 = FRAME_HEADER_SIZE as u32 + MAX_FRAME_SIZE + TAG_LEN as u32;
+pub const EPHEMERAL_KEY_SIZE: usize = 32;
+//separates the keys and signatures of this protocol from any other use of the keys
+const PROTOCOL_LABEL: &[u8] = b"manifoldchain-p2p-v2";
+
+pub fn ephemeral_key() -> Result<(agreement::EphemeralPrivateKey, [u8; EPHEMERAL_KEY_SIZE]), String> {
+    let rng = SystemRandom::new();
+    let private_key = agreement::EphemeralPrivateKey::generate(&agreement::X25519, &rng)
+        .map_err(|_| String::from("fail to generate the ephemeral key"))?;
+    let public_key = private_key
+        .compute_public_key()
+        .map_err(|_| String::from("fail to compute the ephemeral public key"))?;
+    let mut bytes = [0u8; EPHEMERAL_KEY_SIZE];
+    bytes.copy_from_slice(public_key.as_ref());
+    Ok((private_key, bytes))
+}
+
+//the ephemeral keys of the initiator and the responder, in this order
+pub fn transcript(
+    local_public: &[u8; EPHEMERAL_KEY_SIZE],
+    remote_public: &[u8; EPHEMERAL_KEY_SIZE],
+    direction: Direction,
+) -> Vec<u8> {
+    let (initiator, responder) = match direction {
+        Direction::Outgoing => (local_public, remote_public),
+        Direction::Incoming => (remote_public, local_public),
+    };
+    [&initiator[..], &responder[..]].concat()
+}
+
+//what the side of the given direction signs with its identity key, binding the
+//identity to the ephemeral keys of this connection
+pub fn signing_payload(direction: Direction, transcript: &[u8]) -> Vec<u8> {
+    let role: u8 = match direction {
+        Direction::Outgoing => 0,
+        Direction::Incoming => 1,
+    };
+    [PROTOCOL_LABEL, &[role], transcript].concat()
+}
+
+//derive the ciphers of both directions from the x25519 agreement
+pub fn derive_ciphers(
+    private_key: agreement::EphemeralPrivateKey,
+    remote_public: &[u8; EPHEMERAL_KEY_SIZE],
+    transcript: &[u8],
+    direction: Direction,
+) -> Result<(Sealer, Opener), String> {
+    let remote_public = agreement::UnparsedPublicKey::new(&agreement::X25519, remote_public);
+    let (initiator_key, responder_key) = agreement::agree_ephemeral(
+        private_key,
+        &remote_public,
+        String::from("fail to agree on the session key"),
+        |shared_secret| {
+            let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, PROTOCOL_LABEL).extract(shared_secret);
+            let expand = |label: &[u8]| -> Result<aead::LessSafeKey, String> {
+                let info = [label, transcript];
+                let okm = prk
+                    .expand(&info, &aead::CHACHA20_POLY1305)
+                    .map_err(|_| String::from("fail to derive the session key"))?;
+                Ok(aead::LessSafeKey::new(aead::UnboundKey::from(okm)))
+            };
+            Ok((expand(b"initiator")?, expand(b"responder")?))
+        },
+    )?;
+    let (sealing_key, opening_key) = match direction {
+        Direction::Outgoing => (initiator_key, responder_key),
+        Direction::Incoming => (responder_key, initiator_key),
+    };
+    Ok((
+        Sealer { key: sealing_key, counter: 0 },
+        Opener { key: opening_key, counter: 0 },
+    ))
+}
+
+//the nonce is the number of frames sealed before, so it is never reused under a key
+fn nonce(counter: u64) -> aead::Nonce {
+    let mut bytes = [0u8; aead::NONCE_LEN];
+    bytes[4..].copy_from_slice(&counter.to_be_bytes());
+    aead::Nonce::assume_unique_for_key(bytes)
+}
+
+//encrypts the frames written to a peer
+pub struct Sealer {
+    key: aead::LessSafeKey,
+    counter: u64,
+}
+
+impl Sealer {
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let size = plaintext.len() + TAG_LEN;
+        if size > MAX_SEALED_SIZE as usize {
+            return Err(format!("sealed frame of {} bytes exceeds the limit", size));
+        }
+        let size_bytes = (size as u32).to_be_bytes();
+        let mut buffer: Vec<u8> = Vec::with_capacity(SEALED_HEADER_SIZE + size);
+        buffer.extend_from_slice(&size_bytes);
+        buffer.extend_from_slice(plaintext);
+        let tag = self.key
+            .seal_in_place_separate_tag(
+                nonce(self.counter),
+                aead::Aad::from(size_bytes),
+                &mut buffer[SEALED_HEADER_SIZE..],
+            )
+            .map_err(|_| String::from("fail to seal the frame"))?;
+        buffer.extend_from_slice(tag.as_ref());
+        self.counter += 1;
+        Ok(buffer)
+    }
+}
+
+//decrypts the frames read from a peer
+pub struct Opener {
+    key: aead::LessSafeKey,
+    counter: u64,
+}
+
+impl Opener {
+    //check the size before the sealed frame is read
+    pub fn decode_size(header: &[u8; SEALED_HEADER_SIZE], limit: u32) -> Result<usize, String> {
+        let size = u32::from_be_bytes(*header);
+        if size as usize <= TAG_LEN {
+            return Err(format!("sealed frame of {} bytes is too short", size));
+        }
+        if size > limit {
+            return Err(format!("sealed frame of {} bytes exceeds the limit", size));
+        }
+        Ok(size as usize)
+    }
+
+    //a frame which is forged, reordered or replayed fails to open
+    pub fn open(
+        &mut self,
+        header: &[u8; SEALED_HEADER_SIZE],
+        sealed: &mut [u8],
+    ) -> Result<Vec<u8>, String> {
+        let plaintext = self.key
+            .open_in_place(nonce(self.counter), aead::Aad::from(*header), sealed)
+            .map_err(|_| String::from("fail to open the sealed frame"))?;
+        self.counter += 1;
+        Ok(plaintext.to_vec())
+    }
+}
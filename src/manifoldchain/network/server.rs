@@ -1,13 +1,27 @@
 use crate::{
-    types::address::Address,
     manifoldchain::{
         network::{
             peer,
             message::Message,
+            address_book::AddressBook,
+            peer_score::{PeerScores, Misbehavior},
+            emulation::NetworkEmulator,
+            identity::{NodeIdentity, PeerId},
+            secure::{Sealer, Opener, SEALED_HEADER_SIZE, MAX_SEALED_SIZE},
+            stats::NetworkStats,
+            frame::{self, FRAME_HEADER_SIZE},
+            handshake::{
+                Handshake,
+                CAP_FULL_NODE,
+                CAP_DA_SAMPLER,
+                CAP_LIGHT_CLIENT,
+            },
         },
         transaction::{
             generator::Handle as TxGeneratorHandle,
-        }
+        },
+        multichain::Multichain,
+        configuration::Configuration,
     }
 };
 
@@ -21,20 +35,29 @@ use futures::{
     },
     channel::oneshot, stream::StreamExt
 };
-use smol::{Async, Executor};
-use log::{info, trace, debug};
+use smol::{Async, Executor, Timer, future::FutureExt};
+use serde::Serialize;
+use log::{info, trace, debug, warn};
 use std::{
     net,
     sync::Arc,
     thread,
+    time::{Duration, SystemTime},
 };
 
+//the time a peer has to connect and finish the handshake
+const HANDSHAKE_TIMEOUT: u64 = 10; //s
+
 
 pub fn new(
     addr: std::net::SocketAddr,
     msg_sink: smol::channel::Sender<(Vec<u8>, peer::Handle)>,
     tx_generator_handle: &TxGeneratorHandle,
-    shard_id: usize,
+    multichain: &Multichain,
+    config: &Configuration,
+    peer_scores: &PeerScores,
+    identity: &NodeIdentity,
+    stats: &NetworkStats,
 ) -> std::io::Result<(Context, Handle)> {
     let (control_signal_sender, control_signal_receiver) = smol::channel::bounded(10000);
     let handle = Handle {
@@ -48,7 +71,14 @@ pub fn new(
         control_sender: control_signal_sender,
         new_msg_chan: msg_sink,
         tx_generator_handle: tx_generator_handle.clone(),
-        shard_id,
+        address_book: AddressBook::new(),
+        peer_infos: std::collections::HashMap::new(),
+        multichain: multichain.clone(),
+        config: config.clone(),
+        peer_scores: peer_scores.clone(),
+        emulator: NetworkEmulator::new(config),
+        identity: identity.clone(),
+        stats: stats.clone(),
     };
     Ok((ctx, handle))
 }
@@ -61,7 +91,43 @@ pub struct Context {
     control_sender: smol::channel::Sender<ControlSignal>,
     new_msg_chan: smol::channel::Sender<(Vec<u8>, peer::Handle)>,
     tx_generator_handle: TxGeneratorHandle,
-    shard_id: usize,
+    address_book: AddressBook,
+    //connection addr -> the connection state of the connected peer
+    peer_infos: std::collections::HashMap<std::net::SocketAddr, PeerInfo>,
+    multichain: Multichain,
+    config: Configuration,
+    peer_scores: PeerScores,
+    emulator: Option<NetworkEmulator>,
+    identity: NodeIdentity,
+    stats: NetworkStats,
+}
+
+//a peer which finished the handshake, waiting to be registered by the control loop
+struct PendingPeer {
+    stream: Async<net::TcpStream>,
+    direction: peer::Direction,
+    handshake: Handshake,
+    sealer: Sealer,
+    opener: Opener,
+}
+
+struct PeerInfo {
+    handshake: Handshake,
+    direction: peer::Direction,
+    connected_at: SystemTime,
+    stream: AsyncArc<Async<net::TcpStream>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PeerStatus {
+    pub addr: std::net::SocketAddr,
+    pub listen_addr: std::net::SocketAddr,
+    pub shard_id: usize,
+    pub node_id: u64,
+    pub identity: PeerId,
+    pub version: u32,
+    pub direction: String,
+    pub uptime: u64, //in seconds
 }
 
 impl Context {
@@ -86,6 +152,20 @@ impl Context {
         return Ok(());
     }
 
+    /// Start the control loop only, nothing listens or dials, e.g. to replay a
+    /// capture log offline.
+    pub fn start_offline(self) {
+        let ex = Executor::new();
+        let ex = Arc::new(ex);
+        let ex_clone = ex.clone();
+        ex.spawn(async move {
+            self.dispatch_control(ex_clone).await.unwrap();
+        })
+            .detach();
+        thread::spawn(move || smol::block_on(ex.run(futures::future::pending::<()>())));
+        info!("P2P server started offline");
+    }
+
     /// the loop that endlessly accept incoming peers
     async fn listener_loop(
         listener: Async<net::TcpListener>,
@@ -107,8 +187,47 @@ impl Context {
             match ctrl {
                 ControlSignal::ConnectNewPeer(addr, result_chan) => {
                     trace!("Processing ConnectNewPeer command");
-                    let handle = self.connect(&addr, ex.clone()).await;
-                    result_chan.send(handle).unwrap();
+                    //do not open a second connection to a connected peer
+                    if let Some(handle) = self.get_connected(&addr) {
+                        let _ = result_chan.send(Ok(handle));
+                        continue;
+                    }
+                    if self.peer_scores.is_addr_banned(&addr) {
+                        let _ = result_chan.send(Err(Self::banned(&addr)));
+                        continue;
+                    }
+                    //the handshake runs on its own task, so a slow peer does not hold the loop
+                    let local_handshake = self.local_handshake();
+                    let identity = self.identity.clone();
+                    let control_chan = self.control_sender.clone();
+                    ex.spawn(async move {
+                        let res = Self::with_timeout(
+                            Self::connect(addr, local_handshake, identity)
+                        ).await;
+                        match res {
+                            Ok(pending) => {
+                                let _ = control_chan
+                                    .send(ControlSignal::NewPeerReady(pending, Some(result_chan)))
+                                    .await;
+                            }
+                            Err(e) => {
+                                let _ = result_chan.send(Err(e));
+                            }
+                        }
+                    })
+                        .detach();
+                }
+                ControlSignal::NewPeerReady(pending, result_chan) => {
+                    trace!("Processing NewPeerReady command");
+                    let res = self.add_peer(pending, ex.clone()).await;
+                    match result_chan {
+                        Some(result_chan) => {
+                            let _ = result_chan.send(res);
+                        }
+                        None => if let Err(e) = res {
+                            warn!("Rejecting incoming peer: {}", e);
+                        }
+                    }
                 }
                 ControlSignal::BroadcastMessage(msg) => {
                     trace!("Processing BroadcastMessage command");
@@ -120,11 +239,12 @@ impl Context {
                     trace!("Processing BroadcastMessageWithShard command");
                     let shard_id = shard_id as usize;
                     
-                    match self.peers_by_shard.get_mut(&shard_id) {
-                        Some(mut peers) => {
-                            for peer in peers.iter_mut() {
-                            let mut hd = self.peers.get_mut(peer).unwrap();
-                                hd.write(msg.clone());
+                    match self.peers_by_shard.get(&shard_id) {
+                        Some(peers) => {
+                            for peer in peers.iter() {
+                                if let Some(hd) = self.peers.get_mut(peer) {
+                                    hd.write(msg.clone());
+                                }
                             }
                         }
                         None => {}
@@ -133,151 +253,511 @@ impl Context {
                 }
                 ControlSignal::GetNewPeer(stream) => {
                     trace!("Processing GetNewPeer command");
-                    self.accept(stream, ex.clone()).await?;
+                    let local_handshake = self.local_handshake();
+                    let identity = self.identity.clone();
+                    let peer_scores = self.peer_scores.clone();
+                    let control_chan = self.control_sender.clone();
+                    ex.spawn(async move {
+                        match Self::with_timeout(Self::accept(stream, local_handshake, identity, peer_scores)).await {
+                            Ok(pending) => {
+                                let _ = control_chan
+                                    .send(ControlSignal::NewPeerReady(pending, None))
+                                    .await;
+                            }
+                            Err(e) => warn!("Rejecting incoming peer: {}", e),
+                        }
+                    })
+                        .detach();
                 }
                 ControlSignal::DroppedPeer(addr) => {
                     trace!("Processing DroppedPeer({})", addr);
-                    self.peers.remove(&addr);
-                    info!("Peer {} disconnected", addr);
+                    //both the reader and the writer report the drop
+                    if self.remove_peer(&addr) {
+                        info!("Peer {} disconnected", addr);
+                    }
                 }
-                ControlSignal::SendToPeer((_receiver, _msg)) => {
-                    unimplemented!()
+                ControlSignal::DisconnectPeer(addr, result_chan) => {
+                    trace!("Processing DisconnectPeer({})", addr);
+                    //the peer can be identified by either its connection or listening addr
+                    let conn_addrs: Vec<std::net::SocketAddr> = self.peer_infos
+                        .iter()
+                        .filter(|(conn_addr, info)| {
+                            **conn_addr == addr || info.handshake.listen_addr == addr
+                        })
+                        .map(|(conn_addr, _)| conn_addr.clone())
+                        .collect();
+                    for conn_addr in conn_addrs.iter() {
+                        self.disconnect_peer(conn_addr);
+                        info!("Peer {} disconnected on request", conn_addr);
+                    }
+                    let _ = result_chan.send(!conn_addrs.is_empty());
+                }
+                ControlSignal::ReportPeer((conn_addr, misbehavior)) => {
+                    trace!("Processing ReportPeer({}) command", conn_addr);
+                    //the scores are kept by the authenticated identity since both the addr
+                    //and the announced port of a peer can change every time it reconnects
+                    let (identity, listen_addr) = match self.peer_infos.get(&conn_addr) {
+                        Some(info) => (info.handshake.identity, info.handshake.listen_addr),
+                        None => continue,
+                    };
+                    debug!("Peer {} ({}) reported: {:?}", listen_addr, identity, misbehavior);
+                    if self.peer_scores.penalize(identity, listen_addr, misbehavior) {
+                        self.disconnect_peer(&conn_addr);
+                        info!("Peer {} disconnected since it is banned", conn_addr);
+                    }
+                }
+                ControlSignal::MovePeer((conn_addr, shard_id)) => {
+                    trace!("Processing MovePeer({}) command", conn_addr);
+                    if self.move_peer(&conn_addr, shard_id) {
+                        info!("Peer {} moved to shard {}", conn_addr, shard_id);
+                    }
+                }
+                ControlSignal::GetPeerStatus(result_chan) => {
+                    trace!("Processing GetPeerStatus command");
+                    let now = SystemTime::now();
+                    let status: Vec<PeerStatus> = self.peer_infos
+                        .iter()
+                        .map(|(conn_addr, info)| PeerStatus {
+                            addr: conn_addr.clone(),
+                            listen_addr: info.handshake.listen_addr,
+                            shard_id: info.handshake.shard_id as usize,
+                            node_id: info.handshake.node_id,
+                            identity: info.handshake.identity,
+                            version: info.handshake.version,
+                            direction: format!("{:?}", info.direction),
+                            uptime: now
+                                .duration_since(info.connected_at)
+                                .map(|d| d.as_secs())
+                                .unwrap_or(0),
+                        })
+                        .collect();
+                    let _ = result_chan.send(status);
+                }
+                ControlSignal::GetAddrs(result_chan) => {
+                    trace!("Processing GetAddrs command");
+                    let _ = result_chan.send(self.address_book.get_all());
+                }
+                ControlSignal::NewAddrs((source, addrs)) => {
+                    trace!("Processing NewAddrs command");
+                    let addrs: Vec<(std::net::SocketAddr, usize)> = addrs
+                        .into_iter()
+                        .filter(|(addr, _)| *addr != self.addr && !self.peer_scores.is_addr_banned(addr))
+                        .collect();
+                    let added = self.address_book.add_gossip(source.ip(), addrs);
+                    if added > 0 {
+                        debug!("{} new addresses from {}", added, source);
+                    }
+                }
+                ControlSignal::GetConnectedPeers(result_chan) => {
+                    trace!("Processing GetConnectedPeers command");
+                    let connected: Vec<(std::net::SocketAddr, usize)> = self.peer_infos
+                        .values()
+                        .filter(|info| info.handshake.has_capability(CAP_FULL_NODE))
+                        .map(|info| (info.handshake.listen_addr, info.handshake.shard_id as usize))
+                        .collect();
+                    let _ = result_chan.send(connected);
+                }
+                ControlSignal::SendToPeer((receiver, msg)) => {
+                    trace!("Processing SendToPeer({}) command", receiver);
+                    //the receiver can be either the connection or the listening addr
+                    let conn_addr = match self.peers.contains_key(&receiver) {
+                        true => Some(receiver),
+                        false => self.peer_infos
+                            .iter()
+                            .find(|(_, info)| info.handshake.listen_addr == receiver)
+                            .map(|(conn_addr, _)| conn_addr.clone()),
+                    };
+                    match conn_addr.and_then(|addr| self.peers.get_mut(&addr)) {
+                        Some(hd) => hd.write(msg),
+                        None => debug!("Trying to send to unknown peer {}", receiver),
+                    }
+                }
+                ControlSignal::GetPeers((shard_id, result_chan)) => {
+                    trace!("Processing GetPeers command");
+                    let peers: Vec<std::net::SocketAddr> = match shard_id {
+                        Some(shard_id) => self.peers_by_shard
+                            .get(&shard_id)
+                            .cloned()
+                            .unwrap_or_default(),
+                        None => self.peers.keys().cloned().collect(),
+                    };
+                    let _ = result_chan.send(peers);
                 }
             }
         }
         return Ok(());
     }
 
-    /// Connect to a peer, and register this peer
+    //fail the connection or the handshake once it takes longer than HANDSHAKE_TIMEOUT
+    async fn with_timeout<T>(
+        fut: impl std::future::Future<Output = std::io::Result<T>>,
+    ) -> std::io::Result<T> {
+        fut.or(async {
+            Timer::after(Duration::from_secs(HANDSHAKE_TIMEOUT)).await;
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "handshake timed out"))
+        }).await
+    }
+
+    fn banned<T: std::fmt::Display>(peer: &T) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("peer {} is banned", peer),
+        )
+    }
+
+    //the handle of the connection to the peer listening on the addr, if any
+    fn get_connected(&self, addr: &std::net::SocketAddr) -> Option<peer::Handle> {
+        self.peer_infos
+            .iter()
+            .find(|(_, info)| info.handshake.listen_addr == *addr)
+            .and_then(|(conn_addr, _)| self.peers.get(conn_addr))
+            .cloned()
+    }
+
+    //the handle of the connection to the peer with the identity, if any
+    fn get_connected_identity(&self, identity: &PeerId) -> Option<peer::Handle> {
+        self.peer_infos
+            .iter()
+            .find(|(_, info)| info.handshake.identity == *identity)
+            .and_then(|(conn_addr, _)| self.peers.get(conn_addr))
+            .cloned()
+    }
+
+    /// Connect to a peer and finish the handshake, the peer is registered by the control loop
     async fn connect(
-        &mut self,
-        addr: &std::net::SocketAddr,
-        ex: Arc<Executor<'_>>,
-    ) -> std::io::Result<peer::Handle> {
+        addr: std::net::SocketAddr,
+        local_handshake: Handshake,
+        identity: NodeIdentity,
+    ) -> std::io::Result<PendingPeer> {
         info!("Establishing connection to peer {}", addr);
         let mut stream = Async::<std::net::TcpStream>::connect(addr.clone()).await?;
         
-        //send the shard id to the outgoing peer
-        let shard_id = self.shard_id as u32;
-        let serialized_shard_id = shard_id.to_be_bytes();
-        stream.write_all(&serialized_shard_id).await?; 
-        //receive the shard id from incoming peer
-        let mut buffer = [0u8; std::mem::size_of::<u32>()];
-        let bytes_read = stream.read_exact(&mut buffer).await?;
-        let received_shard_id = u32::from_be_bytes(buffer);
-        info!("Connecting node's shard id: {}", received_shard_id);
-
-        // register the new peer
-        self.register(stream, peer::Direction::Outgoing, ex, received_shard_id as usize).await
+        //agree on the session keys, then exchange the signed handshakes
+        let (remote_handshake, sealer, opener) = local_handshake
+            .exchange(&mut stream, &identity, peer::Direction::Outgoing)
+            .await?;
+        if let Err(e) = local_handshake.check_compatible(&remote_handshake) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+        }
+        info!("Connecting node {} ({}) in shard {}, protocol version {}", 
+            remote_handshake.node_id, 
+            remote_handshake.identity,
+            remote_handshake.shard_id, 
+            remote_handshake.version
+        );
+
+        //the dialed address is its listening address
+        let mut remote_handshake = remote_handshake;
+        remote_handshake.listen_addr = addr.clone();
+        Ok(PendingPeer {
+            stream,
+            direction: peer::Direction::Outgoing,
+            handshake: remote_handshake,
+            sealer,
+            opener,
+        })
     }
 
     async fn accept(
-        &mut self,
         mut stream: Async<net::TcpStream>,
+        local_handshake: Handshake,
+        identity: NodeIdentity,
+        peer_scores: PeerScores,
+    ) -> std::io::Result<PendingPeer> {
+        //agree on the session keys, then exchange the signed handshakes
+        let (remote_handshake, sealer, opener) = local_handshake
+            .exchange(&mut stream, &identity, peer::Direction::Incoming)
+            .await?;
+        //the identity is authenticated by the handshake, unlike the addr of the peer
+        if peer_scores.is_banned(&remote_handshake.identity) {
+            return Err(Self::banned(&remote_handshake.identity));
+        }
+        if let Err(e) = local_handshake.check_compatible(&remote_handshake) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+        }
+        info!("Coming node {} ({}) in shard {}, protocol version {}", 
+            remote_handshake.node_id, 
+            remote_handshake.identity,
+            remote_handshake.shard_id, 
+            remote_handshake.version
+        );
+
+        //the peer listens on the announced port of the connecting ip
+        let mut remote_handshake = remote_handshake;
+        remote_handshake.listen_addr = net::SocketAddr::new(
+            stream.get_ref().peer_addr()?.ip(), 
+            remote_handshake.listen_addr.port()
+        );
+        Ok(PendingPeer {
+            stream,
+            direction: peer::Direction::Incoming,
+            handshake: remote_handshake,
+            sealer,
+            opener,
+        })
+    }
+
+    //register a peer which finished the handshake, unless it got banned or connected
+    //over another connection in the meantime
+    async fn add_peer(
+        &mut self,
+        pending: PendingPeer,
         ex: Arc<Executor<'_>>,
-    ) -> std::io::Result<()> {
-        //receive the shard id from incoming peer
-        let mut buffer = [0u8; std::mem::size_of::<u32>()];
-        let bytes_read = stream.read_exact(&mut buffer).await?;
-        let received_shard_id = u32::from_be_bytes(buffer);
-        //send the shard id to the outgoing peer
-        let shard_id = self.shard_id as u32;
-        let serialized_shard_id = shard_id.to_be_bytes();
-        stream.write_all(&serialized_shard_id).await?; 
-        info!("Coming node's shard id: {}", received_shard_id);
-
-        self.register(stream, peer::Direction::Incoming, ex, received_shard_id as usize).await?;
-        Ok(())
+    ) -> std::io::Result<peer::Handle> {
+        let listen_addr = pending.handshake.listen_addr;
+        let remote_identity = pending.handshake.identity;
+        if self.peer_scores.is_banned(&remote_identity) {
+            return Err(Self::banned(&remote_identity));
+        }
+        if remote_identity == self.identity.peer_id() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "connected to self",
+            ));
+        }
+        if pending.direction == peer::Direction::Outgoing {
+            if let Some(handle) = self.get_connected(&listen_addr) {
+                return Ok(handle);
+            }
+        }
+        //one connection per identity, so the peer maps cannot hold the same node twice
+        if let Some(handle) = self.get_connected_identity(&remote_identity) {
+            return match pending.direction {
+                peer::Direction::Outgoing => Ok(handle),
+                peer::Direction::Incoming => Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("peer {} is already connected", remote_identity),
+                )),
+            };
+        }
+        self.register(
+            pending.stream,
+            pending.direction,
+            ex,
+            pending.handshake,
+            pending.sealer,
+            pending.opener,
+        ).await
+    }
+
+    //remove the peer from all maps, return false if it is already removed
+    fn remove_peer(&mut self, addr: &std::net::SocketAddr) -> bool {
+        let is_removed = self.peers.remove(addr).is_some();
+        if let Some(info) = self.peer_infos.remove(addr) {
+            let shard_id = info.handshake.shard_id as usize;
+            if let Some(addrs) = self.peers_by_shard.get_mut(&shard_id) {
+                addrs.retain(|x| x != addr);
+                if addrs.is_empty() {
+                    self.peers_by_shard.remove(&shard_id);
+                }
+            }
+        }
+        is_removed
+    }
+
+    //file the peer under the shard it rotated to, return false if it is unknown or
+    //already there
+    fn move_peer(&mut self, addr: &std::net::SocketAddr, shard_id: usize) -> bool {
+        let info = match self.peer_infos.get_mut(addr) {
+            Some(info) => info,
+            None => return false,
+        };
+        let old_shard = info.handshake.shard_id as usize;
+        if old_shard == shard_id {
+            return false;
+        }
+        info.handshake.shard_id = shard_id as u32;
+        let listen_addr = info.handshake.listen_addr;
+        if !info.handshake.has_capability(CAP_FULL_NODE) {
+            return true;
+        }
+        if let Some(addrs) = self.peers_by_shard.get_mut(&old_shard) {
+            addrs.retain(|x| x != addr);
+            if addrs.is_empty() {
+                self.peers_by_shard.remove(&old_shard);
+            }
+        }
+        self.peers_by_shard.entry(shard_id).or_insert_with(Vec::new).push(addr.clone());
+        self.address_book.add(listen_addr, shard_id);
+        true
+    }
+
+    //close the connection, the reader and writer tasks exit on their own
+    fn disconnect_peer(&mut self, addr: &std::net::SocketAddr) {
+        if let Some(info) = self.peer_infos.get(addr) {
+            let _ = info.stream.get_ref().shutdown(net::Shutdown::Both);
+        }
+        self.remove_peer(addr);
+    }
+
+    fn local_handshake(&self) -> Handshake {
+        let capabilities = match self.config.light_client {
+            true => CAP_LIGHT_CLIENT,
+            false => CAP_FULL_NODE | CAP_DA_SAMPLER,
+        };
+        Handshake::new(
+            &self.multichain,
+            &self.config,
+            &self.identity,
+            self.addr,
+            capabilities,
+        )
     }
 
     async fn register(
         &mut self,
         stream: Async<net::TcpStream>,
-        _direction: peer::Direction,
+        direction: peer::Direction,
         ex: Arc<Executor<'_>>,
-        shard_id: usize,
+        handshake: Handshake,
+        mut sealer: Sealer,
+        mut opener: Opener,
     ) -> std::io::Result<peer::Handle> {
-        let (mut write_queue, handle) = peer::new(&stream)?;
+        let shard_id = handshake.shard_id as usize;
+        let listen_addr = handshake.listen_addr;
+        let (write_queue, mut handle) = peer::new(&stream, handshake.identity)?;
 
         let stream = AsyncArc::new(stream);
         let new_msg_chan = self.new_msg_chan.clone();
         let handle_copy = handle.clone();
         let control_chan = self.control_sender.clone();
+        let reader_control_chan = self.control_sender.clone();
         let addr = stream.get_ref().peer_addr()?;
 
+        // a light client serves nothing, so it is neither relayed to nor gossiped
+        let is_full_node = handshake.has_capability(CAP_FULL_NODE);
+
         //send the new node to tx_generator
-        self.tx_generator_handle.new_node(addr.ip().to_string());
+        if is_full_node {
+            self.tx_generator_handle.new_node(addr.ip().to_string());
+        }
 
         // insert the peer handle so that we can broadcast to this guy later
         self.peers.insert(addr.clone(), handle.clone());
         // update the peers by shard map
-        match self.peers_by_shard.get(&shard_id) {
-            Some(old_addrs) => {
-                let mut new_addrs = old_addrs.clone();
-                new_addrs.push(addr);
-                self.peers_by_shard.insert(shard_id, new_addrs);
-            }
-            None => {
-                self.peers_by_shard.insert(shard_id, vec![addr.clone()]);
+        if is_full_node {
+            match self.peers_by_shard.get(&shard_id) {
+                Some(old_addrs) => {
+                    let mut new_addrs = old_addrs.clone();
+                    new_addrs.push(addr);
+                    self.peers_by_shard.insert(shard_id, new_addrs);
+                }
+                None => {
+                    self.peers_by_shard.insert(shard_id, vec![addr.clone()]);
+                }
             }
         }
+        // remember the listening address of the peer so that it can be gossiped
+        self.peer_infos.insert(addr.clone(), PeerInfo {
+            handshake,
+            direction,
+            connected_at: SystemTime::now(),
+            stream: stream.clone(),
+        });
+        if is_full_node {
+            self.address_book.add(listen_addr, shard_id);
+        }
+        self.stats.register_peer(addr, listen_addr, shard_id);
+        let reader_stats = self.stats.clone();
+        let writer_stats = self.stats.clone();
         // start the reactor for this peer
         // first, start a task that keeps reading from this guy
         let mut reader = BufReader::new(stream.clone());
         ex.spawn(async move {
-            // the buffer to store the frame header, which contains the length of the frame
-            let mut size_buffer: [u8; 4] = [0; 4];
-            // the buffer to store the message content
-            let mut msg_buffer: Vec<u8> = vec![];
+            // the buffer to store the size of the sealed frame
+            let mut size_buffer: [u8; SEALED_HEADER_SIZE] = [0; SEALED_HEADER_SIZE];
+            // the buffer to store the sealed frame
+            let mut sealed_buffer: Vec<u8> = vec![];
             loop {
-                // first, read exactly 4 bytes to get the frame header
-                let msg_size = match reader.read_exact(&mut size_buffer).await {
-                    Ok(_) => u32::from_be_bytes(size_buffer),
+                // first, read exactly the size, and reject oversized frames before
+                // allocating the buffer
+                let size = match reader.read_exact(&mut size_buffer).await {
+                    Ok(_) => Opener::decode_size(&size_buffer, MAX_SEALED_SIZE),
                     Err(_) => {
                         break;
                     }
                 };
-                // then, read exactly msg_size bytes to get the whole message
-                if msg_buffer.len() < msg_size as usize {
-                    msg_buffer.resize(msg_size as usize, 0);
+                let size = match size {
+                    Ok(size) => size,
+                    Err(e) => {
+                        warn!("Malformed frame from {}: {}", addr, e);
+                        let _ = reader_control_chan
+                            .send(ControlSignal::ReportPeer((addr, Misbehavior::MalformedMessage)))
+                            .await;
+                        break;
+                    }
+                };
+                // then, read exactly size bytes to get the whole sealed frame
+                if sealed_buffer.len() < size {
+                    sealed_buffer.resize(size, 0);
+                }
+                if reader.read_exact(&mut sealed_buffer[0..size]).await.is_err() {
+                    break;
                 }
-                match reader
-                    .read_exact(&mut msg_buffer[0..msg_size as usize])
-                    .await
-                {
-                    Ok(_) => {
-                        let new_payload: Vec<u8> = msg_buffer[0..msg_size as usize].to_vec();
+                // the frame inside must be authentic and well-formed
+                let new_payload = opener
+                    .open(&size_buffer, &mut sealed_buffer[0..size])
+                    .and_then(|frame| {
+                        if frame.len() < FRAME_HEADER_SIZE {
+                            return Err(String::from("truncated frame"));
+                        }
+                        let header = frame::decode_header(
+                            frame[0..FRAME_HEADER_SIZE].try_into().unwrap()
+                        )?;
+                        let body = frame[FRAME_HEADER_SIZE..].to_vec();
+                        frame::verify_checksum(&header, &body)?;
+                        Ok(body)
+                    });
+                match new_payload {
+                    Ok(new_payload) => {
+                        reader_stats.record_in(&addr, &new_payload);
                         new_msg_chan
                             .send((new_payload, handle_copy.clone()))
                             .await
                             .unwrap();
                     }
-                    Err(_) => {
+                    Err(e) => {
+                        warn!("Malformed frame from {}: {}", addr, e);
+                        let _ = reader_control_chan
+                            .send(ControlSignal::ReportPeer((addr, Misbehavior::MalformedMessage)))
+                            .await;
                         break;
                     }
                 }
             }
             // the peer is disconnected
+            let _ = reader_control_chan
+                .send(ControlSignal::DroppedPeer(addr))
+                .await;
         })
             .detach();
 
+        // with the network emulation, the frames go through the emulated link first
+        let mut write_queue = match &self.emulator {
+            Some(emulator) => emulator.shape(write_queue, shard_id, &ex),
+            None => write_queue,
+        };
+
         // second, start a task that keeps writing to this guy
         let mut writer = BufWriter::new(stream.clone());
         ex.spawn(async move {
             loop {
-                // first, get a message to write from the queue
-                let new_msg = write_queue.next().await.unwrap();
-
-                // second, encode the length of the message
-                let size_buffer = (new_msg.len() as u32).to_be_bytes();
+                // first, get a frame to write from the queue, the frame is encoded
+                // with its header by the peer handle
+                let new_msg = match write_queue.next().await {
+                    Some(msg) => msg,
+                    None => break,
+                };
 
-                // third, write the frame header and the payload
-                match writer.write_all(&size_buffer).await {
-                    Ok(_) => {}
-                    Err(_) => {
-                        break;
+                // second, seal the frame and write it
+                writer_stats.record_out(&addr, &new_msg[FRAME_HEADER_SIZE..]);
+                let new_msg = match sealer.seal(&new_msg) {
+                    Ok(sealed) => sealed,
+                    Err(e) => {
+                        warn!("Fail to seal the frame to {}: {}", addr, e);
+                        continue;
                     }
-                }
+                };
                 match writer.write_all(&new_msg).await {
                     Ok(_) => {}
                     Err(_) => {
@@ -292,13 +772,15 @@ impl Context {
                 }
             }
             // the peer is disconnected
-            control_chan
+            let _ = control_chan
                 .send(ControlSignal::DroppedPeer(addr))
-                .await
-                .unwrap();
+                .await;
         })
             .detach();
 
+        // ask the new peer for the addresses it knows
+        handle.write(Message::GetAddr);
+
         Ok(handle)
     }
 }
@@ -325,13 +807,21 @@ pub struct Handle {
 
 impl Handle {
     pub fn connect(&self, addr: std::net::SocketAddr) -> std::io::Result<peer::Handle> {
+        smol::block_on(self.start_connect(addr)).unwrap()
+    }
+
+    //start dialing the peer, the receiver gets the peer once the handshake finishes
+    pub fn start_connect(
+        &self,
+        addr: std::net::SocketAddr,
+    ) -> oneshot::Receiver<std::io::Result<peer::Handle>> {
         let (sender, receiver) = oneshot::channel();
         smol::block_on(
             self.control_chan
                 .send(ControlSignal::ConnectNewPeer(addr, sender)),
         )
             .unwrap();
-        smol::block_on(receiver).unwrap()
+        receiver
     }
 
     pub fn broadcast(&self, msg: Message) {
@@ -342,10 +832,65 @@ impl Handle {
         smol::block_on(self.control_chan.send(ControlSignal::BroadcastMessageWithShard((msg, shard_id)))).unwrap();
     }
 
-    pub fn send(&self, receiver: Address, msg: Message) {
+    pub fn get_addrs(&self) -> Vec<(std::net::SocketAddr, usize)> {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::GetAddrs(sender))).unwrap();
+        smol::block_on(receiver).unwrap_or_default()
+    }
+
+    //the addresses gossiped by the peer behind the connection
+    pub fn add_addrs(&self, source: std::net::SocketAddr, addrs: Vec<(std::net::SocketAddr, usize)>) {
+        smol::block_on(self.control_chan.send(ControlSignal::NewAddrs((source, addrs)))).unwrap();
+    }
+
+    //return the listening addresses and shard ids of all connected peers
+    pub fn get_connected_peers(&self) -> Vec<(std::net::SocketAddr, usize)> {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::GetConnectedPeers(sender))).unwrap();
+        smol::block_on(receiver).unwrap_or_default()
+    }
+
+    //close the connection to the peer, return false if it is not connected
+    pub fn disconnect(&self, addr: std::net::SocketAddr) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::DisconnectPeer(addr, sender))).unwrap();
+        smol::block_on(receiver).unwrap_or(false)
+    }
+
+    pub fn get_peer_status(&self) -> Vec<PeerStatus> {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::GetPeerStatus(sender))).unwrap();
+        smol::block_on(receiver).unwrap_or_default()
+    }
+
+    //the connection addrs of the peers in the shard
+    pub fn get_peers_by_shard(&self, shard_id: usize) -> Vec<std::net::SocketAddr> {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::GetPeers((Some(shard_id), sender)))).unwrap();
+        smol::block_on(receiver).unwrap_or_default()
+    }
+
+    //the connection addrs of all peers
+    pub fn get_peers(&self) -> Vec<std::net::SocketAddr> {
+        let (sender, receiver) = oneshot::channel();
+        smol::block_on(self.control_chan.send(ControlSignal::GetPeers((None, sender)))).unwrap();
+        smol::block_on(receiver).unwrap_or_default()
+    }
+
+    pub fn send(&self, receiver: std::net::SocketAddr, msg: Message) {
         smol::block_on(self.control_chan.send(ControlSignal::SendToPeer((receiver, msg)))).unwrap();
     }
 
+    //penalize the peer behind the connection, it is disconnected once banned
+    pub fn report(&self, addr: std::net::SocketAddr, misbehavior: Misbehavior) {
+        smol::block_on(self.control_chan.send(ControlSignal::ReportPeer((addr, misbehavior)))).unwrap();
+    }
+
+    //the peer behind the connection announced that it rotated to the shard
+    pub fn move_peer(&self, addr: std::net::SocketAddr, shard_id: usize) {
+        smol::block_on(self.control_chan.send(ControlSignal::MovePeer((addr, shard_id)))).unwrap();
+    }
+
    // #[cfg(any(test,test_utilities))]
    // pub fn new_for_test() -> (Handle, TestReceiver) {
    //     let (s,r) = smol::channel::unbounded();
@@ -363,6 +908,15 @@ enum ControlSignal {
     BroadcastMessage(Message),
     BroadcastMessageWithShard((Message, usize)),
     GetNewPeer(Async<net::TcpStream>),
+    NewPeerReady(PendingPeer, Option<oneshot::Sender<std::io::Result<peer::Handle>>>),
     DroppedPeer(std::net::SocketAddr),
-    SendToPeer((Address,Message)),
+    SendToPeer((std::net::SocketAddr, Message)),
+    GetPeers((Option<usize>, oneshot::Sender<Vec<std::net::SocketAddr>>)),
+    GetAddrs(oneshot::Sender<Vec<(std::net::SocketAddr, usize)>>),
+    NewAddrs((std::net::SocketAddr, Vec<(std::net::SocketAddr, usize)>)),
+    GetConnectedPeers(oneshot::Sender<Vec<(std::net::SocketAddr, usize)>>),
+    DisconnectPeer(std::net::SocketAddr, oneshot::Sender<bool>),
+    GetPeerStatus(oneshot::Sender<Vec<PeerStatus>>),
+    ReportPeer((std::net::SocketAddr, Misbehavior)),
+    MovePeer((std::net::SocketAddr, usize)),
 }
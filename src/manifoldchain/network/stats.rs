@@ -0,0 +1,200 @@
+use crate::manifoldchain::network::{
+    message,
+    frame::FRAME_HEADER_SIZE,
+    capture::{CaptureWriter, CaptureRecord, CaptureDirection},
+};
+use log::warn;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MessageStats {
+    pub count_in: u64,
+    pub bytes_in: u64, //the size of the frames, before the encryption
+    pub count_out: u64,
+    pub bytes_out: u64,
+    pub processed: u64,
+    pub avg_latency: u64, //us, the time the network worker takes to handle the message
+    pub max_latency: u64, //us
+    #[serde(skip)]
+    total_latency: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TypeStats {
+    pub message: &'static str,
+    #[serde(flatten)]
+    pub stats: MessageStats,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ShardStats {
+    pub shard_id: usize,
+    pub messages: Vec<TypeStats>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PeerStats {
+    pub peer: SocketAddr,
+    pub shard_id: Option<usize>,
+    pub messages: Vec<TypeStats>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NetworkStatsReport {
+    pub total: Vec<TypeStats>,
+    pub by_shard: Vec<ShardStats>,
+    pub by_peer: Vec<PeerStats>,
+}
+
+#[derive(Default)]
+struct PeerEntry {
+    shard_id: Option<usize>,
+    messages: HashMap<u8, MessageStats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    //connection addr -> (listening addr, shard id), the stats of a peer are kept by its
+    //listening addr so that they survive reconnections
+    peer_infos: HashMap<SocketAddr, (SocketAddr, usize)>,
+    total: HashMap<u8, MessageStats>,
+    by_shard: HashMap<usize, HashMap<u8, MessageStats>>,
+    by_peer: HashMap<SocketAddr, PeerEntry>,
+}
+
+impl Stats {
+    fn update<F: Fn(&mut MessageStats)>(&mut self, conn_addr: &SocketAddr, type_id: u8, f: F) {
+        f(self.total.entry(type_id).or_default());
+        let (addr, shard_id) = match self.peer_infos.get(conn_addr) {
+            Some((listen_addr, shard_id)) => (*listen_addr, Some(*shard_id)),
+            None => (*conn_addr, None),
+        };
+        let peer = self.by_peer.entry(addr).or_default();
+        peer.shard_id = shard_id;
+        f(peer.messages.entry(type_id).or_default());
+        if let Some(shard_id) = shard_id {
+            f(self.by_shard.entry(shard_id).or_default().entry(type_id).or_default());
+        }
+    }
+}
+
+fn to_type_stats(messages: &HashMap<u8, MessageStats>) -> Vec<TypeStats> {
+    let mut type_stats: Vec<TypeStats> = messages
+        .iter()
+        .map(|(type_id, stats)| {
+            let mut stats = stats.clone();
+            if stats.processed > 0 {
+                stats.avg_latency = stats.total_latency / stats.processed;
+            }
+            TypeStats {
+                message: message::type_name(*type_id),
+                stats,
+            }
+        })
+        .collect();
+    type_stats.sort_by_key(|type_stats| type_stats.message);
+    type_stats
+}
+
+//the traffic and the processing time of every message type, by peer and by shard.
+//With a capture log, every frame is also written to it.
+#[derive(Clone)]
+pub struct NetworkStats {
+    inner: Arc<Mutex<Stats>>,
+    capture: Option<Arc<Mutex<CaptureWriter>>>,
+}
+
+impl NetworkStats {
+    pub fn new(capture: Option<CaptureWriter>) -> Self {
+        NetworkStats {
+            inner: Arc::new(Mutex::new(Stats::default())),
+            capture: capture.map(|writer| Arc::new(Mutex::new(writer))),
+        }
+    }
+
+    pub fn register_peer(&self, conn_addr: SocketAddr, listen_addr: SocketAddr, shard_id: usize) {
+        self.inner
+            .lock()
+            .unwrap()
+            .peer_infos
+            .insert(conn_addr, (listen_addr, shard_id));
+    }
+
+    fn capture(&self, direction: CaptureDirection, peer: &SocketAddr, body: &[u8]) {
+        if let Some(capture) = &self.capture {
+            let record = CaptureRecord::new(direction, peer.clone(), body.to_vec());
+            if let Err(e) = capture.lock().unwrap().write(&record) {
+                warn!("Fail to capture the frame: {}", e);
+            }
+        }
+    }
+
+    //the body of a frame read from the peer, prefixed by the type id
+    pub fn record_in(&self, conn_addr: &SocketAddr, body: &[u8]) {
+        let type_id = match body.first() {
+            Some(type_id) => *type_id,
+            None => return,
+        };
+        let bytes = (FRAME_HEADER_SIZE + body.len()) as u64;
+        self.inner.lock().unwrap().update(conn_addr, type_id, |stats| {
+            stats.count_in += 1;
+            stats.bytes_in += bytes;
+        });
+        self.capture(CaptureDirection::Inbound, conn_addr, body);
+    }
+
+    //the body of a frame written to the peer, prefixed by the type id
+    pub fn record_out(&self, conn_addr: &SocketAddr, body: &[u8]) {
+        let type_id = match body.first() {
+            Some(type_id) => *type_id,
+            None => return,
+        };
+        let bytes = (FRAME_HEADER_SIZE + body.len()) as u64;
+        self.inner.lock().unwrap().update(conn_addr, type_id, |stats| {
+            stats.count_out += 1;
+            stats.bytes_out += bytes;
+        });
+        self.capture(CaptureDirection::Outbound, conn_addr, body);
+    }
+
+    pub fn record_processed(&self, conn_addr: &SocketAddr, type_id: u8, latency: Duration) {
+        let latency = latency.as_micros() as u64;
+        self.inner.lock().unwrap().update(conn_addr, type_id, |stats| {
+            stats.processed += 1;
+            stats.total_latency += latency;
+            stats.max_latency = stats.max_latency.max(latency);
+        });
+    }
+
+    pub fn get_report(&self) -> NetworkStatsReport {
+        let inner = self.inner.lock().unwrap();
+        let mut by_shard: Vec<ShardStats> = inner.by_shard
+            .iter()
+            .map(|(shard_id, messages)| ShardStats {
+                shard_id: *shard_id,
+                messages: to_type_stats(messages),
+            })
+            .collect();
+        by_shard.sort_by_key(|shard| shard.shard_id);
+        let mut by_peer: Vec<PeerStats> = inner.by_peer
+            .iter()
+            .map(|(peer, entry)| PeerStats {
+                peer: peer.clone(),
+                shard_id: entry.shard_id,
+                messages: to_type_stats(&entry.messages),
+            })
+            .collect();
+        by_peer.sort_by_key(|peer| peer.peer);
+        NetworkStatsReport {
+            total: to_type_stats(&inner.total),
+            by_shard,
+            by_peer,
+        }
+    }
+}
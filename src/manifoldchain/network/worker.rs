@@ -7,6 +7,13 @@ use crate::{
             message::Message,
             peer,
             server::Handle as ServerHandle,
+            address_book::MAX_ADDR_PER_MSG,
+            request::RequestTracker,
+            peer_score::Misbehavior,
+            frame,
+            cache::{SharedCache, CacheStats},
+            orphan_pool::OrphanPool,
+            stats::NetworkStats,
         },
         transaction::{Transaction},
         block::{
@@ -18,23 +25,41 @@ use crate::{
                 VersaHash,
                 ExclusiveFullBlock,
                 InclusiveFullBlock,
-            }
+            },
+            compact_block::{
+                CompactBlock,
+                BlockTxnsRequest,
+                BlockTxns,
+                PartialBlock,
+            },
         },
         configuration::Configuration,
-        validator::{Validator, ValidationSource},
+        validator::{
+            Validator,
+            ValidationSource,
+            ProofCheck,
+            stats::{ValidationStats, FROM_BLOCK, FROM_TRANSACTION},
+        },
         mempool::Mempool,
         multichain::Multichain,
         testimony::Testimony,
-        fraudproof::FraudProof,
+        fraudproof::{
+            FraudProof,
+            InvalidParentProof,
+            bundle::FraudProofBundle,
+        },
         confirmation::Confirmation,
+        light_client::{LightClient, TxProof, MAX_HEADERS},
+        sharding::rotation::{Rotation, StateSnapshot},
     }
 };
 use log::{debug, warn, error, info};
 use std::{
-    time::{self, SystemTime},
+    time::{self, SystemTime, Instant},
     thread,
-    sync::{Arc,Mutex},
+    sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
     collections::HashMap,
+    net::SocketAddr,
 };
 use rand::Rng;
 
@@ -48,22 +73,130 @@ pub struct Worker {
     num_worker: usize,
     server: ServerHandle,
     multichain: Multichain,
-    blk_buff: HashMap<H256, VersaBlock>,
-    fp_map: HashMap<H256, FraudProof>,
+    orphans: OrphanPool,
+    fp_map: SharedCache<H256, FraudProof>,
     //block_hash -> fp, upon receiving a new block, after inserting it, check wherther
     //there is an associated fp, it there is, prune it immediately
-    blk2fp: HashMap<H256, FraudProof>, 
-    sample_map: HashMap<SampleIndex, Vec<Sample>>,
-    blk2sample: HashMap<H256, Vec<SampleIndex>>,
+    blk2fp: SharedCache<H256, FraudProof>, 
+    //the proofs which cannot be checked before the blocks they refer to arrive
+    pending_fps: SharedCache<H256, FraudProof>,
+    sample_map: SharedCache<SampleIndex, Vec<Sample>>,
+    blk2sample: SharedCache<H256, Vec<SampleIndex>>,
+    //the proofs in the export directory, counted once at startup
+    exported_fps: Arc<AtomicUsize>,
     mempool: Arc<Mutex<Mempool>>,
     config: Configuration,
     validator: Validator,
     confirmation: Arc<Mutex<Confirmation>>,
+    requests: RequestTracker,
+    stats: NetworkStats,
+    validation_stats: ValidationStats,
+    //compact block hash -> the block waiting for its missing content, shared by the
+    //workers since the content may be handled by another worker
+    partial_blocks: Arc<Mutex<HashMap<H256, PartialBlock>>>,
+    //in the light-client mode, only the headers and the transaction proofs are handled
+    light_client: Option<LightClient>,
+    //with the committee rotation, the snapshots of the shard the node moves to
+    rotation: Option<Rotation>,
 }
 
+//a partial block is dropped if its content does not come within the time
+const PARTIAL_BLOCK_TIMEOUT: u64 = 60; //s
+
+//the interval between two rounds of orphan retries and cache purges
+const CACHE_MONITOR_INTERVAL: u64 = 1000; //ms
+
 pub type SampleIndex = (H256, u32, u32); //block_hash, tx_index, shard_id
 pub type Sample = (u32, H256);
 
+//the bounded caches of the workers, shared by the worker threads
+#[derive(Clone)]
+pub struct WorkerCaches {
+    orphans: OrphanPool,
+    fp_map: SharedCache<H256, FraudProof>,
+    blk2fp: SharedCache<H256, FraudProof>,
+    pending_fps: SharedCache<H256, FraudProof>,
+    sample_map: SharedCache<SampleIndex, Vec<Sample>>,
+    blk2sample: SharedCache<H256, Vec<SampleIndex>>,
+    exported_fps: Arc<AtomicUsize>,
+}
+
+impl WorkerCaches {
+    pub fn new(config: &Configuration, requests: &RequestTracker) -> Self {
+        let ttl = Some(time::Duration::from_secs(config.cache_ttl));
+        WorkerCaches {
+            orphans: OrphanPool::new(config, requests),
+            fp_map: SharedCache::new("fraud_proofs", config.max_fraud_proofs, ttl),
+            blk2fp: SharedCache::new("block_fraud_proofs", config.max_fraud_proofs, ttl),
+            pending_fps: SharedCache::new("pending_fraud_proofs", config.max_fraud_proofs, ttl),
+            sample_map: SharedCache::new("samples", config.max_samples, ttl),
+            blk2sample: SharedCache::new("block_samples", config.max_samples, ttl),
+            exported_fps: Arc::new(AtomicUsize::new(Self::count_exported_fps(config))),
+        }
+    }
+
+    //the proofs exported by the previous runs count towards the bound
+    fn count_exported_fps(config: &Configuration) -> usize {
+        let dir = match &config.fraud_proof_dir {
+            Some(dir) => dir,
+            None => return 0,
+        };
+        match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "json"))
+                .count(),
+            Err(e) => {
+                warn!("Fail to read the fraud proof directory {}: {}", dir, e);
+                0
+            }
+        }
+    }
+
+    //retry the orphans and drop the expired entries periodically
+    pub fn start(&self) {
+        let caches = self.clone();
+        thread::Builder::new()
+            .name("Cache-Monitor".to_string())
+            .spawn(move || loop {
+                thread::sleep(time::Duration::from_millis(CACHE_MONITOR_INTERVAL));
+                caches.orphans.retry_orphans();
+                caches.fp_map.purge_expired();
+                caches.blk2fp.purge_expired();
+                caches.pending_fps.purge_expired();
+                caches.sample_map.purge_expired();
+                caches.blk2sample.purge_expired();
+            })
+            .unwrap();
+        info!("Cache monitor started");
+    }
+
+    //the fraud proofs generated or received by the workers
+    pub fn get_fraud_proof_hashes(&self) -> Vec<H256> {
+        self.fp_map.keys()
+    }
+
+    pub fn get_fraud_proof(&self, hash: &H256) -> Option<FraudProof> {
+        self.fp_map.get(hash)
+    }
+
+    //the received proofs waiting for the blocks they refer to
+    pub fn get_pending_fraud_proof_hashes(&self) -> Vec<H256> {
+        self.pending_fps.keys()
+    }
+
+    pub fn get_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.orphans.stats(),
+            self.fp_map.stats(),
+            self.blk2fp.stats(),
+            self.pending_fps.stats(),
+            self.sample_map.stats(),
+            self.blk2sample.stats(),
+        ]
+    }
+}
+
 impl Worker {
     pub fn new(
         num_worker: usize,
@@ -73,6 +206,12 @@ impl Worker {
         mempool: &Arc<Mutex<Mempool>>,
         config: &Configuration,
         confirmation: &Arc<Mutex<Confirmation>>,
+        requests: &RequestTracker,
+        caches: &WorkerCaches,
+        stats: &NetworkStats,
+        validation_stats: &ValidationStats,
+        light_client: Option<&LightClient>,
+        rotation: Option<&Rotation>,
     ) -> Self {
         let validator = Validator::new(multichain, mempool, config);
         Self {
@@ -80,15 +219,23 @@ impl Worker {
             num_worker,
             server: server.clone(),
             multichain: multichain.clone(),
-            blk_buff: HashMap::new(),
+            orphans: caches.orphans.clone(),
             mempool: Arc::clone(mempool),
             config: config.clone(),
             validator,
-            fp_map: HashMap::new(),
-            sample_map: HashMap::new(),
-            blk2sample: HashMap::new(),
+            fp_map: caches.fp_map.clone(),
+            sample_map: caches.sample_map.clone(),
+            blk2sample: caches.blk2sample.clone(),
             confirmation: Arc::clone(confirmation),
-            blk2fp: HashMap::new(),
+            blk2fp: caches.blk2fp.clone(),
+            pending_fps: caches.pending_fps.clone(),
+            exported_fps: Arc::clone(&caches.exported_fps),
+            requests: requests.clone(),
+            stats: stats.clone(),
+            validation_stats: validation_stats.clone(),
+            partial_blocks: Arc::new(Mutex::new(HashMap::new())),
+            light_client: light_client.cloned(),
+            rotation: rotation.cloned(),
         }
     }
 
@@ -115,7 +262,23 @@ impl Worker {
             }
             let msg = result.unwrap();
             let (msg, mut peer) = msg;
-            let msg: Message = bincode::deserialize(&msg).unwrap();
+            let msg: Message = match frame::decode(&msg) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("Malformed message from {}: {}", peer.addr(), e);
+                    self.server.report(peer.addr().clone(), Misbehavior::MalformedMessage);
+                    self.server.disconnect(peer.addr().clone());
+                    continue;
+                }
+            };
+            let type_id = msg.type_id();
+            let started_at = Instant::now();
+            //the message may answer some outstanding requests
+            let answered = self.requests.complete(&msg);
+            //a light client has no blocks to validate or serve
+            if self.light_client.is_some() && !Self::is_light_message(&msg) {
+                continue;
+            }
             match msg {
                 Message::Ping(nonce) => {
                     debug!("Ping: {}", nonce);
@@ -128,7 +291,7 @@ impl Worker {
                     //info!("New transaction hashs");
                     if let Some(response) = self
                         .handle_new_transaction_hash(tx_hashs, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     } 
                 }
                 Message::GetTransactions((tx_hashs, shard_id)) => {
@@ -140,7 +303,19 @@ impl Worker {
                 }
                 Message::Transactions((txs, shard_id)) => {
                     //info!("Comming transactions");
-                    if let Some(response) = self.handle_transactions(txs, shard_id as usize) {
+                    //transactions are gossiped without being requested, only the unsolicited
+                    //ones which are known or rejected are wasted work
+                    let unsolicited = txs.len().saturating_sub(answered);
+                    let (response, wasted) = self
+                        .handle_transactions(txs, shard_id as usize, peer.addr());
+                    let wasted = wasted.min(unsolicited);
+                    if wasted > 0 {
+                        self.server.report(
+                            peer.addr().clone(),
+                            Misbehavior::UnsolicitedTransactions(wasted),
+                        );
+                    }
+                    if let Some(response) = response {
                         if let Message::NewTransactionHash((tx_hashs, shard_id)) = response {
                             self.server.broadcast_with_shard(
                                 Message::NewTransactionHash((tx_hashs, shard_id)),
@@ -179,7 +354,7 @@ impl Worker {
                         .collect();
                     if let Some(response) = self
                         .handle_new_block_hash(versa_hash_vec, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     }
                 }
                 Message::GetExBlocks((ex_hash_vec, shard_id)) => {
@@ -194,13 +369,13 @@ impl Worker {
                     }
                 }
                 Message::ExBlocks((ex_blocks, shard_id)) => {
-                    info!("Coming exclusive blocks");
+                    info!("Coming exclusive blocks from {}", peer.identity());
                     let versa_blocks = ex_blocks
                         .into_iter()
                         .map(|x| VersaBlock::ExBlock(x))
                         .collect();
                     let (response_1, response_2, response_3, response_4, response_5) = self
-                        .handle_blocks(versa_blocks, shard_id as usize); 
+                        .handle_blocks(versa_blocks, shard_id as usize, peer.addr()); 
                     if let Some(res_1) = response_1 {
                         self.server.broadcast(res_1.clone());
                         //Request the samples from peer 
@@ -210,10 +385,11 @@ impl Worker {
                                 match self.blk2sample.get(&block_hash) {
                                     Some(sample_index_vec) => {
                                         for sample_index in sample_index_vec.iter() {
-                                            let samples = self.sample_map
-                                                .get(&sample_index)
-                                                .unwrap()
-                                                .clone();
+                                            //the samples may have been evicted
+                                            let samples = match self.sample_map.get(&sample_index) {
+                                                Some(samples) => samples,
+                                                None => continue,
+                                            };
                                             if self.validator.verify_samples(
                                                 &sample_index, 
                                                 samples   
@@ -234,7 +410,7 @@ impl Worker {
                                     .gen_range(0..self.config.block_size);
                                 rq_samples.push((block_hash, tx_index as u32, shard_id as u32));
                             }
-                            peer.write(Message::GetSamples(rq_samples));
+                            self.requests.request(&mut peer, Message::GetSamples(rq_samples));
                         } 
                     }
                     if let Some(res_2) = response_2 {
@@ -267,7 +443,7 @@ impl Worker {
                     //handle missing blocks
                     if let Some(res_5) = response_5 {
                         for message in res_5 {
-                            peer.write(message);
+                            self.requests.request(&mut peer, message);
                         }
                     }
                 }
@@ -280,7 +456,7 @@ impl Worker {
                         .collect();
                     if let Some(response) = self
                         .handle_new_block_hash(versa_hash_vec, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     }
                 }
                 Message::GetInBlocks((in_hash_vec, shard_id)) => {
@@ -295,13 +471,13 @@ impl Worker {
                     }
                 }
                 Message::InBlocks((in_blocks, shard_id)) => {
-                    info!("Coming inclusive blocks");
+                    info!("Coming inclusive blocks from {}", peer.identity());
                     let versa_blocks = in_blocks
                         .into_iter()
                         .map(|x| VersaBlock::InBlock(x))
                         .collect();
                     let (response_1, response_2, response_3, response_4, response_5) = self
-                        .handle_blocks(versa_blocks, shard_id as usize); 
+                        .handle_blocks(versa_blocks, shard_id as usize, peer.addr()); 
                     if let Some(res_1) = response_1 {
                         self.server.broadcast(res_1.clone());
                         //Request the samples from peer 
@@ -311,10 +487,11 @@ impl Worker {
                                 match self.blk2sample.get(&block_hash) {
                                     Some(sample_index_vec) => {
                                         for sample_index in sample_index_vec.iter() {
-                                            let samples = self.sample_map
-                                                .get(&sample_index)
-                                                .unwrap()
-                                                .clone();
+                                            //the samples may have been evicted
+                                            let samples = match self.sample_map.get(&sample_index) {
+                                                Some(samples) => samples,
+                                                None => continue,
+                                            };
                                             if self.validator.verify_samples(
                                                 &sample_index, 
                                                 samples   
@@ -336,7 +513,7 @@ impl Worker {
                                     .gen_range(0..self.config.block_size);
                                 rq_samples.push((block_hash, tx_index as u32, shard_id as u32));
                             }
-                            peer.write(Message::GetSamples(rq_samples));
+                            self.requests.request(&mut peer, Message::GetSamples(rq_samples));
                         } 
 
                     }
@@ -366,7 +543,7 @@ impl Worker {
                     //handle missing blocks
                     if let Some(res_5) = response_5 {
                         for message in res_5 {
-                            peer.write(message);
+                            self.requests.request(&mut peer, message);
                         }
                     }
                  }
@@ -379,7 +556,7 @@ impl Worker {
                         .collect();
                     if let Some(response) = self
                         .handle_new_block_hash(versa_hash_vec, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, self.prefer_compact(response));
                     }
                 }
                 Message::GetExFullBlocks((ex_full_hash_vec, shard_id)) => {
@@ -394,18 +571,14 @@ impl Worker {
                     }
                 }
                 Message::ExFullBlocks((ex_full_blocks, shard_id)) => {
-                    info!("Coming exclusive full blocks");
-                    if self.config.network_delay != 0 {
-                        let interval = time::Duration::from_micros(self.config.network_delay as u64);
-                        thread::sleep(interval);
-                    }
+                    info!("Coming exclusive full blocks from {}", peer.identity());
                     //debug!("Coming exclusive full blocks");
                     let versa_blocks = ex_full_blocks
                         .into_iter()
                         .map(|x| VersaBlock::ExFullBlock(x))
                         .collect();
                     let (response_1, response_2, response_3, response_4, response_5) = self
-                        .handle_blocks(versa_blocks, shard_id as usize); 
+                        .handle_blocks(versa_blocks, shard_id as usize, peer.addr()); 
                     if let Some(res_1) = response_1 {
                         self.server.broadcast_with_shard(res_1, shard_id as usize);
                     }
@@ -435,7 +608,7 @@ impl Worker {
                     //handle missing blocks
                     if let Some(res_5) = response_5 {
                         for message in res_5 {
-                            peer.write(message);
+                            self.requests.request(&mut peer, message);
                         }
                     }
                 }
@@ -448,7 +621,7 @@ impl Worker {
                         .collect();
                     if let Some(response) = self
                         .handle_new_block_hash(versa_hash_vec, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, self.prefer_compact(response));
                     }
                 }
                 Message::GetInFullBlocks((in_full_hash_vec, shard_id)) => {
@@ -463,18 +636,14 @@ impl Worker {
                     }
                 }
                 Message::InFullBlocks((in_full_blocks, shard_id)) => {
-                    info!("Coming inclusive full blocks");
-                    if self.config.network_delay != 0 {
-                        let interval = time::Duration::from_micros(self.config.network_delay as u64);
-                        thread::sleep(interval);
-                    }
+                    info!("Coming inclusive full blocks from {}", peer.identity());
                     //debug!("Coming inclusive full blocks");
                     let versa_blocks = in_full_blocks
                         .into_iter()
                         .map(|x| VersaBlock::InFullBlock(x))
                         .collect();
                     let (response_1, response_2, response_3, response_4, response_5) = self
-                        .handle_blocks(versa_blocks, shard_id as usize); 
+                        .handle_blocks(versa_blocks, shard_id as usize, peer.addr()); 
                     if let Some(res_1) = response_1 {
                         self.server.broadcast_with_shard(res_1, shard_id as usize);
                     }
@@ -504,16 +673,49 @@ impl Worker {
                     //handle missing blocks
                     if let Some(res_5) = response_5 {
                         for message in res_5 {
-                            peer.write(message);
+                            self.requests.request(&mut peer, message);
                         }
                     }
                 }
+                //Compact full blocks
+                Message::GetCompactBlocks((hash_vec, shard_id)) => {
+                    info!("Get compact blocks");
+                    if let Some(response) = self
+                        .handle_get_compact_blocks(hash_vec, shard_id as usize) {
+                        peer.write(response);
+                    }
+                }
+                Message::CompactBlocks((compact_blocks, shard_id)) => {
+                    info!("Coming compact blocks from {}", peer.identity());
+                    let (full_blocks, requests) = self
+                        .handle_compact_blocks(compact_blocks, shard_id as usize);
+                    for request in requests {
+                        self.requests.request(&mut peer, request);
+                    }
+                    self.handle_full_blocks(full_blocks, shard_id as usize, &mut peer);
+                }
+                Message::GetBlockTxns((request, shard_id)) => {
+                    if let Some(response) = self
+                        .handle_get_block_txns(request, shard_id as usize) {
+                        peer.write(response);
+                    }
+                }
+                Message::BlockTxns((block_txns, shard_id)) => {
+                    let (full_block, request) = self
+                        .handle_block_txns(block_txns, shard_id as usize);
+                    if let Some(request) = request {
+                        self.requests.request(&mut peer, request);
+                    }
+                    if let Some(full_block) = full_block {
+                        self.handle_full_blocks(vec![full_block], shard_id as usize, &mut peer);
+                    }
+                }
                 //Testimony
                 Message::NewTestimonyHash((tmy_hash_vec, shard_id)) => {
                     //info!("New testimony hash");
                     if let Some(response) = self
                         .handle_new_testimony_hash(tmy_hash_vec, shard_id as usize) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     }
                 }
                 Message::GetTestimonies((tmy_hash_vec, shard_id)) => {
@@ -535,7 +737,7 @@ impl Worker {
                     //info!("New Fraud Proofs");
                     if let Some(response) = self
                         .handle_new_fraud_proof_hash(fp_hash_vec) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     }
                 }
                 Message::GetFraudProofs(fp_hash_vec) => {
@@ -546,9 +748,9 @@ impl Worker {
                     }
                 }
                 Message::FraudProofs(fps) => {
-                    //info!("Coming Fraud Proofs");
+                    info!("Coming {} fraud proofs from {}", fps.len(), peer.identity());
                     if let Some(response) =
-                        self.handle_fraud_proofs(fps) {
+                        self.handle_fraud_proofs(fps, peer.addr()) {
                         self.server.broadcast(response);
                     }
                 }
@@ -556,7 +758,7 @@ impl Worker {
                     //info!("New Samples");
                     if let Some(response) = self
                         .handle_new_samples(sample_info) {
-                        peer.write(response);
+                        self.requests.request(&mut peer, response);
                     }
                 }
                 Message::GetSamples(sample_info) => {
@@ -625,18 +827,189 @@ impl Worker {
                         }
                     }
                 }
-                _ => unimplemented!()
+                Message::GetAddr => {
+                    debug!("Get addresses");
+                    let addrs: Vec<(std::net::SocketAddr, u32)> = self.server
+                        .get_addrs()
+                        .into_iter()
+                        .map(|(addr, shard_id)| (addr, shard_id as u32))
+                        .collect();
+                    if !addrs.is_empty() {
+                        peer.write(Message::Addr(addrs));
+                    }
+                }
+                Message::Addr(addrs) => {
+                    debug!("Coming {} addresses", addrs.len());
+                    let addrs: Vec<(std::net::SocketAddr, usize)> = addrs
+                        .into_iter()
+                        .take(MAX_ADDR_PER_MSG)
+                        .filter(|(_, shard_id)| (*shard_id as usize) < self.config.shard_num)
+                        .map(|(addr, shard_id)| (addr, shard_id as usize))
+                        .collect();
+                    self.server.add_addrs(peer.addr().clone(), addrs);
+                }
+                Message::GetHeaders((locator, shard_id)) => {
+                    debug!("Get headers of shard {}", shard_id);
+                    if let Some(response) = self
+                        .handle_get_headers(locator, shard_id as usize) {
+                        peer.write(response);
+                    }
+                }
+                Message::Headers((headers, shard_id)) => {
+                    debug!("Coming {} headers of shard {}", headers.len(), shard_id);
+                    if let Some(light_client) = &self.light_client {
+                        match light_client.insert_headers(&headers, shard_id as usize) {
+                            Ok(inserted) => {
+                                //a full batch means that the peer has more headers
+                                if inserted > 0 && headers.len() == MAX_HEADERS {
+                                    for (locator_shard, locator) in light_client.get_locators() {
+                                        if locator_shard == shard_id as usize {
+                                            peer.write(Message::GetHeaders((locator, shard_id)));
+                                        }
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Invalid headers from {}: {}", peer.addr(), e);
+                                self.server.report(peer.addr().clone(), Misbehavior::InvalidBlock);
+                            }
+                        }
+                    }
+                }
+                Message::GetTxProof((tx_hash, shard_id)) => {
+                    debug!("Get the proof of tx {:?}", tx_hash);
+                    if let Some(response) = self
+                        .handle_get_tx_proof(tx_hash, shard_id as usize) {
+                        peer.write(response);
+                    }
+                }
+                Message::TxProof((proof, shard_id)) => {
+                    debug!("Coming the proof of tx {:?}", proof.tx.hash());
+                    if let Some(light_client) = &self.light_client {
+                        if let Err(e) = light_client.handle_tx_proof(&proof, shard_id as usize) {
+                            warn!("Invalid tx proof from {}: {}", peer.addr(), e);
+                            self.server.report(peer.addr().clone(), Misbehavior::InvalidTxProof);
+                        }
+                    }
+                }
+                Message::GetSnapshot((block_hash, shard_id)) => {
+                    debug!("Get the snapshot of shard {} at {:?}", shard_id, block_hash);
+                    if let Some(response) = self
+                        .handle_get_snapshot(block_hash, shard_id as usize) {
+                        peer.write(response);
+                    }
+                }
+                Message::Snapshot((snapshot, shard_id)) => {
+                    debug!("Coming the snapshot of shard {} at {:?}", shard_id, snapshot.block_hash);
+                    if let Some(rotation) = &self.rotation {
+                        if let Err(e) = rotation.handle_snapshot(&snapshot, shard_id as usize) {
+                            warn!("Invalid snapshot from {}: {}", peer.addr(), e);
+                            self.server.report(peer.addr().clone(), Misbehavior::InvalidSnapshot);
+                        }
+                    }
+                }
+                Message::Rotate(shard_id) => {
+                    debug!("Peer {} rotates to shard {}", peer.addr(), shard_id);
+                    let shard_id = shard_id as usize;
+                    //only the shard the beacon assigns to the node id of the peer
+                    let node_id = self.server
+                        .get_peer_status()
+                        .into_iter()
+                        .find(|status| status.addr == *peer.addr())
+                        .map(|status| status.node_id as usize);
+                    match node_id {
+                        Some(node_id) if shard_id < self.config.shard_num &&
+                            Rotation::is_scheduled(&self.multichain, &self.config, node_id, shard_id) => {
+                            self.server.move_peer(peer.addr().clone(), shard_id);
+                        }
+                        _ => warn!("Peer {} rotates to shard {} out of the schedule", peer.addr(), shard_id),
+                    }
+                }
             }
+            self.stats.record_processed(peer.addr(), type_id, started_at.elapsed());
         }
     }
    
+    fn is_light_message(msg: &Message) -> bool {
+        match msg {
+            Message::Ping(_) | Message::Pong(_) => true,
+            Message::GetAddr | Message::Addr(_) => true,
+            Message::Headers(_) | Message::TxProof(_) => true,
+            _ => false,
+        }
+    }
+
+    //the headers of the longest chain of the shard after the first hash of the
+    //locator on it
+    fn handle_get_headers(&self, locator: Vec<H256>, shard_id: usize) -> Option<Message> {
+        if shard_id >= self.config.shard_num {
+            return None;
+        }
+        let chain = self.multichain.all_blocks_in_longest_chain_with_shard(shard_id);
+        let heights: HashMap<H256, usize> = chain
+            .iter()
+            .enumerate()
+            .map(|(height, hash)| (hash.clone(), height))
+            .collect();
+        //the light client starts from the genesis block
+        let start = locator
+            .iter()
+            .find_map(|hash| heights.get(hash))
+            .cloned()
+            .unwrap_or(0) + 1;
+        let headers: Vec<VersaBlock> = chain
+            .iter()
+            .skip(start)
+            .take(MAX_HEADERS)
+            .filter_map(|hash| self.multichain.get_block_by_shard(hash, shard_id))
+            .map(|block| block.to_header())
+            .collect();
+        if headers.is_empty() {
+            return None;
+        }
+        Some(Message::Headers((headers, shard_id as u32)))
+    }
+
+    //the inclusion proof of a transaction in the longest chain of this shard
+    fn handle_get_tx_proof(&self, tx_hash: H256, shard_id: usize) -> Option<Message> {
+        if shard_id != self.config.shard_id() {
+            return None;
+        }
+        let (block, tx_index) = self.multichain.get_block_with_tx(&tx_hash)?;
+        let tx = block.get_txs_ref()?.get(tx_index)?.clone();
+        let tx_merkle_proof = block.get_tx_merkle_proof(tx_index)?;
+        let testimony = block
+            .get_tmys()
+            .and_then(|tmys| tmys.get(&tx_hash).cloned());
+        let proof = TxProof {
+            tx,
+            block_hash: block.hash(),
+            tx_index: tx_index as u32,
+            tx_merkle_proof,
+            testimony,
+        };
+        Some(Message::TxProof((proof, shard_id as u32)))
+    }
+
+    //the state of the own shard after the block, not served while the node itself
+    //is still syncing it
+    fn handle_get_snapshot(&self, block_hash: H256, shard_id: usize) -> Option<Message> {
+        if shard_id != self.config.shard_id() || self.config.role.is_syncing() {
+            return None;
+        }
+        let state = self.multichain.get_state(&block_hash)?;
+        let snapshot = StateSnapshot::new(shard_id, block_hash, state);
+        Some(Message::Snapshot((snapshot, shard_id as u32)))
+    }
+
     //handle transaction message
     fn handle_new_transaction_hash(
         &self, 
         tx_hashs: Vec<H256>, 
         shard_id: usize) -> Option<Message> 
     {
-        if shard_id != self.config.shard_id {
+        //the txs cannot be validated before the state of the shard is synced
+        if shard_id != self.config.shard_id() || self.config.role.is_syncing() {
             return None;
         }
         let mut unreceived_txs: Vec<H256> = Vec::new();
@@ -661,7 +1034,7 @@ impl Worker {
         tx_hashs: Vec<H256>, 
         shard_id: usize) -> Option<Message> 
     {
-        if shard_id != self.config.shard_id {
+        if shard_id != self.config.shard_id() {
             return None;
         }
         let mut res_txs: Vec<Transaction> = Vec::new();
@@ -682,35 +1055,58 @@ impl Worker {
             None
         }
     }
+    //return the hashes of the new transactions and the number of the known or
+    //rejected ones
     fn handle_transactions(
         &self, 
         txs: Vec<Transaction>, 
-        shard_id: usize) -> Option<Message> 
+        shard_id: usize,
+        peer_addr: &SocketAddr) -> (Option<Message>, usize) 
     {
-        if shard_id != self.config.shard_id {
-            return None;
+        if shard_id != self.config.shard_id() {
+            return (None, 0);
         }
         let mut new_tx_hashs: Vec<H256> = Vec::new();
+        let mut wasted = 0;
+        let tip = self.multichain.get_longest_chain_hash();
+        let get_utxo = |utxo: &(H256, u32)| self.multichain.get_utxo_in_longest_chain(utxo);
         for tx in txs.iter() {
             //find tx in mempool
             let tx_hash = tx.hash();
             if let Some(tx) = self.mempool.lock().unwrap().get_tx(&tx_hash) {
+                wasted += 1;
                 continue;
             }
             //2.validate the transaction
             match self.validator.validate_tx(tx, None, None, ValidationSource::FromTransaction) {
                 Ok(_) => {}
-                Err(_) => {
+                Err(err) => {
+                    self.validation_stats.record(FROM_TRANSACTION, &err);
+                    if err.is_definite() {
+                        self.server.report(peer_addr.clone(), Misbehavior::InvalidTransaction);
+                    }
+                    wasted += 1;
                     continue;
                 }
             }
+            //3.check the signatures against the longest chain, they are cached for the
+            //validation of the block including the transaction; a spent or unknown utxo
+            //may only be a chain the peer is ahead or behind of
+            if let Err(err) = self.validator.check_tx_signatures_with(tx, &tip, &get_utxo) {
+                self.validation_stats.record(FROM_TRANSACTION, &err);
+                if err.is_definite() {
+                    self.server.report(peer_addr.clone(), Misbehavior::InvalidTransaction);
+                }
+                wasted += 1;
+                continue;
+            }
             new_tx_hashs.push(tx_hash);
             self.mempool.lock().unwrap().insert_tx(tx.clone());
         }
         if !new_tx_hashs.is_empty() {
-            Some(Message::NewTransactionHash((new_tx_hashs, shard_id as u32)))
+            (Some(Message::NewTransactionHash((new_tx_hashs, shard_id as u32))), wasted)
         } else {
-            None
+            (None, wasted)
         }
     }
     fn handle_new_block_hash(
@@ -728,7 +1124,7 @@ impl Worker {
         match first_hash {
             VersaHash::ExHash(_) => {
                 //miner never accept exclusive blocks within his shard
-                if shard_id != self.config.shard_id {
+                if shard_id != self.config.shard_id() {
                     for versa_block_hash in block_hash_vec {
                         if let VersaHash::ExHash(ex_block_hash) = versa_block_hash {
                             match self.multichain.get_block_by_shard(
@@ -770,7 +1166,7 @@ impl Worker {
             }
             VersaHash::ExFullHash(_) => {
                 //miner only accept full block within this shard
-                if shard_id == self.config.shard_id {
+                if shard_id == self.config.shard_id() {
                     for versa_block_hash in block_hash_vec {
                         if let VersaHash::ExFullHash(ex_full_block_hash) = versa_block_hash {
                             match self.multichain.get_block_by_shard(
@@ -788,7 +1184,7 @@ impl Worker {
             }
             VersaHash::InFullHash(_) => {
                 //miner only accept full blocks within his shard
-                if shard_id == self.config.shard_id {
+                if shard_id == self.config.shard_id() {
                     for versa_block_hash in block_hash_vec {
                         if let VersaHash::InFullHash(in_full_block_hash) = versa_block_hash {
                             match self.multichain.get_block_by_shard(
@@ -831,7 +1227,7 @@ impl Worker {
         match first_hash {
             VersaHash::ExHash(_) => {
                 //miner never accept exclusive blocks within his shard
-                if shard_id != self.config.shard_id {
+                if shard_id != self.config.shard_id() {
                     for versa_hash in hash_vec {
                         if let VersaHash::ExHash(ex_hash) = versa_hash {
                             match self.multichain.get_block_by_shard(
@@ -865,7 +1261,7 @@ impl Worker {
             }
             VersaHash::ExFullHash(_) => {
                 //miner does not have full blocks outside his shard
-                if shard_id == self.config.shard_id {
+                if shard_id == self.config.shard_id() {
                     for versa_hash in hash_vec {
                         if let VersaHash::ExFullHash(ex_full_hash) = versa_hash {
                             match self.multichain.get_block_by_shard(
@@ -883,7 +1279,7 @@ impl Worker {
             }
             VersaHash::InFullHash(_) => {
                 //miner only accept full blocks within his shard
-                if shard_id == self.config.shard_id {
+                if shard_id == self.config.shard_id() {
                     for versa_hash in hash_vec {
                         if let VersaHash::InFullHash(in_full_hash) = versa_hash {
                             match self.multichain.get_block_by_shard(
@@ -953,7 +1349,167 @@ impl Worker {
         }
     }
 
-    fn handle_blocks(&mut self, blocks: Vec<VersaBlock>, shard_id: usize) 
+    //ask for the compact form of the full blocks if it is enabled
+    fn prefer_compact(&self, request: Message) -> Message {
+        if !self.config.compact_blocks {
+            return request;
+        }
+        match request {
+            Message::GetExFullBlocks((hash_vec, shard_id)) 
+                => Message::GetCompactBlocks((hash_vec, shard_id)),
+            Message::GetInFullBlocks((hash_vec, shard_id)) 
+                => Message::GetCompactBlocks((hash_vec, shard_id)),
+            _ => request,
+        }
+    }
+
+    fn handle_get_compact_blocks(&self, hash_vec: Vec<H256>, shard_id: usize)
+        -> Option<Message>
+    {
+        //miner does not have full blocks outside his shard
+        if shard_id != self.config.shard_id() {
+            return None;
+        }
+        let compact_blocks: Vec<CompactBlock> = hash_vec
+            .iter()
+            .filter_map(|hash| self.multichain.get_block_by_shard(hash, shard_id))
+            .filter_map(|block| CompactBlock::from_full_block(&block))
+            .collect();
+        if !compact_blocks.is_empty() {
+            Some(Message::CompactBlocks((compact_blocks, shard_id as u32)))
+        } else {
+            None
+        }
+    }
+
+    //rebuild the full blocks from the mempool, return the rebuilt blocks and the
+    //requests for the missing content
+    fn handle_compact_blocks(&mut self, compact_blocks: Vec<CompactBlock>, shard_id: usize)
+        -> (Vec<VersaBlock>, Vec<Message>)
+    {
+        let mut full_blocks: Vec<VersaBlock> = vec![];
+        let mut requests: Vec<Message> = vec![];
+        //miner only accept full blocks within his shard
+        if shard_id != self.config.shard_id() {
+            return (full_blocks, requests);
+        }
+        let mut partial_blocks = self.partial_blocks.lock().unwrap();
+        let now = SystemTime::now();
+        partial_blocks.retain(|_, partial| {
+            now.duration_since(partial.received_at)
+                .map(|d| d.as_secs() < PARTIAL_BLOCK_TIMEOUT)
+                .unwrap_or(true)
+        });
+        for compact_block in compact_blocks {
+            let block_hash = compact_block.hash();
+            if partial_blocks.contains_key(&block_hash) ||
+                self.multichain.get_block_by_shard(&block_hash, shard_id).is_some() {
+                continue;
+            }
+            let is_exclusive = compact_block.is_exclusive();
+            let partial = PartialBlock::new(compact_block, &self.mempool.lock().unwrap());
+            if !partial.is_complete() {
+                let missing = partial.get_missing();
+                debug!("Compact block {:?} misses {} txs and {} testimonies", 
+                    block_hash, 
+                    missing.tx_indexes.len(), 
+                    missing.tmy_indexes.len()
+                );
+                requests.push(Message::GetBlockTxns((missing, shard_id as u32)));
+                partial_blocks.insert(block_hash, partial);
+                continue;
+            }
+            match partial.build() {
+                Some(full_block) => full_blocks.push(full_block),
+                //a short id collision, fall back to the full block
+                None => requests.push(match is_exclusive {
+                    true => Message::GetExFullBlocks((vec![block_hash], shard_id as u32)),
+                    false => Message::GetInFullBlocks((vec![block_hash], shard_id as u32)),
+                }),
+            }
+        }
+        (full_blocks, requests)
+    }
+
+    fn handle_get_block_txns(&self, request: BlockTxnsRequest, shard_id: usize)
+        -> Option<Message>
+    {
+        if shard_id != self.config.shard_id() {
+            return None;
+        }
+        let block = self.multichain.get_block_by_shard(&request.block_hash, shard_id)?;
+        let block_txns = BlockTxns::from_full_block(&block, &request)?;
+        Some(Message::BlockTxns((block_txns, shard_id as u32)))
+    }
+
+    //return the rebuilt block, or the request for the full block if the rebuilding fails
+    fn handle_block_txns(&mut self, block_txns: BlockTxns, shard_id: usize)
+        -> (Option<VersaBlock>, Option<Message>)
+    {
+        let block_hash = block_txns.block_hash;
+        let is_exclusive = block_txns.is_exclusive;
+        let mut partial = match self.partial_blocks.lock().unwrap().remove(&block_hash) {
+            Some(partial) => partial,
+            None => return (None, None),
+        };
+        partial.fill(block_txns);
+        match partial.build() {
+            Some(full_block) => (Some(full_block), None),
+            None => {
+                info!("Fail to rebuild compact block {:?}, request the full block", block_hash);
+                let request = match is_exclusive {
+                    true => Message::GetExFullBlocks((vec![block_hash], shard_id as u32)),
+                    false => Message::GetInFullBlocks((vec![block_hash], shard_id as u32)),
+                };
+                (None, Some(request))
+            }
+        }
+    }
+
+    //insert the rebuilt full blocks and relay them, as upon ExFullBlocks/InFullBlocks
+    fn handle_full_blocks(
+        &mut self, 
+        full_blocks: Vec<VersaBlock>, 
+        shard_id: usize,
+        peer: &mut peer::Handle) 
+    {
+        //handle_blocks expects blocks of the same kind
+        let (ex_full_blocks, in_full_blocks): (Vec<VersaBlock>, Vec<VersaBlock>) = full_blocks
+            .into_iter()
+            .partition(|block| matches!(block, VersaBlock::ExFullBlock(_)));
+        for versa_blocks in vec![ex_full_blocks, in_full_blocks] {
+            let (response_1, response_2, response_3, response_4, response_5) = self
+                .handle_blocks(versa_blocks, shard_id, peer.addr()); 
+            if let Some(res_1) = response_1 {
+                self.server.broadcast_with_shard(res_1, shard_id);
+            }
+            if let Some(res_2) = response_2 {
+                self.server.broadcast(res_2);
+            }
+            for message in response_3.into_iter().flatten() {
+                if let Message::Transactions((txs, shard_id)) = message {
+                    self.server.broadcast_with_shard(
+                        Message::Transactions((txs, shard_id)),
+                        shard_id as usize
+                    );
+                }
+            }
+            for message in response_4.into_iter().flatten() {
+                if let Message::Testimonies((tmys, shard_id)) = message {
+                    self.server.broadcast_with_shard(
+                        Message::Testimonies((tmys, shard_id)),
+                        shard_id as usize
+                    );
+                }
+            }
+            //handle missing blocks
+            for message in response_5.into_iter().flatten() {
+                self.requests.request(peer, message);
+            }
+        }
+    }
+
+    fn handle_blocks(&mut self, blocks: Vec<VersaBlock>, shard_id: usize, peer_addr: &SocketAddr) 
         -> (Option<Message>, Option<Message>, Option<Vec<Message>>, Option<Vec<Message>>, Option<Vec<Message>>) 
     //new_block_hash, fraud_proof_hash, return_tx, return_tmy
     {
@@ -961,31 +1517,33 @@ impl Worker {
             return (None, None, None, None, None);
         }
         for block in blocks.iter() {
-            info!("Comming block {:?} in shard {}", block.hash(), self.config.shard_id);
+            info!("Comming block {:?} in shard {}", block.hash(), self.config.shard_id());
         }
         let first_block = blocks[0].clone();
          match first_block.clone() {
             VersaBlock::ExBlock(ex_block) => {
-                if ex_block.get_shard_id() == self.config.shard_id {
+                if ex_block.get_shard_id() == self.config.shard_id() {
                     return (None, None, None, None, None);
                 }
             }
             VersaBlock::InBlock(_) => {
                 
             }
+            //while the node syncs the state of its new shard, the full blocks are
+            //left out, the children coming later request them again as missing parents
             VersaBlock::ExFullBlock(_) => {
-                if shard_id != self.config.shard_id {
+                if shard_id != self.config.shard_id() || self.config.role.is_syncing() {
                     return (None, None, None, None, None);
                 }
             }
             VersaBlock::InFullBlock(_) => {
-                if shard_id != self.config.shard_id {
+                if shard_id != self.config.shard_id() || self.config.role.is_syncing() {
                     return (None, None, None, None, None);
                 }
             }
         } 
         for block in blocks.iter() {
-            info!("Handling block {:?} in shard {}", block.hash(), self.config.shard_id);
+            info!("Handling block {:?} in shard {}", block.hash(), self.config.shard_id());
         }       
         //key: hash of fraud_proof, value: shard_id
         let mut fraud_proofs: HashMap<FraudProof, usize> = HashMap::new();
@@ -996,7 +1554,24 @@ impl Worker {
         let mut missing_parents: HashMap<usize, Vec<H256>> = HashMap::new();
         for block in blocks {
             //verification
-
+            let in_block = match &block {
+                VersaBlock::InBlock(in_block) => Some(in_block.clone()),
+                VersaBlock::InFullBlock(in_full_block) => Some(in_full_block.get_inclusive_block()),
+                _ => None,
+            };
+            //the parents of an inclusive block are checked before waiting for them
+            if let Some(in_block) = in_block.as_ref() {
+                if let Err(err) = self.validator.validate_parents(in_block) {
+                    info!("block insertion failure: {}", err);
+                    self.validation_stats.record(FROM_BLOCK, &err);
+                    self.server.report(peer_addr.clone(), Misbehavior::InvalidBlock);
+                    if let Some(proof) = err.into_fraud_proof() {
+                        self.record_fraud_proof(&proof);
+                        fraud_proofs.insert(proof, shard_id);
+                    }
+                    continue;
+                }
+            }
             
             //check whether the parent exits
             let parents: Vec<(H256, usize)> = match block.clone() {
@@ -1052,18 +1627,38 @@ impl Worker {
                 //this is important
                 //the inclusive block can not be inserted in his own shard
                 if let VersaBlock::InBlock(_) = block {
-                    if inserted_shard_id == self.config.shard_id &&
-                        block.get_shard_id() == self.config.shard_id {
+                    if inserted_shard_id == self.config.shard_id() &&
+                        block.get_shard_id() == self.config.shard_id() {
                         continue;
                     }
                 }
                 
+                //a parent proved invalid, which may have been pruned, makes the block invalid
+                if let Some(in_block) = in_block.as_ref() {
+                    if let Some(parent_proof) = self.blk2fp.get(&parent_hash) {
+                        if self.validator.verify_fraud_proof(&parent_proof) == ProofCheck::Valid {
+                            info!("block insertion failure: parent {:?} is invalid", parent_hash);
+                            let proof = FraudProof::InvalidParent(InvalidParentProof {
+                                shard_id: in_block.get_shard_id() as u32,
+                                block: in_block.clone(),
+                                parent_hash: parent_hash.clone(),
+                                parent_shard_id: inserted_shard_id as u32,
+                                parent_proof: Some(Box::new(parent_proof)),
+                            });
+                            self.record_fraud_proof(&proof);
+                            fraud_proofs.insert(proof, shard_id);
+                            break;
+                        }
+                    }
+                }
+
                 //check whether the parent exits
                 match self.multichain.get_block_by_shard(&parent_hash, inserted_shard_id) {
                     Some(_) => {}
                     None => {
-                        self.blk_buff.insert(
+                        self.orphans.insert(
                             parent_hash.clone(),
+                            inserted_shard_id,
                             block.clone()
                         );
                         info!("block insertion failure in shard {}: parent {:?} not fould", inserted_shard_id, parent_hash);
@@ -1086,7 +1681,7 @@ impl Worker {
                 }
                 match self.validator.validate_block(&block, &parent_hash) {
                     Ok(_) => {}
-                    Err(proof) => {
+                    Err(err) => {
                         //match &block {
                         //    &VersaBlock::ExBlock(_) => {
                         //        info!("Ex");
@@ -1101,10 +1696,14 @@ impl Worker {
                         //        info!("InFull");
                         //    }
                         //}
-                        info!("block insertion failure: the verification fails");
-                        if let FraudProof::UnsolvedFault = proof {
-                            continue;
-                        }
+                        info!("block insertion failure: {}", err);
+                        self.validation_stats.record(FROM_BLOCK, &err);
+                        let proof = match err.into_fraud_proof() {
+                            Some(proof) => proof,
+                            None => continue,
+                        };
+                        //the peer relays a block which it should have rejected
+                        self.server.report(peer_addr.clone(), Misbehavior::InvalidBlock);
                         match &block {
                             &VersaBlock::ExBlock(_) => {
                                 continue;
@@ -1114,14 +1713,13 @@ impl Worker {
                             }
                             _ => {}
                         }
-                        fraud_proofs.insert(proof.clone(), shard_id);
-                        self.fp_map.insert(block.hash(), proof);
+                        self.record_fraud_proof(&proof);
+                        fraud_proofs.insert(proof, shard_id);
                         continue;
                     }
                 }
 
                 let mut inserted_blk = block.clone();
-                let mut removed_buff: Vec<H256> = vec![];
                 loop {
                     match self.multichain.insert_block_with_parent(
                         inserted_blk.clone(),
@@ -1136,7 +1734,7 @@ impl Worker {
                             //iteration
                             match self.blk2fp.get(&new_hash) {
                                 Some(fp) => {
-                                    if self.validator.verify_fraud_proof(fp) {
+                                    if self.validator.verify_fraud_proof(&fp) == ProofCheck::Valid {
                                         info!("skip block {:?}", new_hash);
                                         let shard_id = fp.get_shard_id();
                                         let block_hash = fp.get_invalid_block();
@@ -1182,10 +1780,9 @@ impl Worker {
 
                             //if there are some blocks in the buff whose parent is the new block,
                             //continue to insert it
-                            match self.blk_buff.get(&new_hash) {
+                            match self.orphans.take(&new_hash) {
                                 Some(child_blk) => {
-                                    inserted_blk = child_blk.clone();
-                                    removed_buff.push(new_hash);
+                                    inserted_blk = child_blk;
                                 }
                                 None => {
                                     break;
@@ -1193,14 +1790,11 @@ impl Worker {
                             }
                         }
                         Err(e) => {
-                            info!("Reject block {:?} in shard {}: insertion fails: {}", inserted_blk.hash(), self.config.shard_id, e);
+                            info!("Reject block {:?} in shard {}: insertion fails: {}", inserted_blk.hash(), self.config.shard_id(), e);
                             break;
                         }
                     }
                 }
-                for item2 in removed_buff {
-                    self.blk_buff.remove(&item2);
-                }
             }
         }
         let res_hash: Vec<H256> = new_hashs
@@ -1208,10 +1802,14 @@ impl Worker {
             .map(|(key, _)| key)
             .collect();
 
-        let res_fp: Vec<FraudProof> = fraud_proofs
+        let mut res_fp: Vec<H256> = fraud_proofs
             .into_iter()
-            .map(|(key, val)| key)
+            .map(|(key, val)| key.hash())
             .collect();
+        //the new blocks may be what the parked proofs are waiting for
+        if !res_hash.is_empty() {
+            res_fp.extend(self.retry_pending_fraud_proofs());
+        }
 
         let res_blk_hash = match res_hash.is_empty() {
             false => {
@@ -1226,7 +1824,7 @@ impl Worker {
         };
         
         let res_fp_hash = match res_fp.is_empty() {
-            false => Some(Message::FraudProofs(res_fp)),
+            false => Some(Message::NewFraudProofHash(res_fp)),
             true => None,
         };
 
@@ -1289,7 +1887,7 @@ impl Worker {
     fn handle_new_testimony_hash(&self, tmy_hash_vec: Vec<H256>, shard_id: usize) 
         -> Option<Message> 
     {
-        if shard_id != self.config.shard_id {
+        if shard_id != self.config.shard_id() {
             return None;
         }
         let mut unreceived_tmys: Vec<H256> = Vec::new();
@@ -1311,7 +1909,7 @@ impl Worker {
     fn handle_get_testimonies(&self, tmy_hash_vec: Vec<H256>, shard_id: usize)
         -> Option<Message>
     {
-        if shard_id != self.config.shard_id {
+        if shard_id != self.config.shard_id() {
             return None;
         }
         let mut res_tmys: Vec<Testimony> = Vec::new();
@@ -1332,7 +1930,7 @@ impl Worker {
     fn handle_testimonies(&mut self, tmys: Vec<Testimony>, shard_id: usize)
         -> Option<Message>
     {
-        if shard_id != self.config.shard_id {
+        if shard_id != self.config.shard_id() {
             return None;
         }
         let mut new_tmy_hash: Vec<H256> = Vec::new();
@@ -1351,15 +1949,60 @@ impl Worker {
         }
     }
 
+    //keep the proof to serve it to the peers, and connect it to its block
+    fn record_fraud_proof(&self, fp: &FraudProof) {
+        self.fp_map.insert(fp.hash(), fp.clone());
+        self.blk2fp.insert(fp.get_invalid_block(), fp.clone());
+        self.export_fraud_proof(fp);
+    }
+
+    //write the proof with the headers it depends on into the export directory, if any
+    fn export_fraud_proof(&self, fp: &FraudProof) {
+        let dir = match &self.config.fraud_proof_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let path = format!("{}/{}.json", dir, fp.hash());
+        //a proof exported again only overwrites its file
+        let is_new = !std::path::Path::new(&path).exists();
+        //the directory is bounded, the proofs exported first are kept. A slot is
+        //taken before the proof is written, so the workers cannot exceed the bound
+        if is_new {
+            let max = self.config.max_exported_fraud_proofs;
+            let taken = self.exported_fps.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if n < max { Some(n + 1) } else { None }
+            });
+            if let Err(exported) = taken {
+                warn!("Fraud proof {:?} not exported, {} holds {} proofs", fp.hash(), dir, exported);
+                return;
+            }
+        }
+        //only a proof which holds against the exported headers is written
+        let bundle = FraudProofBundle::export(fp, &self.multichain, &self.config);
+        let res = match bundle.verify() {
+            Ok(true) => bundle.save(&path),
+            _ => Err(String::from("it fails against its headers")),
+        };
+        match res {
+            Ok(_) => info!("Fraud proof exported to {}", path),
+            Err(e) => {
+                warn!("Fraud proof {:?} not exported: {}", fp.hash(), e);
+                if is_new {
+                    self.exported_fps.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+
     fn handle_new_fraud_proof_hash(&self, fp_hash_vec: Vec<H256>) 
         -> Option<Message> 
     {
         let mut unreceived_fps: Vec<H256> = vec![];
         for fp_hash in fp_hash_vec {
-            match self.fp_map.get(&fp_hash) {
-                Some(_) => {}
-                None => unreceived_fps.push(fp_hash),
+            if self.fp_map.contains_key(&fp_hash) || self.pending_fps.contains_key(&fp_hash) {
+                continue;
             }
+            unreceived_fps.push(fp_hash);
         }
         if !unreceived_fps.is_empty() {
             Some(Message::GetFraudProofs(unreceived_fps))
@@ -1374,7 +2017,7 @@ impl Worker {
         let mut res_fps: Vec<FraudProof> = vec![];
         for fp_hash in fp_hash_vec {
             match self.fp_map.get(&fp_hash) {
-                Some(fp) => res_fps.push(fp.clone()),
+                Some(fp) => res_fps.push(fp),
                 None => {}
             }
         }
@@ -1384,29 +2027,31 @@ impl Worker {
             None
         }
     }
-    fn handle_fraud_proofs(&mut self, fps: Vec<FraudProof>)
+    fn handle_fraud_proofs(&mut self, fps: Vec<FraudProof>, peer_addr: &SocketAddr)
         -> Option<Message>
     {
         let mut new_fp_hash: Vec<H256> = vec![];
         for fp in fps {
             let fp_hash = fp.hash();
-            match self.fp_map.get(&fp_hash) {
-                Some(_) => continue,
-                None => {
-                    self.fp_map.insert(fp_hash.clone(), fp.clone());
+            if self.fp_map.contains_key(&fp_hash) || self.pending_fps.contains_key(&fp_hash) {
+                continue;
+            }
+            //only a verified proof is kept, connected to its block and relayed, since
+            //it gets the descendants of the block rejected
+            match self.validator.verify_fraud_proof(&fp) {
+                ProofCheck::Valid => {
+                    self.record_fraud_proof(&fp);
                     new_fp_hash.push(fp_hash);
-                    //connect this fraudproof to its corresponding block
-                    let block_hash = fp.get_invalid_block();
-                    self.blk2fp.insert(block_hash, fp.clone());
                 }
-            }
-            if self.validator.verify_fraud_proof(&fp) {
-                let shard_id = fp.get_shard_id();
-                let block_hash = fp.get_invalid_block();
-                if shard_id == self.config.shard_id {
-                    continue;
+                //the proof is judged against the blocks we have, so it is wrong for sure
+                ProofCheck::Invalid => {
+                    self.server.report(peer_addr.clone(), Misbehavior::InvalidFraudProof);
+                }
+                //we may only lag behind the sender, the proof is checked again once
+                //new blocks arrive
+                ProofCheck::Unknown => {
+                    self.pending_fps.insert(fp_hash, fp);
                 }
-                //self.multichain.prune_fork_with_shard(&block_hash, shard_id);
             }
         } 
 
@@ -1417,6 +2062,29 @@ impl Worker {
         }
     }
 
+    //check the parked proofs again, return the hashes of the ones which turn out valid
+    fn retry_pending_fraud_proofs(&self) -> Vec<H256> {
+        let mut valid_fps: Vec<H256> = vec![];
+        for fp_hash in self.pending_fps.keys() {
+            let fp = match self.pending_fps.get(&fp_hash) {
+                Some(fp) => fp,
+                None => continue,
+            };
+            match self.validator.verify_fraud_proof(&fp) {
+                ProofCheck::Valid => {
+                    self.pending_fps.remove(&fp_hash);
+                    self.record_fraud_proof(&fp);
+                    valid_fps.push(fp_hash);
+                }
+                ProofCheck::Invalid => {
+                    self.pending_fps.remove(&fp_hash);
+                }
+                ProofCheck::Unknown => {}
+            }
+        }
+        valid_fps
+    }
+
     fn handle_new_samples(&self, sample_index_vec: Vec<SampleIndex>) -> Option<Message> {
         let mut unreceived_samples: Vec<SampleIndex> = vec![];
 
@@ -1425,7 +2093,7 @@ impl Worker {
             let tx_index = sample.1 as usize;
             let shard_id = sample.2 as usize;
 
-            if shard_id == self.config.shard_id {
+            if shard_id == self.config.shard_id() {
                 continue;
             }
             //match self.sample_map.get(&sample) {
@@ -1449,14 +2117,14 @@ impl Worker {
             let shard_id = sample_eq.2 as usize;
             match self.sample_map.get(&sample_eq) {
                 Some(samples) => {
-                    res_samples.push((sample_eq.clone(), samples.clone()));
+                    res_samples.push((sample_eq.clone(), samples));
                     continue;
                 }
                 None => {}
             }
             
             //need more consideration here
-            if shard_id != self.config.shard_id {
+            if shard_id != self.config.shard_id() {
                 continue;
             }
 
@@ -1499,7 +2167,7 @@ impl Worker {
             let shard_id = sample_key.2 as usize;
 
             //consider more here
-            //if shard_id == self.config.shard_id {
+            //if shard_id == self.config.shard_id() {
             //    continue;
             //}
             
@@ -1516,9 +2184,9 @@ impl Worker {
             //    None => {}
             //}
 
-            match self.sample_map.get(&sample_key) {
+            let merged_sample = match self.sample_map.get(&sample_key) {
                 Some(old_sample) => {
-                    let mut new_sample = old_sample.clone();
+                    let mut new_sample = old_sample;
                     let mut is_updated = false;
                     for sample_unit in sample_value.iter() {
                         if !new_sample.contains(sample_unit) {
@@ -1527,15 +2195,17 @@ impl Worker {
                         }
                     }
                     if is_updated {
-                        self.sample_map.insert(sample_key.clone(), new_sample);
+                        self.sample_map.insert(sample_key.clone(), new_sample.clone());
                         new_samples.push(sample_key.clone());
                     }
+                    new_sample
                 }
                 None => {
                     self.sample_map.insert(sample_key.clone(), sample_value.clone());
                     new_samples.push(sample_key.clone());
+                    sample_value
                 }
-            }
+            };
             
             match self.blk2sample.get(&block_hash) {
                 Some(old_sample_keys) => {
@@ -1552,7 +2222,7 @@ impl Worker {
             //It should verify blocks across all shards 
             if self.validator.verify_samples(
                 &sample_key,
-                self.sample_map.get(&sample_key).unwrap().clone()
+                merged_sample
             ) {
                 for shard_id in 0..self.config.shard_num {
                     match self.multichain.verify_block_with_shard(
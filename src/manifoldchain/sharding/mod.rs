@@ -0,0 +1,366 @@
+pub mod reshard;
+pub mod rotation;
+
+use crate::{
+    types::{
+        address::Address,
+        hash::Hashable,
+    },
+    manifoldchain::{
+        configuration::Configuration,
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Arc,
+};
+
+//a cluster of the locality-aware table holds at most this many times the average
+//number of addresses per shard
+const TABLE_SLACK: f64 = 1.2;
+
+//how the addresses are mapped to the shards, every node of the network must use
+//the same policy
+pub trait ShardPolicy: Send + Sync {
+    /// The shard of the address among shard_num shards
+    fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize;
+}
+
+//the last bytes of the address modulo the number of shards
+pub struct ModuloPolicy;
+
+//jump consistent hashing of the hash of the address: adding a shard only moves the
+//addresses that go to the new shard
+pub struct ConsistentHashPolicy;
+
+//explicit ranges of the first 4 bytes of the address, a range starts at its prefix
+//and ends at the next one
+pub struct PrefixRangePolicy {
+    ranges: Vec<(u32, usize)>, //(first prefix, shard), sorted by prefix
+}
+
+//the shards assigned to the addresses of a table, the other addresses fall back to
+//the modulo policy
+pub struct TablePolicy {
+    table: HashMap<Address, usize>,
+}
+
+impl ShardPolicy for ModuloPolicy {
+    fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize {
+        addr.get_shard_id(shard_num)
+    }
+}
+
+impl ShardPolicy for ConsistentHashPolicy {
+    fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize {
+        let hash: [u8; 32] = addr.hash().into();
+        let mut key = u64::from_be_bytes(hash[..8].try_into().unwrap());
+        //Lamping and Veach, "A Fast, Minimal Memory, Consistent Hash Algorithm"
+        let mut b: i64 = -1;
+        let mut j: i64 = 0;
+        while j < shard_num as i64 {
+            b = j;
+            key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+            j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+        }
+        b.max(0) as usize
+    }
+}
+
+impl PrefixRangePolicy {
+    pub fn new(mut ranges: Vec<(u32, usize)>) -> Result<Self, String> {
+        ranges.sort_by_key(|range| range.0);
+        if ranges.first().map(|range| range.0) != Some(0) {
+            return Err(String::from("the first prefix range must start at 0"));
+        }
+        if ranges.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(String::from("two prefix ranges start at the same prefix"));
+        }
+        Ok(PrefixRangePolicy { ranges })
+    }
+}
+
+impl ShardPolicy for PrefixRangePolicy {
+    fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize {
+        let prefix = addr.get_prefix();
+        //the last range starting at or before the prefix
+        let index = self.ranges.partition_point(|range| range.0 <= prefix) - 1;
+        self.ranges[index].1 % shard_num
+    }
+}
+
+impl TablePolicy {
+    pub fn new(table: HashMap<Address, usize>) -> Self {
+        TablePolicy { table }
+    }
+
+    /// Load a table of encoded addresses and their shards from a json file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("fail to read {}: {}", path, e))?;
+        let entries: HashMap<String, usize> = serde_json::from_str(&content)
+            .map_err(|e| format!("fail to parse {}: {}", path, e))?;
+        let mut table: HashMap<Address, usize> = HashMap::new();
+        for (addr, shard_id) in entries {
+            table.insert(addr.parse::<Address>()?, shard_id);
+        }
+        Ok(TablePolicy { table })
+    }
+
+    /// Save the table in the format read by load
+    pub fn save(table: &HashMap<Address, usize>, path: &str) -> Result<(), String> {
+        let entries: HashMap<String, usize> = table
+            .iter()
+            .map(|(addr, shard_id)| (addr.encode(), *shard_id))
+            .collect();
+        let content = serde_json::to_string_pretty(&entries).unwrap();
+        fs::write(path, content)
+            .map_err(|e| format!("fail to write {}: {}", path, e))
+    }
+
+    /// A locality-aware assignment: the addresses trading the most with each other are
+    /// merged into clusters of bounded size, then the clusters are spread over the
+    /// shards, the largest first into the emptiest shard
+    pub fn cluster(trades: &[(Address, Address, u64)], shard_num: usize) -> HashMap<Address, usize> {
+        //the volume between every pair of addresses, in both directions
+        let mut volumes: HashMap<(Address, Address), u64> = HashMap::new();
+        let mut addrs: HashSet<Address> = HashSet::new();
+        for (sender, receiver, count) in trades.iter() {
+            addrs.insert(*sender);
+            addrs.insert(*receiver);
+            if sender == receiver {
+                continue;
+            }
+            let pair = match sender < receiver {
+                true => (*sender, *receiver),
+                false => (*receiver, *sender),
+            };
+            let volume = volumes.entry(pair).or_insert(0);
+            *volume = volume.saturating_add(*count);
+        }
+        let mut addrs: Vec<Address> = addrs.into_iter().collect();
+        addrs.sort();
+        let index: HashMap<Address, usize> = addrs
+            .iter()
+            .enumerate()
+            .map(|(i, addr)| (*addr, i))
+            .collect();
+        let capacity = ((addrs.len() as f64 / shard_num as f64) * TABLE_SLACK).ceil().max(1.0) as usize;
+
+        //merge the clusters along the heaviest pairs first
+        let mut edges: Vec<((Address, Address), u64)> = volumes.into_iter().collect();
+        edges.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut parents: Vec<usize> = (0..addrs.len()).collect();
+        let mut sizes: Vec<usize> = vec![1; addrs.len()];
+        fn find(parents: &mut Vec<usize>, mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for ((a, b), _) in edges.iter() {
+            let root_a = find(&mut parents, index[a]);
+            let root_b = find(&mut parents, index[b]);
+            if root_a == root_b || sizes[root_a] + sizes[root_b] > capacity {
+                continue;
+            }
+            let (root, child) = match root_a < root_b {
+                true => (root_a, root_b),
+                false => (root_b, root_a),
+            };
+            parents[child] = root;
+            sizes[root] += sizes[child];
+        }
+
+        let mut clusters: HashMap<usize, Vec<Address>> = HashMap::new();
+        for (i, addr) in addrs.iter().enumerate() {
+            let root = find(&mut parents, i);
+            clusters.entry(root).or_insert(vec![]).push(*addr);
+        }
+        let mut clusters: Vec<Vec<Address>> = clusters.into_values().collect();
+        clusters.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+        let mut loads: Vec<usize> = vec![0; shard_num];
+        let mut table: HashMap<Address, usize> = HashMap::new();
+        for cluster in clusters {
+            let shard_id = (0..shard_num).min_by_key(|shard_id| loads[*shard_id]).unwrap();
+            loads[shard_id] += cluster.len();
+            for addr in cluster {
+                table.insert(addr, shard_id);
+            }
+        }
+        table
+    }
+}
+
+impl ShardPolicy for TablePolicy {
+    fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize {
+        match self.table.get(addr) {
+            Some(shard_id) => *shard_id % shard_num,
+            None => addr.get_shard_id(shard_num),
+        }
+    }
+}
+
+//the policy of the configuration, with the spec it is built from
+#[derive(Clone)]
+pub struct ShardMap {
+    spec: String,
+    policy: Arc<dyn ShardPolicy>,
+}
+
+impl Default for ShardMap {
+    fn default() -> Self {
+        ShardMap {
+            spec: String::from("modulo"),
+            policy: Arc::new(ModuloPolicy),
+        }
+    }
+}
+
+impl std::fmt::Debug for ShardMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ShardMap({})", self.spec)
+    }
+}
+
+impl ShardMap {
+    /// Build the policy of a spec: `modulo`, `consistent`, `ranges:PREFIX=SHARD,...`
+    /// with hex prefixes of up to 4 bytes, or `table:PATH`
+    pub fn from_spec(spec: &str, shard_num: usize) -> Result<Self, String> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec, None),
+        };
+        let policy: Arc<dyn ShardPolicy> = match (name, arg) {
+            ("modulo", None) => Arc::new(ModuloPolicy),
+            ("consistent", None) => Arc::new(ConsistentHashPolicy),
+            ("ranges", Some(arg)) => {
+                let mut ranges: Vec<(u32, usize)> = vec![];
+                for range in arg.split(',') {
+                    let (prefix, shard_id) = range
+                        .split_once('=')
+                        .ok_or(format!("error parsing prefix range {}", range))?;
+                    if prefix.is_empty() || prefix.len() > 8 {
+                        return Err(format!("error parsing prefix {}", prefix));
+                    }
+                    //the prefix is left-aligned on the first 4 bytes
+                    let value = u32::from_str_radix(prefix, 16)
+                        .map_err(|e| format!("error parsing prefix {}: {}", prefix, e))?;
+                    let value = value << (4 * (8 - prefix.len()) as u32);
+                    let shard_id = shard_id
+                        .parse::<usize>()
+                        .map_err(|e| format!("error parsing shard {}: {}", shard_id, e))?;
+                    if shard_id >= shard_num {
+                        return Err(format!("prefix range {} is in no shard", range));
+                    }
+                    ranges.push((value, shard_id));
+                }
+                //the keys of every shard are drawn until they land in it
+                if let Some(shard_id) = (0..shard_num).find(|i| ranges.iter().all(|range| range.1 != *i)) {
+                    return Err(format!("shard {} has no prefix range", shard_id));
+                }
+                Arc::new(PrefixRangePolicy::new(ranges)?)
+            }
+            ("table", Some(path)) => {
+                let policy = TablePolicy::load(path)?;
+                if let Some(shard_id) = policy.table.values().find(|shard_id| **shard_id >= shard_num) {
+                    return Err(format!("shard {} of the table is out of range", shard_id));
+                }
+                Arc::new(policy)
+            }
+            _ => return Err(format!("unknown shard policy {}", spec)),
+        };
+        Ok(ShardMap {
+            spec: spec.to_string(),
+            policy,
+        })
+    }
+
+    pub fn get_spec(&self) -> &str {
+        &self.spec
+    }
+
+    pub fn get_shard_id(&self, addr: &Address, shard_num: usize) -> usize {
+        self.policy.get_shard_id(addr, shard_num)
+    }
+}
+
+//the domestic and cross-shard txs under a policy
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ShardLoad {
+    pub domestic: u64,
+    pub cross: u64,
+    pub shard_txs: Vec<u64>, //the txs each shard takes part in
+    pub domestic_ratio: f64,
+}
+
+impl ShardLoad {
+    fn add(&mut self, shards: &HashSet<usize>, count: u64) {
+        match shards.len() {
+            0 => return,
+            1 => self.domestic += count,
+            _ => self.cross += count,
+        }
+        for shard_id in shards.iter() {
+            self.shard_txs[*shard_id] += count;
+        }
+        let total = self.domestic + self.cross;
+        self.domestic_ratio = self.domestic as f64 / total as f64;
+    }
+
+    /// The load of trades between pairs of addresses, with their counts
+    pub fn from_trades(trades: &[(Address, Address, u64)], config: &Configuration) -> Self {
+        let mut load = ShardLoad {
+            shard_txs: vec![0; config.shard_num],
+            ..Default::default()
+        };
+        for (sender, receiver, count) in trades.iter() {
+            let shards: HashSet<usize> = [sender, receiver]
+                .iter()
+                .map(|addr| Validator::get_shard_id(addr, config))
+                .collect();
+            load.add(&shards, *count);
+        }
+        load
+    }
+
+    /// The load of the domestic and input txs, a cross-tx is counted once in the
+    /// shard of its inputs
+    pub fn from_txs(txs: &[Transaction], config: &Configuration) -> Self {
+        let mut load = ShardLoad {
+            shard_txs: vec![0; config.shard_num],
+            ..Default::default()
+        };
+        for tx in txs.iter() {
+            if tx.flag != TxFlag::Domestic && tx.flag != TxFlag::Input {
+                continue;
+            }
+            let shards: HashSet<usize> = tx.inputs
+                .iter()
+                .map(|input| Validator::get_shard_id(&input.sender_addr, config))
+                .chain(tx.outputs
+                    .iter()
+                    .map(|output| Validator::get_shard_id(&output.receiver_addr, config)))
+                .collect();
+            load.add(&shards, 1);
+        }
+        load
+    }
+
+    /// Read trades from a json file of [sender, receiver, count] with encoded addresses
+    pub fn load_trades(path: &str) -> Result<Vec<(Address, Address, u64)>, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("fail to read {}: {}", path, e))?;
+        let entries: Vec<(String, String, u64)> = serde_json::from_str(&content)
+            .map_err(|e| format!("fail to parse {}: {}", path, e))?;
+        entries
+            .into_iter()
+            .map(|(sender, receiver, count)| Ok((sender.parse()?, receiver.parse()?, count)))
+            .collect()
+    }
+}
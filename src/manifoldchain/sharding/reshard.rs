@@ -0,0 +1,420 @@
+use crate::{
+    types::{
+        address::Address,
+        hash::{H256, Hashable},
+    },
+    manifoldchain::{
+        block::{Info, versa_block::VersaBlock},
+        blockchain::{Blockchain, State},
+        configuration::Configuration,
+        multichain::Multichain,
+        sharding::ShardMap,
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+};
+use serde::{Serialize, Deserialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+//the end of an epoch: the shards stop at the boundary height and the network
+//restarts with shard_num shards from the checkpoints of their states. No input-tx
+//is included in the last drain blocks, so that the cross-shard txs settle before
+//the boundary
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReshardPlan {
+    pub height: usize, //the boundary height, the last height of the epoch
+    pub shard_num: usize, //the number of shards of the next epoch
+    pub shard_policy: String, //the spec of the shard map of the next epoch
+    pub drain: usize, //the number of blocks without input-txs before the boundary
+}
+
+impl ReshardPlan {
+    /// Parse a plan of the form HEIGHT:SHARDS[:POLICY], the policy defaults to the
+    /// current one
+    pub fn from_spec(spec: &str, config: &Configuration) -> Result<Self, String> {
+        let mut parts = spec.splitn(3, ':');
+        let height = parts
+            .next()
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|e| format!("error parsing the boundary height of {}: {}", spec, e))?;
+        let shard_num = parts
+            .next()
+            .ok_or(format!("no number of shards in {}", spec))?
+            .parse::<usize>()
+            .map_err(|e| format!("error parsing the number of shards of {}: {}", spec, e))?;
+        let shard_policy = parts
+            .next()
+            .unwrap_or(config.shard_map.get_spec())
+            .to_string();
+        if shard_num == 0 {
+            return Err(String::from("the next epoch has no shard"));
+        }
+        ShardMap::from_spec(&shard_policy, shard_num)?;
+        //the cross-shard txs are confirmed k blocks deep in both shards, then the
+        //accept-tx or reject-tx is confirmed in the input shard
+        let drain = 3 * config.k;
+        if height <= drain {
+            return Err(format!("the boundary height {} leaves no room for a drain of {} blocks",
+                height, drain));
+        }
+        Ok(ReshardPlan {
+            height,
+            shard_num,
+            shard_policy,
+            drain,
+        })
+    }
+
+    /// Whether the transaction may be included in a block at the height
+    pub fn allows(&self, tx: &Transaction, height: usize) -> bool {
+        match tx.flag {
+            TxFlag::Empty => true,
+            TxFlag::Input => height + self.drain <= self.height,
+            _ => height <= self.height,
+        }
+    }
+
+    /// The configuration of the next epoch
+    pub fn next_config(&self, config: &Configuration) -> Result<Configuration, String> {
+        let mut next = config.clone();
+        next.shard_map = ShardMap::from_spec(&self.shard_policy, self.shard_num)?;
+        next.shard_num = self.shard_num;
+        next.reshard = None;
+        Ok(next)
+    }
+
+    /// The shard of a node in the next epoch: the nodes are dealt round-robin over
+    /// the new shards, in the order of their index within their old shard
+    pub fn reassign(shard_id: usize, node_index: usize, prev_shard_num: usize, shard_num: usize) -> usize {
+        (node_index * prev_shard_num + shard_id) % shard_num
+    }
+}
+
+//the state of a shard at the boundary of the epoch, served by a node of the shard
+//to build the checkpoints of the next epoch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateHandoff {
+    pub shard_id: usize,
+    pub shard_num: usize,
+    pub shard_policy: String,
+    pub plan: ReshardPlan,
+    pub boundary: H256, //the block of the longest chain at the boundary height
+    pub header: VersaBlock, //the block of the shard committing the state at the boundary
+    pub utxos: Vec<((H256, u32), Transaction)>,
+    pub pending: Vec<Transaction>, //the input-txs without an accept-tx or reject-tx
+    pub delivered: Vec<H256>, //the cross-shard txs whose output-tx is in the shard
+}
+
+impl StateHandoff {
+    /// The handoff of the own shard, once the boundary is confirmed
+    pub fn from_multichain(multichain: &Multichain) -> Result<Self, String> {
+        let config = &multichain.config;
+        let plan = config.reshard
+            .clone()
+            .ok_or(String::from("no resharding is planned"))?;
+        let path = multichain.all_blocks_in_longest_chain();
+        if path.len() <= plan.height + config.k {
+            return Err(format!("the boundary height {} is not confirmed, the chain is at {}",
+                plan.height, path.len() - 1));
+        }
+        let boundary = path[plan.height].clone();
+        let header = multichain
+            .get_state_block_with_shard(&boundary, config.shard_id())
+            .ok_or(format!("no state block for {}", boundary))?;
+        let state = multichain
+            .get_state(&header.hash())
+            .ok_or(format!("state of block {} not found", header.hash()))?;
+        let mut utxos: Vec<((H256, u32), Transaction)> = state
+            .into_iter()
+            .map(|(key, (tx, _))| (key, tx))
+            .collect();
+        utxos.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut inputs: Vec<Transaction> = vec![];
+        let mut settled: HashSet<H256> = HashSet::new();
+        let mut delivered: Vec<H256> = vec![];
+        for blk_hash in path[..=plan.height].iter() {
+            let block = multichain
+                .get_block(blk_hash)
+                .ok_or(format!("block {} not found", blk_hash))?;
+            let txs = match block.get_txs_ref() {
+                Some(txs) => txs,
+                None => continue,
+            };
+            for tx in txs.iter() {
+                let cross_id = tx.get_related_hash(TxFlag::Input);
+                match tx.flag {
+                    TxFlag::Input => inputs.push(tx.clone()),
+                    TxFlag::Accept | TxFlag::Reject => {
+                        settled.insert(cross_id);
+                    }
+                    TxFlag::Output => delivered.push(cross_id),
+                    _ => {}
+                }
+            }
+        }
+        let pending: Vec<Transaction> = inputs
+            .into_iter()
+            .filter(|tx| !settled.contains(&tx.get_related_hash(TxFlag::Input)))
+            .collect();
+        Ok(StateHandoff {
+            shard_id: config.shard_id(),
+            shard_num: config.shard_num,
+            shard_policy: config.shard_map.get_spec().to_string(),
+            plan,
+            boundary,
+            header: header.to_header(),
+            utxos,
+            pending,
+            delivered,
+        })
+    }
+
+    /// The utxos as a state, the testimonies are left behind with the old epoch
+    pub fn get_state(&self) -> State {
+        self.utxos
+            .iter()
+            .map(|(key, tx)| (key.clone(), (tx.clone(), None)))
+            .collect()
+    }
+
+    /// Check the utxos against the state root committed by the header
+    pub fn verify(&self) -> Result<(), String> {
+        if !self.header.verify_hash() {
+            return Err(format!("the header of shard {} is malformed", self.shard_id));
+        }
+        if self.header.get_shard_id() != self.shard_id {
+            return Err(format!("the header of shard {} belongs to shard {}",
+                self.shard_id, self.header.get_shard_id()));
+        }
+        let root = Blockchain::get_state_tree(&self.get_state()).root();
+        if root != self.header.get_state_root() {
+            return Err(format!("the utxos of shard {} do not match the state root {}",
+                self.shard_id, self.header.get_state_root()));
+        }
+        Ok(())
+    }
+
+    /// Fetch the handoff of a node from its API server
+    pub fn fetch(api_addr: &str) -> Result<StateHandoff, String> {
+        let url = format!("http://{}/reshard/handoff", api_addr);
+        reqwest::blocking::get(&url)
+            .and_then(|resp| resp.json::<StateHandoff>())
+            .map_err(|e| format!("error fetching {}: {}", url, e))
+    }
+}
+
+//the state a shard of the next epoch starts from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub shard_id: usize,
+    pub shard_num: usize,
+    pub shard_policy: String,
+    pub prev_shard_num: usize,
+    pub sources: Vec<H256>, //the state blocks of the old shards at the boundary
+    pub state_root: H256,
+    pub utxos: Vec<((H256, u32), Transaction)>,
+}
+
+impl Checkpoint {
+    /// Re-map the utxos of the handoffs of every old shard to the new shards
+    pub fn build(handoffs: &[StateHandoff]) -> Result<Vec<Checkpoint>, String> {
+        let first = handoffs
+            .first()
+            .ok_or(String::from("no handoff"))?;
+        let mut by_shard: HashMap<usize, &StateHandoff> = HashMap::new();
+        for handoff in handoffs.iter() {
+            if handoff.shard_num != first.shard_num
+                || handoff.shard_policy != first.shard_policy
+                || handoff.plan != first.plan
+            {
+                return Err(format!("the handoff of shard {} follows another epoch", handoff.shard_id));
+            }
+            if handoff.shard_id >= handoff.shard_num {
+                return Err(format!("no shard {} in the epoch", handoff.shard_id));
+            }
+            handoff.verify()?;
+            match by_shard.get(&handoff.shard_id) {
+                Some(other) if other.header.hash() != handoff.header.hash() => {
+                    return Err(format!("two handoffs of shard {} disagree", handoff.shard_id));
+                }
+                Some(_) => {}
+                None => {
+                    by_shard.insert(handoff.shard_id, handoff);
+                }
+            }
+        }
+        if let Some(shard_id) = (0..first.shard_num).find(|i| !by_shard.contains_key(i)) {
+            return Err(format!("no handoff of shard {}", shard_id));
+        }
+
+        let mut prev_config = Configuration::new();
+        prev_config.shard_num = first.shard_num;
+        prev_config.shard_map = ShardMap::from_spec(&first.shard_policy, first.shard_num)?;
+        let config = first.plan.next_config(&prev_config)?;
+
+        //the coins of an unsettled input-tx are either locked in its input shard or
+        //already created by the output-txs, otherwise they would be lost
+        let delivered: HashMap<usize, HashSet<&H256>> = by_shard
+            .iter()
+            .map(|(shard_id, handoff)| (*shard_id, handoff.delivered.iter().collect()))
+            .collect();
+        for handoff in by_shard.values() {
+            for tx in handoff.pending.iter() {
+                let cross_id = tx.get_related_hash(TxFlag::Input);
+                for output in tx.outputs.iter() {
+                    let shard_id = Validator::get_shard_id(&output.receiver_addr, &prev_config);
+                    if !delivered.get(&shard_id).map_or(false, |ids| ids.contains(&cross_id)) {
+                        return Err(format!("cross-shard tx {} is still in flight to shard {}",
+                            cross_id, shard_id));
+                    }
+                }
+            }
+        }
+
+        let mut utxos: Vec<Vec<((H256, u32), Transaction)>> = vec![vec![]; config.shard_num];
+        let mut sources: Vec<H256> = vec![];
+        for shard_id in 0..first.shard_num {
+            let handoff = by_shard[&shard_id];
+            sources.push(handoff.header.hash());
+            for (key, tx) in handoff.utxos.iter() {
+                let owner = Self::get_owner(key, tx)
+                    .ok_or(format!("utxo {:?} of shard {} has no owner", key, shard_id))?;
+                let new_shard = Validator::get_shard_id(&owner, &config);
+                utxos[new_shard].push((key.clone(), tx.clone()));
+            }
+        }
+        Ok(utxos
+            .into_iter()
+            .enumerate()
+            .map(|(shard_id, mut utxos)| {
+                utxos.sort_by(|a, b| a.0.cmp(&b.0));
+                let mut checkpoint = Checkpoint {
+                    shard_id,
+                    shard_num: config.shard_num,
+                    shard_policy: config.shard_map.get_spec().to_string(),
+                    prev_shard_num: first.shard_num,
+                    sources: sources.clone(),
+                    state_root: H256::default(),
+                    utxos,
+                };
+                checkpoint.state_root = Blockchain::get_state_tree(&checkpoint.get_state()).root();
+                checkpoint
+            })
+            .collect())
+    }
+
+    //the address owning the utxo, a reject-tx returns the utxos of its inputs
+    fn get_owner(key: &(H256, u32), tx: &Transaction) -> Option<Address> {
+        let index = key.1 as usize;
+        match tx.flag {
+            TxFlag::Reject => tx.inputs.get(index).map(|input| input.sender_addr),
+            _ => tx.outputs.get(index).map(|output| output.receiver_addr),
+        }
+    }
+
+    pub fn get_state(&self) -> State {
+        self.utxos
+            .iter()
+            .map(|(key, tx)| (key.clone(), (tx.clone(), None)))
+            .collect()
+    }
+
+    /// Check the state root and that every utxo belongs to the shard
+    pub fn verify(&self) -> Result<(), String> {
+        let mut config = Configuration::new();
+        config.shard_num = self.shard_num;
+        config.shard_map = ShardMap::from_spec(&self.shard_policy, self.shard_num)?;
+        for (key, tx) in self.utxos.iter() {
+            let owner = Self::get_owner(key, tx)
+                .ok_or(format!("utxo {:?} has no owner", key))?;
+            if Validator::get_shard_id(&owner, &config) != self.shard_id {
+                return Err(format!("utxo {:?} does not belong to shard {}", key, self.shard_id));
+            }
+        }
+        if Blockchain::get_state_tree(&self.get_state()).root() != self.state_root {
+            return Err(format!("the utxos of checkpoint {} do not match its state root", self.shard_id));
+        }
+        Ok(())
+    }
+
+    /// Save the checkpoints as checkpoint_<shard>.json in the directory
+    pub fn save(checkpoints: &[Checkpoint], dir: &str) -> Result<(), String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("fail to create {}: {}", dir, e))?;
+        for checkpoint in checkpoints.iter() {
+            let path = format!("{}/checkpoint_{}.json", dir, checkpoint.shard_id);
+            let content = serde_json::to_string(checkpoint).unwrap();
+            fs::write(&path, content)
+                .map_err(|e| format!("fail to write {}: {}", path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Load the checkpoints saved in the directory, ordered by shard
+    pub fn load_all(dir: &str) -> Result<Vec<Checkpoint>, String> {
+        let mut checkpoints: Vec<Checkpoint> = vec![];
+        let mut shard_id = 0;
+        loop {
+            let path = format!("{}/checkpoint_{}.json", dir, shard_id);
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => break,
+            };
+            let checkpoint: Checkpoint = serde_json::from_str(&content)
+                .map_err(|e| format!("fail to parse {}: {}", path, e))?;
+            checkpoints.push(checkpoint);
+            shard_id += 1;
+        }
+        if checkpoints.is_empty() {
+            return Err(format!("no checkpoint in {}", dir));
+        }
+        Ok(checkpoints)
+    }
+
+    /// Move the configuration of a node of the old epoch to the next one, returning
+    /// the state its new shard starts from
+    pub fn apply(checkpoints: &[Checkpoint], config: &mut Configuration) -> Result<State, String> {
+        let first = checkpoints
+            .first()
+            .ok_or(String::from("no checkpoint"))?;
+        if first.prev_shard_num != config.shard_num {
+            return Err(format!("the checkpoints follow {} shards, the node has {}",
+                first.prev_shard_num, config.shard_num));
+        }
+        if checkpoints.len() != first.shard_num {
+            return Err(format!("{} checkpoints for {} shards", checkpoints.len(), first.shard_num));
+        }
+        for (shard_id, checkpoint) in checkpoints.iter().enumerate() {
+            if checkpoint.shard_id != shard_id
+                || checkpoint.shard_num != first.shard_num
+                || checkpoint.shard_policy != first.shard_policy
+                || checkpoint.sources != first.sources
+            {
+                return Err(format!("checkpoint {} belongs to another epoch", shard_id));
+            }
+            checkpoint.verify()?;
+        }
+        let node_index = match config.shard_size {
+            0 => config.node_id,
+            shard_size => config.node_id % shard_size,
+        };
+        config.set_shard_id(ReshardPlan::reassign(config.shard_id(), node_index, first.prev_shard_num, first.shard_num));
+        config.shard_num = first.shard_num;
+        //the difficulties of the old shards do not carry over to the new ones
+        if config.shard_diffs.len() != config.shard_num {
+            config.shard_diffs.clear();
+        }
+        config.shard_map = ShardMap::from_spec(&first.shard_policy, first.shard_num)?;
+        config.genesis_roots = checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.state_root.clone())
+            .collect();
+        config.reshard = None;
+        Ok(checkpoints[config.shard_id()].get_state())
+    }
+}
@@ -0,0 +1,456 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        block::{Info, versa_block::VersaBlock},
+        blockchain::{Blockchain, State},
+        configuration::Configuration,
+        mempool::Mempool,
+        multichain::Multichain,
+        network::{
+            message::Message,
+            request::RequestTracker,
+            server::Handle as ServerHandle,
+        },
+        testimony::Testimony,
+        transaction::Transaction,
+    },
+};
+use log::{debug, info, warn};
+use serde::{Serialize, Deserialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{self, SystemTime},
+};
+
+//the interval between two checks of the epoch
+const ROTATION_INTERVAL: u64 = 500; //ms
+
+//a snapshot not served within the time is asked from another peer of the shard
+const SNAPSHOT_RETRY: u64 = 3000; //ms
+
+//the number of switches kept with their costs
+const MAX_SWITCH_HISTORY: usize = 100;
+
+//the shard a node mines on. Every component of the node reads it from its own copy
+//of the configuration, the copies share the role so that a rotation moves them all
+#[derive(Clone, Default)]
+pub struct ShardRole {
+    inner: Arc<RoleState>,
+}
+
+#[derive(Default)]
+struct RoleState {
+    shard_id: AtomicUsize,
+    syncing: AtomicBool, //the state of the shard is not synced yet
+}
+
+impl ShardRole {
+    pub fn new(shard_id: usize) -> Self {
+        let role = Self::default();
+        role.set_shard_id(shard_id);
+        role
+    }
+
+    pub fn get_shard_id(&self) -> usize {
+        self.inner.shard_id.load(Ordering::SeqCst)
+    }
+
+    pub fn set_shard_id(&self, shard_id: usize) {
+        self.inner.shard_id.store(shard_id, Ordering::SeqCst);
+    }
+
+    /// Whether the node is still syncing the state of its shard, it does not mine
+    /// meanwhile
+    pub fn is_syncing(&self) -> bool {
+        self.inner.syncing.load(Ordering::SeqCst)
+    }
+
+    /// Move the node to the shard, it mines again once the state is synced
+    pub fn begin_switch(&self, shard_id: usize) {
+        self.inner.syncing.store(true, Ordering::SeqCst);
+        self.set_shard_id(shard_id);
+    }
+
+    pub fn end_switch(&self) {
+        self.inner.syncing.store(false, Ordering::SeqCst);
+    }
+}
+
+impl fmt::Debug for ShardRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShardRole({})", self.get_shard_id())
+    }
+}
+
+//the utxos of a shard after one of its blocks, served to the nodes rotating into it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateSnapshot {
+    pub shard_id: usize,
+    pub block_hash: H256,
+    pub utxos: Vec<((H256, u32), (Transaction, Option<Testimony>))>,
+}
+
+impl StateSnapshot {
+    pub fn new(shard_id: usize, block_hash: H256, state: State) -> Self {
+        let mut utxos: Vec<((H256, u32), (Transaction, Option<Testimony>))> = state
+            .into_iter()
+            .collect();
+        utxos.sort_by(|a, b| a.0.cmp(&b.0));
+        StateSnapshot {
+            shard_id,
+            block_hash,
+            utxos,
+        }
+    }
+
+    pub fn get_state(&self) -> State {
+        self.utxos.iter().cloned().collect()
+    }
+
+    /// Check the utxos against the state root of the block, which must be a block
+    /// of the shard
+    pub fn verify(&self, block: &VersaBlock) -> Result<(), String> {
+        if block.hash() != self.block_hash || block.get_shard_id() != self.shard_id {
+            return Err(format!("block {} is not the block of shard {} of the snapshot",
+                block.hash(), self.shard_id));
+        }
+        let root = Blockchain::get_state_tree(&self.get_state()).root();
+        if root != block.get_state_root() {
+            return Err(format!("the utxos of the snapshot do not match the state root {}",
+                block.get_state_root()));
+        }
+        Ok(())
+    }
+}
+
+//what moving to a new shard took: the synced state and the dropped mempool
+#[derive(Serialize, Debug, Clone)]
+pub struct SwitchCost {
+    pub epoch: usize,
+    pub from: usize,
+    pub to: usize,
+    pub utxos: usize,
+    pub bytes: usize, //the size of the snapshot on the wire
+    pub sync_ms: u64, //from the start of the epoch to the imported snapshot
+    pub requests: usize, //the snapshot requests sent, including the retries
+    pub dropped_txs: usize, //the mempool txs of the old shard
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RotationStatus {
+    pub epoch: usize,
+    pub epoch_len: usize,
+    pub shard_id: usize,
+    pub syncing: bool,
+    pub switches: u64,
+    pub stays: u64, //the epochs which kept the node in its shard
+    pub avg_sync_ms: u64,
+    pub recent: Vec<SwitchCost>,
+}
+
+struct PendingSwitch {
+    epoch: usize,
+    from: usize,
+    to: usize,
+    started_at: SystemTime,
+    block_hash: Option<H256>, //the block whose snapshot is requested
+    requested_at: SystemTime,
+    requests: usize,
+    dropped_txs: usize,
+}
+
+#[derive(Default)]
+struct RotationState {
+    epoch: usize,
+    pending: Option<PendingSwitch>,
+    switches: u64,
+    stays: u64,
+    total_sync_ms: u64,
+    history: VecDeque<SwitchCost>,
+}
+
+//Every epoch_len confirmed blocks, the beacon of the epoch reassigns the miners to
+//the shards. A node moving to a new shard stops mining, drops its mempool, pulls
+//the state of the new shard from its peers there and mines again once imported.
+#[derive(Clone)]
+pub struct Rotation {
+    inner: Arc<Mutex<RotationState>>,
+    multichain: Multichain,
+    mempool: Arc<Mutex<Mempool>>,
+    server: ServerHandle,
+    requests: RequestTracker,
+    config: Configuration,
+}
+
+//a counter as a hash input
+fn counter_hash(counter: usize) -> H256 {
+    let mut bytes = [0u8; 32];
+    bytes[24..32].copy_from_slice(&(counter as u64).to_be_bytes());
+    bytes.into()
+}
+
+impl Rotation {
+    pub fn new(
+        multichain: &Multichain,
+        mempool: &Arc<Mutex<Mempool>>,
+        server: &ServerHandle,
+        requests: &RequestTracker,
+        config: &Configuration,
+    ) -> Self {
+        Rotation {
+            inner: Arc::new(Mutex::new(RotationState::default())),
+            multichain: multichain.clone(),
+            mempool: Arc::clone(mempool),
+            server: server.clone(),
+            requests: requests.clone(),
+            config: config.clone(),
+        }
+    }
+
+    /// The last epoch whose first height is confirmed in every shard, the epoch 0
+    /// keeps the shards given at start
+    pub fn get_epoch(multichain: &Multichain, config: &Configuration) -> usize {
+        if config.rotation_epoch == 0 {
+            return 0;
+        }
+        (0..config.shard_num)
+            .map(|shard_id| {
+                let len = multichain.all_blocks_in_longest_chain_with_shard(shard_id).len();
+                len.saturating_sub(1 + config.k) / config.rotation_epoch
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// The randomness of the epoch: the epoch number and, for every shard, the last
+    /// inclusive block at or below the first height of the epoch. None until the
+    /// height is confirmed in every shard
+    pub fn get_beacon(multichain: &Multichain, config: &Configuration, epoch: usize) -> Option<H256> {
+        let height = epoch * config.rotation_epoch;
+        let mut hashes: Vec<H256> = vec![counter_hash(epoch)];
+        for shard_id in 0..config.shard_num {
+            let path = multichain.all_blocks_in_longest_chain_with_shard(shard_id);
+            if path.len() <= height + config.k {
+                return None;
+            }
+            let last_inclusive = path[..=height]
+                .iter()
+                .rev()
+                .find(|hash| match multichain.get_block_by_shard(hash, shard_id) {
+                    Some(VersaBlock::InBlock(_)) | Some(VersaBlock::InFullBlock(_)) => true,
+                    _ => false,
+                })
+                .unwrap_or(&path[0]);
+            hashes.push(last_inclusive.clone());
+        }
+        Some(H256::multi_hash(&hashes))
+    }
+
+    /// The shard of every node id: the ids ordered by their hash with the beacon are
+    /// dealt over the shards, so that every shard keeps the same number of nodes
+    pub fn assign(beacon: &H256, node_num: usize, shard_num: usize) -> Vec<usize> {
+        let mut order: Vec<(H256, usize)> = (0..node_num)
+            .map(|node_id| (H256::chash(beacon, &counter_hash(node_id)), node_id))
+            .collect();
+        order.sort();
+        let mut shards: Vec<usize> = vec![0; node_num];
+        for (pos, (_, node_id)) in order.into_iter().enumerate() {
+            shards[node_id] = pos % shard_num;
+        }
+        shards
+    }
+
+    /// Whether the schedule moves the node to the shard in an epoch around the one the
+    /// chains are at, the views of the node and of its peers may be an epoch apart
+    pub fn is_scheduled(
+        multichain: &Multichain,
+        config: &Configuration,
+        node_id: usize,
+        shard_id: usize
+    ) -> bool {
+        if config.rotation_epoch == 0 {
+            return false;
+        }
+        let node_num = config.shard_num * config.shard_size;
+        let epoch = Self::get_epoch(multichain, config);
+        (epoch.saturating_sub(1).max(1)..=epoch + 1).any(|epoch| {
+            match Self::get_beacon(multichain, config, epoch) {
+                Some(beacon) => Self::assign(&beacon, node_num, config.shard_num)
+                    .get(node_id) == Some(&shard_id),
+                None => false,
+            }
+        })
+    }
+
+    pub fn start(&self) {
+        let rotation = self.clone();
+        thread::Builder::new()
+            .name("Shard-Rotation".to_string())
+            .spawn(move || loop {
+                rotation.next_epoch();
+                rotation.request_snapshot();
+                thread::sleep(time::Duration::from_millis(ROTATION_INTERVAL));
+            })
+            .unwrap();
+        info!("Shard rotation started, {} blocks per epoch", self.config.rotation_epoch);
+    }
+
+    //move to the shard assigned by the beacon once a new epoch starts
+    fn next_epoch(&self) {
+        let epoch = Self::get_epoch(&self.multichain, &self.config);
+        {
+            let inner = self.inner.lock().unwrap();
+            if epoch <= inner.epoch {
+                return;
+            }
+            //the epoch is taken once the node is synced in the shard it moves to
+            if let Some(pending) = inner.pending.as_ref() {
+                debug!("Epoch {}: node still syncing shard {}", epoch, pending.to);
+                return;
+            }
+        }
+        let beacon = match Self::get_beacon(&self.multichain, &self.config, epoch) {
+            Some(beacon) => beacon,
+            None => return,
+        };
+        let node_num = self.config.shard_num * self.config.shard_size;
+        let shards = Self::assign(&beacon, node_num, self.config.shard_num);
+        let from = self.config.shard_id();
+        let to = match shards.get(self.config.node_id) {
+            Some(to) => *to,
+            None => {
+                warn!("Node {} out of the {} nodes of the rotation", self.config.node_id, node_num);
+                return;
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.epoch = epoch;
+        if to == from {
+            inner.stays += 1;
+            debug!("Epoch {}: node stays in shard {}", epoch, from);
+            return;
+        }
+        //the txs of the old shard cannot be mined in the new one
+        let dropped_txs = {
+            let mut mempool = self.mempool.lock().unwrap();
+            let tx_hashs = mempool.get_all_tx_hash();
+            let dropped_txs = tx_hashs.len();
+            mempool.delete_txs(tx_hashs);
+            dropped_txs
+        };
+        self.config.role.begin_switch(to);
+        self.server.broadcast(Message::Rotate(to as u32));
+        let now = SystemTime::now();
+        inner.pending = Some(PendingSwitch {
+            epoch,
+            from,
+            to,
+            started_at: now,
+            block_hash: None,
+            requested_at: now,
+            requests: 0,
+            dropped_txs,
+        });
+        info!("Epoch {}: node moves from shard {} to shard {}", epoch, from, to);
+    }
+
+    //ask the peers of the new shard for the state at its tip, again if the tip moved
+    //or the request is not served in time
+    fn request_snapshot(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let pending = match inner.pending.as_mut() {
+            Some(pending) => pending,
+            None => return,
+        };
+        let tip = match self.multichain
+            .all_blocks_in_longest_chain_with_shard(pending.to)
+            .last()
+        {
+            Some(tip) => tip.clone(),
+            None => return,
+        };
+        let block_hash = match self.multichain.get_state_block_with_shard(&tip, pending.to) {
+            Some(block) => block.hash(),
+            None => return,
+        };
+        let elapsed = SystemTime::now()
+            .duration_since(pending.requested_at)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        if pending.block_hash == Some(block_hash) && elapsed < SNAPSHOT_RETRY {
+            return;
+        }
+        pending.block_hash = Some(block_hash);
+        pending.requested_at = SystemTime::now();
+        pending.requests += 1;
+        self.requests.request_from_shard(
+            Message::GetSnapshot((block_hash, pending.to as u32)),
+            pending.to,
+        );
+    }
+
+    /// Import the snapshot of the shard the node is moving to, return false if no
+    /// switch waits for it, or an error if it does not match its block
+    pub fn handle_snapshot(&self, snapshot: &StateSnapshot, shard_id: usize) -> Result<bool, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let to = match inner.pending.as_ref() {
+            Some(pending) if pending.to == shard_id && snapshot.shard_id == shard_id => pending.to,
+            _ => return Ok(false),
+        };
+        let block = self.multichain
+            .get_block_by_shard(&snapshot.block_hash, to)
+            .ok_or(format!("block {} unknown in shard {}", snapshot.block_hash, to))?;
+        snapshot.verify(&block)?;
+        self.multichain.import_state_with_shard(&snapshot.block_hash, to, snapshot.get_state())?;
+        self.config.role.end_switch();
+
+        let pending = inner.pending.take().unwrap();
+        let sync_ms = SystemTime::now()
+            .duration_since(pending.started_at)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let cost = SwitchCost {
+            epoch: pending.epoch,
+            from: pending.from,
+            to: pending.to,
+            utxos: snapshot.utxos.len(),
+            bytes: bincode::serialized_size(snapshot).unwrap_or(0) as usize,
+            sync_ms,
+            requests: pending.requests,
+            dropped_txs: pending.dropped_txs,
+        };
+        info!("Node synced shard {} in {} ms: {} utxos, {} bytes, {} requests",
+            cost.to, cost.sync_ms, cost.utxos, cost.bytes, cost.requests);
+        inner.switches += 1;
+        inner.total_sync_ms += sync_ms;
+        if inner.history.len() >= MAX_SWITCH_HISTORY {
+            inner.history.pop_front();
+        }
+        inner.history.push_back(cost);
+        Ok(true)
+    }
+
+    pub fn get_status(&self) -> RotationStatus {
+        let inner = self.inner.lock().unwrap();
+        RotationStatus {
+            epoch: inner.epoch,
+            epoch_len: self.config.rotation_epoch,
+            shard_id: self.config.shard_id(),
+            syncing: self.config.role.is_syncing(),
+            switches: inner.switches,
+            stays: inner.stays,
+            avg_sync_ms: match inner.switches {
+                0 => 0,
+                switches => inner.total_sync_ms / switches,
+            },
+            recent: inner.history.iter().cloned().collect(),
+        }
+    }
+}
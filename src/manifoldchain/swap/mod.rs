@@ -0,0 +1,857 @@
+use crate::{
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+    },
+    manifoldchain::{
+        blockchain::State,
+        configuration::Configuration,
+        mempool::Mempool,
+        multichain::Multichain,
+        network::{
+            message::Message,
+            server::Handle as ServerHandle,
+        },
+        transaction::{
+            Transaction,
+            TxFlag,
+            UtxoInput,
+            UtxoOutput,
+            script::{Lock, Witness},
+        },
+        validator::Validator,
+    },
+};
+use log::{info, debug, warn};
+use rand::Rng;
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use serde::{Serialize, Deserialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time,
+};
+
+//the interval between two rounds of automatic claims and refunds
+const SWAP_UPDATE_INTERVAL: u64 = 5000; //ms
+
+//the size of a generated secret
+const SECRET_SIZE: usize = 32;
+
+//the leg of the initiator stays locked at least this many times as long as the leg of
+//the responder, so that the responder can still claim once the secret is revealed
+const TIMEOUT_MARGIN: u64 = 2;
+
+//one side of a swap: the sender locks the coins in the shard of the receiver, the
+//receiver claims them with the secret, or the sender takes them back after the timeout
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SwapLeg {
+    pub sender: Address,
+    pub sender_key: Vec<u8>,
+    pub receiver: Address,
+    pub receiver_key: Vec<u8>,
+    pub value: u64,
+    pub timeout: u64, //in blocks of the shard of the receiver
+}
+
+//an atomic swap between two users of two shards, both legs are locked under the same
+//hashlock. The initiator knows the secret and reveals it by claiming the leg of the
+//responder, which times out first so that the responder can still claim the other one
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Swap {
+    pub secret_hash: H256,
+    pub initiator: SwapLeg, //sent by the initiator to the responder
+    pub responder: SwapLeg, //sent by the responder to the initiator
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SwapRole {
+    Initiator,
+    Responder,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SwapStatus {
+    Created,
+    Funded, //the leg of the user is locked
+    Claimed, //the user claimed the leg of the counterparty
+    Refunded, //the user took its leg back
+}
+
+impl Hashable for Swap {
+    fn hash(&self) -> H256 {
+        let bytes = bincode::serialize(self).unwrap();
+        digest(&SHA256, &bytes).into()
+    }
+}
+
+impl SwapLeg {
+    pub fn get_lock(&self, secret_hash: H256) -> Lock {
+        Lock::htlc(&self.receiver_key, secret_hash, &self.sender_key, self.timeout)
+    }
+}
+
+//the flag of a tx spending coins of the input shards: it is a cross-tx if any output
+//goes to another shard
+fn get_flag(inputs: &Vec<UtxoInput>, outputs: &Vec<UtxoOutput>, config: &Configuration) -> TxFlag {
+    let input_shards: HashSet<usize> = inputs
+        .iter()
+        .map(|input| Validator::get_shard_id(&input.sender_addr, config))
+        .collect();
+    let is_cross_tx = outputs
+        .iter()
+        .any(|output| !input_shards.contains(
+            &Validator::get_shard_id(&output.receiver_addr, config)
+        ));
+    match is_cross_tx {
+        true => TxFlag::Input,
+        false => TxFlag::Domestic,
+    }
+}
+
+//the difficulty target of the shard as a number, a shard with a larger target grows
+//proportionally faster under the same hash power
+fn get_target(config: &Configuration, shard_id: usize) -> f64 {
+    config
+        .get_difficulty(shard_id)
+        .as_ref()
+        .iter()
+        .fold(0f64, |target, byte| target * 256.0 + *byte as f64)
+}
+
+/// The expected blocks the shard `to_shard` grows while `from_shard` grows the given
+/// blocks, rounded up
+pub fn scale_timeout(blocks: u64, from_shard: usize, to_shard: usize, config: &Configuration) -> u64 {
+    if from_shard == to_shard {
+        return blocks;
+    }
+    let ratio = get_target(config, to_shard) / get_target(config, from_shard);
+    (blocks as f64 * ratio).ceil() as u64
+}
+
+impl Swap {
+    pub fn new(
+        secret_hash: H256,
+        initiator: SwapLeg,
+        responder: SwapLeg,
+        config: &Configuration,
+    ) -> Result<Self, String> {
+        if initiator.sender != responder.receiver ||
+            initiator.sender_key != responder.receiver_key ||
+            initiator.receiver != responder.sender ||
+            initiator.receiver_key != responder.sender_key {
+            return Err(String::from("the legs are not between the same users"));
+        }
+        if initiator.value == 0 || responder.value == 0 {
+            return Err(String::from("a leg swaps no coins"));
+        }
+        //the responder must be able to claim after the secret is revealed. The legs
+        //time out in blocks of different shards, so they are compared in blocks of
+        //the shard of the responder's lock
+        let initiator_lock_shard = Validator::get_shard_id(&initiator.receiver, config);
+        let responder_lock_shard = Validator::get_shard_id(&responder.receiver, config);
+        let initiator_timeout = scale_timeout(
+            initiator.timeout,
+            initiator_lock_shard,
+            responder_lock_shard,
+            config
+        );
+        if initiator_timeout < responder.timeout.saturating_mul(TIMEOUT_MARGIN) {
+            return Err(format!(
+                "the leg of the initiator must last {} times as long as the leg of the responder",
+                TIMEOUT_MARGIN
+            ));
+        }
+        Ok(Swap {
+            secret_hash,
+            initiator,
+            responder,
+        })
+    }
+
+    pub fn get_leg(&self, role: SwapRole) -> &SwapLeg {
+        match role {
+            SwapRole::Initiator => &self.initiator,
+            SwapRole::Responder => &self.responder,
+        }
+    }
+
+    //the leg the user of the role claims
+    pub fn get_counter_leg(&self, role: SwapRole) -> &SwapLeg {
+        match role {
+            SwapRole::Initiator => &self.responder,
+            SwapRole::Responder => &self.initiator,
+        }
+    }
+
+    /// Find the secret among the preimages revealed by the inputs of a tx
+    pub fn find_secret(&self, tx: &Transaction) -> Option<Vec<u8>> {
+        tx.inputs
+            .iter()
+            .filter_map(|input| input.witness.as_ref())
+            .flat_map(|witness| witness.preimages.iter())
+            .find(|preimage| Lock::hash_secret(preimage) == self.secret_hash)
+            .cloned()
+    }
+
+    /// Find the utxo locking a leg in a state of the shard of its receiver
+    pub fn find_funding(&self, leg: &SwapLeg, state: &State) -> Option<(Transaction, u32)> {
+        let lock = leg.get_lock(self.secret_hash);
+        state
+            .iter()
+            .find(|(key, (tx, _))| match tx.outputs.get(key.1 as usize) {
+                Some(output) => tx.flag != TxFlag::Reject &&
+                    output.receiver_addr == leg.receiver &&
+                    output.value == leg.value &&
+                    output.lock.as_ref() == Some(&lock),
+                None => false,
+            })
+            .map(|(key, (tx, _))| (tx.clone(), key.1))
+    }
+
+    /// Lock the coins of a leg in the shard of its receiver, the rest of the utxos
+    /// goes back to the sender
+    pub fn fund_tx(
+        &self,
+        leg: &SwapLeg,
+        utxos: Vec<(&Transaction, usize)>,
+        key: &Ed25519KeyPair,
+        config: &Configuration,
+    ) -> Option<Transaction> {
+        let senders: Vec<(&Address, &Ed25519KeyPair)> = vec![(&leg.sender, key); utxos.len()];
+        let available_coins: u64 = utxos
+            .iter()
+            .try_fold(0u64, |sum, (tx, index)| match tx.flag {
+                TxFlag::Reject => sum.checked_add(tx.inputs.get(*index)?.value),
+                _ => sum.checked_add(tx.outputs.get(*index)?.value),
+            })?;
+        let left_coins = available_coins.checked_sub(leg.value)?;
+        let mut receivers: Vec<(&Address, &Ed25519KeyPair, u64)> = vec![(&leg.receiver, key, leg.value)];
+        if left_coins > 0 {
+            receivers.push((&leg.sender, key, left_coins));
+        }
+        let mut tx = Transaction::consume(utxos, senders, receivers, TxFlag::Domestic)?;
+        //the lock replaces the key of the first output
+        tx.outputs[0].public_key_ref = vec![];
+        tx.outputs[0].lock = Some(leg.get_lock(self.secret_hash));
+        tx.flag = get_flag(&tx.inputs, &tx.outputs, config);
+        Some(tx)
+    }
+
+    //spend the locked utxo of a leg, it is an output of the spent tx in the shard of
+    //the receiver. The witness signs the spending tx, so it cannot be moved to another
+    fn spend_funding(
+        leg: &SwapLeg,
+        spent: (&Transaction, u32),
+        preimages: Vec<Vec<u8>>,
+        key: &Ed25519KeyPair,
+        outputs: Vec<UtxoOutput>,
+        config: &Configuration,
+    ) -> Transaction {
+        let input = UtxoInput {
+            sender_addr: leg.receiver.clone(),
+            tx_hash: spent.0.hash(),
+            value: leg.value,
+            index: spent.1,
+            sig_ref: vec![],
+            witness: None,
+        };
+        let inputs = vec![input];
+        let flag = get_flag(&inputs, &outputs, config);
+        let mut tx = Transaction {
+            inputs,
+            outputs,
+            flag,
+        };
+        let sig = Transaction::sign(&tx.get_unsigned(), key).as_ref().to_vec();
+        tx.inputs[0].witness = Some(Witness {
+            sigs: vec![sig],
+            preimages,
+        });
+        tx
+    }
+
+    /// Claim a leg with the secret. If the sender is in another shard, the claim also
+    /// sends it nothing, so that the secret reaches its shard in an output-tx proved
+    /// by a testimony
+    pub fn claim_tx(
+        &self,
+        leg: &SwapLeg,
+        spent: (&Transaction, u32),
+        secret: &[u8],
+        key: &Ed25519KeyPair,
+        config: &Configuration,
+    ) -> Transaction {
+        let mut outputs = vec![UtxoOutput {
+            receiver_addr: leg.receiver.clone(),
+            value: leg.value,
+            public_key_ref: leg.receiver_key.clone(),
+            lock: None,
+        }];
+        if Validator::get_shard_id(&leg.sender, config) !=
+            Validator::get_shard_id(&leg.receiver, config) {
+            outputs.push(UtxoOutput {
+                receiver_addr: leg.sender.clone(),
+                value: 0,
+                public_key_ref: leg.sender_key.clone(),
+                lock: None,
+            });
+        }
+        Self::spend_funding(leg, spent, vec![secret.to_vec()], key, outputs, config)
+    }
+
+    /// Take a leg back after its timeout
+    pub fn refund_tx(
+        &self,
+        leg: &SwapLeg,
+        spent: (&Transaction, u32),
+        key: &Ed25519KeyPair,
+        config: &Configuration,
+    ) -> Transaction {
+        let outputs = vec![UtxoOutput {
+            receiver_addr: leg.sender.clone(),
+            value: leg.value,
+            public_key_ref: leg.sender_key.clone(),
+            lock: None,
+        }];
+        Self::spend_funding(leg, spent, vec![], key, outputs, config)
+    }
+
+    /// The version of a tx in the shards of its outputs, for a funding tx it holds
+    /// the locked utxo
+    pub fn get_arrived_tx(tx: &Transaction) -> Transaction {
+        let mut tx = tx.clone();
+        if tx.flag == TxFlag::Input {
+            tx.flag = TxFlag::Output;
+        }
+        tx
+    }
+}
+
+//a swap of the wallet, with the progress of the user in it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwapEntry {
+    pub id: H256,
+    pub swap: Swap,
+    pub role: SwapRole,
+    pub status: SwapStatus,
+    pub secret: Option<Vec<u8>>, //known by the initiator, learnt by the responder
+    pub funding: Option<Transaction>, //locks the leg of the user
+    pub funded_at: Option<usize>, //the height of the shard of the lock when funded
+    pub counter_funding: Option<(Transaction, u32)>, //locks the leg of the counterparty
+    pub claim: Option<Transaction>,
+    pub refund: Option<Transaction>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WalletStatus {
+    pub address: String, //encoded
+    pub public_key: String,
+    pub balance: u64,
+    pub swaps: Vec<SwapEntry>,
+}
+
+struct WalletState {
+    swaps: HashMap<H256, SwapEntry>,
+    used_utxo: HashSet<(H256, u32)>,
+}
+
+//what is written to disk, the swaps cannot be claimed or refunded without the key
+//and the secrets after a restart
+#[derive(Serialize, Deserialize)]
+struct SavedWallet {
+    key: String, //the hex encoded pkcs8 key of the user
+    swaps: Vec<SwapEntry>,
+    used_utxo: Vec<(H256, u32)>,
+}
+
+//the swaps of the user of the node, whose address is in the shard the node first
+//joined. The claims and refunds are made automatically as the chains grow; once the
+//committee rotation moves the node to another shard, the state of the shard of the
+//user is unknown, so only the refunds go on until the node comes back
+#[derive(Clone)]
+pub struct SwapWallet {
+    inner: Arc<Mutex<WalletState>>,
+    path: PathBuf,
+    address: Address,
+    shard_id: usize, //the shard of the address
+    key: Arc<Ed25519KeyPair>,
+    pkcs8: Arc<Vec<u8>>, //the key as it is saved
+    multichain: Multichain,
+    mempool: Arc<Mutex<Mempool>>,
+    server: ServerHandle,
+    config: Configuration,
+}
+
+impl SwapWallet {
+    //load the wallet saved by the previous run of this node, or create one
+    pub fn new(
+        multichain: &Multichain,
+        mempool: &Arc<Mutex<Mempool>>,
+        server: &ServerHandle,
+        config: &Configuration,
+    ) -> Self {
+        let path = PathBuf::from(format!(
+            "./keys/exper_{}/wallet_{}.json",
+            config.exper_number,
+            config.node_id
+        ));
+        let saved = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| match serde_json::from_str::<SavedWallet>(&content) {
+                Ok(saved) => Some(saved),
+                Err(e) => {
+                    warn!("Fail to parse the swap wallet {:?}: {}", path, e);
+                    None
+                }
+            });
+        let (pkcs8, swaps, used_utxo) = match saved.and_then(|saved| {
+            let pkcs8 = hex::decode(&saved.key).ok()?;
+            Some((pkcs8, saved.swaps, saved.used_utxo))
+        }) {
+            Some(saved) => {
+                info!("Load {} swaps from {:?}", saved.1.len(), path);
+                saved
+            }
+            //the address is derived from the key, draw keys until it is in the shard
+            None => loop {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+                let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+                let address = Address::from_key_pair(&key);
+                if Validator::get_shard_id(&address, config) == config.shard_id() {
+                    break (pkcs8.as_ref().to_vec(), vec![], vec![]);
+                }
+            },
+        };
+        let key = Ed25519KeyPair::from_pkcs8(&pkcs8).unwrap_or_else(|_| {
+            panic!("invalid key in the swap wallet {:?}", path)
+        });
+        let address = Address::from_key_pair(&key);
+        let wallet = SwapWallet {
+            inner: Arc::new(Mutex::new(WalletState {
+                swaps: swaps.into_iter().map(|entry| (entry.id, entry)).collect(),
+                used_utxo: used_utxo.into_iter().collect(),
+            })),
+            path,
+            shard_id: Validator::get_shard_id(&address, config),
+            address,
+            key: Arc::new(key),
+            pkcs8: Arc::new(pkcs8),
+            multichain: multichain.clone(),
+            mempool: Arc::clone(mempool),
+            server: server.clone(),
+            config: config.clone(),
+        };
+        wallet.save();
+        wallet
+    }
+
+    fn save(&self) {
+        let saved = {
+            let inner = self.inner.lock().unwrap();
+            SavedWallet {
+                key: hex::encode(self.pkcs8.as_ref()),
+                swaps: inner.swaps.values().cloned().collect(),
+                used_utxo: inner.used_utxo.iter().cloned().collect(),
+            }
+        };
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let res = serde_json::to_string_pretty(&saved)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&self.path, content).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            warn!("Fail to save the swap wallet to {:?}: {}", self.path, e);
+        }
+    }
+
+    pub fn start(&self) {
+        let wallet = self.clone();
+        thread::Builder::new()
+            .name("Swap-Wallet".to_string())
+            .spawn(move || loop {
+                wallet.update();
+                thread::sleep(time::Duration::from_millis(SWAP_UPDATE_INTERVAL));
+            })
+            .unwrap();
+        info!("Swap wallet started at address {}", self.address.encode());
+    }
+
+    pub fn get_public_key(&self) -> Vec<u8> {
+        self.key.public_key().as_ref().to_vec()
+    }
+
+    //whether the node is in the shard of the user, and so has the state it spends from
+    fn is_home(&self) -> bool {
+        self.config.shard_id() == self.shard_id
+    }
+
+    fn check_home(&self) -> Result<(), String> {
+        match self.is_home() {
+            true => Ok(()),
+            false => Err(format!(
+                "the node has moved from shard {} of the wallet to shard {}",
+                self.shard_id,
+                self.config.shard_id()
+            )),
+        }
+    }
+
+    //the unlocked utxos of the user in the longest chain
+    fn get_utxos(&self) -> Vec<(Transaction, u32, u64)> {
+        if !self.is_home() {
+            return vec![];
+        }
+        let tip = self.multichain.get_longest_chain_hash();
+        let state = match self.multichain.get_state(&tip) {
+            Some(state) => state,
+            None => return vec![],
+        };
+        state
+            .iter()
+            .filter_map(|(key, (tx, _))| {
+                let output = tx.outputs.get(key.1 as usize)?;
+                if output.receiver_addr != self.address || output.lock.is_some() {
+                    return None;
+                }
+                let value = match tx.flag {
+                    TxFlag::Reject => tx.inputs.get(key.1 as usize)?.value,
+                    _ => output.value,
+                };
+                Some((tx.clone(), key.1, value))
+            })
+            .collect()
+    }
+
+    pub fn get_status(&self) -> WalletStatus {
+        let inner = self.inner.lock().unwrap();
+        let balance = self.get_utxos()
+            .iter()
+            .filter(|(tx, index, _)| !inner.used_utxo.contains(&(tx.hash(), *index)))
+            .map(|(_, _, value)| value)
+            .sum();
+        WalletStatus {
+            address: self.address.encode(),
+            public_key: hex::encode(self.get_public_key()),
+            balance,
+            swaps: inner.swaps.values().cloned().collect(),
+        }
+    }
+
+    pub fn get_swap(&self, id: &H256) -> Option<SwapEntry> {
+        self.inner.lock().unwrap().swaps.get(id).cloned()
+    }
+
+    //send the tx to its input shards, like the tx generator does
+    fn submit(&self, tx: &Transaction) {
+        let input_shards: HashSet<usize> = tx.inputs
+            .iter()
+            .map(|input| Validator::get_shard_id(&input.sender_addr, &self.config))
+            .collect();
+        for shard_id in input_shards {
+            self.server.broadcast_with_shard(
+                Message::Transactions((vec![tx.clone()], shard_id as u32)),
+                shard_id,
+            );
+            if shard_id == self.config.shard_id() {
+                self.mempool.lock().unwrap().insert_tx(tx.clone());
+            }
+        }
+    }
+
+    /// Give the user coins to swap, the way the tx generator gives its users their
+    /// initial coins
+    pub fn mint(&self, value: u64) -> Result<Transaction, String> {
+        self.check_home()?;
+        let tx = Transaction::create_initial_tx((&self.address, &self.key), value);
+        self.server.broadcast_with_shard(
+            Message::Transactions((vec![tx.clone()], self.shard_id as u32)),
+            self.shard_id,
+        );
+        self.mempool.lock().unwrap().insert_tx(tx.clone());
+        Ok(tx)
+    }
+
+    /// Agree on a swap with a user of another shard. The initiator generates the
+    /// secret, the responder is given its hash. The leg of the responder times out
+    /// after the given blocks of the shard of the initiator, the leg of the initiator
+    /// after the blocks the shard of the responder is expected to grow in twice the time
+    pub fn create(
+        &self,
+        role: SwapRole,
+        secret_hash: Option<H256>,
+        value: u64,
+        counter_addr: Address,
+        counter_key: Vec<u8>,
+        counter_value: u64,
+        timeout: u64,
+    ) -> Result<SwapEntry, String> {
+        let counter_key_bytes: &[u8; 32] = counter_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| String::from("the key of the counterparty is not 32 bytes"))?;
+        if Address::from_public_key_bytes(counter_key_bytes) != counter_addr {
+            return Err(String::from("the address of the counterparty is not derived from its key"));
+        }
+        let (secret, secret_hash) = match (role, secret_hash) {
+            (SwapRole::Initiator, None) => {
+                let secret: [u8; SECRET_SIZE] = rand::thread_rng().gen();
+                (Some(secret.to_vec()), Lock::hash_secret(&secret))
+            }
+            (SwapRole::Responder, Some(secret_hash)) => (None, secret_hash),
+            (SwapRole::Initiator, Some(_)) => {
+                return Err(String::from("the initiator generates the secret"));
+            }
+            (SwapRole::Responder, None) => {
+                return Err(String::from("missing the hash of the secret"));
+            }
+        };
+        let counter_shard = Validator::get_shard_id(&counter_addr, &self.config);
+        let own_leg = SwapLeg {
+            sender: self.address.clone(),
+            sender_key: self.get_public_key(),
+            receiver: counter_addr.clone(),
+            receiver_key: counter_key.clone(),
+            value,
+            timeout,
+        };
+        let counter_leg = SwapLeg {
+            sender: counter_addr,
+            sender_key: counter_key,
+            receiver: self.address.clone(),
+            receiver_key: self.get_public_key(),
+            value: counter_value,
+            timeout,
+        };
+        //the legs are locked in the shards of their receivers
+        let (initiator_lock_shard, responder_lock_shard) = match role {
+            SwapRole::Initiator => (counter_shard, self.shard_id),
+            SwapRole::Responder => (self.shard_id, counter_shard),
+        };
+        let initiator_timeout = scale_timeout(
+            timeout.saturating_mul(TIMEOUT_MARGIN),
+            responder_lock_shard,
+            initiator_lock_shard,
+            &self.config
+        );
+        let swap = match role {
+            SwapRole::Initiator => Swap::new(
+                secret_hash,
+                SwapLeg { timeout: initiator_timeout, ..own_leg },
+                counter_leg,
+                &self.config,
+            )?,
+            SwapRole::Responder => Swap::new(
+                secret_hash,
+                SwapLeg { timeout: initiator_timeout, ..counter_leg },
+                own_leg,
+                &self.config,
+            )?,
+        };
+        let entry = SwapEntry {
+            id: swap.hash(),
+            swap,
+            role,
+            status: SwapStatus::Created,
+            secret,
+            funding: None,
+            funded_at: None,
+            counter_funding: None,
+            claim: None,
+            refund: None,
+        };
+        self.inner.lock().unwrap().swaps.insert(entry.id, entry.clone());
+        self.save();
+        Ok(entry)
+    }
+
+    /// Lock the leg of the user with its unspent coins
+    pub fn fund(&self, id: &H256) -> Result<Transaction, String> {
+        self.check_home()?;
+        let mut inner = self.inner.lock().unwrap();
+        let used_utxo = inner.used_utxo.clone();
+        let entry = inner.swaps.get_mut(id).ok_or(String::from("swap not found"))?;
+        if entry.funding.is_some() {
+            return Err(String::from("swap already funded"));
+        }
+        let leg = entry.swap.get_leg(entry.role).clone();
+        let mut available_coins: Vec<(Transaction, usize)> = vec![];
+        let mut curr_coins: u64 = 0;
+        for (tx, index, value) in self.get_utxos() {
+            if curr_coins >= leg.value {
+                break;
+            }
+            if used_utxo.contains(&(tx.hash(), index)) {
+                continue;
+            }
+            curr_coins = curr_coins.saturating_add(value);
+            available_coins.push((tx, index as usize));
+        }
+        if curr_coins < leg.value {
+            return Err(String::from("coins not enough"));
+        }
+        let utxos: Vec<(&Transaction, usize)> = available_coins
+            .iter()
+            .map(|(tx, index)| (tx, *index))
+            .collect();
+        let tx = entry.swap.fund_tx(&leg, utxos, &self.key, &self.config)
+            .ok_or(String::from("error creating the funding tx"))?;
+        let lock_shard = Validator::get_shard_id(&leg.receiver, &self.config);
+        entry.funding = Some(tx.clone());
+        entry.funded_at = Some(self.multichain.get_tips()[lock_shard].1);
+        entry.status = SwapStatus::Funded;
+        for (utxo_tx, index) in available_coins.iter() {
+            inner.used_utxo.insert((utxo_tx.hash(), *index as u32));
+        }
+        drop(inner);
+        self.save();
+        self.submit(&tx);
+        Ok(tx)
+    }
+
+    /// Claim the leg of the counterparty, once its lock is in the shard and the
+    /// secret is known
+    pub fn claim(&self, id: &H256) -> Result<Transaction, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.swaps.get_mut(id).ok_or(String::from("swap not found"))?;
+        let secret = entry.secret.clone().ok_or(String::from("secret unknown"))?;
+        let (spent_tx, index) = entry.counter_funding
+            .clone()
+            .ok_or(String::from("the leg of the counterparty is not locked"))?;
+        let leg = entry.swap.get_counter_leg(entry.role).clone();
+        let tx = entry.swap.claim_tx(
+            &leg,
+            (&spent_tx, index),
+            &secret,
+            &self.key,
+            &self.config
+        );
+        entry.claim = Some(tx.clone());
+        drop(inner);
+        self.save();
+        self.submit(&tx);
+        Ok(tx)
+    }
+
+    /// Take the leg of the user back. It is rejected by the shard of the lock before
+    /// the timeout, or once the leg is claimed
+    pub fn refund(&self, id: &H256) -> Result<Transaction, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.swaps.get_mut(id).ok_or(String::from("swap not found"))?;
+        let funding = entry.funding.clone().ok_or(String::from("swap not funded"))?;
+        let leg = entry.swap.get_leg(entry.role).clone();
+        let spent_tx = Swap::get_arrived_tx(&funding);
+        let tx = entry.swap.refund_tx(&leg, (&spent_tx, 0), &self.key, &self.config);
+        entry.refund = Some(tx.clone());
+        drop(inner);
+        self.save();
+        self.submit(&tx);
+        Ok(tx)
+    }
+
+    //follow the swaps along the longest chain of the shard: find the lock of the
+    //counterparty and the revealed secret, then claim; refund after the timeout.
+    //away from the shard of the wallet only the timeouts are followed, by the tips
+    pub fn update(&self) {
+        let home = self.is_home();
+        let (state, txs) = match home {
+            true => {
+                let tip = self.multichain.get_longest_chain_hash();
+                match self.multichain.get_state(&tip) {
+                    Some(state) => (Some(state), self.multichain.get_all_txs_in_longest_chain()),
+                    None => return,
+                }
+            }
+            false => (None, vec![]),
+        };
+        let tx_hashes: HashSet<H256> = txs.iter().map(|tx| tx.hash()).collect();
+        let tips = self.multichain.get_tips();
+        let mut to_claim: Vec<H256> = vec![];
+        let mut to_refund: Vec<H256> = vec![];
+        let mut changed = false;
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if !home && inner.swaps.values().any(|entry| {
+                entry.status != SwapStatus::Claimed && entry.status != SwapStatus::Refunded
+            }) {
+                debug!(
+                    "The node left shard {} of the swap wallet, only refunds are followed",
+                    self.shard_id
+                );
+            }
+            for (id, entry) in inner.swaps.iter_mut() {
+                if entry.status == SwapStatus::Claimed || entry.status == SwapStatus::Refunded {
+                    continue;
+                }
+                let swap = entry.swap.clone();
+                let own_leg = swap.get_leg(entry.role);
+                let counter_leg = swap.get_counter_leg(entry.role);
+                if let Some(state) = state.as_ref() {
+                    //the claim spends a lock of this shard, the refund arrives in it
+                    if entry.claim
+                        .as_ref()
+                        .map_or(false, |tx| tx_hashes.contains(&tx.hash())) {
+                        entry.status = SwapStatus::Claimed;
+                        changed = true;
+                        continue;
+                    }
+                    if entry.refund
+                        .as_ref()
+                        .map_or(false, |tx| tx_hashes.contains(&Swap::get_arrived_tx(tx).hash())) {
+                        entry.status = SwapStatus::Refunded;
+                        changed = true;
+                        continue;
+                    }
+                    if entry.counter_funding.is_none() {
+                        entry.counter_funding = swap.find_funding(counter_leg, state);
+                        changed |= entry.counter_funding.is_some();
+                    }
+                    if entry.secret.is_none() {
+                        entry.secret = txs.iter().find_map(|tx| swap.find_secret(tx));
+                        if entry.secret.is_some() {
+                            info!("Secret of swap {} revealed", id);
+                            changed = true;
+                        }
+                    }
+                    if entry.counter_funding.is_some() && entry.secret.is_some() {
+                        to_claim.push(id.clone());
+                        continue;
+                    }
+                }
+                if let Some(funded_at) = entry.funded_at {
+                    let lock_shard = Validator::get_shard_id(
+                        &own_leg.receiver,
+                        &self.config
+                    );
+                    if tips[lock_shard].1 as u64 >= funded_at as u64 + own_leg.timeout {
+                        to_refund.push(id.clone());
+                    }
+                }
+            }
+        }
+        if changed {
+            self.save();
+        }
+        //a claim or refund is sent again until it is settled
+        for id in to_claim.iter() {
+            match self.claim(id) {
+                Ok(tx) => debug!("Claim {} of swap {} sent", tx.hash(), id),
+                Err(e) => debug!("Error claiming swap {}: {}", id, e),
+            }
+        }
+        for id in to_refund.iter() {
+            match self.refund(id) {
+                Ok(tx) => debug!("Refund {} of swap {} sent", tx.hash(), id),
+                Err(e) => debug!("Error refunding swap {}: {}", id, e),
+            }
+        }
+    }
+}
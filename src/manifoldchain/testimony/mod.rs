@@ -3,6 +3,7 @@ use crate::{
         hash::{H256, Hashable},
     },
     manifoldchain::{
+        configuration::Configuration,
         transaction::{Transaction, TxFlag},
         block::{
             versa_block::{
@@ -117,7 +118,7 @@ impl Testimony {
         block: &VersaBlock,
         tx_index: usize,
         shard_id: usize,
-        shard_num: usize,
+        config: &Configuration,
         decision: bool,
     ) -> Option<Testimony> {
         let mut tmy_units: Vec<TestimonyUnit> = vec![];
@@ -126,7 +127,7 @@ impl Testimony {
                 for input in tx.inputs.iter() {
                     if Validator::get_shard_id(
                         &input.sender_addr,
-                        shard_num
+                        config
                     ) == shard_id {
                         match block.get_tx_merkle_proof(tx_index) {
                             Some(proof) => {
@@ -156,7 +157,7 @@ impl Testimony {
                 for output in tx.outputs.iter() {
                     if Validator::get_shard_id(
                         &output.receiver_addr,
-                        shard_num
+                        config
                     ) == shard_id {
                         match block.get_tx_merkle_proof(tx_index) {
                             Some(proof) => {
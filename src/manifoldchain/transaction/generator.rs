@@ -1,7 +1,8 @@
 use crate::{
     types::{
         key_pair,
-        hash::{Hashable, H256}
+        hash::{Hashable, H256},
+        address::Address,
     },
     manifoldchain::{
         network::{
@@ -57,13 +58,13 @@ pub struct Context {
     control_chan: Receiver<ControlSignal>,
     operating_state: OperatingState,
     server: ServerHandle,
-    users: HashMap<usize, H256>, //shard_id->user
-    keys: HashMap<H256, Ed25519KeyPair>,
+    users: HashMap<usize, Address>, //shard_id->user
+    keys: HashMap<Address, Ed25519KeyPair>,
     config: Configuration,
     nodes: HashMap<String, bool>,
     used_utxo: HashMap<(H256, u32), bool>, //(tx_hash, index) -> bool,
     mempool: Arc<Mutex<Mempool>>,
-    initial_bonus: usize,
+    initial_bonus: u64,
     api_port: u16, 
 }
 
@@ -86,26 +87,20 @@ pub fn new_ctx(
     api_port: u16
 ) -> Context {
     
-    let mut rng = rand::thread_rng();    
-    let mut users: HashMap<usize, H256> = HashMap::new();
-    let mut keys: HashMap<H256, Ed25519KeyPair> = HashMap::new();
+    let mut users: HashMap<usize, Address> = HashMap::new();
+    let mut keys: HashMap<Address, Ed25519KeyPair> = HashMap::new();
     
     for i in 0..config.shard_num {
-        let mut random_bytes: [u8; 32] = rng.gen();
-        let mut j = 31;
-        let mut x: usize = i;
-        loop {
-            random_bytes[j] = (x % 256) as u8;
-            x = x / 256;
-            j -= 1;
-            if x <= 0 || j < 0 {
-                break;
+        //the address is derived from the key, draw keys until it is in the shard
+        let (user, key) = loop {
+            let key = key_pair::random();
+            let user = Address::from_key_pair(&key);
+            if Validator::get_shard_id(&user, config) == i {
+                break (user, key);
             }
-        }
-        let user_hash: H256 = (&random_bytes).into();
-        let key = key_pair::random();
-        users.insert(i, user_hash.clone());
-        keys.insert(user_hash, key);
+        };
+        users.insert(i, user);
+        keys.insert(user, key);
     }
 
     
@@ -244,13 +239,13 @@ impl Context {
                             self.initial_bonus += 1;
                             let shard_id = Validator::get_shard_id(
                                 &item.0,
-                                self.config.shard_num,
+                                &self.config,
                             );
                             self.server.broadcast_with_shard(Message::Transactions((
                                 vec![initial_tx.clone()], 
                                 shard_id as u32
                             )), shard_id);
-                            //if shard_id == self.config.shard_id {
+                            //if shard_id == self.config.shard_id() {
                             //    self.mempool.lock().unwrap().insert_tx(initial_tx);
                             //}
                         }
@@ -263,23 +258,23 @@ impl Context {
                 //generating transactions
                 let mut rng = rand::thread_rng();
                 let num_node = self.users.len();
-                let payer = self.users.get(&self.config.shard_id).unwrap().clone();
+                let payer = self.users.get(&self.config.shard_id()).unwrap().clone();
                 let domestic_ratio: f64 = self.config.domestic_tx_ratio;
                 let sample_range: usize = 10000;
                 let sample_value: usize = rng.gen_range(0..sample_range);
                 let threshold: f64 = (sample_range as f64) * domestic_ratio;
                 let sample_value = sample_value as f64;
                 
-                let mut receivers: Vec<H256> = vec![];
+                let mut receivers: Vec<Address> = vec![];
                 if sample_value <= threshold {
                     //create domestic tx
-                    receivers.push(self.users.get(&self.config.shard_id).unwrap().clone());
+                    receivers.push(self.users.get(&self.config.shard_id()).unwrap().clone());
                 } else {
                     //create cross tx
                     let mut count = 0;
                     while count < self.config.num_tx_recv {
                         let recv_index: usize = rng.gen_range(0..self.config.shard_num);
-                        if recv_index == self.config.shard_id {
+                        if recv_index == self.config.shard_id() {
                             continue;
                         }
                         receivers.push(self.users.get(&recv_index).unwrap().clone());
@@ -287,20 +282,20 @@ impl Context {
                     }
                 }
                 
-                let coins: Vec<usize> = (0..receivers.len()).map(|_| 1).collect();
+                let coins: Vec<u64> = (0..receivers.len()).map(|_| 1).collect();
                 if let Some(tx) = self.create_tx(payer.clone(), receivers.clone(), coins) {
                     //classify the users by shards
                     info!("create non-initial tx");
                     let mut input_shards: HashMap::<usize, bool> = HashMap::new();
                     let mut output_shards: HashMap::<usize, bool> = HashMap::new();
-                    let payer_shard_id = Validator::get_shard_id(&payer, self.config.shard_num);
+                    let payer_shard_id = Validator::get_shard_id(&payer, &self.config);
                     input_shards.insert(
-                        Validator::get_shard_id(&payer, self.config.shard_num),
+                        Validator::get_shard_id(&payer, &self.config),
                         true,
                     );
                     for receiver in receivers.iter() {
                         output_shards.insert(
-                            Validator::get_shard_id(receiver, self.config.shard_num),
+                            Validator::get_shard_id(receiver, &self.config),
                             true,
                         );
                     }
@@ -321,7 +316,7 @@ impl Context {
                                 vec![input_tx.clone()],
                                 key as u32,
                             )), key);
-                            if key == self.config.shard_id {
+                            if key == self.config.shard_id() {
                                 self.mempool.lock().unwrap().insert_tx(input_tx.clone());
                             }
                         }
@@ -332,7 +327,7 @@ impl Context {
                         //        vec![output_tx.clone()],
                         //        key as u32,
                         //    )), key);
-                        //    if key == self.config.shard_id {
+                        //    if key == self.config.shard_id() {
                         //        self.mempool.lock().unwrap().insert_tx(output_tx.clone());
                         //    }
                         //}
@@ -341,7 +336,7 @@ impl Context {
                             vec![tx.clone()],
                             payer_shard_id as u32,
                         )), payer_shard_id);
-                        if payer_shard_id == self.config.shard_id {
+                        if payer_shard_id == self.config.shard_id() {
                             self.mempool.lock().unwrap().insert_tx(tx.clone());
                         }
                     }
@@ -354,7 +349,7 @@ impl Context {
                         self.config.initial_balance + self.initial_bonus,
                     );
                     self.initial_bonus += 1;
-                    let payer_shard_id = Validator::get_shard_id(&payer, self.config.shard_num);
+                    let payer_shard_id = Validator::get_shard_id(&payer, &self.config);
                     self.server.broadcast_with_shard(Message::Transactions((
                         vec![initial_tx], 
                         payer_shard_id as u32
@@ -366,9 +361,9 @@ impl Context {
     }
 
     fn create_tx(&mut self, 
-        payer: H256, 
-        receivers: Vec<H256>, 
-        coins: Vec<usize>) -> Option<Transaction> {
+        payer: Address, 
+        receivers: Vec<Address>, 
+        coins: Vec<u64>) -> Option<Transaction> {
         if receivers.len() != coins.len() {
             //debug!("the size of receives and coins are not equal");
             return None;
@@ -386,7 +381,7 @@ impl Context {
             //    }
             //}
 
-            let require_coins: usize = coins.iter().sum();
+            let require_coins: u64 = coins.iter().sum();
 
             let mut available_coins: Vec<(Transaction, u32)> = Vec::new();
             //info!("total {} available_utxos for payer {:?}", available_utxos.len(), payer);
@@ -394,7 +389,7 @@ impl Context {
             //    let value = item.0.outputs[item.1 as usize].value;
             //    debug!("coins: {}", value);
             //}
-            let mut curr_coins: usize = 0;
+            let mut curr_coins: u64 = 0;
             loop {
                 if let Some(utxo) = available_utxos.pop_front() {
                     let tx = &utxo.0;
@@ -405,9 +400,9 @@ impl Context {
                     }
 
                     if let TxFlag::Reject = tx.flag {
-                        curr_coins += tx.inputs[index].value as usize;
+                        curr_coins += tx.inputs[index].value;
                     } else {
-                        curr_coins += tx.outputs[index].value as usize;
+                        curr_coins += tx.outputs[index].value;
                     }
 
                     available_coins.push(utxo);
@@ -448,8 +443,8 @@ impl Context {
                 .iter()
                 .map(|x| (&x.0, x.1 as usize))
                 .collect();
-            let senders: Vec<(&H256, &Ed25519KeyPair)> = vec![(&payer, self.keys.get(&payer).unwrap()); utxos.len()];
-            let mut receivers_coins: Vec<(&H256, &Ed25519KeyPair, usize)> = vec![];
+            let senders: Vec<(&Address, &Ed25519KeyPair)> = vec![(&payer, self.keys.get(&payer).unwrap()); utxos.len()];
+            let mut receivers_coins: Vec<(&Address, &Ed25519KeyPair, u64)> = vec![];
             for i in 0..receivers.len() {
                 receivers_coins.push((
                     &receivers[i],
@@ -482,16 +477,26 @@ impl Context {
         
     }
 
-    fn get_utxo_by_api(&self, user: &H256) 
+    fn get_utxo_by_api(&self, user: &Address) 
         -> Result<Vec<(Transaction, u32)>, Box<dyn std::error::Error>> 
     {
          
         let mut utxos: Vec<(Transaction, u32)> = Vec::new();
         for (val, _) in self.nodes.iter() {
-            let req_url = format!("http://{}:{}/blockchain/available-utxo?user={}", val, self.api_port, user);
+            let req_url = format!(
+                "http://{}:{}/blockchain/available-utxo?user={}",
+                val,
+                self.api_port,
+                user.encode()
+            );
             let resp = reqwest::blocking::get(req_url)?
                 .json::<Vec<(Transaction, u32)>>()?;
-            utxos.extend(resp);
+            //only the utxos of the user can be spent with its key
+            utxos.extend(resp.into_iter().filter(|(tx, index)| {
+                tx.outputs
+                    .get(*index as usize)
+                    .map_or(false, |output| output.receiver_addr == *user)
+            }));
         }
         
         Ok(utxos)
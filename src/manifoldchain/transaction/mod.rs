@@ -1,9 +1,14 @@
 pub mod generator;
+pub mod script;
 
 use serde::{Serialize, Deserialize};
 use ring::signature::{self, Ed25519KeyPair, Signature, KeyPair};
-use crate::types::hash::{H256, Hashable};
+use crate::types::{
+    hash::{H256, Hashable},
+    address::Address,
+};
 use rand::{self, Rng};
+use script::{Lock, Witness, LockContext};
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub enum TxFlag{
     Initial,
@@ -45,18 +50,20 @@ pub struct Transaction {
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
 pub struct UtxoInput {
-    pub sender_addr: H256,
+    pub sender_addr: Address,
     pub tx_hash: H256,
-    pub value: u32,
+    pub value: u64,
     pub index: u32,
     pub sig_ref: Vec<u8>,
+    pub witness: Option<Witness>, //unlocks a utxo with a lock instead of sig_ref
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
 pub struct UtxoOutput {
-    pub receiver_addr: H256,
-    pub value: u32,
+    pub receiver_addr: Address,
+    pub value: u64,
     pub public_key_ref: Vec<u8>,
+    pub lock: Option<Lock>, //replaces the single key of public_key_ref
 }
 
 impl Hashable for UtxoInput {
@@ -70,19 +77,24 @@ impl Hashable for UtxoInput {
         let tmp_hash: H256 = ring::digest::digest(
             &ring::digest::SHA256, input_str.as_bytes()
         ).into();
-        let hash_vec: Vec<H256> = vec![
-            self.sender_addr.clone(), 
+        let mut hash_vec: Vec<H256> = vec![
+            self.sender_addr.hash(), 
             tmp_hash, 
             self.tx_hash.clone()
         ];
+        if let Some(witness) = self.witness.as_ref() {
+            hash_vec.push(witness.hash());
+        }
         H256::multi_hash(&hash_vec)
     }
 }
 impl UtxoInput {
     pub fn get_mem_size(&self) -> usize {
         H256::get_mem_size() * 2 
-            + std::mem::size_of::<u32>() * 2 
+            + std::mem::size_of::<u64>()
+            + std::mem::size_of::<u32>()
             + std::mem::size_of::<u8>() * self.sig_ref.len()
+            + self.witness.as_ref().map_or(0, |witness| witness.get_mem_size())
     }
 }
 
@@ -96,7 +108,10 @@ impl Hashable for UtxoOutput {
         let tmp_hash: H256 = ring::digest::digest(
             &ring::digest::SHA256, output_str.as_bytes()
         ).into();
-        let hash_vec: Vec<H256> = vec![self.receiver_addr.clone(), tmp_hash];
+        let mut hash_vec: Vec<H256> = vec![self.receiver_addr.hash(), tmp_hash];
+        if let Some(lock) = self.lock.as_ref() {
+            hash_vec.push(lock.hash());
+        }
         H256::multi_hash(&hash_vec)
     }
 }
@@ -104,8 +119,9 @@ impl Hashable for UtxoOutput {
 impl UtxoOutput {
     pub fn get_mem_size(&self) -> usize {
         H256::get_mem_size()
-            + std::mem::size_of::<u32>() 
+            + std::mem::size_of::<u64>() 
             + std::mem::size_of::<u8>() * self.public_key_ref.len()
+            + self.lock.as_ref().map_or(0, |lock| lock.get_mem_size())
     }
 }
 
@@ -147,21 +163,23 @@ impl Transaction {
     //generate a random transaction
     pub fn gen_rand_tx() -> Self {
         let mut rng = rand::thread_rng();
-        let sender_addr: [u8; 32] = rng.gen();
-        let sender_addr_hash: H256 = (&sender_addr).into();
-        let rand_addr: [u8; 32] = rng.gen();
-        let rand_addr_hash = (&rand_addr).into();
+        let sender_addr: [u8; 20] = rng.gen();
+        let sender_addr_hash: Address = (&sender_addr).into();
+        let rand_addr: [u8; 20] = rng.gen();
+        let rand_addr_hash: Address = (&rand_addr).into();
         let input = UtxoInput {
             sender_addr: sender_addr_hash,
             tx_hash: H256::default(),
             value: 0,
             index: 0,
-            sig_ref: Vec::new()
+            sig_ref: Vec::new(),
+            witness: None,
         };
         let output = UtxoOutput {
             receiver_addr: rand_addr_hash,
             value: 0,
-            public_key_ref: Vec::new()
+            public_key_ref: Vec::new(),
+            lock: None,
         };
         let inputs: Vec<UtxoInput> = vec![input];
         let outputs: Vec<UtxoOutput> = vec![output];
@@ -207,6 +225,58 @@ impl Transaction {
         }
     }
 
+    /// The tx without the signatures of its inputs. The witness signatures sign it, so
+    /// that they are bound to the inputs and outputs of the spending tx
+    pub fn get_unsigned(&self) -> Transaction {
+        let mut tx = self.clone();
+        for input in tx.inputs.iter_mut() {
+            input.sig_ref = vec![];
+            input.witness = None;
+        }
+        tx
+    }
+
+    /// Whether the input of the spending tx may spend its utxo, an output of the spent
+    /// tx. A signature is checked with the given function of the signed tx, the public
+    /// key and the signature
+    pub fn check_spend(
+        spent_tx: &Transaction,
+        spending_tx: &Transaction,
+        input: &UtxoInput,
+        ctx: &LockContext,
+        verify: &dyn Fn(&Transaction, &[u8], &[u8]) -> bool,
+    ) -> bool {
+        let output = match spent_tx.outputs.get(input.index as usize) {
+            Some(output) => output,
+            None => return false,
+        };
+        //the input is sent by the receiver of the utxo, the shard of the utxo is found
+        //with the address the input names
+        if input.sender_addr != output.receiver_addr {
+            return false;
+        }
+        match output.lock.as_ref() {
+            Some(lock) => {
+                let no_witness = Witness::default();
+                let unsigned = spending_tx.get_unsigned();
+                lock.eval(
+                    input.witness.as_ref().unwrap_or(&no_witness),
+                    ctx,
+                    &|public_key_ref, sig_ref| verify(&unsigned, public_key_ref, sig_ref)
+                )
+            }
+            None => {
+                //the key of the utxo is the one its address is derived from
+                let public_key: &[u8; 32] = match output.public_key_ref.as_slice().try_into() {
+                    Ok(public_key) => public_key,
+                    Err(_) => return false,
+                };
+                Address::from_public_key_bytes(public_key) == output.receiver_addr &&
+                    verify(spent_tx, &output.public_key_ref, &input.sig_ref)
+            }
+        }
+    }
+
     pub fn verify_owner(
         tx: &Transaction,  
         input_txs: Vec<&Transaction>, 
@@ -234,12 +304,13 @@ impl Transaction {
         tx.hash()
     }
 
-    pub fn create_initial_tx(user: (&H256, &Ed25519KeyPair), value: usize) -> Transaction {
+    pub fn create_initial_tx(user: (&Address, &Ed25519KeyPair), value: u64) -> Transaction {
         let input = UtxoInput::default();
         let output = UtxoOutput {
             receiver_addr: user.0.clone(),
-            value: value as u32,
+            value,
             public_key_ref: user.1.public_key().as_ref().to_vec(),
+            lock: None,
         };
         Transaction {
             inputs: vec![input],
@@ -248,6 +319,20 @@ impl Transaction {
         }
     }
 
+    //the coins spent by the inputs, none if the sum overflows
+    pub fn get_input_value(&self) -> Option<u64> {
+        self.inputs
+            .iter()
+            .try_fold(0u64, |sum, input| sum.checked_add(input.value))
+    }
+
+    //the coins created by the outputs, none if the sum overflows
+    pub fn get_output_value(&self) -> Option<u64> {
+        self.outputs
+            .iter()
+            .try_fold(0u64, |sum, output| sum.checked_add(output.value))
+    }
+
     pub fn create_empty_tx(num_input: usize, num_output: usize) -> Transaction {
         let inputs: Vec<UtxoInput> = (0..num_input)
             .into_iter()
@@ -267,13 +352,13 @@ impl Transaction {
 
     pub fn consume(
         utxos: Vec<(&Transaction, usize)>, //tx, index 
-        senders: Vec<(&H256, &Ed25519KeyPair)>, //user_addr, user_key, sent_coin
-        receivers: Vec<(&H256, &Ed25519KeyPair, usize)>, //user_addr, user_key, received_coin
+        senders: Vec<(&Address, &Ed25519KeyPair)>, //user_addr, user_key, sent_coin
+        receivers: Vec<(&Address, &Ed25519KeyPair, u64)>, //user_addr, user_key, received_coin
         flag: TxFlag,
     ) -> Option<Transaction> {
 
         let mut inputs: Vec<UtxoInput> = vec![];
-        let mut sent_coins = 0;
+        let mut sent_coins: u64 = 0;
         for i in 0..utxos.len() {
             let x = utxos[i];
             let sender = senders[i];
@@ -287,6 +372,7 @@ impl Transaction {
                         value: tx.outputs[index].value,
                         index: index as u32,
                         sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        witness: None,
                     }
                 }
                 &TxFlag::Domestic => {
@@ -296,6 +382,7 @@ impl Transaction {
                         value: tx.outputs[index].value,
                         index: index as u32,
                         sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        witness: None,
                     }
                 }
                 &TxFlag::Output => {
@@ -305,6 +392,7 @@ impl Transaction {
                         value: tx.outputs[index].value,
                         index: index as u32,
                         sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        witness: None,
                     }
                 }
                 &TxFlag::Reject => {
@@ -314,6 +402,7 @@ impl Transaction {
                         value: tx.inputs[index].value,
                         index: index as u32,
                         sig_ref: vec![],
+                        witness: None,
                     }
                 }
                 _ => {
@@ -321,19 +410,20 @@ impl Transaction {
                 }
             };
             inputs.push(input.clone());
-            sent_coins += input.value as usize;
+            sent_coins = sent_coins.checked_add(input.value)?;
         }
 
         let mut outputs: Vec<UtxoOutput> = vec![];
-        let mut received_coins = 0;
+        let mut received_coins: u64 = 0;
         for x in receivers {
             let output  = UtxoOutput {
                 receiver_addr: x.0.clone(),
-                value: x.2 as u32,
+                value: x.2,
                 public_key_ref: x.1.public_key().as_ref().to_vec(),
+                lock: None,
             };
             outputs.push(output);
-            received_coins += x.2;
+            received_coins = received_coins.checked_add(x.2)?;
         }
 
         if sent_coins != received_coins {
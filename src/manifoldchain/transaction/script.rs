@@ -0,0 +1,178 @@
+use serde::{Serialize, Deserialize};
+use ring::digest::{digest, SHA256};
+use crate::types::hash::{H256, Hashable};
+
+//a lock is a tree of bounded depth and size, it has no loops and is evaluated in
+//time linear to its size
+pub const MAX_LOCK_DEPTH: usize = 4;
+pub const MAX_LOCK_KEYS: usize = 16;
+pub const MAX_LOCK_NODES: usize = 32;
+//a witness holds no more signatures and secrets than a lock can check
+pub const MAX_WITNESS_SIG_LEN: usize = 64;
+pub const MAX_WITNESS_PREIMAGE_LEN: usize = 256;
+
+//the conditions a tx spending a utxo has to satisfy, checked in the shard of the utxo
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub enum Lock {
+    //m signatures of the spent tx by n public keys
+    Multisig {
+        threshold: u32,
+        keys: Vec<Vec<u8>>,
+    },
+    //the sha256 hash of a secret revealed by the spending tx
+    Hashlock(H256),
+    //the spending block is at least at this height
+    After(u64),
+    //the spending block is at least this many blocks above the block of the utxo
+    Older(u64),
+    All(Vec<Lock>),
+    Any(Vec<Lock>),
+}
+
+//what the spending tx reveals to satisfy the lock of a utxo
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
+pub struct Witness {
+    pub sigs: Vec<Vec<u8>>,
+    pub preimages: Vec<Vec<u8>>,
+}
+
+//where the spending tx is checked
+#[derive(Debug, Clone, Copy)]
+pub struct LockContext {
+    pub height: u64, //the height of the spending block
+    pub age: u64, //the blocks between the block of the utxo and the spending block
+}
+
+impl Hashable for Lock {
+    fn hash(&self) -> H256 {
+        let bytes = bincode::serialize(self).unwrap();
+        digest(&SHA256, &bytes).into()
+    }
+}
+
+impl Hashable for Witness {
+    fn hash(&self) -> H256 {
+        let bytes = bincode::serialize(self).unwrap();
+        digest(&SHA256, &bytes).into()
+    }
+}
+
+impl Lock {
+    /// A hash-time-locked contract: the receiver spends it with the secret, or the
+    /// sender takes it back after the timeout
+    pub fn htlc(receiver_key: &[u8], secret_hash: H256, sender_key: &[u8], timeout: u64) -> Self {
+        Lock::Any(vec![
+            Lock::All(vec![
+                Lock::Multisig { threshold: 1, keys: vec![receiver_key.to_vec()] },
+                Lock::Hashlock(secret_hash),
+            ]),
+            Lock::All(vec![
+                Lock::Multisig { threshold: 1, keys: vec![sender_key.to_vec()] },
+                Lock::Older(timeout),
+            ]),
+        ])
+    }
+
+    pub fn hash_secret(secret: &[u8]) -> H256 {
+        digest(&SHA256, secret).into()
+    }
+
+    fn count(&self, depth: usize, nodes: &mut usize, keys: &mut usize) -> bool {
+        *nodes += 1;
+        if depth > MAX_LOCK_DEPTH || *nodes > MAX_LOCK_NODES {
+            return false;
+        }
+        match self {
+            Lock::Multisig { threshold, keys: lock_keys } => {
+                *keys += lock_keys.len();
+                *threshold >= 1 &&
+                    *threshold as usize <= lock_keys.len() &&
+                    *keys <= MAX_LOCK_KEYS
+            }
+            Lock::All(locks) | Lock::Any(locks) => {
+                !locks.is_empty() &&
+                    locks.iter().all(|lock| lock.count(depth + 1, nodes, keys))
+            }
+            _ => true,
+        }
+    }
+
+    /// Check the depth and the size of the lock, and that every threshold can be met
+    pub fn is_well_formed(&self) -> bool {
+        self.count(1, &mut 0, &mut 0)
+    }
+
+    /// Whether the lock depends on the height of the spending block, not only on its
+    /// distance to the block of the utxo
+    pub fn has_absolute_timelock(&self) -> bool {
+        match self {
+            Lock::After(_) => true,
+            Lock::All(locks) | Lock::Any(locks) => locks.iter().any(|lock| lock.has_absolute_timelock()),
+            _ => false,
+        }
+    }
+
+    /// Evaluate the lock, a signature is checked with the given function of the public
+    /// key and the signature
+    pub fn eval(
+        &self,
+        witness: &Witness,
+        ctx: &LockContext,
+        verify: &dyn Fn(&[u8], &[u8]) -> bool
+    ) -> bool {
+        if !self.is_well_formed() {
+            return false;
+        }
+        self.eval_inner(witness, ctx, verify)
+    }
+
+    fn eval_inner(
+        &self,
+        witness: &Witness,
+        ctx: &LockContext,
+        verify: &dyn Fn(&[u8], &[u8]) -> bool
+    ) -> bool {
+        match self {
+            Lock::Multisig { threshold, keys } => {
+                //each key is counted once, whichever signature matches it
+                let signed = keys
+                    .iter()
+                    .filter(|key| witness.sigs.iter().any(|sig| verify(key, sig)))
+                    .count();
+                signed >= *threshold as usize
+            }
+            Lock::Hashlock(secret_hash) => witness.preimages
+                .iter()
+                .any(|preimage| Self::hash_secret(preimage) == *secret_hash),
+            Lock::After(height) => ctx.height >= *height,
+            Lock::Older(age) => ctx.age >= *age,
+            Lock::All(locks) => locks.iter().all(|lock| lock.eval_inner(witness, ctx, verify)),
+            Lock::Any(locks) => locks.iter().any(|lock| lock.eval_inner(witness, ctx, verify)),
+        }
+    }
+
+    pub fn get_mem_size(&self) -> usize {
+        match self {
+            Lock::Multisig { keys, .. } => std::mem::size_of::<u32>() +
+                keys.iter().map(|key| key.len()).sum::<usize>(),
+            Lock::Hashlock(_) => H256::get_mem_size(),
+            Lock::After(_) | Lock::Older(_) => std::mem::size_of::<u64>(),
+            Lock::All(locks) | Lock::Any(locks) => locks.iter().map(|lock| lock.get_mem_size()).sum(),
+        }
+    }
+}
+
+impl Witness {
+    /// Check the number and the size of the signatures and the secrets
+    pub fn is_well_formed(&self) -> bool {
+        self.sigs.len() <= MAX_LOCK_KEYS &&
+            self.preimages.len() <= MAX_LOCK_NODES &&
+            self.sigs.iter().all(|sig| sig.len() <= MAX_WITNESS_SIG_LEN) &&
+            self.preimages.iter().all(|preimage| preimage.len() <= MAX_WITNESS_PREIMAGE_LEN)
+    }
+
+    pub fn get_mem_size(&self) -> usize {
+        self.sigs.iter().map(|sig| sig.len()).sum::<usize>() +
+            self.preimages.iter().map(|preimage| preimage.len()).sum::<usize>()
+    }
+}
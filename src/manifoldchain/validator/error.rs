@@ -0,0 +1,300 @@
+use crate::{
+    types::hash::H256,
+    manifoldchain::fraudproof::FraudProof,
+};
+use std::fmt;
+
+//why a block or a tx is rejected. The faults the other nodes can check carry the
+//fraud proof built for them, if there is one.
+#[derive(Debug, Clone)]
+pub enum ValidationError {
+    //the block
+    WrongBlockHash {
+        block_hash: H256,
+    },
+    UnknownParent {
+        block_hash: H256,
+        parent_hash: H256,
+    },
+    WrongStateRoot {
+        block_hash: H256,
+        expected: H256,
+        actual: H256,
+        proof: Option<Box<FraudProof>>,
+    },
+    InvalidParent {
+        block_hash: H256,
+        parent_hash: H256,
+        shard_id: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    WrongParentShard {
+        block_hash: H256,
+        parent_hash: H256,
+        shard_id: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    WrongParentRoot {
+        block_hash: H256,
+        proof: Option<Box<FraudProof>>,
+    },
+    //the tx
+    KnownTx {
+        tx_hash: H256,
+    },
+    WrongShard {
+        tx_hash: H256,
+        shard_id: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    UnequalCoins {
+        tx_hash: H256,
+        input_value: u64,
+        output_value: u64,
+        proof: Option<Box<FraudProof>>,
+    },
+    ValueOverflow {
+        tx_hash: H256,
+        proof: Option<Box<FraudProof>>,
+    },
+    WrongInputValue {
+        tx_hash: H256,
+        input_index: usize,
+        claimed: u64,
+        actual: u64,
+        proof: Option<Box<FraudProof>>,
+    },
+    DoubleSpending {
+        tx_hash: H256,
+        input_index: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    MissingInput {
+        tx_hash: H256,
+        input_index: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    WrongSignature {
+        tx_hash: H256,
+        input_index: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    LockNotSatisfied {
+        tx_hash: H256,
+        input_index: usize,
+        proof: Option<Box<FraudProof>>,
+    },
+    MalformedLock {
+        tx_hash: H256,
+        output_index: usize,
+    },
+    MalformedWitness {
+        tx_hash: H256,
+        input_index: usize,
+    },
+    EpochClosed {
+        tx_hash: H256,
+        height: usize,
+    },
+    MissingTestimony {
+        tx_hash: H256,
+        proof: Option<Box<FraudProof>>,
+    },
+    IncompleteTestimony {
+        tx_hash: H256,
+    },
+    WrongTestimony {
+        tx_hash: H256,
+        cause: Box<ValidationError>,
+        proof: Option<Box<FraudProof>>,
+    },
+    //the unit of a cross-shard tx, checked against its originate block
+    MissingTestimonyUnit {
+        tx_hash: H256,
+        unit_hash: H256,
+    },
+    OriginBlockNotFound {
+        tx_hash: H256,
+        block_hash: H256,
+        shard_id: usize,
+    },
+    WrongOriginProof {
+        tx_hash: H256,
+        block_hash: H256,
+        shard_id: usize,
+    },
+    OriginNotInLongestChain {
+        tx_hash: H256,
+        block_hash: H256,
+        shard_id: usize,
+    },
+    OriginNotConfirmed {
+        tx_hash: H256,
+        block_hash: H256,
+        shard_id: usize,
+    },
+}
+
+impl ValidationError {
+    //the name of the error in the stats
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ValidationError::WrongBlockHash { .. } => "wrong_block_hash",
+            ValidationError::UnknownParent { .. } => "unknown_parent",
+            ValidationError::WrongStateRoot { .. } => "wrong_state_root",
+            ValidationError::InvalidParent { .. } => "invalid_parent",
+            ValidationError::WrongParentShard { .. } => "wrong_parent_shard",
+            ValidationError::WrongParentRoot { .. } => "wrong_parent_root",
+            ValidationError::KnownTx { .. } => "known_tx",
+            ValidationError::WrongShard { .. } => "wrong_shard",
+            ValidationError::UnequalCoins { .. } => "unequal_coins",
+            ValidationError::WrongInputValue { .. } => "wrong_input_value",
+            ValidationError::ValueOverflow { .. } => "value_overflow",
+            ValidationError::DoubleSpending { .. } => "double_spending",
+            ValidationError::MissingInput { .. } => "missing_input",
+            ValidationError::WrongSignature { .. } => "wrong_signature",
+            ValidationError::LockNotSatisfied { .. } => "lock_not_satisfied",
+            ValidationError::MalformedLock { .. } => "malformed_lock",
+            ValidationError::MalformedWitness { .. } => "malformed_witness",
+            ValidationError::EpochClosed { .. } => "epoch_closed",
+            ValidationError::MissingTestimony { .. } => "missing_testimony",
+            ValidationError::IncompleteTestimony { .. } => "incomplete_testimony",
+            ValidationError::WrongTestimony { .. } => "wrong_testimony",
+            ValidationError::MissingTestimonyUnit { .. } => "missing_testimony_unit",
+            ValidationError::OriginBlockNotFound { .. } => "origin_block_not_found",
+            ValidationError::WrongOriginProof { .. } => "wrong_origin_proof",
+            ValidationError::OriginNotInLongestChain { .. } => "origin_not_in_longest_chain",
+            ValidationError::OriginNotConfirmed { .. } => "origin_not_confirmed",
+        }
+    }
+
+    //whether the tx is invalid on any chain, so that relaying it is the fault of the
+    //peer rather than of a chain it has not caught up with
+    pub fn is_definite(&self) -> bool {
+        match self {
+            ValidationError::WrongShard { .. } |
+            ValidationError::UnequalCoins { .. } |
+            ValidationError::ValueOverflow { .. } |
+            ValidationError::WrongInputValue { .. } |
+            ValidationError::WrongSignature { .. } |
+            ValidationError::MalformedLock { .. } |
+            ValidationError::MalformedWitness { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn proof_slot(&mut self) -> Option<&mut Option<Box<FraudProof>>> {
+        match self {
+            ValidationError::WrongStateRoot { proof, .. } |
+            ValidationError::InvalidParent { proof, .. } |
+            ValidationError::WrongParentShard { proof, .. } |
+            ValidationError::WrongParentRoot { proof, .. } |
+            ValidationError::WrongShard { proof, .. } |
+            ValidationError::UnequalCoins { proof, .. } |
+            ValidationError::ValueOverflow { proof, .. } |
+            ValidationError::WrongInputValue { proof, .. } |
+            ValidationError::DoubleSpending { proof, .. } |
+            ValidationError::MissingInput { proof, .. } |
+            ValidationError::WrongSignature { proof, .. } |
+            ValidationError::LockNotSatisfied { proof, .. } |
+            ValidationError::MissingTestimony { proof, .. } |
+            ValidationError::WrongTestimony { proof, .. } => Some(proof),
+            _ => None,
+        }
+    }
+
+    pub fn fraud_proof(&self) -> Option<&FraudProof> {
+        match self {
+            ValidationError::WrongStateRoot { proof, .. } |
+            ValidationError::InvalidParent { proof, .. } |
+            ValidationError::WrongParentShard { proof, .. } |
+            ValidationError::WrongParentRoot { proof, .. } |
+            ValidationError::WrongShard { proof, .. } |
+            ValidationError::UnequalCoins { proof, .. } |
+            ValidationError::ValueOverflow { proof, .. } |
+            ValidationError::WrongInputValue { proof, .. } |
+            ValidationError::DoubleSpending { proof, .. } |
+            ValidationError::MissingInput { proof, .. } |
+            ValidationError::WrongSignature { proof, .. } |
+            ValidationError::LockNotSatisfied { proof, .. } |
+            ValidationError::MissingTestimony { proof, .. } |
+            ValidationError::WrongTestimony { proof, .. } => proof.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn fraud_proof_mut(&mut self) -> Option<&mut FraudProof> {
+        self.proof_slot()?.as_deref_mut()
+    }
+
+    //attach the proof, a fault which cannot be proved keeps none
+    pub fn with_fraud_proof(mut self, fraud_proof: FraudProof) -> Self {
+        if let Some(proof) = self.proof_slot() {
+            *proof = Some(Box::new(fraud_proof));
+        }
+        self
+    }
+
+    pub fn into_fraud_proof(mut self) -> Option<FraudProof> {
+        self.proof_slot()?.take().map(|proof| *proof)
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::WrongBlockHash { block_hash } =>
+                write!(f, "block {} does not match its hash", block_hash),
+            ValidationError::UnknownParent { block_hash, parent_hash } =>
+                write!(f, "parent {} of block {} not found", parent_hash, block_hash),
+            ValidationError::WrongStateRoot { block_hash, expected, actual, .. } =>
+                write!(f, "block {} commits state root {}, expected {}", block_hash, actual, expected),
+            ValidationError::InvalidParent { block_hash, parent_hash, shard_id, .. } =>
+                write!(f, "parent {} of block {} is not a valid block of shard {}", parent_hash, block_hash, shard_id),
+            ValidationError::WrongParentShard { block_hash, parent_hash, shard_id, .. } =>
+                write!(f, "parent {} of block {} is not a block of shard {}", parent_hash, block_hash, shard_id),
+            ValidationError::WrongParentRoot { block_hash, .. } =>
+                write!(f, "parents of block {} do not match its header", block_hash),
+            ValidationError::KnownTx { tx_hash } =>
+                write!(f, "tx {} is already known", tx_hash),
+            ValidationError::WrongShard { tx_hash, shard_id, .. } =>
+                write!(f, "tx {} does not belong to shard {}", tx_hash, shard_id),
+            ValidationError::UnequalCoins { tx_hash, input_value, output_value, .. } =>
+                write!(f, "tx {} spends {} coins with inputs of {} coins", tx_hash, output_value, input_value),
+            ValidationError::ValueOverflow { tx_hash, .. } =>
+                write!(f, "the coins of tx {} overflow", tx_hash),
+            ValidationError::WrongInputValue { tx_hash, input_index, claimed, actual, .. } =>
+                write!(f, "input {} of tx {} claims {} coins of a utxo of {} coins", input_index, tx_hash, claimed, actual),
+            ValidationError::DoubleSpending { tx_hash, input_index, .. } =>
+                write!(f, "input {} of tx {} is spent already", input_index, tx_hash),
+            ValidationError::MissingInput { tx_hash, input_index, .. } =>
+                write!(f, "input {} of tx {} not found in the state", input_index, tx_hash),
+            ValidationError::WrongSignature { tx_hash, input_index, .. } =>
+                write!(f, "wrong signature of input {} of tx {}", input_index, tx_hash),
+            ValidationError::LockNotSatisfied { tx_hash, input_index, .. } =>
+                write!(f, "input {} of tx {} does not satisfy the lock of its utxo", input_index, tx_hash),
+            ValidationError::MalformedLock { tx_hash, output_index } =>
+                write!(f, "lock of output {} of tx {} is malformed", output_index, tx_hash),
+            ValidationError::MalformedWitness { tx_hash, input_index } =>
+                write!(f, "witness of input {} of tx {} is malformed", input_index, tx_hash),
+            ValidationError::EpochClosed { tx_hash, height } =>
+                write!(f, "tx {} is not allowed at height {} before the end of the epoch", tx_hash, height),
+            ValidationError::MissingTestimony { tx_hash, .. } =>
+                write!(f, "testimony of tx {} not found", tx_hash),
+            ValidationError::IncompleteTestimony { tx_hash } =>
+                write!(f, "testimony of tx {} misses some units", tx_hash),
+            ValidationError::WrongTestimony { tx_hash, cause, .. } =>
+                write!(f, "wrong testimony of tx {}: {}", tx_hash, cause),
+            ValidationError::MissingTestimonyUnit { tx_hash, unit_hash } =>
+                write!(f, "unit {} of tx {} not found in the testimony", unit_hash, tx_hash),
+            ValidationError::OriginBlockNotFound { tx_hash, block_hash, shard_id } =>
+                write!(f, "originate block {} of tx {} not found in shard {}", block_hash, tx_hash, shard_id),
+            ValidationError::WrongOriginProof { tx_hash, block_hash, shard_id } =>
+                write!(f, "tx {} not proved in originate block {} of shard {}", tx_hash, block_hash, shard_id),
+            ValidationError::OriginNotInLongestChain { tx_hash, block_hash, shard_id } =>
+                write!(f, "originate block {} of tx {} not in the longest chain of shard {}", block_hash, tx_hash, shard_id),
+            ValidationError::OriginNotConfirmed { tx_hash, block_hash, shard_id } =>
+                write!(f, "originate block {} of tx {} not confirmed in shard {}", block_hash, tx_hash, shard_id),
+        }
+    }
+}
@@ -1,16 +1,27 @@
+pub mod sig_cache;
+pub mod error;
+pub mod stats;
+
 use crate::{
     manifoldchain::{
         blockchain::{
+            Blockchain,
             State,
             VerStatus,
         },
         multichain::Multichain,
-        transaction::{Transaction, UtxoInput, TxFlag},
+        transaction::{
+            Transaction,
+            TxFlag,
+            UtxoInput,
+            script::LockContext,
+        },
         block::{
             Info, 
             Content, 
             exclusive_block::ExclusiveBlock,
             inclusive_block::InclusiveBlock,
+            consensus_block::ConsensusBlock,
             versa_block::{
                 VersaBlock,
                 ExclusiveFullBlock,
@@ -31,6 +42,10 @@ use crate::{
             WrongSignatureProof,
             TestimonyLostProof,
             WrongTestimonyProof,
+            WrongStateRootProof,
+            InvalidParentProof,
+            WrongParentShardProof,
+            WrongParentRootProof,
         },
         network::worker::{
             Sample,
@@ -39,19 +54,25 @@ use crate::{
     },
     types::{
         hash::{Hashable, H256},
+        address::Address,
         merkle::MerkleTree,
+        sparse_merkle::SparseMerkleProof,
     },
 };
 use std::{
     sync::{Arc, Mutex},
     collections::HashMap,
+    thread,
 };
 use log::{info, debug};
+use sig_cache::SigCache;
+use error::ValidationError;
 
 pub struct Validator {
     multichain: Multichain,
     mempool: Arc<Mutex<Mempool>>,
     config: Configuration,
+    sig_cache: SigCache,
 }
 
 impl Clone for Validator {
@@ -60,6 +81,7 @@ impl Clone for Validator {
             multichain: self.multichain.clone(),
             mempool: Arc::clone(&self.mempool),
             config: self.config.clone(),
+            sig_cache: self.sig_cache.clone(),
         }
     }
 }
@@ -75,6 +97,62 @@ pub enum CrossUtxoStatus {
     Confirmed,
 }
 
+//the outcome of checking a fraud proof, which cannot be judged before the blocks it
+//refers to are known
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProofCheck {
+    Valid,
+    Invalid,
+    Unknown,
+}
+
+impl From<bool> for ProofCheck {
+    fn from(is_valid: bool) -> Self {
+        match is_valid {
+            true => ProofCheck::Valid,
+            false => ProofCheck::Invalid,
+        }
+    }
+}
+
+//the blocks the fraud proofs are checked against: the chains of the node, or the
+//blocks exported along with a proof
+pub trait ChainView {
+    fn get_block_by_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock>;
+    fn get_verify_status_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VerStatus>;
+    fn all_blocks_end_with_block_with_shard(&self, hash: &H256, shard_id: usize)
+        -> Option<Vec<H256>>;
+    fn get_consensus_block_by_shard(&self, shard_id: usize, hash: &H256) -> Option<ConsensusBlock>;
+    fn get_state_block_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock>;
+    fn is_block_in_longest_chain(&self, shard_id: usize, hash: &H256) -> bool;
+    fn is_block_confirmed(&self, shard_id: usize, hash: &H256) -> bool;
+}
+
+impl ChainView for Multichain {
+    fn get_block_by_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock> {
+        Multichain::get_block_by_shard(self, hash, shard_id)
+    }
+    fn get_verify_status_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VerStatus> {
+        Multichain::get_verify_status_with_shard(self, hash, shard_id)
+    }
+    fn all_blocks_end_with_block_with_shard(&self, hash: &H256, shard_id: usize)
+        -> Option<Vec<H256>> {
+        Multichain::all_blocks_end_with_block_with_shard(self, hash, shard_id)
+    }
+    fn get_consensus_block_by_shard(&self, shard_id: usize, hash: &H256) -> Option<ConsensusBlock> {
+        Multichain::get_consensus_block_by_shard(self, shard_id, hash)
+    }
+    fn get_state_block_with_shard(&self, hash: &H256, shard_id: usize) -> Option<VersaBlock> {
+        Multichain::get_state_block_with_shard(self, hash, shard_id)
+    }
+    fn is_block_in_longest_chain(&self, shard_id: usize, hash: &H256) -> bool {
+        Multichain::is_block_in_longest_chain(self, shard_id, hash)
+    }
+    fn is_block_confirmed(&self, shard_id: usize, hash: &H256) -> bool {
+        Multichain::is_block_confirmed(self, shard_id, hash)
+    }
+}
+
 
 impl Validator {
     pub fn new(
@@ -86,31 +164,132 @@ impl Validator {
             multichain: multichain.clone(),
             mempool: Arc::clone(mempool),
             config: config.clone(),
+            sig_cache: SigCache::new(config.max_sig_cache),
+        }
+    }
+
+    //the heights a lock of the spent tx is checked with, for a tx spending it in a
+    //block on top of the parent
+    fn get_lock_context(&self, input_tx: &Transaction, parent: &H256) -> LockContext {
+        let height = self.multichain
+            .get_block_height_with_shard(parent, self.config.shard_id())
+            .map_or(0, |height| height + 1) as u64;
+        //a utxo whose block is unknown, e.g. one migrated by a checkpoint, counts as
+        //just created, so that no relative timelock is satisfied early
+        let age = match self.multichain.get_tx_height(&input_tx.hash(), parent) {
+            Some(utxo_height) => height.saturating_sub(utxo_height as u64),
+            None => 0,
+        };
+        LockContext {
+            height,
+            age,
         }
     }
 
+    //whether the input may spend its utxo, the heights are only looked up for a lock
+    fn check_spend(
+        &self,
+        input_tx: &Transaction,
+        tx: &Transaction,
+        input: &UtxoInput,
+        parent: &H256
+    ) -> bool {
+        let locked = input_tx.outputs
+            .get(input.index as usize)
+            .map_or(false, |output| output.lock.is_some());
+        let ctx = match locked {
+            true => self.get_lock_context(input_tx, parent),
+            false => LockContext { height: 0, age: 0 },
+        };
+        Transaction::check_spend(input_tx, tx, input, &ctx, &|signed_tx, public_key_ref, sig_ref| {
+            self.sig_cache.verify(signed_tx, public_key_ref, sig_ref)
+        })
+    }
+
+    //the input claims the value of the output it spends, otherwise the sums of the tx
+    //do not mean anything
+    fn check_input_value(
+        tx: &Transaction,
+        input_index: usize,
+        input_tx: &Transaction
+    ) -> Result<(), ValidationError> {
+        let input = &tx.inputs[input_index];
+        let actual = match input_tx.outputs.get(input.index as usize) {
+            Some(output) => output.value,
+            None => return Ok(()),
+        };
+        if input.value == actual {
+            return Ok(());
+        }
+        Err(ValidationError::WrongInputValue {
+            tx_hash: tx.hash(),
+            input_index,
+            claimed: input.value,
+            actual,
+            proof: None,
+        }.with_fraud_proof(FraudProof::UnequalCoins(
+            UnequalCoinsProof {
+                shard_id: 0,
+                block_hash: H256::default(),
+                invalid_tx: Transaction::default(),
+                invalid_tx_merkle_proof: vec![],
+                invalid_index: 0,
+
+                conflict_tx: Some(input_tx.clone()),
+                conflict_block_hash: H256::default(),
+                conflict_tx_merkle_proof: vec![],
+                conflict_index: 0,
+            }
+        )))
+    }
+
     pub fn check_input_from_state(
-        input: & UtxoInput, 
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        parent: &H256,
         state: & State
-    ) -> Result<(Transaction, Option<Testimony>), FraudProof> 
+    ) -> Result<(Transaction, Option<Testimony>), ValidationError> 
     {
+        let input = &tx.inputs[input_index];
         match state.get(&(input.tx_hash.clone(), input.index)) {
             Some(item) => {
-                let tx = item.0.clone();
+                let input_tx = item.0.clone();
                 let tmy = item.1.clone();
-                let sig_vec = input.sig_ref.clone();
                 let index: usize = input.index as usize;
                 //handle reject-tx
-                if let TxFlag::Reject = tx.flag {
+                if let TxFlag::Reject = input_tx.flag {
                     //complete later
-                    return Ok((tx, tmy));
+                    return Ok((input_tx, tmy));
+                }
+                let locked = match input_tx.outputs.get(index) {
+                    Some(output) => output.lock.is_some(),
+                    None => return Err(ValidationError::MissingInput {
+                        tx_hash: tx.hash(),
+                        input_index,
+                        proof: None,
+                    }),
+                };
+                if let Err(err) = Self::check_input_value(tx, input_index, &input_tx) {
+                    return Err(err);
                 }
-                let output = tx.outputs.get(index).unwrap();
-                let pub_key = output.public_key_ref.clone();
-                match Transaction::verify(&tx, pub_key.as_slice(), sig_vec.as_slice()) {
-                    true => Ok((tx, tmy)),
+                match self.check_spend(&input_tx, tx, input, parent) {
+                    true => Ok((input_tx, tmy)),
                     false => {
-                        Err(FraudProof::WrongSignature(
+                        //an unsatisfied lock is proved the same way as a wrong signature
+                        let err = match locked {
+                            true => ValidationError::LockNotSatisfied {
+                                tx_hash: tx.hash(),
+                                input_index,
+                                proof: None,
+                            },
+                            false => ValidationError::WrongSignature {
+                                tx_hash: tx.hash(),
+                                input_index,
+                                proof: None,
+                            },
+                        };
+                        Err(err.with_fraud_proof(FraudProof::WrongSignature(
                             WrongSignatureProof {
                                 shard_id: 0,
 
@@ -120,44 +299,43 @@ impl Validator {
                                 invalid_index: 0,
 
                                 conflict_block_hash: H256::default(),
-                                conflict_tx: tx.clone(),
+                                conflict_tx: input_tx.clone(),
                                 conflict_tx_merkle_proof: vec![],
                                 conflict_index: 0,
                             }
-                        ))
+                        )))
                     }
                 }
             }
             None => {
-                Err(FraudProof::UnsolvedFault)
+                Err(ValidationError::MissingInput {
+                    tx_hash: tx.hash(),
+                    input_index,
+                    proof: None,
+                })
             }
         }
     }
 
     
     
-    pub fn get_shard_id(hash: &H256, shard_num: usize) -> usize {
-        //one u8 can represent 256 shards
-        let byte_size = shard_num / 256 + 1;
-        let mut value: usize = 0;
-        for i in 32-byte_size..32 {
-            value = value*256 + (hash.0[i] as usize);
-        }
-        value % shard_num
+    /// The shard of an address under the shard policy of the configuration
+    pub fn get_shard_id(addr: &Address, config: &Configuration) -> usize {
+        config.shard_map.get_shard_id(addr, config.shard_num)
     }
     
     //if there is one input/output locating at the current shard, then it belongs to the current
     //shard
-    pub fn check_tx_ownership(tx: &Transaction, shard_id: usize, shard_num: usize) -> bool {
+    pub fn check_tx_ownership(tx: &Transaction, shard_id: usize, config: &Configuration) -> bool {
         for input in tx.inputs.iter() {
-            let input_shard_id = Self::get_shard_id(&input.sender_addr, shard_num);
+            let input_shard_id = Self::get_shard_id(&input.sender_addr, config);
             if input_shard_id == shard_id {
                 return true;
             }
         }
 
         for output in tx.outputs.iter() {
-            let output_shard_id = Self::get_shard_id(&output.receiver_addr, shard_num);
+            let output_shard_id = Self::get_shard_id(&output.receiver_addr, config);
             if output_shard_id == shard_id {
                 return true;
             }
@@ -204,7 +382,7 @@ impl Validator {
         tx: &Transaction,
         tmy: Option<Testimony>,
         parent: Option<&H256>, 
-        flag: ValidationSource) -> Result<bool, FraudProof> 
+        flag: ValidationSource) -> Result<bool, ValidationError> 
     {
         if let TxFlag::Initial = tx.flag {
           //      let history = self.multichain.all_blocks_end_with_block(parent_hash).unwrap();
@@ -220,52 +398,18 @@ impl Validator {
             return Ok(true);
         }
 
-        //1. check whether the tx belongs to the node accroding to the shard id
-        let tx_hash = tx.hash();
-        if !Self::check_tx_ownership(tx, self.config.shard_id, self.config.shard_num) {
-            //Just return a husk
-            return Err(FraudProof::WrongShard(
-                WrongShardProof {
-                    shard_id: self.config.shard_id as u32,
-                    block_hash: H256::default(),
-                    invalid_tx: tx.clone(),
-                    invalid_tx_merkle_proof: vec![],
-                    invalid_index: 0
-                }
-            ));
-        } 
-
-        let mut available_coins: u32 = 0;
-        let mut spent_coins: u32 = 0;
-        
-        for input in tx.inputs.iter() {
-            available_coins += input.value;
-        }
-
-        for output in tx.outputs.iter() {
-            spent_coins += output.value;
-        }
-
-        if available_coins != spent_coins {
-            return Err(FraudProof::UnequalCoins(
-                UnequalCoinsProof {
-                    shard_id: self.config.shard_id as u32,
-                    block_hash: H256::default(),
-                    invalid_tx: tx.clone(),
-                    invalid_tx_merkle_proof: vec![],
-                    invalid_index: 0
-                }
-            ));
-        }
+        //1. check the format of the tx
+        self.check_tx_format(tx)?;
 
+        let tx_hash = tx.hash();
         if let ValidationSource::FromTransaction = flag {
             //2. If it is a coming transaction, check if it is new 
             if self.mempool.lock().unwrap().check(&tx_hash) {
-                return Err(FraudProof::UnsolvedFault);
+                return Err(ValidationError::KnownTx { tx_hash });
             }
             if let Some(_) = self.multichain
                         .get_tx_in_longest_chain(&tx_hash) {
-                return Err(FraudProof::UnsolvedFault);
+                return Err(ValidationError::KnownTx { tx_hash });
             }
             Ok(true)
         } else {
@@ -292,13 +436,217 @@ impl Validator {
        
     }
 
-    pub fn validate_block(&self, block: &VersaBlock, parent: &H256) -> Result<bool, FraudProof> {
+    //the checks of a tx which need neither the state nor the other txs
+    pub fn check_tx_format(&self, tx: &Transaction) -> Result<bool, ValidationError> {
+        if let TxFlag::Initial = tx.flag {
+            return Ok(true);
+        }
+        if let TxFlag::Empty = tx.flag {
+            return Ok(true);
+        }
+
+        //check whether the tx belongs to the node accroding to the shard id
+        if !Self::check_tx_ownership(tx, self.config.shard_id(), &self.config) {
+            //Just return a husk
+            return Err(ValidationError::WrongShard {
+                tx_hash: tx.hash(),
+                shard_id: self.config.shard_id(),
+                proof: None,
+            }.with_fraud_proof(FraudProof::WrongShard(
+                WrongShardProof {
+                    shard_id: self.config.shard_id() as u32,
+                    block_hash: H256::default(),
+                    invalid_tx: tx.clone(),
+                    invalid_tx_merkle_proof: vec![],
+                    invalid_index: 0
+                }
+            )));
+        } 
+
+        //the locks of the outputs and the witnesses of the inputs are bounded, so that
+        //spending them is cheap to check
+        for (input_index, input) in tx.inputs.iter().enumerate() {
+            if let Some(witness) = input.witness.as_ref() {
+                if !witness.is_well_formed() {
+                    return Err(ValidationError::MalformedWitness {
+                        tx_hash: tx.hash(),
+                        input_index,
+                    });
+                }
+            }
+        }
+        for (output_index, output) in tx.outputs.iter().enumerate() {
+            if let Some(lock) = output.lock.as_ref() {
+                if !lock.is_well_formed() {
+                    return Err(ValidationError::MalformedLock {
+                        tx_hash: tx.hash(),
+                        output_index,
+                    });
+                }
+            }
+        }
+
+        //an overflowing sum is proved the same way as unequal coins
+        let unequal_coins_fp = FraudProof::UnequalCoins(
+            UnequalCoinsProof {
+                shard_id: self.config.shard_id() as u32,
+                block_hash: H256::default(),
+                invalid_tx: tx.clone(),
+                invalid_tx_merkle_proof: vec![],
+                invalid_index: 0,
+
+                conflict_tx: None,
+                conflict_block_hash: H256::default(),
+                conflict_tx_merkle_proof: vec![],
+                conflict_index: 0,
+            }
+        );
+        let (available_coins, spent_coins) = match (
+            tx.get_input_value(),
+            tx.get_output_value()
+        ) {
+            (Some(available_coins), Some(spent_coins)) => (available_coins, spent_coins),
+            _ => {
+                return Err(ValidationError::ValueOverflow {
+                    tx_hash: tx.hash(),
+                    proof: None,
+                }.with_fraud_proof(unequal_coins_fp));
+            }
+        };
+
+        if available_coins != spent_coins {
+            return Err(ValidationError::UnequalCoins {
+                tx_hash: tx.hash(),
+                input_value: available_coins,
+                output_value: spent_coins,
+                proof: None,
+            }.with_fraud_proof(unequal_coins_fp));
+        }
+        Ok(true)
+    }
+
+    //check the signatures of the inputs spending the coins of this shard, the coins
+    //missing from the state are left to the check against the state
+    pub fn check_tx_signatures(
+        &self,
+        tx: &Transaction,
+        parent: &H256,
+        state: &State
+    ) -> Result<bool, ValidationError> {
+        self.check_tx_signatures_with(tx, parent, &|utxo| {
+            state.get(utxo).map(|(input_tx, _)| input_tx.clone())
+        })
+    }
+
+    //the same check with the spent txs looked up one by one, e.g. in the shared state
+    //of the longest chain
+    pub fn check_tx_signatures_with(
+        &self,
+        tx: &Transaction,
+        parent: &H256,
+        get_utxo: &dyn Fn(&(H256, u32)) -> Option<Transaction>
+    ) -> Result<bool, ValidationError> {
+        if tx.flag != TxFlag::Input && tx.flag != TxFlag::Domestic {
+            return Ok(true);
+        }
+        for (input_index, input) in tx.inputs.iter().enumerate() {
+            let shard_id = Self::get_shard_id(&input.sender_addr, &self.config);
+            if shard_id != self.config.shard_id() {
+                continue;
+            }
+            let input_tx = match get_utxo(&(input.tx_hash.clone(), input.index)) {
+                Some(input_tx) => input_tx,
+                None => continue,
+            };
+            if let TxFlag::Reject = input_tx.flag {
+                continue;
+            }
+            let output = match input_tx.outputs.get(input.index as usize) {
+                Some(output) => output,
+                None => continue,
+            };
+            if output.value != input.value {
+                return Err(ValidationError::WrongInputValue {
+                    tx_hash: tx.hash(),
+                    input_index,
+                    claimed: input.value,
+                    actual: output.value,
+                    proof: None,
+                });
+            }
+            if !self.check_spend(&input_tx, tx, input, parent) {
+                return Err(match output.lock.is_some() {
+                    true => ValidationError::LockNotSatisfied {
+                        tx_hash: tx.hash(),
+                        input_index,
+                        proof: None,
+                    },
+                    false => ValidationError::WrongSignature {
+                        tx_hash: tx.hash(),
+                        input_index,
+                        proof: None,
+                    },
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    //check the format and the signatures of the txs of a block on several threads, the
+    //signatures are only cached here and the faults are found again in the order of the txs
+    fn precheck_txs(
+        &self,
+        txs: &[Transaction],
+        parent: &H256,
+        state: &State
+    ) -> Vec<Result<bool, ValidationError>> {
+        let num_threads = match self.config.verify_threads {
+            0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        };
+        let chunk_size = (txs.len() + num_threads - 1) / num_threads;
+        if chunk_size == 0 {
+            return vec![];
+        }
+        crossbeam::thread::scope(|s| {
+            let handles: Vec<_> = txs
+                .chunks(chunk_size)
+                .map(|chunk| s.spawn(move |_| {
+                    chunk
+                        .iter()
+                        .map(|tx| {
+                            let res = self.check_tx_format(tx);
+                            if res.is_ok() {
+                                let _ = self.check_tx_signatures(tx, parent, state);
+                            }
+                            res
+                        })
+                        .collect::<Vec<_>>()
+                }))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        }).unwrap()
+    }
+
+    /// The height of a block of the own shard mined on top of the parents
+    pub fn get_block_height(&self, inter_parents: &[H256]) -> usize {
+        inter_parents
+            .iter()
+            .filter_map(|parent| self.multichain.get_block_height_with_shard(parent, self.config.shard_id()))
+            .max()
+            .unwrap_or(0) + 1
+    }
+
+    pub fn validate_block(&self, block: &VersaBlock, parent: &H256) -> Result<bool, ValidationError> {
         //check whether the PoW is valid
         let blk_hash = block.hash();
         
         //check the hash value is corrent
         if !block.verify_hash() {
-            return Err(FraudProof::UnsolvedFault);
+            return Err(ValidationError::WrongBlockHash { block_hash: blk_hash });
         }
         
         //For exclusive blocks and inclusive blocks, skip the verification of transactions
@@ -317,7 +665,7 @@ impl Validator {
         //for parent in block.get_inter_parents() {
         //    match self.multichain.get_block_height_with_shard(
         //        &parent,
-        //        self.config.shard_id,
+        //        self.config.shard_id(),
         //    ) {
         //        Some(height) => {
         //            if height >= longest_height {
@@ -336,7 +684,10 @@ impl Validator {
             Some(_) => {}
             None => {
                 info!("validation: parent not found");
-                return Err(FraudProof::UnsolvedFault);              
+                return Err(ValidationError::UnknownParent {
+                    block_hash: blk_hash,
+                    parent_hash: parent.clone(),
+                });
             }
         }
         let states = self.multichain.get_states();
@@ -350,102 +701,57 @@ impl Validator {
         let mut set: HashMap<H256, (Transaction, usize)> = HashMap::new();
         let txs = block.get_txs_ref().unwrap();
         let tmys = block.get_tmys().unwrap();
+        //the txs which would not settle before the end of the epoch are closed
+        if let Some(plan) = self.config.reshard.as_ref() {
+            let height = self.get_block_height(&block.get_inter_parents());
+            if let Some(tx) = txs.iter().find(|tx| !plan.allows(tx, height)) {
+                return Err(ValidationError::EpochClosed {
+                    tx_hash: tx.hash(),
+                    height,
+                });
+            }
+        }
+        let prechecks = self.precheck_txs(txs, parent, state);
         for i in 0..txs.len() {
             let tx_ref = &txs[i];
             let tx_hash = tx_ref.hash();
             let tmy = tmys.get(&tx_hash);
-            match self.validate_tx(
-                tx_ref,
-                tmy.cloned(),
-                Some(parent),
-                ValidationSource::FromBlock
-            ) {
+            let res = match &prechecks[i] {
+                Ok(_) => self.check_tx_from_state(
+                    tx_ref,
+                    tmy.cloned(),
+                    parent,
+                    state
+                ),
+                Err(proof) => Err(proof.clone()),
+            };
+            match res {
                 Ok(_) => {}
-                Err(proof) => {
+                Err(mut err) => {
                     info!("invalid tx {:?} in validating block: {:?}", tx_ref, blk_hash);
-                    info!("invalid type: {:?}", proof);
-                    match proof {
-                        FraudProof::DoubleSpending(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.invalid_block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::DoubleSpending(fp));
-                        }
-                        FraudProof::UtxoLost(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::UtxoLost(fp));
-                        }
-                        FraudProof::WrongShard(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::WrongShard(fp));
-                        }
-                        FraudProof::UnequalCoins(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::UnequalCoins(fp));
-                        }
-                        FraudProof::WrongSignature(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.invalid_block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::WrongSignature(fp));
-                        }
-                        FraudProof::TestimonyLost(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-                            return Err(FraudProof::TestimonyLost(fp));
-                        }
-                        FraudProof::WrongTestimony(mut fp) => {
-                            let invalid_tx_merkle_proof = block
-                                .get_tx_merkle_proof(i).unwrap();
-                            fp.block_hash = blk_hash.clone();
-                            fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
-                            fp.invalid_index = i as u32;
-
-                            let invalid_tmy = tmy.unwrap();
-                            let (invalid_tmy_merkle_proof, invalid_tmy_index) = block
-                                .get_tmy_merkle_proof(&invalid_tmy.hash()).unwrap();
-                            fp.invalid_tmy = invalid_tmy.clone();
-                            fp.invalid_tmy_merkle_proof = invalid_tmy_merkle_proof;
-                            fp.invalid_tmy_index = invalid_tmy_index as u32;
-                            return Err(FraudProof::WrongTestimony(fp));
-                        }
-                        FraudProof::UnsolvedFault => {
-                            return Err(FraudProof::UnsolvedFault);
-                        }
+                    info!("invalid type: {}", err);
+                    if let Some(proof) = err.fraud_proof_mut() {
+                        Self::locate_fraud_proof(proof, block, i, tmy);
                     }
+                    return Err(err);
                 }
             }
             if tx_ref.flag == TxFlag::Input ||
                 tx_ref.flag == TxFlag::Domestic {
-                for input in tx_ref.inputs.iter() {
+                for (input_index, input) in tx_ref.inputs.iter().enumerate() {
                     match set.get(&input.hash()) {
                         Some((conflict_tx, conflict_index)) => {
                             let invalid_tx_merkle_proof = block
                                 .get_tx_merkle_proof(i).unwrap();
                             let conflict_tx_merkle_proof = block
                                 .get_tx_merkle_proof(*conflict_index).unwrap();
-                            return Err(FraudProof::DoubleSpending(
+                            return Err(ValidationError::DoubleSpending {
+                                tx_hash,
+                                input_index,
+                                proof: None,
+                            }.with_fraud_proof(FraudProof::DoubleSpending(
                                 DoubleSpendingProof {
-                                    shard_id: self.config.shard_id as u32,
+                                    shard_id: self.config.shard_id() as u32,
 
                                     invalid_tx: tx_ref.clone(),
                                     invalid_block_hash: blk_hash.clone(),
@@ -457,7 +763,7 @@ impl Validator {
                                     conflict_tx_merkle_proof,
                                     conflict_index: *conflict_index as u32,
                                 }
-                            ))
+                            )))
                         }
                         None => {
                             set.insert(input.hash(), (tx_ref.clone(), i));
@@ -466,9 +772,196 @@ impl Validator {
                 }
             }
         }
+
+        //check the state root against the state of the parent
+        let state_root = Blockchain::get_next_state_root(
+            state,
+            txs,
+            self.config.shard_id(),
+            &self.config
+        );
+        if state_root != block.get_state_root() {
+            info!("wrong state root in validating block: {:?}", blk_hash);
+            //replay the updates on the parent's state, proving each one against
+            //the root left by the previous one
+            let updates = Blockchain::get_state_updates(
+                txs,
+                self.config.shard_id(),
+                &self.config
+            );
+            let mut tree = Blockchain::get_state_tree(state);
+            let mut state_proofs: Vec<SparseMerkleProof> = vec![];
+            for (key, value) in updates {
+                state_proofs.push(tree.proof(&key));
+                tree.update(&key, value.as_ref());
+            }
+            return Err(ValidationError::WrongStateRoot {
+                block_hash: blk_hash.clone(),
+                expected: state_root,
+                actual: block.get_state_root(),
+                proof: None,
+            }.with_fraud_proof(FraudProof::WrongStateRoot(
+                WrongStateRootProof {
+                    shard_id: self.config.shard_id() as u32,
+                    block_hash: blk_hash.clone(),
+                    parent_hash: parent.clone(),
+                    txs: txs.clone(),
+                    state_proofs,
+                }
+            )));
+        }
+        Ok(true)
+    }
+
+    //fill in where the invalid tx of the proof is in the block
+    fn locate_fraud_proof(
+        proof: &mut FraudProof,
+        block: &VersaBlock,
+        index: usize,
+        tmy: Option<&Testimony>
+    ) {
+        let blk_hash = block.hash();
+        let invalid_tx_merkle_proof = block
+            .get_tx_merkle_proof(index).unwrap();
+        match proof {
+            FraudProof::DoubleSpending(fp) => {
+                fp.invalid_block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::UtxoLost(fp) => {
+                fp.block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::WrongShard(fp) => {
+                fp.block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::UnequalCoins(fp) => {
+                fp.block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::WrongSignature(fp) => {
+                fp.invalid_block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::TestimonyLost(fp) => {
+                fp.block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+            }
+            FraudProof::WrongTestimony(fp) => {
+                fp.block_hash = blk_hash;
+                fp.invalid_tx_merkle_proof = invalid_tx_merkle_proof;
+                fp.invalid_index = index as u32;
+
+                //the testimony of the tx, the one of the spent tx is kept otherwise
+                if let Some(invalid_tmy) = tmy {
+                    let (invalid_tmy_merkle_proof, invalid_tmy_index) = block
+                        .get_tmy_merkle_proof(&invalid_tmy.hash()).unwrap();
+                    fp.invalid_tmy = invalid_tmy.clone();
+                    fp.invalid_tmy_merkle_proof = invalid_tmy_merkle_proof;
+                    fp.invalid_tmy_index = invalid_tmy_index as u32;
+                }
+            }
+            //the proofs of the whole block
+            FraudProof::WrongStateRoot(_) => {}
+            FraudProof::InvalidParent(_) => {}
+            FraudProof::WrongParentShard(_) => {}
+            FraudProof::WrongParentRoot(_) => {}
+        }
+    }
+
+    //check the parents an inclusive block refers to, before looking for them in the
+    //chains, since a parent which is not a block would leave it an orphan forever
+    pub fn validate_parents(&self, block: &InclusiveBlock) -> Result<bool, ValidationError> {
+        let shard_id = block.get_shard_id() as u32;
+        let block_hash = block.hash();
+        if !block.verify_format() {
+            return Err(ValidationError::WrongParentRoot {
+                block_hash,
+                proof: None,
+            }.with_fraud_proof(FraudProof::WrongParentRoot(
+                WrongParentRootProof {
+                    shard_id,
+                    block: block.clone(),
+                }
+            )));
+        }
+        for (parent_hash, parent_shard_id) in block.get_all_parents() {
+            if !Self::is_possible_block(&self.config, &parent_hash, parent_shard_id) {
+                return Err(ValidationError::InvalidParent {
+                    block_hash,
+                    parent_hash: parent_hash.clone(),
+                    shard_id: parent_shard_id,
+                    proof: None,
+                }.with_fraud_proof(FraudProof::InvalidParent(
+                    InvalidParentProof {
+                        shard_id,
+                        block: block.clone(),
+                        parent_hash,
+                        parent_shard_id: parent_shard_id as u32,
+                        parent_proof: None,
+                    }
+                )));
+            }
+            if self.multichain.get_block_by_shard(&parent_hash, parent_shard_id).is_some() {
+                continue;
+            }
+            //the parent may be known as an exclusive block of another shard
+            for other_shard_id in 0..self.config.shard_num {
+                if other_shard_id == parent_shard_id {
+                    continue;
+                }
+                let parent_header = match self.multichain
+                    .get_consensus_block_by_shard(other_shard_id, &parent_hash) {
+                    Some(parent_header) => parent_header,
+                    None => continue,
+                };
+                if Self::is_wrong_parent_shard(&self.config, &parent_header, parent_shard_id) {
+                    return Err(ValidationError::WrongParentShard {
+                        block_hash,
+                        parent_hash,
+                        shard_id: parent_shard_id,
+                        proof: None,
+                    }.with_fraud_proof(FraudProof::WrongParentShard(
+                        WrongParentShardProof {
+                            shard_id,
+                            block: block.clone(),
+                            parent_shard_id: parent_shard_id as u32,
+                            parent_header,
+                        }
+                    )));
+                }
+            }
+        }
         Ok(true)
     }
 
+    //whether the hash can be the hash of a block in the shard: the genesis of the
+    //shard, or a hash meeting the PoW the shard is mined at
+    fn is_possible_block(config: &Configuration, hash: &H256, shard_id: usize) -> bool {
+        shard_id < config.shard_num && (
+            *hash == ExclusiveBlock::genesis_of(shard_id, config).hash() ||
+            *hash <= config.get_difficulty(shard_id)
+        )
+    }
+
+    //an exclusive block only lives in its own shard, while an inclusive block can be
+    //a parent in every shard
+    fn is_wrong_parent_shard(
+        config: &Configuration,
+        parent_header: &ConsensusBlock,
+        shard_id: usize
+    ) -> bool {
+        parent_header.get_shard_id() != shard_id &&
+            parent_header.hash() > config.thredshold
+    }
+
     pub fn validate_cross_utxo(
         &self, 
         tx: &Transaction, 
@@ -476,30 +969,54 @@ impl Validator {
         tmy: &Testimony,
         ori_shard_id: usize,
         status: CrossUtxoStatus
-    ) -> Result<bool, String> {
+    ) -> Result<bool, ValidationError> {
+        Self::check_cross_utxo(
+            &self.multichain,
+            &self.config,
+            tx,
+            unit_hash,
+            tmy,
+            ori_shard_id,
+            status
+        )
+    }
+
+    pub fn check_cross_utxo(
+        view: &dyn ChainView,
+        config: &Configuration,
+        tx: &Transaction, 
+        unit_hash: &H256, 
+        tmy: &Testimony,
+        ori_shard_id: usize,
+        status: CrossUtxoStatus
+    ) -> Result<bool, ValidationError> {
         let tx_hash = tx.hash();
+        let missing_unit = || ValidationError::MissingTestimonyUnit {
+            tx_hash: tx_hash.clone(),
+            unit_hash: unit_hash.clone(),
+        };
         let ori_block_hash = match tmy
             .get_ori_blk_hash(unit_hash.clone()) {
                 Some(hash) => hash,
                 None => {
-                    return Err(String::from("Originate block not exit"));
+                    return Err(missing_unit());
                 }
             };
         let tx_index = match tmy
             .get_tx_index(unit_hash.clone()) {
                 Some(index) => index,
                 None => {
-                    return Err(String::from("originate tx not found"));
+                    return Err(missing_unit());
                 }
             };
         let tx_merkle_proof = match tmy 
             .get_tx_merkle_proof(unit_hash.clone()) {
                 Some(proof) => proof,
                 None => {
-                    return Err(String::from("originate proof not found"));
+                    return Err(missing_unit());
                 }
             };
-        match self.multichain.get_consensus_block_by_shard(
+        match view.get_consensus_block_by_shard(
             ori_shard_id,
             &ori_block_hash
         ) {
@@ -510,50 +1027,50 @@ impl Validator {
                     &tx_hash,
                     &tx_merkle_proof,
                     tx_index,
-                    self.config.block_size,
+                    config.block_size,
                 ) {
-                    return Err(
-                        String::from(
-                            "validating cross utxo: some testimonies are not valid"
-                        )
-                    );
+                    return Err(ValidationError::WrongOriginProof {
+                        tx_hash,
+                        block_hash: ori_block_hash,
+                        shard_id: ori_shard_id,
+                    });
                 }
                 //verify whether the utxo is growing on the longest chain
                 match status {
                     CrossUtxoStatus::Available => {
-                        if !self.multichain.is_block_in_longest_chain(
+                        if !view.is_block_in_longest_chain(
                             ori_shard_id,
                             &ori_block_hash
                         ) {
-                            return Err(
-                                String::from(
-                                    "validating cross utxo exit: not extending the longest chain"
-                                )
-                            );
+                            return Err(ValidationError::OriginNotInLongestChain {
+                                tx_hash,
+                                block_hash: ori_block_hash,
+                                shard_id: ori_shard_id,
+                            });
                         }
                         Ok(true)
                     }
                     CrossUtxoStatus::Confirmed => {
-                        if !self.multichain.is_block_confirmed(
+                        if !view.is_block_confirmed(
                             ori_shard_id,
                             &ori_block_hash
                         ) {
-                            return Err(
-                                String::from(
-                                    "validating cross utxo confimed: originate block not confirmed"
-                                )
-                            );
+                            return Err(ValidationError::OriginNotConfirmed {
+                                tx_hash,
+                                block_hash: ori_block_hash,
+                                shard_id: ori_shard_id,
+                            });
                         }
                         Ok(true)
                     }
                 }
             }
             None => {
-                return Err(
-                    String::from(
-                        "validating cross utxo: originate block not found"
-                    )
-                );
+                return Err(ValidationError::OriginBlockNotFound {
+                    tx_hash,
+                    block_hash: ori_block_hash,
+                    shard_id: ori_shard_id,
+                });
             }
         }
     }
@@ -562,7 +1079,7 @@ impl Validator {
         tmy: Option<Testimony>,
         verified_parent: &H256,
         state: &State
-    ) -> Result<bool, FraudProof> {
+    ) -> Result<bool, ValidationError> {
         let flag = tx.flag.clone();
         match flag {
             TxFlag::Initial => Ok(true),
@@ -581,21 +1098,21 @@ impl Validator {
                 if let Some(tmy) = tmy {
                     self.check_output_tx(tx, &tmy)
                 } else {
-                    Err(FraudProof::UnsolvedFault)
+                    Err(self.missing_testimony(tx))
                 }
             }
             TxFlag::Accept => {
                 if let Some(tmy) = tmy {
                     self.check_accept_reject_tx(tx, &tmy)
                 } else {
-                    Err(FraudProof::UnsolvedFault)
+                    Err(self.missing_testimony(tx))
                 }
             }
             TxFlag::Reject => {
                 if let Some(tmy) = tmy {
                     self.check_accept_reject_tx(tx, &tmy)
                 } else {
-                    Err(FraudProof::UnsolvedFault)
+                    Err(self.missing_testimony(tx))
                 }
             }
         }
@@ -606,42 +1123,28 @@ impl Validator {
         tx: &Transaction,
         verified_parent: &H256,
         state: &State
-    ) -> Result<bool, FraudProof> {
+    ) -> Result<bool, ValidationError> {
         let mut set: HashMap<H256, bool> = HashMap::new();
-        for input in tx.inputs.iter() {
+        for (input_index, input) in tx.inputs.iter().enumerate() {
             let shard_id = Self::get_shard_id(
                 &input.sender_addr,
-                self.config.shard_num
+                &self.config
             );
-            if shard_id != self.config.shard_id {
+            if shard_id != self.config.shard_id() {
                 continue;
             }
             let input_hash = input.hash();
             //check whether the double spending happens inside the tx
             match set.get(&input_hash) {
                 Some(_) => {
-                    return Err(FraudProof::DoubleSpending(
-                        DoubleSpendingProof {
-                            shard_id: self.config.shard_id as u32,
-
-                            invalid_block_hash: H256::default(),
-                            invalid_tx: tx.clone(),
-                            invalid_tx_merkle_proof: vec![],
-                            invalid_index: 0 as u32,
-
-                            conflict_tx: tx.clone(),
-                            conflict_block_hash: H256::default(),
-                            conflict_tx_merkle_proof: vec![],
-                            conflict_index: 0 as u32,
-                        }
-                    ));                   
+                    return Err(self.inside_double_spending(tx, input_index));
                 }
                 None => {
                     set.insert(input_hash, true);
                 }
             }
             //check whether the coins exit in the state
-            match Self::check_input_from_state(input, state) {
+            match self.check_input_from_state(tx, input_index, verified_parent, state) {
                 Ok((input_tx, possible_tmy)) => {
                     match possible_tmy {
                         Some(tmy) => {
@@ -654,7 +1157,7 @@ impl Validator {
                                     for input_tx_input in input_tx.inputs.iter() {
                                         let ori_shard_id = Self::get_shard_id(
                                             &input_tx_input.sender_addr,
-                                            self.config.shard_num
+                                            &self.config
                                         );
                                         match self.validate_cross_utxo(
                                             &input_tx,
@@ -664,20 +1167,8 @@ impl Validator {
                                             CrossUtxoStatus::Confirmed
                                         ) {
                                             Ok(_) => {}
-                                            Err(_) => {
-                                                 return Err(FraudProof::WrongTestimony(
-                                                    WrongTestimonyProof {
-                                                        shard_id: self.config.shard_id as u32,
-                                                        block_hash: H256::default(),
-                                                        invalid_tx: tx.clone(),
-                                                        invalid_tx_merkle_proof: vec![],
-                                                        invalid_index: 0 as u32,
-
-                                                        invalid_tmy: tmy.clone(),
-                                                        invalid_tmy_merkle_proof: vec![],
-                                                        invalid_tmy_index: 0 as u32,
-                                                    }
-                                                ));
+                                            Err(e) => {
+                                                return Err(self.wrong_testimony(tx, &tmy, e));
                                             }
                                         }
                                     } 
@@ -686,7 +1177,7 @@ impl Validator {
                                     for input_tx_output in input_tx.outputs.iter() {
                                         let ori_shard_id = Self::get_shard_id(
                                             &input_tx_output.receiver_addr,
-                                            self.config.shard_num
+                                            &self.config
                                         );
                                         match self.validate_cross_utxo(
                                             &input_tx,
@@ -696,20 +1187,8 @@ impl Validator {
                                             CrossUtxoStatus::Confirmed
                                         ) {
                                             Ok(_) => {}
-                                            Err(_) => {
-                                                 return Err(FraudProof::WrongTestimony(
-                                                    WrongTestimonyProof {
-                                                        shard_id: self.config.shard_id as u32,
-                                                        block_hash: H256::default(),
-                                                        invalid_tx: tx.clone(),
-                                                        invalid_tx_merkle_proof: vec![],
-                                                        invalid_index: 0 as u32,
-
-                                                        invalid_tmy: tmy.clone(),
-                                                        invalid_tmy_merkle_proof: vec![],
-                                                        invalid_tmy_index: 0 as u32,
-                                                    }
-                                                ));
+                                            Err(e) => {
+                                                return Err(self.wrong_testimony(tx, &tmy, e));
                                             }                                           
                                         }
                                     }
@@ -724,14 +1203,13 @@ impl Validator {
                         }
                     }
                 }
-                Err(proof) => {
-                    let new_proof = self.handle_check_tx_from_state_err(
+                Err(err) => {
+                    return Err(self.handle_check_tx_from_state_err(
                         tx, 
-                        proof, 
+                        err, 
                         verified_parent,
                         &input_hash,
-                    );
-                    return Err(new_proof);
+                    ));
                 }
             }
         }
@@ -742,30 +1220,16 @@ impl Validator {
         &self,
         tx: &Transaction,
         tmy: &Testimony,
-    ) -> Result<bool, FraudProof> { //(commit or not, fraud proof)
+    ) -> Result<bool, ValidationError> { //(commit or not, fraud proof)
         let mut set: HashMap<H256, bool> = HashMap::new();
         let mut all_valid_inputs = true;
         let mut one_valid_input = false;
-        for input in tx.inputs.iter() {
+        for (input_index, input) in tx.inputs.iter().enumerate() {
             let input_hash = input.hash();
             //check whether the double spending occurs inside the tx
             match set.get(&input_hash) {
                 Some(_) => {
-                     return Err(FraudProof::DoubleSpending(
-                        DoubleSpendingProof {
-                            shard_id: self.config.shard_id as u32,
-
-                            invalid_block_hash: H256::default(),
-                            invalid_tx: tx.clone(),
-                            invalid_tx_merkle_proof: vec![],
-                            invalid_index: 0 as u32,
-
-                            conflict_tx: tx.clone(),
-                            conflict_block_hash: H256::default(),
-                            conflict_tx_merkle_proof: vec![],
-                            conflict_index: 0 as u32,
-                        }
-                    ));                   
+                    return Err(self.inside_double_spending(tx, input_index));
                 }
                 None => {
                     set.insert(input_hash, true);
@@ -775,7 +1239,7 @@ impl Validator {
             //check whether there is at least one valid input
             let ori_shard_id = Self::get_shard_id(
                 &input.sender_addr,
-                self.config.shard_num
+                &self.config
             );
             let mut ori_tx = tx.clone();
             ori_tx.flag = TxFlag::Input;
@@ -813,7 +1277,7 @@ impl Validator {
         &self,
         tx: &Transaction,
         tmy: &Testimony,
-    ) -> Result<bool, FraudProof> {
+    ) -> Result<bool, ValidationError> {
         //simply
         return Ok(true);
         let tx_hash = tx.hash();
@@ -821,13 +1285,17 @@ impl Validator {
         input_tx.flag = TxFlag::Input;
         if let None = self.multichain
             .get_tx_in_longest_chain(&input_tx.hash()) {
-            return Err(FraudProof::UnsolvedFault);
+            return Err(ValidationError::MissingInput {
+                tx_hash,
+                input_index: 0,
+                proof: None,
+            });
         }
         for output in tx.outputs.iter() {
             let output_hash = output.hash();
             let ori_shard_id = Self::get_shard_id(
                 &output.receiver_addr,
-                self.config.shard_num
+                &self.config
             );
             let mut ori_tx = tx.clone();
             ori_tx.flag = TxFlag::Output;
@@ -840,19 +1308,7 @@ impl Validator {
             ){
                 Ok(_) => {}
                 Err(e) => {
-                     return Err(FraudProof::WrongTestimony(
-                        WrongTestimonyProof {
-                            shard_id: self.config.shard_id as u32,
-                            block_hash: H256::default(),
-                            invalid_tx: tx.clone(),
-                            invalid_tx_merkle_proof: vec![],
-                            invalid_index: 0 as u32,
-
-                            invalid_tmy: tmy.clone(),
-                            invalid_tmy_merkle_proof: vec![],
-                            invalid_tmy_index: 0 as u32,
-                        }
-                    ));
+                    return Err(self.wrong_testimony(tx, tmy, e));
                 }
             }
         }
@@ -862,26 +1318,33 @@ impl Validator {
     fn handle_check_tx_from_state_err(
         &self, 
         tx: &Transaction, 
-        proof: FraudProof, 
+        err: ValidationError, 
         verified_parent: &H256,
         input_hash: &H256,
-    ) -> FraudProof {
-        match proof {
-            FraudProof::WrongSignature(mut fp) => {
-                fp.shard_id = self.config.shard_id as u32;
-                fp.invalid_tx = tx.clone();                            
-                //complete the information of conflict tx
-                let conflict_tx_hash = tx.hash();
-                let (conflict_block, conflict_index) = self.multichain
-                    .get_block_with_tx(&conflict_tx_hash).unwrap();
-                fp.conflict_block_hash = conflict_block.hash();
-                let conflict_tx_merkle_proof = conflict_block
-                    .get_tx_merkle_proof(conflict_index).unwrap();
-                fp.conflict_tx_merkle_proof = conflict_tx_merkle_proof;
-                fp.conflict_index = conflict_index as u32;
-                return FraudProof::WrongSignature(fp);
+    ) -> ValidationError {
+        match err {
+            ValidationError::WrongSignature { tx_hash, input_index, proof } => {
+                let err = ValidationError::WrongSignature { tx_hash, input_index, proof: None };
+                match self.complete_wrongsig_fp(tx, proof) {
+                    Some(fp) => err.with_fraud_proof(fp),
+                    None => err,
+                }
             }
-            FraudProof::UnsolvedFault => {
+            ValidationError::LockNotSatisfied { tx_hash, input_index, proof } => {
+                let err = ValidationError::LockNotSatisfied { tx_hash, input_index, proof: None };
+                match self.complete_wrongsig_fp(tx, proof) {
+                    Some(fp) => err.with_fraud_proof(fp),
+                    None => err,
+                }
+            }
+            ValidationError::WrongInputValue { tx_hash, input_index, claimed, actual, proof } => {
+                let err = ValidationError::WrongInputValue { tx_hash, input_index, claimed, actual, proof: None };
+                match self.complete_unequalcoins_fp(tx, proof) {
+                    Some(fp) => err.with_fraud_proof(fp),
+                    None => err,
+                }
+            }
+            ValidationError::MissingInput { tx_hash, input_index, .. } => {
                 let history_hash_vec = self.multichain
                     .all_blocks_end_with_block(verified_parent).unwrap();
                 let history_blocks: Vec<VersaBlock> = history_hash_vec
@@ -915,9 +1378,13 @@ impl Validator {
                     let conflict_tx_index = conflict_tx_index.unwrap();
                     let conflict_tx_merkle_proof = conflict_block
                         .get_tx_merkle_proof(conflict_tx_index).unwrap();
-                    return FraudProof::DoubleSpending(
+                    return ValidationError::DoubleSpending {
+                        tx_hash,
+                        input_index,
+                        proof: None,
+                    }.with_fraud_proof(FraudProof::DoubleSpending(
                         DoubleSpendingProof {
-                            shard_id: self.config.shard_id as u32,
+                            shard_id: self.config.shard_id() as u32,
                             invalid_block_hash: H256::default(),
                             invalid_tx: tx.clone(),
                             invalid_tx_merkle_proof: vec![],
@@ -928,48 +1395,173 @@ impl Validator {
                             conflict_tx_merkle_proof,
                             conflict_index: conflict_tx_index as u32,
                         }
-                    ); 
+                    )); 
                 } else {
-                    return FraudProof::UtxoLost(
+                    return ValidationError::MissingInput {
+                        tx_hash,
+                        input_index,
+                        proof: None,
+                    }.with_fraud_proof(FraudProof::UtxoLost(
                         UtxoLostProof {
-                            shard_id: self.config.shard_id as u32,
+                            shard_id: self.config.shard_id() as u32,
                             block_hash: H256::default(),
                             invalid_tx: tx.clone(),
                             invalid_tx_merkle_proof: vec![],
                             invalid_index: 0 as u32,
                         }
-                    );
+                    ));
                 }
             }
-            _ => {
-                return FraudProof::UnsolvedFault;
-            }
+            err => err,
         }
     }
 
-    pub fn verify_fraud_proof(&self, fraud_proof: &FraudProof) -> bool {
-        //need to be completed in the future
-        return true;
-        if let FraudProof::UnsolvedFault = fraud_proof {
-            return true;
+    //complete the information of conflict tx, the proof is dropped if the block of the
+    //spent tx is not known
+    fn complete_wrongsig_fp(
+        &self,
+        tx: &Transaction,
+        proof: Option<Box<FraudProof>>
+    ) -> Option<FraudProof> {
+        let mut fp = match proof.map(|proof| *proof) {
+            Some(FraudProof::WrongSignature(fp)) => fp,
+            _ => return None,
+        };
+        fp.shard_id = self.config.shard_id() as u32;
+        fp.invalid_tx = tx.clone();                            
+        let conflict_tx_hash = fp.conflict_tx.hash();
+        let (conflict_block, conflict_index) = self.multichain
+            .get_block_with_tx(&conflict_tx_hash)?;
+        fp.conflict_block_hash = conflict_block.hash();
+        fp.conflict_tx_merkle_proof = conflict_block
+            .get_tx_merkle_proof(conflict_index).unwrap();
+        fp.conflict_index = conflict_index as u32;
+        Some(FraudProof::WrongSignature(fp))
+    }
+
+    //complete the information of the spent tx, the same way as for a wrong signature
+    fn complete_unequalcoins_fp(
+        &self,
+        tx: &Transaction,
+        proof: Option<Box<FraudProof>>
+    ) -> Option<FraudProof> {
+        let mut fp = match proof.map(|proof| *proof) {
+            Some(FraudProof::UnequalCoins(fp)) => fp,
+            _ => return None,
+        };
+        fp.shard_id = self.config.shard_id() as u32;
+        fp.invalid_tx = tx.clone();
+        let conflict_tx_hash = fp.conflict_tx.as_ref()?.hash();
+        let (conflict_block, conflict_index) = self.multichain
+            .get_block_with_tx(&conflict_tx_hash)?;
+        fp.conflict_block_hash = conflict_block.hash();
+        fp.conflict_tx_merkle_proof = conflict_block
+            .get_tx_merkle_proof(conflict_index).unwrap();
+        fp.conflict_index = conflict_index as u32;
+        Some(FraudProof::UnequalCoins(fp))
+    }
+
+    //an input spent twice by the tx itself
+    fn inside_double_spending(&self, tx: &Transaction, input_index: usize) -> ValidationError {
+        ValidationError::DoubleSpending {
+            tx_hash: tx.hash(),
+            input_index,
+            proof: None,
+        }.with_fraud_proof(FraudProof::DoubleSpending(
+            DoubleSpendingProof {
+                shard_id: self.config.shard_id() as u32,
+
+                invalid_block_hash: H256::default(),
+                invalid_tx: tx.clone(),
+                invalid_tx_merkle_proof: vec![],
+                invalid_index: 0 as u32,
+
+                conflict_tx: tx.clone(),
+                conflict_block_hash: H256::default(),
+                conflict_tx_merkle_proof: vec![],
+                conflict_index: 0 as u32,
+            }
+        ))
+    }
+
+    fn missing_testimony(&self, tx: &Transaction) -> ValidationError {
+        ValidationError::MissingTestimony {
+            tx_hash: tx.hash(),
+            proof: None,
+        }.with_fraud_proof(FraudProof::TestimonyLost(
+            TestimonyLostProof {
+                shard_id: self.config.shard_id() as u32,
+                block_hash: H256::default(),
+                invalid_tx: tx.clone(),
+                invalid_tx_merkle_proof: vec![],
+                invalid_index: 0 as u32,
+            }
+        ))
+    }
+
+    fn wrong_testimony(&self, tx: &Transaction, tmy: &Testimony, cause: ValidationError)
+        -> ValidationError {
+        ValidationError::WrongTestimony {
+            tx_hash: tx.hash(),
+            cause: Box::new(cause),
+            proof: None,
+        }.with_fraud_proof(FraudProof::WrongTestimony(
+            WrongTestimonyProof {
+                shard_id: self.config.shard_id() as u32,
+                block_hash: H256::default(),
+                invalid_tx: tx.clone(),
+                invalid_tx_merkle_proof: vec![],
+                invalid_index: 0 as u32,
+
+                invalid_tmy: tmy.clone(),
+                invalid_tmy_merkle_proof: vec![],
+                invalid_tmy_index: 0 as u32,
+            }
+        ))
+    }
+
+    pub fn verify_fraud_proof(&self, fraud_proof: &FraudProof) -> ProofCheck {
+        Self::check_fraud_proof(&self.multichain, &self.config, fraud_proof)
+    }
+
+    //the checks of a fraud proof against a view of the chains, the same for the proofs
+    //gossiped to the node and the ones verified offline
+    pub fn check_fraud_proof(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fraud_proof: &FraudProof
+    ) -> ProofCheck {
+        //a gossiped proof may name any shard
+        if fraud_proof.get_shard_id() >= config.shard_num {
+            return ProofCheck::Invalid;
+        }
+        //a wrong state root or wrong parents are faults of the whole block
+        match fraud_proof {
+            FraudProof::WrongStateRoot(wsr_fp) => return Self::verify_wrongstateroot_fp(view, config, wsr_fp),
+            FraudProof::InvalidParent(ip_fp) => return Self::verify_invalidparent_fp(view, config, ip_fp),
+            FraudProof::WrongParentShard(wps_fp) => return Self::verify_wrongparentshard_fp(view, config, wps_fp),
+            FraudProof::WrongParentRoot(wpr_fp) => return Self::verify_wrongparentroot_fp(view, config, wpr_fp),
+            _ => {}
         }
         let invalid_block_hash = fraud_proof.get_invalid_block();
         let shard_id = fraud_proof.get_shard_id();
     
         //check whether the block exits
-        let invalid_block = match self.multichain
+        let invalid_block = match view
             .get_block_by_shard(&invalid_block_hash, shard_id) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
 
-        //check whether the block is still unverified
-        match self.multichain.get_verify_status_with_shard(
+        //check whether the block is still unverified, a pruned block may only be a
+        //race with another proof
+        match view.get_verify_status_with_shard(
             &invalid_block_hash,
             shard_id
-        ).unwrap() {
-            VerStatus::Unverified => {},
-            _ => return false,
+        ) {
+            Some(VerStatus::Unverified) => {},
+            Some(VerStatus::Verified) => return ProofCheck::Invalid,
+            _ => return ProofCheck::Unknown,
         };
 
         //check the inclusion of invalid transaction
@@ -983,46 +1575,53 @@ impl Validator {
             &invalid_tx_hash,
             &invalid_tx_merkle_proof,
             invalid_index,
-            self.config.block_size
+            config.block_size
         ) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         match fraud_proof {
-            FraudProof::DoubleSpending(ds_fp) => self.verify_doublespending_fp(ds_fp),
-            FraudProof::UtxoLost(ul_fp) => self.verify_utxolost_fp(ul_fp),
-            FraudProof::WrongShard(ws_fp) => self.verify_wrongshard_fp(ws_fp),
-            FraudProof::UnequalCoins(uc_fp) => self.verify_unequalcoins_fp(uc_fp),
-            FraudProof::WrongSignature(wsig_fp) => self.verify_wrongsig_fp(wsig_fp),
-            FraudProof::TestimonyLost(tl_fp) => self.verify_tmylost_fp(tl_fp),
-            FraudProof::WrongTestimony(wt_fp) => self.verify_wrongtmy_fp(wt_fp),
-            FraudProof::UnsolvedFault => true,
+            FraudProof::DoubleSpending(ds_fp) => Self::verify_doublespending_fp(view, config, ds_fp),
+            FraudProof::UtxoLost(ul_fp) => Self::verify_utxolost_fp(view, config, ul_fp),
+            FraudProof::WrongShard(ws_fp) => Self::verify_wrongshard_fp(view, config, ws_fp),
+            FraudProof::UnequalCoins(uc_fp) => Self::verify_unequalcoins_fp(view, config, uc_fp),
+            FraudProof::WrongSignature(wsig_fp) => Self::verify_wrongsig_fp(view, config, wsig_fp),
+            FraudProof::TestimonyLost(tl_fp) => Self::verify_tmylost_fp(view, config, tl_fp),
+            FraudProof::WrongTestimony(wt_fp) => Self::verify_wrongtmy_fp(view, config, wt_fp),
+            FraudProof::WrongStateRoot(wsr_fp) => Self::verify_wrongstateroot_fp(view, config, wsr_fp),
+            FraudProof::InvalidParent(ip_fp) => Self::verify_invalidparent_fp(view, config, ip_fp),
+            FraudProof::WrongParentShard(wps_fp) => Self::verify_wrongparentshard_fp(view, config, wps_fp),
+            FraudProof::WrongParentRoot(wpr_fp) => Self::verify_wrongparentroot_fp(view, config, wpr_fp),
         }
     }
 
-    fn verify_doublespending_fp(&self, fp: &DoubleSpendingProof) -> bool {
+    fn verify_doublespending_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &DoubleSpendingProof
+    ) -> ProofCheck {
         let shard_id = fp.shard_id as usize;
-        let invalid_block = match self.multichain
+        let invalid_block = match view
             .get_block_by_shard(&fp.invalid_block_hash, shard_id) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
 
 
-        let conflict_block = match self.multichain
+        let conflict_block = match view
             .get_block_by_shard(&fp.conflict_block_hash, shard_id) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
         //check whether the conflict block is the ancestor of the invalid block
-        let history_blocks = match self.multichain
-            .all_blocks_end_with_block(&fp.invalid_block_hash) {
+        let history_blocks = match view
+            .all_blocks_end_with_block_with_shard(&fp.invalid_block_hash, shard_id) {
             Some(res) => res,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
     
         if !history_blocks.contains(&fp.conflict_block_hash) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         //check the inclusion of conflict tx
@@ -1033,9 +1632,9 @@ impl Validator {
             &conflict_tx_hash,
             &fp.conflict_tx_merkle_proof,
             fp.conflict_index as usize,
-            self.config.block_size
+            config.block_size
         ) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         //check whether there are multiple same inputs in one tx
@@ -1044,7 +1643,7 @@ impl Validator {
         for in_input in fp.invalid_tx.inputs.iter() {
             let in_input_hash = in_input.hash();
             if let Some(_) = set.get(&in_input_hash) {
-                return true;
+                return ProofCheck::Valid;
             } else {
                 set.insert(in_input_hash.clone(), true);
             }
@@ -1053,75 +1652,121 @@ impl Validator {
                 let con_input_hash = con_input.hash();
 
                 if con_input_hash == in_input_hash {
-                    return true;
+                    return ProofCheck::Valid;
                 }
             }
         
         }
 
-        return false;
+        return ProofCheck::Invalid;
     }
 
-    fn verify_utxolost_fp(&self, fp: &UtxoLostProof) -> bool {
+    fn verify_utxolost_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &UtxoLostProof
+    ) -> ProofCheck {
         //...
-        true
+        ProofCheck::Valid
     }
 
-    fn verify_wrongshard_fp(&self, fp: &WrongShardProof) -> bool {
+    fn verify_wrongshard_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &WrongShardProof
+    ) -> ProofCheck {
         for input in fp.invalid_tx.inputs.iter() {
-            let shard_id = Self::get_shard_id(&input.sender_addr, self.config.shard_num as usize) as u32;
+            let shard_id = Self::get_shard_id(&input.sender_addr, &config) as u32;
             if shard_id == fp.shard_id {
-                return false;
+                return ProofCheck::Invalid;
             }
         } 
 
         for output in fp.invalid_tx.outputs.iter() {
-            let shard_id = Self::get_shard_id(&output.receiver_addr, self.config.shard_num as usize) as u32;
+            let shard_id = Self::get_shard_id(&output.receiver_addr, &config) as u32;
             if shard_id == fp.shard_id {
-                return false;
+                return ProofCheck::Invalid;
             }
         }
 
-        true
+        ProofCheck::Valid
     } 
     
-    fn verify_unequalcoins_fp(&self, fp: &UnequalCoinsProof) -> bool {
-        let mut input_coins = 0 as usize;
-        for input in fp.invalid_tx.inputs.iter() {
-            input_coins += input.value as usize;
+    fn verify_unequalcoins_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &UnequalCoinsProof
+    ) -> ProofCheck {
+        let conflict_tx = match fp.conflict_tx.as_ref() {
+            Some(conflict_tx) => conflict_tx,
+            //a sum which overflows is as invalid as unequal sums
+            None => return ProofCheck::from(match (fp.invalid_tx.get_input_value(), fp.invalid_tx.get_output_value()) {
+                (Some(input_coins), Some(output_coins)) => input_coins != output_coins,
+                _ => true,
+            }),
+        };
+        //an input claiming another value than its utxo, the utxo is proved to be in an
+        //ancestor of the invalid block
+        let shard_id = fp.shard_id as usize;
+        let conflict_block = match view
+            .get_block_by_shard(&fp.conflict_block_hash, shard_id) {
+            Some(block) => block,
+            None => return ProofCheck::Unknown,
+        };
+        let history_blocks = match view
+            .all_blocks_end_with_block_with_shard(&fp.block_hash, shard_id) {
+            Some(res) => res,
+            None => return ProofCheck::Unknown,
+        };
+        if !history_blocks.contains(&fp.conflict_block_hash) {
+            return ProofCheck::Invalid;
         }
-        
-        let mut output_coins = 0 as usize;
-        for output in fp.invalid_tx.outputs.iter() {
-            output_coins += output.value as usize;
+        let conflict_tx_hash = conflict_tx.hash();
+        if !MerkleTree::<Transaction>::verify(
+            &conflict_block.get_tx_merkle_root(),
+            &conflict_tx_hash,
+            &fp.conflict_tx_merkle_proof,
+            fp.conflict_index as usize,
+            config.block_size
+        ) {
+            return ProofCheck::Invalid;
         }
-
-        input_coins != output_coins
+        ProofCheck::from(fp.invalid_tx.inputs.iter().any(|input| {
+            input.tx_hash == conflict_tx_hash &&
+                Self::get_shard_id(&input.sender_addr, config) == shard_id &&
+                conflict_tx.outputs
+                    .get(input.index as usize)
+                    .map_or(false, |output| output.value != input.value)
+        }))
     }
 
-    fn verify_wrongsig_fp(&self, fp: &WrongSignatureProof) -> bool {
+    fn verify_wrongsig_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &WrongSignatureProof
+    ) -> ProofCheck {
         let shard_id = fp.shard_id as usize;
-        let invalid_block = match self.multichain
+        let invalid_block = match view
             .get_block_by_shard(&fp.invalid_block_hash, shard_id) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
             
 
 
-        let conflict_block = match self.multichain
+        let conflict_block = match view
             .get_block_by_shard(&fp.conflict_block_hash, shard_id) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
         //check whether the conflict block is the ancestor of the invalid block
-        let history_blocks = match self.multichain
-            .all_blocks_end_with_block(&fp.invalid_block_hash) {
+        let history_blocks = match view
+            .all_blocks_end_with_block_with_shard(&fp.invalid_block_hash, shard_id) {
             Some(res) => res,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
         if !history_blocks.contains(&fp.conflict_block_hash) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         //check the inclusion of conflict tx
@@ -1132,48 +1777,78 @@ impl Validator {
             &conflict_tx_hash,
             &fp.conflict_tx_merkle_proof,
             fp.conflict_index as usize,
-            self.config.block_size
+            config.block_size
         ) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
 
+        //the heights of the spending block and of the spent tx, the height of the
+        //spending block is only known if its path goes back to the genesis block
+        let height = (history_blocks.len() - 1) as u64;
+        let utxo_height = history_blocks
+            .iter()
+            .position(|hash| *hash == fp.conflict_block_hash)
+            .unwrap() as u64;
+        let from_genesis = history_blocks[0] == ExclusiveBlock::genesis_of(shard_id, config).hash();
+        let ctx = LockContext {
+            height,
+            age: height - utxo_height,
+        };
+
         for input in fp.invalid_tx.inputs.iter() {
             if input.tx_hash == conflict_tx_hash {
-                let sig_vec = input.sig_ref.clone();
                 let utxo_index = input.index as usize;
                 match fp.conflict_tx.outputs.get(utxo_index) {
                     Some(output) => {
-                        let pub_key = output.public_key_ref.clone();
-                        return !Transaction::verify(
-                            &fp.conflict_tx, 
-                            pub_key.as_slice(), 
-                            sig_vec.as_slice()
-                        ); 
+                        if let Some(lock) = output.lock.as_ref() {
+                            //the height of the block is unknown without its whole path
+                            if lock.has_absolute_timelock() && !from_genesis {
+                                return ProofCheck::Unknown;
+                            }
+                        }
+                        return ProofCheck::from(!Transaction::check_spend(
+                            &fp.conflict_tx,
+                            &fp.invalid_tx,
+                            input,
+                            &ctx,
+                            &|signed_tx, public_key_ref, sig_ref| Transaction::verify(
+                                signed_tx,
+                                public_key_ref,
+                                sig_ref
+                            )
+                        )); 
                     }
-                    None => return true,
+                    None => return ProofCheck::Valid,
                 }
-                break;
             }    
         }
 
-        return false;       
+        return ProofCheck::Invalid;       
     }
 
-    fn verify_tmylost_fp(&self, fp: &TestimonyLostProof) -> bool {
+    fn verify_tmylost_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &TestimonyLostProof
+    ) -> ProofCheck {
         //...
-        true
+        ProofCheck::Valid
     }
 
-    fn verify_wrongtmy_fp(&self, fp: &WrongTestimonyProof) -> bool {
+    fn verify_wrongtmy_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &WrongTestimonyProof
+    ) -> ProofCheck {
         let shard_id = fp.shard_id as usize;
         //check the inclusion 
-        let invalid_block = match self.multichain.get_block_by_shard(
+        let invalid_block = match view.get_block_by_shard(
             &fp.block_hash,
             shard_id
         ) {
             Some(block) => block,
-            None => return false,
+            None => return ProofCheck::Unknown,
         };
 
         let invalid_tmy_merkle_root = invalid_block.get_testimony_merkle_root();
@@ -1184,22 +1859,24 @@ impl Validator {
             &invalid_tmy_hash,
             &fp.invalid_tmy_merkle_proof,
             fp.invalid_tmy_index as usize,
-            self.config.block_size
+            config.block_size
         ) {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         //check the validity of tmy
         if invalid_tmy.get_tx_hash() != fp.invalid_tx.hash() {
-            return false;
+            return ProofCheck::Invalid;
         }
 
         for input in fp.invalid_tx.inputs.iter() {
             let input_shard_id = Self::get_shard_id(
-                &input.sender_addr, self.config.shard_num
+                &input.sender_addr, &config
             );
             if input_shard_id != shard_id {
-                match self.validate_cross_utxo(
+                match Self::check_cross_utxo(
+                    view,
+                    config,
                     &fp.invalid_tx,
                     &input.hash(),
                     &invalid_tmy,
@@ -1207,13 +1884,122 @@ impl Validator {
                     CrossUtxoStatus::Available
                 ) {
                     Ok(_) => {},
-                    Err(e) => return true,
+                    //the origin block may not have reached the node yet
+                    Err(ValidationError::OriginBlockNotFound { .. }) => return ProofCheck::Unknown,
+                    Err(_) => return ProofCheck::Valid,
                 }
             }
         }
 
-        return false;
+        return ProofCheck::Invalid;
+
+    }
+
+    fn verify_wrongstateroot_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &WrongStateRootProof
+    ) -> ProofCheck {
+        let shard_id = fp.shard_id as usize;
+        let invalid_block = match view
+            .get_block_by_shard(&fp.block_hash, shard_id) {
+            Some(block) => block,
+            None => return ProofCheck::Unknown,
+        };
+        if !invalid_block.get_inter_parents().contains(&fp.parent_hash) {
+            return ProofCheck::Invalid;
+        }
+
+        //check the txs are the whole content of the block
+        if fp.txs.len() != config.block_size ||
+            MerkleTree::<Transaction>::new(&fp.txs).root() != invalid_block.get_tx_merkle_root() {
+            return ProofCheck::Invalid;
+        }
+
+        //the state of the parent is committed by the closest block of the shard
+        let mut state_root = match view
+            .get_state_block_with_shard(&fp.parent_hash, shard_id) {
+            Some(block) => block.get_state_root(),
+            None => return ProofCheck::Unknown,
+        };
+
+        //replay the updates
+        let updates = Blockchain::get_state_updates(
+            &fp.txs,
+            shard_id,
+            config
+        );
+        if updates.len() != fp.state_proofs.len() {
+            return ProofCheck::Invalid;
+        }
+        for ((key, value), proof) in updates.iter().zip(fp.state_proofs.iter()) {
+            if !proof.verify(&state_root, key, proof.get_value(key).as_ref()) {
+                return ProofCheck::Invalid;
+            }
+            state_root = match proof.update(key, value.as_ref()) {
+                Some(root) => root,
+                None => return ProofCheck::Invalid,
+            };
+        }
+
+        ProofCheck::from(state_root != invalid_block.get_state_root())
+    }
+
+    //the block of the proof must be well formed and refer to the parent in the shard
+    fn check_parent_ref(
+        shard_id: u32,
+        block: &InclusiveBlock,
+        parent_hash: &H256,
+        parent_shard_id: u32
+    ) -> bool {
+        block.verify_format() &&
+            block.get_shard_id() == shard_id as usize &&
+            block
+                .get_all_parents()
+                .contains(&(parent_hash.clone(), parent_shard_id as usize))
+    }
+
+    fn verify_invalidparent_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &InvalidParentProof
+    ) -> ProofCheck {
+        if !Self::check_parent_ref(fp.shard_id, &fp.block, &fp.parent_hash, fp.parent_shard_id) {
+            return ProofCheck::Invalid;
+        }
+        match &fp.parent_proof {
+            Some(parent_proof) => {
+                if parent_proof.get_invalid_block() != fp.parent_hash {
+                    return ProofCheck::Invalid;
+                }
+                Self::check_fraud_proof(view, config, parent_proof)
+            }
+            None => ProofCheck::from(!Self::is_possible_block(config, &fp.parent_hash, fp.parent_shard_id as usize)),
+        }
+    }
+
+    fn verify_wrongparentshard_fp(
+        view: &dyn ChainView,
+        config: &Configuration,
+        fp: &WrongParentShardProof
+    ) -> ProofCheck {
+        let parent_hash = fp.parent_header.hash();
+        ProofCheck::from(
+            Self::check_parent_ref(fp.shard_id, &fp.block, &parent_hash, fp.parent_shard_id) &&
+                Self::is_wrong_parent_shard(config, &fp.parent_header, fp.parent_shard_id as usize)
+        )
+    }
 
+    fn verify_wrongparentroot_fp(
+        _view: &dyn ChainView,
+        _config: &Configuration,
+        fp: &WrongParentRootProof
+    ) -> ProofCheck {
+        ProofCheck::from(
+            fp.block.verify_hash() &&
+                fp.block.get_shard_id() == fp.shard_id as usize &&
+                !fp.block.verify_format()
+        )
     }
 
     fn recursive_samples(
@@ -0,0 +1,49 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    manifoldchain::{
+        transaction::Transaction,
+        network::cache::{SharedCache, CacheStats},
+    },
+};
+use ring::digest::{digest, SHA256};
+
+//the signatures verified already, so that a tx checked when it enters the mempool is
+//not checked again when its block is validated
+#[derive(Clone)]
+pub struct SigCache {
+    verified: SharedCache<H256, ()>,
+}
+
+impl SigCache {
+    pub fn new(capacity: usize) -> Self {
+        SigCache {
+            verified: SharedCache::new("signatures", capacity, None),
+        }
+    }
+
+    //a signature is identified by the signed tx, the key and the signature itself
+    fn get_key(tx: &Transaction, public_key_ref: &[u8], sig_ref: &[u8]) -> H256 {
+        H256::multi_hash(&vec![
+            tx.hash(),
+            digest(&SHA256, public_key_ref).into(),
+            digest(&SHA256, sig_ref).into(),
+        ])
+    }
+
+    //only the valid signatures are kept, an invalid one is checked again if it comes back
+    pub fn verify(&self, tx: &Transaction, public_key_ref: &[u8], sig_ref: &[u8]) -> bool {
+        let key = Self::get_key(tx, public_key_ref, sig_ref);
+        if self.verified.get(&key).is_some() {
+            return true;
+        }
+        if !Transaction::verify(tx, public_key_ref, sig_ref) {
+            return false;
+        }
+        self.verified.insert(key, ());
+        true
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.verified.stats()
+    }
+}
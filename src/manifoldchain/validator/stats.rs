@@ -0,0 +1,94 @@
+use crate::{
+    types::hash::Hashable,
+    manifoldchain::validator::error::ValidationError,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+//the number of rejections kept with their messages
+const MAX_RECENT_ERRORS: usize = 100;
+
+//where the rejected block or tx comes from
+pub const FROM_BLOCK: &str = "block";
+pub const FROM_TRANSACTION: &str = "transaction";
+pub const FROM_MINER: &str = "miner";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ErrorCount {
+    pub source: &'static str,
+    pub error: &'static str,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecentError {
+    pub time: u128, //ms since the unix epoch
+    pub source: &'static str,
+    pub error: &'static str,
+    pub message: String,
+    pub fraud_proof: Option<String>, //the hash of the attached fraud proof
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ValidationReport {
+    pub errors: Vec<ErrorCount>,
+    pub recent: Vec<RecentError>,
+}
+
+#[derive(Default)]
+struct Stats {
+    counts: HashMap<(&'static str, &'static str), u64>,
+    recent: VecDeque<RecentError>,
+}
+
+//the blocks and txs rejected by the workers and the miner, by source and by error
+#[derive(Clone, Default)]
+pub struct ValidationStats {
+    inner: Arc<Mutex<Stats>>,
+}
+
+impl ValidationStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, source: &'static str, err: &ValidationError) {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let mut stats = self.inner.lock().unwrap();
+        *stats.counts.entry((source, err.kind())).or_insert(0) += 1;
+        if stats.recent.len() >= MAX_RECENT_ERRORS {
+            stats.recent.pop_front();
+        }
+        stats.recent.push_back(RecentError {
+            time,
+            source,
+            error: err.kind(),
+            message: err.to_string(),
+            fraud_proof: err.fraud_proof().map(|fp| fp.hash().to_string()),
+        });
+    }
+
+    pub fn get_report(&self) -> ValidationReport {
+        let stats = self.inner.lock().unwrap();
+        let mut errors: Vec<ErrorCount> = stats.counts
+            .iter()
+            .map(|((source, error), count)| ErrorCount {
+                source,
+                error,
+                count: *count,
+            })
+            .collect();
+        errors.sort_by_key(|count| (count.source, count.error));
+        ValidationReport {
+            errors,
+            recent: stats.recent.iter().cloned().collect(),
+        }
+    }
+}
@@ -16,9 +16,9 @@ use crate::{
         multichain::Multichain,
         configuration::Configuration,
         network::{
-            server::Handle as ServerHandle,
             message::Message,
             worker::{SampleIndex},
+            request::RequestTracker,
         }
     },
 };
@@ -28,17 +28,17 @@ use rand::Rng;
 pub struct Context {
     multichain: Multichain,
     config: Configuration,
-    server: ServerHandle,
+    requests: RequestTracker,
 }
 
 
 pub fn new(multichain: &Multichain,
-    server: &ServerHandle,
+    requests: &RequestTracker,
     config: &Configuration) -> Context 
 {
     Context {
         multichain: multichain.clone(),
-        server: server.clone(),
+        requests: requests.clone(),
         config: config.clone(),
     }
 }
@@ -64,14 +64,21 @@ impl Context {
                 //self.finished_block_chan
                 //    .send(MinerMessage::GetSamples(unverified_blocks))
                 //    .unwrap();
-                let mut rq_samples: Vec<SampleIndex> = vec![];
+                //ask one peer of the block's shard, the request tracker retries
+                //another one if it does not answer
+                let mut rq_samples: HashMap<usize, Vec<SampleIndex>> = HashMap::new();
                 info!("Miner worker get {} samples", unverified_blocks.len());
                 for (blk_hash, shard_id) in unverified_blocks {
                     let mut rng = rand::thread_rng();
                     let tx_index: usize = rng.gen_range(0..self.config.block_size);
-                    rq_samples.push((blk_hash, tx_index as u32, shard_id as u32)); 
+                    rq_samples
+                        .entry(shard_id)
+                        .or_insert_with(Vec::new)
+                        .push((blk_hash, tx_index as u32, shard_id as u32)); 
                 }    
-                self.server.broadcast(Message::GetSamples(rq_samples));
+                for (shard_id, samples) in rq_samples {
+                    self.requests.request_from_shard(Message::GetSamples(samples), shard_id);
+                }
             } else {
                 //info!("no unverified blocks");
             }
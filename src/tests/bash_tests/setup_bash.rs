@@ -134,6 +134,7 @@ fn generate_exper_bash(exper_number: usize, config: &ConfigData) -> Result<(), E
             let domestic_ratio_cmd = format!("--domesticRatio {}", config.domestic_ratio);
             let total_diff_cmd = format!("--eDiff {}", exclusive_diff);
             let inclusive_diff_cmd = format!("--iDiff {}", config.inclusive_diff);
+            let shard_diffs_cmd = format!("--shardDiffs {}", config.exclusive_diffs.join(","));
             let mut final_cmd: String = back_to_root.clone();
             for delay in add_node_delay {
                 final_cmd = format!("{}{}", final_cmd, delay);
@@ -152,6 +153,7 @@ fn generate_exper_bash(exper_number: usize, config: &ConfigData) -> Result<(), E
             final_cmd = format!("{} {}", final_cmd, domestic_ratio_cmd);
             final_cmd = format!("{} {}", final_cmd, total_diff_cmd);
             final_cmd = format!("{} {}", final_cmd, inclusive_diff_cmd);
+            final_cmd = format!("{} {}", final_cmd, shard_diffs_cmd);
             let path = format!("{}start_node_{}.sh", nodes_path.clone(), node_id);
             let mut output = File::create(path)?;
             write!(output, "{}", final_cmd)?;
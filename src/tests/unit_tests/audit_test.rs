@@ -0,0 +1,115 @@
+use crate::{
+    manifoldchain::{
+        audit::{ShardLedger, AuditReport},
+        transaction::*,
+    },
+    types::{
+        hash::{
+            H256,
+            Hashable,
+        },
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::Ed25519KeyPair;
+
+fn ledger(shard_id: usize, txs: &Vec<Transaction>) -> ShardLedger {
+    //one tx per block
+    let txs: Vec<(H256, Transaction)> = txs
+        .iter()
+        .map(|tx| (tx.hash().hash(), tx.clone()))
+        .collect();
+    ShardLedger {
+        shard_id,
+        shard_num: 2,
+        shard_policy: String::from("modulo"),
+        tip: txs.last().map_or(H256::default(), |tx| tx.0.clone()),
+        height: txs.len(),
+        audited: true,
+        txs,
+        state_value: None,
+        migrated: 0,
+    }
+}
+
+fn kinds(report: &AuditReport) -> Vec<&'static str> {
+    report.discrepancies.iter().map(|d| d.kind).collect()
+}
+
+#[test]
+fn audit_test_one() {
+    //user0 in shard 0, user1 in shard 1
+    let user0: Address = (&[2u8; 20]).into();
+    let key0: Ed25519KeyPair = key_pair::random();
+    let user1: Address = (&[3u8; 20]).into();
+    let key1: Ed25519KeyPair = key_pair::random();
+    let tx1 = Transaction::create_initial_tx((&user0, &key0), 10);
+    let tx2 = Transaction::create_initial_tx((&user1, &key1), 20);
+    let pay = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![(&user0, &key0)],
+        vec![(&user1, &key1, 6), (&user0, &key0, 4)],
+        TxFlag::Input,
+    ).unwrap();
+    let with_flag = |flag: TxFlag| {
+        let mut tx = pay.clone();
+        tx.flag = flag;
+        tx
+    };
+    let (output, accept, reject) = (
+        with_flag(TxFlag::Output),
+        with_flag(TxFlag::Accept),
+        with_flag(TxFlag::Reject),
+    );
+
+    //the coins are in flight until the input-tx is accepted
+    let mut ledgers = vec![
+        ledger(0, &vec![tx1.clone(), pay.clone(), output.clone()]),
+        ledger(1, &vec![tx2.clone(), output.clone()]),
+    ];
+    let report = AuditReport::audit(&ledgers);
+    assert!(report.is_clean());
+    assert_eq!(report.issued, 30);
+    assert_eq!(report.in_flight.len(), 1);
+    assert_eq!(report.in_flight[0].tx_hash, pay.hash());
+    assert_eq!(report.shards[0].in_flight, 10);
+    ledgers[0] = ledger(0, &vec![tx1.clone(), pay.clone(), output.clone(), accept.clone()]);
+    let report = AuditReport::audit(&ledgers);
+    assert!(report.is_clean());
+    assert!(report.in_flight.is_empty());
+    assert_eq!(report.shards[0].supply.get_total(), Some(4));
+    assert_eq!(report.shards[1].supply.get_total(), Some(26));
+
+    //the state of a shard disagrees with its supply
+    ledgers[1].state_value = Some(27);
+    assert_eq!(kinds(&AuditReport::audit(&ledgers)), vec!["state_mismatch"]);
+    ledgers[1].state_value = Some(26);
+
+    //delivered coins refunded, and coins delivered without being locked
+    ledgers[0] = ledger(0, &vec![tx1.clone(), pay.clone(), output.clone(), reject.clone()]);
+    let report = AuditReport::audit(&ledgers);
+    assert_eq!(kinds(&report), vec!["refunded_and_delivered"]);
+    assert_eq!(report.discrepancies[0].tx_hash, Some(reject.hash()));
+    ledgers[0] = ledger(0, &vec![tx1.clone()]);
+    let report = AuditReport::audit(&ledgers);
+    assert_eq!(kinds(&report), vec!["output_without_input"]);
+    assert_eq!(report.discrepancies[0].shard_id, 1);
+    assert_eq!(report.discrepancies[0].tx_hash, Some(output.hash()));
+
+    //coins accepted without being delivered, unless the receiving shard is not audited
+    ledgers[0] = ledger(0, &vec![tx1.clone(), pay.clone(), output.clone(), accept.clone()]);
+    ledgers[1] = ledger(1, &vec![tx2.clone()]);
+    assert_eq!(kinds(&AuditReport::audit(&ledgers)), vec!["accepted_undelivered"]);
+    ledgers[1].audited = false;
+    assert!(AuditReport::audit(&ledgers).is_clean());
+
+    //the ledger of an audited shard is kept when merging
+    let mut header_only = ledger(0, &vec![]);
+    header_only.height = 10;
+    header_only.audited = false;
+    let merged = ShardLedger::merge(vec![header_only, ledgers[1].clone(), ledgers[0].clone()]);
+    assert_eq!(merged.len(), 2);
+    assert!(merged[0].audited);
+    assert_eq!(merged[0].txs.len(), 4);
+}
@@ -4,9 +4,17 @@ use crate::{
             versa_block::*,
             exclusive_block::*,
             inclusive_block::*,
+            compact_block::*,
         },
+        fraudproof::{
+            *,
+            bundle::*,
+        },
+        configuration::Configuration,
         transaction::*,
+        validator::Validator,
         testimony::*,
+        mempool::Mempool,
     },
     types::{
         hash::{
@@ -14,15 +22,17 @@ use crate::{
             Hashable,
         },
         key_pair,
+        address::Address,
     }
 };
 use ring::signature::{self, Ed25519KeyPair, Signature, KeyPair};
+use rand::Rng;
 #[test]
 fn block_test_one() {
     //generate some users
-    let user2: H256 = (&[2u8; 32]).into();
-    let user3: H256 = (&[3u8; 32]).into();
-    let user4: H256 = (&[4u8; 32]).into();
+    let user2: Address = (&[2u8; 20]).into();
+    let user3: Address = (&[3u8; 20]).into();
+    let user4: Address = (&[4u8; 20]).into();
    
     let key2: Ed25519KeyPair = key_pair::random();
     let key3: Ed25519KeyPair = key_pair::random();
@@ -104,3 +114,312 @@ fn block_test_one() {
     println!("size of in_block with {} txs: {:.2} KB", tx_size, in_size);
 
 }
+
+#[test]
+fn compact_block_test_one() {
+    let txs: Vec<Transaction> = (0..8).map(|_| Transaction::gen_rand_tx()).collect();
+    let tmys: Vec<Testimony> = txs
+        .iter()
+        .map(|tx| Testimony::gen_rand_tmy_by_tx(&tx.hash()))
+        .collect();
+    let ex_full_block = ExclusiveFullBlock::generate(
+        H256::default(),
+        0,
+        0,
+        H256::default(),
+        txs.clone(),
+        tmys.clone(),
+        vec![H256::default()],
+        vec![],
+    );
+    let full_block = VersaBlock::ExFullBlock(ex_full_block.clone());
+    let compact_block = CompactBlock::from_full_block(&full_block).unwrap();
+    assert_eq!(compact_block.hash(), ex_full_block.hash());
+    assert_eq!(compact_block.short_ids.len(), 8);
+
+    //half of the txs are in the mempool, one of them has been popped by the miner
+    let mut mempool = Mempool::new();
+    for tx in txs[0..4].iter() {
+        mempool.insert_tx(tx.clone());
+    }
+    mempool.add_testimony(tmys[0].clone());
+    mempool.pop_one_tx();
+    let mut partial = PartialBlock::new(compact_block, &mempool);
+    assert!(!partial.is_complete());
+    let missing = partial.get_missing();
+    assert_eq!(missing.tx_indexes, vec![4, 5, 6, 7]);
+    assert_eq!(missing.tmy_indexes, vec![1, 2, 3, 4, 5, 6, 7]);
+    assert!(partial.build().is_none());
+
+    let block_txns = BlockTxns::from_full_block(&full_block, &missing).unwrap();
+    partial.fill(block_txns);
+    assert!(partial.is_complete());
+    match partial.build() {
+        Some(VersaBlock::ExFullBlock(rebuilt)) => {
+            assert_eq!(rebuilt.hash(), ex_full_block.hash());
+            assert_eq!(rebuilt.tx_block.get_tx_merkle_root(), ex_full_block.tx_block.get_tx_merkle_root());
+        }
+        _ => panic!("fail to rebuild the compact block"),
+    }
+}
+
+#[test]
+fn block_test_two() {
+    let parent_0: H256 = (&[1u8; 32]).into();
+    let parent_1: H256 = (&[2u8; 32]).into();
+    let global_parents = vec![(vec![parent_0.clone()], 0), (vec![parent_1.clone()], 1)];
+    let (in_block, _) = InclusiveBlock::generate(
+        parent_0.clone(),
+        0,
+        0,
+        H256::default(),
+        vec![],
+        vec![],
+        vec![parent_0.clone()],
+        global_parents,
+    );
+    assert!(in_block.verify_format());
+    assert_eq!(
+        in_block.get_all_parents(),
+        vec![(parent_0.clone(), 0), (parent_0.clone(), 0), (parent_1.clone(), 1)]
+    );
+
+    //the parents moved to another shard no longer match the header
+    let moved_block = InclusiveBlock::create(
+        in_block.get_cons_block(),
+        in_block.hash(),
+        vec![parent_0.clone()],
+        vec![(vec![parent_0.clone()], 1), (vec![parent_1.clone()], 0)],
+    );
+    assert!(moved_block.verify_hash());
+    assert!(!moved_block.verify_format());
+    let fp = FraudProof::WrongParentRoot(WrongParentRootProof {
+        shard_id: 0,
+        block: moved_block,
+    });
+    assert_eq!(fp.get_invalid_block(), in_block.hash());
+    assert_ne!(
+        fp.hash(),
+        FraudProof::WrongParentRoot(WrongParentRootProof {
+            shard_id: 0,
+            block: in_block.clone(),
+        }).hash()
+    );
+
+    //the proof against a parent carries the proof of the parent
+    let parent_fp = FraudProof::InvalidParent(InvalidParentProof {
+        shard_id: 0,
+        block: in_block.clone(),
+        parent_hash: parent_1.clone(),
+        parent_shard_id: 1,
+        parent_proof: Some(Box::new(fp.clone())),
+    });
+    let bytes = bincode::serialize(&parent_fp).unwrap();
+    let decoded: FraudProof = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(decoded, parent_fp);
+    assert_eq!(decoded.hash(), parent_fp.hash());
+}
+
+#[test]
+fn block_test_three() {
+    let parent_0: H256 = (&[1u8; 32]).into();
+    let parent_1: H256 = (&[2u8; 32]).into();
+    let (in_block, _) = InclusiveBlock::generate(
+        parent_0.clone(),
+        0,
+        0,
+        H256::default(),
+        vec![],
+        vec![],
+        vec![parent_0.clone()],
+        vec![(vec![parent_0.clone()], 0), (vec![parent_1.clone()], 1)],
+    );
+    let moved_block = InclusiveBlock::create(
+        in_block.get_cons_block(),
+        in_block.hash(),
+        vec![parent_0.clone()],
+        vec![(vec![parent_0.clone()], 1), (vec![parent_1.clone()], 0)],
+    );
+    let config = Configuration::new();
+    let new_bundle = |block: InclusiveBlock| FraudProofBundle {
+        fraud_proof: FraudProof::WrongParentRoot(WrongParentRootProof {
+            shard_id: 0,
+            block,
+        }),
+        shard_num: 2,
+        shard_policy: String::from("modulo"),
+        genesis_roots: vec![],
+        block_size: config.block_size,
+        difficulty: config.difficulty.clone(),
+        thredshold: config.thredshold.clone(),
+        shard_diffs: vec![],
+        blocks: vec![],
+    };
+
+    //the bundle survives the file and is verified without any chain
+    let bundle = new_bundle(moved_block);
+    let path = std::env::temp_dir().join(format!("bundle_test_{}.json", rand::thread_rng().gen::<u32>()));
+    let path = path.to_str().unwrap();
+    bundle.save(path).unwrap();
+    let loaded = FraudProofBundle::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded.fraud_proof, bundle.fraud_proof);
+    assert_eq!(loaded.verify(), Ok(true));
+
+    //a proof against a valid block is rejected
+    assert_eq!(new_bundle(in_block.clone()).verify(), Ok(false));
+
+    //a header out of the shards is refused
+    let mut bad_bundle = new_bundle(in_block.clone());
+    bad_bundle.blocks.push(BundleBlock {
+        shard_id: 2,
+        header: VersaBlock::InBlock(in_block),
+        parent: None,
+        in_longest_chain: true,
+        confirmed: false,
+    });
+    assert!(bad_bundle.verify().is_err());
+}
+
+#[test]
+fn block_test_four() {
+    //shard 1 is mined at a lower difficulty than shard 0
+    let easy: H256 = (&[255u8; 32]).into();
+    let mut hard_bytes = [255u8; 32];
+    hard_bytes[0] = 0;
+    let hard: H256 = (&hard_bytes).into();
+    let parent_0: H256 = (&[1u8; 32]).into();
+    let parent_1: H256 = (&[2u8; 32]).into();
+    let (in_block, _) = InclusiveBlock::generate(
+        parent_0.clone(),
+        0,
+        0,
+        easy.clone(),
+        vec![],
+        vec![],
+        vec![parent_0.clone()],
+        vec![(vec![parent_0.clone()], 0), (vec![parent_1.clone()], 1)],
+    );
+    let mut config = Configuration::new();
+    config.difficulty = easy.clone();
+    let new_bundle = |shard_diffs: Vec<H256>| FraudProofBundle {
+        fraud_proof: FraudProof::InvalidParent(InvalidParentProof {
+            shard_id: 0,
+            block: in_block.clone(),
+            parent_hash: parent_1.clone(),
+            parent_shard_id: 1,
+            parent_proof: None,
+        }),
+        shard_num: 2,
+        shard_policy: String::from("modulo"),
+        genesis_roots: vec![],
+        block_size: config.block_size,
+        difficulty: config.difficulty.clone(),
+        thredshold: config.thredshold.clone(),
+        shard_diffs,
+        blocks: vec![],
+    };
+
+    //the parent meets the difficulty of shard 0 only, so it is no block of shard 1
+    assert_eq!(new_bundle(vec![]).verify(), Ok(false));
+    assert_eq!(new_bundle(vec![easy.clone(), hard.clone()]).verify(), Ok(true));
+    assert_eq!(new_bundle(vec![hard.clone(), easy.clone()]).verify(), Ok(false));
+    config.shard_diffs = vec![easy.clone(), hard.clone()];
+    assert_eq!(config.get_difficulty(1), hard);
+    assert_eq!(config.get_difficulty(0), config.difficulty);
+}
+
+#[test]
+fn block_test_five() {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    //the merkle proofs are checked against blocks of block_size txs
+    config.block_size = 1;
+    let (user, key) = loop {
+        let key = key_pair::random();
+        let user = Address::from_key_pair(&key);
+        if Validator::get_shard_id(&user, &config) == 0 {
+            break (user, key);
+        }
+    };
+    let ini_tx = Transaction::create_initial_tx((&user, &key), 10);
+    let tx = Transaction::consume(
+        vec![(&ini_tx, 0)],
+        vec![(&user, &key)],
+        vec![(&user, &key, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    //the sums of the tx are equal, but its input claims more than the utxo holds
+    let mut inflated = tx.clone();
+    inflated.inputs[0].value = 20;
+    inflated.outputs[0].value = 20;
+
+    let utxo_block = ExclusiveFullBlock::generate(
+        H256::default(),
+        0,
+        0,
+        config.difficulty.clone(),
+        vec![ini_tx.clone()],
+        vec![],
+        vec![],
+        vec![],
+    );
+    let utxo_hash = utxo_block.get_exclusive_block().hash();
+    let new_bundle = |spending_tx: &Transaction| {
+        let spending_block = ExclusiveFullBlock::generate(
+            utxo_hash.clone(),
+            0,
+            0,
+            config.difficulty.clone(),
+            vec![spending_tx.clone()],
+            vec![],
+            vec![utxo_hash.clone()],
+            vec![],
+        );
+        let spending_hash = spending_block.get_exclusive_block().hash();
+        FraudProofBundle {
+            fraud_proof: FraudProof::UnequalCoins(UnequalCoinsProof {
+                shard_id: 0,
+                block_hash: spending_hash.clone(),
+                invalid_tx: spending_tx.clone(),
+                invalid_tx_merkle_proof: spending_block.get_tx_merkle_proof(0),
+                invalid_index: 0,
+
+                conflict_tx: Some(ini_tx.clone()),
+                conflict_block_hash: utxo_hash.clone(),
+                conflict_tx_merkle_proof: utxo_block.get_tx_merkle_proof(0),
+                conflict_index: 0,
+            }),
+            shard_num: 2,
+            shard_policy: String::from("modulo"),
+            genesis_roots: vec![],
+            block_size: config.block_size,
+            difficulty: config.difficulty.clone(),
+            thredshold: config.thredshold.clone(),
+            shard_diffs: vec![],
+            blocks: vec![
+                BundleBlock {
+                    shard_id: 0,
+                    header: VersaBlock::ExBlock(utxo_block.get_exclusive_block()),
+                    parent: None,
+                    in_longest_chain: true,
+                    confirmed: false,
+                },
+                BundleBlock {
+                    shard_id: 0,
+                    header: VersaBlock::ExBlock(spending_block.get_exclusive_block()),
+                    parent: Some(utxo_hash.clone()),
+                    in_longest_chain: true,
+                    confirmed: false,
+                },
+            ],
+        }
+    };
+
+    //the spent utxo proves the inflated input, but not the honest one
+    assert_eq!(new_bundle(&inflated).verify(), Ok(true));
+    assert_eq!(new_bundle(&tx).verify(), Ok(false));
+    let mut bundle = new_bundle(&inflated);
+    bundle.blocks[1].parent = None;
+    assert_eq!(bundle.verify(), Ok(false));
+}
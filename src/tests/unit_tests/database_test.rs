@@ -14,6 +14,7 @@ use crate::{
             Hashable,
         },
         key_pair,
+        address::Address,
     }
 };
 use ring::signature::{self, Ed25519KeyPair, Signature, KeyPair};
@@ -25,9 +26,9 @@ fn generate_random_datatypes()
 -> (ExclusiveFullBlock, ExclusiveBlock, InclusiveFullBlock, InclusiveBlock, Transaction, Testimony) 
 {
     //generate some users
-    let user2: H256 = (&[2u8; 32]).into();
-    let user3: H256 = (&[3u8; 32]).into();
-    let user4: H256 = (&[4u8; 32]).into();
+    let user2: Address = (&[2u8; 20]).into();
+    let user3: Address = (&[3u8; 20]).into();
+    let user4: Address = (&[4u8; 20]).into();
    
     let key2: Ed25519KeyPair = key_pair::random();
     let key3: Ed25519KeyPair = key_pair::random();
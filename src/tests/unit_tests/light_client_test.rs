@@ -0,0 +1,119 @@
+use crate::{
+    manifoldchain::{
+        block::{
+            Info,
+            versa_block::*,
+            exclusive_block::*,
+        },
+        transaction::*,
+        configuration::Configuration,
+        light_client::HeaderChain,
+    },
+    types::{
+        hash::{
+            H256,
+            Hashable,
+        },
+        merkle::MerkleTree,
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::Ed25519KeyPair;
+
+#[test]
+fn light_client_test_one() {
+    let mut config = Configuration::new();
+    config.difficulty = (&[255u8; 32]).into();
+    config.thredshold = (&[0u8; 32]).into();
+    config.block_size = 4;
+
+    let user: Address = (&[2u8; 20]).into();
+    let key: Ed25519KeyPair = key_pair::random();
+    let txs: Vec<Transaction> = (0..config.block_size)
+        .map(|i| Transaction::create_initial_tx((&user, &key), 10 + i as u64))
+        .collect();
+
+    let mut chain = HeaderChain::new(0, &config);
+    let genesis = ExclusiveBlock::genesis(0).hash();
+    let block_1 = VersaBlock::ExFullBlock(ExclusiveFullBlock::generate(
+        genesis,
+        0,
+        1,
+        config.difficulty,
+        txs.clone(),
+        vec![],
+        vec![genesis],
+        vec![],
+    ));
+    let block_2 = VersaBlock::ExFullBlock(ExclusiveFullBlock::generate(
+        block_1.hash(),
+        0,
+        2,
+        config.difficulty,
+        txs.clone(),
+        vec![],
+        vec![block_1.hash()],
+        vec![],
+    ));
+
+    //only the headers with a valid PoW are accepted
+    assert!(HeaderChain::check_header(&block_1, &config).is_err());
+    let header_1 = block_1.to_header();
+    let header_2 = block_2.to_header();
+    assert!(HeaderChain::check_header(&header_1, &config).is_ok());
+    let easy_block = VersaBlock::ExFullBlock(ExclusiveFullBlock::generate(
+        genesis,
+        0,
+        3,
+        (&[254u8; 32]).into(),
+        txs.clone(),
+        vec![],
+        vec![genesis],
+        vec![],
+    ));
+    assert!(HeaderChain::check_header(&easy_block.to_header(), &config).is_err());
+
+    //the parent must be known
+    assert_eq!(chain.insert(&header_2), Ok(false));
+    assert_eq!(chain.insert(&header_1), Ok(true));
+    assert_eq!(chain.insert(&header_1), Ok(false));
+    assert_eq!(chain.insert(&header_2), Ok(true));
+    assert_eq!(chain.tip(), (block_2.hash(), 2));
+    assert_eq!(chain.locator(), vec![block_2.hash(), block_1.hash(), genesis]);
+    assert!(chain.is_in_longest_chain(&block_1.hash()));
+    assert_eq!(chain.get_depth(&block_1.hash()), Some(1));
+
+    //the payment is proved against the synced header
+    let header = chain.get_header(&block_1.hash()).unwrap();
+    let proof = block_1.get_tx_merkle_proof(1).unwrap();
+    assert!(MerkleTree::<Transaction>::check_proof(
+        &header.get_tx_merkle_root(),
+        &txs[1].hash(),
+        &proof,
+        1,
+        config.block_size,
+    ));
+    assert!(!MerkleTree::<Transaction>::check_proof(
+        &header.get_tx_merkle_root(),
+        &txs[2].hash(),
+        &proof,
+        1,
+        config.block_size,
+    ));
+    //a malformed proof is rejected instead of panicking
+    assert!(!MerkleTree::<Transaction>::check_proof(
+        &header.get_tx_merkle_root(),
+        &txs[1].hash(),
+        &proof[1..],
+        1,
+        config.block_size,
+    ));
+    assert!(!MerkleTree::<Transaction>::check_proof(
+        &header.get_tx_merkle_root(),
+        &txs[1].hash(),
+        &proof,
+        config.block_size,
+        config.block_size,
+    ));
+}
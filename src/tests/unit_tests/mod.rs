@@ -5,4 +5,14 @@ pub mod hash_test;
 pub mod mempool_test;
 pub mod block_test;
 pub mod database_test;
+pub mod light_client_test;
+pub mod state_test;
+pub mod validation_test;
+pub mod audit_test;
+pub mod swap_test;
+pub mod sharding_test;
+pub mod reshard_test;
+pub mod rotation_test;
+pub mod worker_test;
 
+pub mod network_test;
@@ -0,0 +1,530 @@
+use crate::{
+    manifoldchain::{
+        network::{
+            handshake::*,
+            message::{self, Message, NUM_MESSAGE_TYPES},
+            request::RequestItem,
+            peer_score::{PeerScores, Misbehavior},
+            frame::{self, FRAME_HEADER_SIZE, MAX_FRAME_SIZE},
+            emulation::{EmulationConfig, NetworkEmulator},
+            identity::NodeIdentity,
+            secure::{self, Opener, SEALED_HEADER_SIZE, MAX_SEALED_SIZE},
+            peer::Direction,
+            cache::Cache,
+            stats::NetworkStats,
+            capture::{CaptureWriter, CaptureReader, CaptureRecord, CaptureDirection},
+            address_book::*,
+            connection_manager,
+        },
+        configuration::Configuration,
+    },
+    types::hash::H256,
+};
+use rand::Rng;
+use futures::{channel::mpsc, sink::SinkExt, stream::StreamExt};
+use smol::Executor;
+use smol::Async;
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant, SystemTime},
+};
+use super::worker_test::{start_node, wait_for};
+
+fn sample_handshake() -> Handshake {
+    Handshake {
+        magic: network_magic(1),
+        chain_id: (&[1u8; 32]).into(),
+        version: PROTOCOL_VERSION,
+        node_id: 0,
+        identity: H256::default(),
+        listen_addr: "127.0.0.1:6000".parse().unwrap(),
+        shard_id: 0,
+        tips: vec![(H256::default(), 0), (H256::default(), 0)],
+        capabilities: CAP_FULL_NODE | CAP_DA_SAMPLER,
+    }
+}
+
+#[test]
+fn handshake_test_one() {
+    let local = sample_handshake();
+    let mut remote = local.clone();
+    remote.node_id = 1;
+    remote.identity = (&[1u8; 32]).into();
+    remote.listen_addr = "127.0.0.1:6001".parse().unwrap();
+    remote.shard_id = 1;
+    assert!(local.check_compatible(&remote).is_ok());
+    //connecting to itself
+    assert!(local.check_compatible(&local).is_err());
+    let mut same_identity = remote.clone();
+    same_identity.identity = local.identity;
+    assert!(local.check_compatible(&same_identity).is_err());
+    //another experiment
+    let mut other_exper = remote.clone();
+    other_exper.magic = network_magic(2);
+    assert!(local.check_compatible(&other_exper).is_err());
+    //another chain
+    let mut other_chain = remote.clone();
+    other_chain.chain_id = (&[2u8; 32]).into();
+    assert!(local.check_compatible(&other_chain).is_err());
+    //unknown shard
+    let mut other_shard = remote.clone();
+    other_shard.shard_id = 2;
+    assert!(local.check_compatible(&other_shard).is_err());
+    //outdated protocol
+    let mut outdated = remote.clone();
+    outdated.version = MIN_PROTOCOL_VERSION - 1;
+    assert!(local.check_compatible(&outdated).is_err());
+}
+
+//run the handshake exchange between the two identities over a local tcp connection
+fn exchange_handshakes(
+    local: (&Handshake, &NodeIdentity),
+    remote: (&Handshake, &NodeIdentity),
+) -> (
+    std::io::Result<(Handshake, secure::Sealer, secure::Opener)>,
+    std::io::Result<(Handshake, secure::Sealer, secure::Opener)>,
+) {
+    let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+    let addr = listener.get_ref().local_addr().unwrap();
+    smol::block_on(async {
+        let outgoing = async {
+            let mut stream = Async::<TcpStream>::connect(addr).await.unwrap();
+            local.0.exchange(&mut stream, local.1, Direction::Outgoing).await
+        };
+        let incoming = async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            remote.0.exchange(&mut stream, remote.1, Direction::Incoming).await
+        };
+        futures::join!(outgoing, incoming)
+    })
+}
+
+#[test]
+fn handshake_test_two() {
+    let local_identity = NodeIdentity::random();
+    let remote_identity = NodeIdentity::random();
+    let mut local = sample_handshake();
+    local.identity = local_identity.peer_id();
+    let mut remote = local.clone();
+    remote.node_id = 1;
+    remote.identity = remote_identity.peer_id();
+    let (local_res, remote_res) = exchange_handshakes(
+        (&local, &local_identity),
+        (&remote, &remote_identity),
+    );
+    let (recv_remote, mut local_sealer, mut local_opener) = local_res.unwrap();
+    let (recv_local, mut remote_sealer, mut remote_opener) = remote_res.unwrap();
+    assert_eq!(recv_remote.identity, remote_identity.peer_id());
+    assert_eq!(recv_local.identity, local_identity.peer_id());
+    assert_eq!(recv_local.tips, local.tips);
+
+    //both directions have their own keys
+    let open = |opener: &mut secure::Opener, sealed: &[u8]| {
+        let header: [u8; SEALED_HEADER_SIZE] = sealed[0..SEALED_HEADER_SIZE].try_into().unwrap();
+        let mut body = sealed[SEALED_HEADER_SIZE..].to_vec();
+        opener.open(&header, &mut body)
+    };
+    let sealed = local_sealer.seal(b"ping").unwrap();
+    assert_eq!(open(&mut remote_opener, &sealed).unwrap(), b"ping".to_vec());
+    let sealed = remote_sealer.seal(b"pong").unwrap();
+    assert!(open(&mut remote_opener, &sealed).is_err());
+    assert_eq!(open(&mut local_opener, &sealed).unwrap(), b"pong".to_vec());
+    //a replayed or tampered frame fails to open
+    let sealed = local_sealer.seal(b"ping").unwrap();
+    let mut tampered = sealed.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(open(&mut remote_opener, &tampered).is_err());
+    assert!(open(&mut remote_opener, &sealed).is_ok());
+    assert!(open(&mut remote_opener, &sealed).is_err());
+    //an oversized frame is rejected before allocating its buffer
+    let oversized = (MAX_SEALED_SIZE + 1).to_be_bytes();
+    assert!(Opener::decode_size(&oversized, MAX_SEALED_SIZE).is_err());
+
+    //a handshake claiming an identity whose key it does not hold is rejected
+    let mut impostor = remote.clone();
+    impostor.identity = local_identity.peer_id();
+    let (local_res, _) = exchange_handshakes(
+        (&local, &local_identity),
+        (&impostor, &remote_identity),
+    );
+    assert!(local_res.is_err());
+}
+
+#[test]
+fn request_test_one() {
+    let h1: H256 = (&[1u8; 32]).into();
+    let h2: H256 = (&[2u8; 32]).into();
+    let request = Message::GetTransactions((vec![h1, h2], 3));
+    let items = RequestItem::from_request(&request);
+    assert_eq!(items, vec![
+        (RequestItem::Transaction(h1), Some(3)),
+        (RequestItem::Transaction(h2), Some(3)),
+    ]);
+    let items: Vec<RequestItem> = items.into_iter().map(|(item, _)| item).collect();
+    match RequestItem::into_requests(items, RequestItem::request_shard_id(&request)).as_slice() {
+        [Message::GetTransactions((hashs, shard_id))] => {
+            assert_eq!(*hashs, vec![h1, h2]);
+            assert_eq!(*shard_id, 3);
+        }
+        _ => panic!("request test failure"),
+    }
+    //samples are served by the shard of the sampled block
+    let samples = Message::GetSamples(vec![(h1, 0, 1), (h2, 5, 2)]);
+    assert_eq!(RequestItem::from_request(&samples), vec![
+        (RequestItem::Sample((h1, 0, 1)), Some(1)),
+        (RequestItem::Sample((h2, 5, 2)), Some(2)),
+    ]);
+    //inclusive blocks are served by any peer, but retried with the shard they are asked for
+    let in_blocks = Message::GetInBlocks((vec![h1], 2));
+    assert_eq!(RequestItem::from_request(&in_blocks), vec![(RequestItem::InBlock(h1), None)]);
+    match RequestItem::into_requests(vec![RequestItem::InBlock(h1)], RequestItem::request_shard_id(&in_blocks)).as_slice() {
+        [Message::GetInBlocks((hashs, shard_id))] => {
+            assert_eq!(*hashs, vec![h1]);
+            assert_eq!(*shard_id, 2);
+        }
+        _ => panic!("request test failure"),
+    }
+    //responses are not requests
+    assert!(RequestItem::from_request(&Message::Transactions((vec![], 0))).is_empty());
+}
+
+#[test]
+fn peer_score_test_one() {
+    let mut config = Configuration::new();
+    config.max_bans = 2;
+    config.max_unsolicited_txs = 10;
+    let scores = PeerScores::new_in_memory(&config);
+    let peer = NodeIdentity::random().peer_id();
+    let other = NodeIdentity::random().peer_id();
+    let addr: std::net::SocketAddr = "127.0.0.1:6001".parse().unwrap();
+    let new_addr: std::net::SocketAddr = "127.0.0.2:6003".parse().unwrap();
+    //small penalties do not ban the peer
+    assert!(!scores.penalize(peer, addr, Misbehavior::InvalidTransaction));
+    assert!(!scores.penalize(peer, addr, Misbehavior::InvalidBlock));
+    assert!(!scores.is_banned(&peer));
+    //the score follows the identity to another addr
+    assert!(scores.penalize(peer, new_addr, Misbehavior::InvalidFraudProof));
+    assert!(scores.is_banned(&peer));
+    assert!(scores.is_addr_banned(&new_addr));
+    assert!(!scores.is_addr_banned(&addr));
+    assert!(!scores.is_banned(&other));
+    assert_eq!(scores.get_bans()[0].until.is_some(), true);
+    //unsolicited transactions are tolerated until they flood
+    assert!(!scores.penalize(other, addr, Misbehavior::UnsolicitedTransactions(10)));
+    assert_eq!(scores.get_scores().iter().find(|s| s.identity == other).unwrap().score, 0);
+    assert!(!scores.penalize(other, addr, Misbehavior::UnsolicitedTransactions(1)));
+    assert_eq!(scores.get_scores().iter().find(|s| s.identity == other).unwrap().score, 20);
+    //the second ban is permanent
+    assert!(scores.unban(&peer));
+    assert!(!scores.is_banned(&peer));
+    scores.ban(peer, addr, String::from("test"), false);
+    scores.ban(peer, addr, String::from("test"), false);
+    assert!(scores.is_banned(&peer));
+    assert!(scores.get_bans()[0].until.is_none());
+}
+
+fn sample_messages() -> Vec<Message> {
+    let hash: H256 = (&[7u8; 32]).into();
+    vec![
+        Message::Ping(String::from("ping")),
+        Message::GetAddr,
+        Message::Addr(vec![("127.0.0.1:6000".parse().unwrap(), 1)]),
+        Message::GetTransactions((vec![hash, hash], 1)),
+        Message::GetSamples(vec![(hash, 3, 1)]),
+        Message::NewMissBlockHash((vec![hash], 0)),
+    ]
+}
+
+//decode the frame as the reader task and the worker do
+fn decode_frame(bytes: &[u8]) -> Result<Message, String> {
+    if bytes.len() < FRAME_HEADER_SIZE {
+        return Err(String::from("truncated header"));
+    }
+    let mut header_buffer = [0u8; FRAME_HEADER_SIZE];
+    header_buffer.copy_from_slice(&bytes[0..FRAME_HEADER_SIZE]);
+    let header = frame::decode_header(&header_buffer)?;
+    let body = &bytes[FRAME_HEADER_SIZE..];
+    frame::verify_checksum(&header, body)?;
+    frame::decode(body)
+}
+
+#[test]
+fn frame_test_one() {
+    for msg in sample_messages() {
+        let bytes = frame::encode(&msg).unwrap();
+        let decoded = decode_frame(&bytes).unwrap();
+        assert_eq!(decoded.type_id(), msg.type_id());
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", msg));
+        //every truncation fails
+        for len in 0..bytes.len() {
+            assert!(decode_frame(&bytes[0..len]).is_err());
+        }
+        //a wrong type prefix is rejected even if the checksum is right
+        let mut body = bytes[FRAME_HEADER_SIZE..].to_vec();
+        body[0] = (body[0] + 1) % NUM_MESSAGE_TYPES;
+        assert!(frame::decode(&body).is_err());
+        body[0] = 255;
+        assert!(frame::decode(&body).is_err());
+    }
+    //oversized and empty frames are rejected before reading the body
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    header[0..4].copy_from_slice(&(MAX_FRAME_SIZE + 1).to_be_bytes());
+    assert!(frame::decode_header(&header).is_err());
+    assert!(frame::decode_header(&[0u8; FRAME_HEADER_SIZE]).is_err());
+}
+
+#[test]
+fn frame_test_two() {
+    let mut rng = rand::thread_rng();
+    let msgs = sample_messages();
+    for _ in 0..2000 {
+        //random bit flips in a valid frame are caught by the checksum
+        let msg = &msgs[rng.gen_range(0..msgs.len())];
+        let mut bytes = frame::encode(msg).unwrap();
+        let i = rng.gen_range(FRAME_HEADER_SIZE..bytes.len());
+        bytes[i] ^= 1 << rng.gen_range(0..8);
+        assert!(decode_frame(&bytes).is_err());
+
+        //random bodies never panic, and a huge length inside the payload
+        //does not allocate unbounded memory
+        let len = rng.gen_range(0..64);
+        let mut body: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        let _ = frame::decode(&body);
+        if len > 8 {
+            body[0] = rng.gen_range(0..NUM_MESSAGE_TYPES);
+            for b in body[1..5].iter_mut() {
+                *b = 0;
+            }
+            for b in body[5..].iter_mut() {
+                *b = 0xff;
+            }
+            assert!(frame::decode(&body).is_err());
+        }
+    }
+}
+
+#[test]
+fn emulation_test_one() {
+    let mut config = Configuration::new();
+    config.set_shard_id(0);
+    config.node_id = 1;
+    config.shard_size = 2;
+    assert!(NetworkEmulator::new(&config).is_none());
+    config.emulation = Some(EmulationConfig {
+        propagation_delay: 100,
+        bandwidths: vec![vec![0, 80], vec![0, 0]], //10000 bytes per second for node 1
+        delays: vec![vec![50]],
+        drop_rates: vec![vec![0.0, 1.0]],
+    });
+    let emulator = NetworkEmulator::new(&config).unwrap();
+    assert_eq!(emulator.get_delay(0), Duration::from_millis(50));
+    assert_eq!(emulator.get_delay(1), Duration::from_millis(100));
+    assert_eq!(emulator.get_drop_rate(1), 1.0);
+
+    let ex = Executor::new();
+    smol::block_on(ex.run(async {
+        //the frames beyond the burst wait for the bandwidth, then all of them are delayed
+        let (mut sender, receiver) = mpsc::unbounded();
+        let mut shaped = emulator.shape(receiver, 0, &ex);
+        let start = Instant::now();
+        for i in 0..3u8 {
+            sender.send(vec![i; 1000]).await.unwrap();
+        }
+        for i in 0..3u8 {
+            assert_eq!(shaped.next().await.unwrap(), vec![i; 1000]);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(190));
+        drop(sender);
+        assert!(shaped.next().await.is_none());
+
+        //every frame to shard 1 is dropped
+        let (mut sender, receiver) = mpsc::unbounded();
+        let mut shaped = emulator.shape(receiver, 1, &ex);
+        sender.send(vec![0; 10]).await.unwrap();
+        drop(sender);
+        assert!(shaped.next().await.is_none());
+    }));
+}
+
+#[test]
+fn cache_test_one() {
+    let mut cache: Cache<u32, u32> = Cache::new("test", 3, None);
+    for i in 0..3 {
+        cache.insert(i, i);
+    }
+    //0 is used recently, so 1 is evicted
+    assert_eq!(cache.get(&0), Some(&0));
+    cache.insert(3, 3);
+    assert_eq!(cache.len(), 3);
+    assert!(cache.get(&1).is_none());
+    assert!(cache.contains_key(&0));
+    //replacing an entry evicts nothing
+    assert_eq!(cache.insert(2, 20), Some(2));
+    assert_eq!(cache.get(&2), Some(&20));
+    let stats = cache.stats();
+    assert_eq!(stats.size, 3);
+    assert_eq!((stats.hits, stats.misses, stats.evicted), (2, 1, 1));
+
+    let mut cache: Cache<u32, u32> = Cache::new("test", 10, Some(Duration::from_millis(50)));
+    cache.insert(0, 0);
+    assert!(cache.get(&0).is_some());
+    std::thread::sleep(Duration::from_millis(60));
+    cache.insert(1, 1);
+    assert!(cache.get(&0).is_none());
+    assert_eq!(cache.purge_expired(), 0);
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(cache.purge_expired(), 1);
+    assert_eq!(cache.len(), 0);
+    assert_eq!(cache.stats().expired, 2);
+}
+
+#[test]
+fn stats_test_one() {
+    let stats = NetworkStats::new(None);
+    let conn_addr: std::net::SocketAddr = "127.0.0.1:40000".parse().unwrap();
+    let listen_addr: std::net::SocketAddr = "127.0.0.1:6001".parse().unwrap();
+    stats.register_peer(conn_addr, listen_addr, 1);
+    let ping = frame::encode(&Message::Ping(String::from("ping"))).unwrap();
+    let body = &ping[FRAME_HEADER_SIZE..];
+    stats.record_in(&conn_addr, body);
+    stats.record_in(&conn_addr, body);
+    stats.record_out(&conn_addr, body);
+    stats.record_processed(&conn_addr, 0, Duration::from_micros(100));
+    stats.record_processed(&conn_addr, 0, Duration::from_micros(300));
+    let report = stats.get_report();
+    assert_eq!(report.total.len(), 1);
+    let ping_stats = &report.total[0];
+    assert_eq!(ping_stats.message, "Ping");
+    assert_eq!(ping_stats.stats.count_in, 2);
+    assert_eq!(ping_stats.stats.bytes_in, 2 * ping.len() as u64);
+    assert_eq!(ping_stats.stats.count_out, 1);
+    assert_eq!(ping_stats.stats.avg_latency, 200);
+    assert_eq!(ping_stats.stats.max_latency, 300);
+    //the peer is reported by its listening addr and shard
+    assert_eq!(report.by_shard[0].shard_id, 1);
+    assert_eq!(report.by_peer[0].peer, listen_addr);
+    //the names follow the type ids
+    for msg in sample_messages() {
+        assert_eq!(message::type_name(msg.type_id()), format!("{:?}", msg).split('(').next().unwrap());
+    }
+}
+
+#[test]
+fn capture_test_one() {
+    let path = std::env::temp_dir().join(format!("capture_test_{}.bin", rand::thread_rng().gen::<u32>()));
+    let path = path.to_str().unwrap();
+    let peer: std::net::SocketAddr = "127.0.0.1:40000".parse().unwrap();
+    let frames: Vec<Vec<u8>> = sample_messages()
+        .iter()
+        .map(|msg| frame::encode(msg).unwrap()[FRAME_HEADER_SIZE..].to_vec())
+        .collect();
+    let mut writer = CaptureWriter::create(path).unwrap();
+    for (i, body) in frames.iter().enumerate() {
+        let direction = match i % 2 {
+            0 => CaptureDirection::Inbound,
+            _ => CaptureDirection::Outbound,
+        };
+        writer.write(&CaptureRecord::new(direction, peer, body.clone())).unwrap();
+    }
+    drop(writer);
+    let mut reader = CaptureReader::open(path).unwrap();
+    for (i, body) in frames.iter().enumerate() {
+        let record = reader.read().unwrap().unwrap();
+        assert_eq!(record.peer, peer);
+        assert_eq!(record.direction == CaptureDirection::Inbound, i % 2 == 0);
+        assert_eq!(frame::decode(&record.body).unwrap().type_id(), body[0]);
+    }
+    assert!(reader.read().unwrap().is_none());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn address_book_test_one() {
+    let gossip = |host: u8, from: u16, num: u16, shard_id: usize| -> Vec<(SocketAddr, usize)> {
+        (from..from + num)
+            .map(|port| (SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 1, host)), port), shard_id))
+            .collect()
+    };
+    let source = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let mut book = AddressBook::new();
+
+    //one message and one source add a bounded number of addresses
+    assert_eq!(book.add_gossip(source, gossip(1, 0, 100, 0)), MAX_NEW_ADDR_PER_MSG);
+    for i in 1..10 {
+        book.add_gossip(source, gossip(1, i * 100, 100, 0));
+    }
+    assert_eq!(book.len(), MAX_ADDR_PER_SOURCE);
+
+    //a known address is not moved by gossip
+    let (known, _) = gossip(1, 0, 1, 0)[0];
+    assert_eq!(book.add_gossip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), vec![(known, 1)]), 0);
+    assert_eq!(book.get_shard(&known), Some(0));
+
+    //a shard is bounded even with many sources
+    for host in 0..20u8 {
+        let source = IpAddr::V4(Ipv4Addr::new(10, 0, 2, host));
+        book.add_gossip(source, gossip(host, 0, 32, 1));
+        book.add_gossip(source, gossip(host, 32, 32, 1));
+    }
+    assert_eq!(book.get_by_shard(1).len(), MAX_ADDR_PER_SHARD);
+
+    //a peer which finished the handshake takes the place of a gossiped address
+    let direct: SocketAddr = "10.0.3.1:6000".parse().unwrap();
+    assert!(book.add(direct, 1));
+    assert_eq!(book.get_by_shard(1).len(), MAX_ADDR_PER_SHARD);
+    assert_eq!(book.get_shard(&direct), Some(1));
+
+    //the gossiped addresses go stale, the direct one stays
+    let later = SystemTime::now() + Duration::from_secs(ADDR_TTL + 1);
+    assert_eq!(book.evict_stale(later), MAX_ADDR_PER_SOURCE + MAX_ADDR_PER_SHARD - 1);
+    assert_eq!(book.get_all(), vec![(direct, 1)]);
+}
+
+#[test]
+fn addr_test_one() {
+    let node = start_node(0, 0);
+    let peer = start_node(0, 1);
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| !node.server.get_peers_by_shard(0).is_empty()));
+
+    //a gossiped Addr adds a bounded number of addresses, of the known shards only
+    let host = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+    let addrs: Vec<(SocketAddr, u32)> = (0..100u16)
+        .map(|i| (SocketAddr::new(host, 7000 + i), if i % 2 == 0 { 1 } else { 5 }))
+        .collect();
+    peer.server.send(node.addr, Message::Addr(addrs));
+    assert!(wait_for(|| node.server.get_addrs().len() > 1));
+    let known = node.server.get_addrs();
+    assert_eq!(known.iter().filter(|(_, shard_id)| *shard_id == 1).count(), MAX_NEW_ADDR_PER_MSG);
+    assert!(known.iter().all(|(_, shard_id)| *shard_id < 2));
+    assert!(known.contains(&(peer.addr, 0)));
+
+    //GetAddr is answered with the address book
+    peer.server.send(node.addr, Message::GetAddr);
+    assert!(wait_for(|| peer.server.get_addrs().len() > 1));
+    let learned = peer.server.get_addrs();
+    assert!(learned.contains(&(node.addr, 0)));
+    assert!(learned.iter().all(|addr| known.contains(addr) || *addr == (node.addr, 0)));
+}
+
+#[test]
+fn connection_test_one() {
+    let node = start_node(1, 0);
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.set_shard_id(1);
+    config.target_peers = 3;
+    config.min_foreign_peers = 1;
+    let (manager, _) = connection_manager::new(&node.server, node.addr, &config);
+    let peer = |port: u16, shard_id: usize| (SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port), shard_id);
+
+    //the own shard is filled up to the target, every other one to the minimum
+    assert_eq!(manager.get_missing_peers(&vec![]), vec![(0, 1), (1, 3)]);
+    let connected = vec![peer(6000, 0), peer(6001, 0), peer(6002, 1)];
+    assert_eq!(manager.get_missing_peers(&connected), vec![(1, 2)]);
+
+    //the targets follow the node to its new shard
+    config.set_shard_id(0);
+    assert_eq!(manager.get_missing_peers(&connected), vec![(0, 1)]);
+}
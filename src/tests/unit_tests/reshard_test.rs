@@ -0,0 +1,178 @@
+use crate::{
+    manifoldchain::{
+        block::{
+            Info,
+            versa_block::VersaBlock,
+            exclusive_block::ExclusiveBlock,
+        },
+        blockchain::{
+            Blockchain,
+            State,
+            supply::Supply,
+        },
+        configuration::Configuration,
+        sharding::reshard::*,
+        testimony::Testimony,
+        transaction::*,
+    },
+    types::{
+        hash::{
+            H256,
+            Hashable,
+        },
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::Ed25519KeyPair;
+use std::collections::HashMap;
+
+#[test]
+fn reshard_test_one() {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.k = 2;
+
+    let plan = ReshardPlan::from_spec("20:3", &config).unwrap();
+    assert_eq!(plan, ReshardPlan {
+        height: 20,
+        shard_num: 3,
+        shard_policy: String::from("modulo"),
+        drain: 6,
+    });
+    assert!(ReshardPlan::from_spec("6:3", &config).is_err());
+    assert!(ReshardPlan::from_spec("20:0", &config).is_err());
+    assert!(ReshardPlan::from_spec("20:3:random", &config).is_err());
+    assert!(ReshardPlan::from_spec("20:3:ranges:0=0,4=1,8=2", &config).is_ok());
+
+    //the input-txs stop before the drain, the others at the boundary
+    let user: Address = (&[2u8; 20]).into();
+    let key: Ed25519KeyPair = key_pair::random();
+    let initial = Transaction::create_initial_tx((&user, &key), 10);
+    let mut input = initial.clone();
+    input.flag = TxFlag::Input;
+    let mut output = initial.clone();
+    output.flag = TxFlag::Output;
+    assert!(plan.allows(&input, 14));
+    assert!(!plan.allows(&input, 15));
+    assert!(plan.allows(&output, 20));
+    assert!(!plan.allows(&output, 21));
+    assert!(!plan.allows(&initial, 21));
+    let mut empty = Transaction::default();
+    empty.flag = TxFlag::Empty;
+    assert!(plan.allows(&empty, 21));
+
+    //the nodes of the old shards are dealt over the new ones
+    let mut counts = vec![0; 3];
+    for node_index in 0..3 {
+        for shard_id in 0..2 {
+            counts[ReshardPlan::reassign(shard_id, node_index, 2, 3)] += 1;
+        }
+    }
+    assert_eq!(counts, vec![2, 2, 2]);
+}
+
+#[test]
+fn reshard_test_two() {
+    //user0 in shard 0, user1 in shard 1 of 2, they both go to shard 2 and 0 of 3
+    let user0: Address = (&[2u8; 20]).into();
+    let key0: Ed25519KeyPair = key_pair::random();
+    let user1: Address = (&[3u8; 20]).into();
+    let key1: Ed25519KeyPair = key_pair::random();
+    let tx1 = Transaction::create_initial_tx((&user0, &key0), 10);
+    let tx2 = Transaction::create_initial_tx((&user1, &key1), 20);
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.k = 2;
+    let plan = ReshardPlan::from_spec("20:3", &config).unwrap();
+    config.reshard = Some(plan.clone());
+
+    //user0 pays 6 coins to user1 without an accept-tx yet, user1 is refunded
+    let pay = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![(&user0, &key0)],
+        vec![(&user1, &key1, 6), (&user0, &key0, 4)],
+        TxFlag::Input,
+    ).unwrap();
+    let mut pay_output = pay.clone();
+    pay_output.flag = TxFlag::Output;
+    let refund = Transaction::consume(
+        vec![(&tx2, 0)],
+        vec![(&user1, &key1)],
+        vec![(&user0, &key0, 20)],
+        TxFlag::Input,
+    ).unwrap();
+    let mut refund_reject = refund.clone();
+    refund_reject.flag = TxFlag::Reject;
+    let tmys: HashMap<H256, Testimony> = vec![&pay_output, &refund_reject]
+        .into_iter()
+        .map(|tx| (tx.hash(), Testimony::default()))
+        .collect();
+
+    let shard_txs = vec![
+        vec![tx1.clone(), pay.clone(), pay_output.clone()],
+        vec![tx2.clone(), pay_output.clone(), refund.clone(), refund_reject.clone()],
+    ];
+    let mut handoffs: Vec<StateHandoff> = vec![];
+    for shard_id in 0..2 {
+        let mut state: State = HashMap::new();
+        for tx in shard_txs[shard_id].iter() {
+            Blockchain::apply_tx(tx, &mut state, &tmys, shard_id, &config);
+        }
+        let root = Blockchain::get_state_tree(&state).root();
+        let header = VersaBlock::ExBlock(ExclusiveBlock::genesis_with_state(shard_id, &root));
+        let mut utxos: Vec<((H256, u32), Transaction)> = state
+            .into_iter()
+            .map(|(key, (tx, _))| (key, tx))
+            .collect();
+        utxos.sort_by(|a, b| a.0.cmp(&b.0));
+        handoffs.push(StateHandoff {
+            shard_id,
+            shard_num: 2,
+            shard_policy: String::from("modulo"),
+            plan: plan.clone(),
+            boundary: header.hash(),
+            header,
+            utxos,
+            pending: match shard_id {
+                0 => vec![pay.clone()],
+                _ => vec![],
+            },
+            delivered: vec![pay.get_related_hash(TxFlag::Input)],
+        });
+    }
+    assert!(handoffs.iter().all(|handoff| handoff.verify().is_ok()));
+
+    let checkpoints = Checkpoint::build(&handoffs).unwrap();
+    assert_eq!(checkpoints.len(), 3);
+    let values: Vec<u64> = checkpoints
+        .iter()
+        .map(|checkpoint| Supply::get_state_value(&checkpoint.get_state()).unwrap())
+        .collect();
+    assert_eq!(values, vec![26, 0, 4]);
+    assert!(checkpoints.iter().all(|checkpoint| checkpoint.verify().is_ok()));
+
+    //a payment still on its way, a forged utxo and a missing shard are refused
+    let mut in_flight = handoffs.clone();
+    in_flight[1].delivered.clear();
+    assert!(Checkpoint::build(&in_flight).is_err());
+    let mut forged = handoffs.clone();
+    forged[1].utxos.push(((tx1.hash(), 1), tx1.clone()));
+    assert!(Checkpoint::build(&forged).is_err());
+    assert!(Checkpoint::build(&handoffs[..1]).is_err());
+
+    //the node 1 of shard 1 moves to shard 0, which starts from its checkpoint
+    config.set_shard_id(1);
+    config.node_id = 1;
+    let state = Checkpoint::apply(&checkpoints, &mut config).unwrap();
+    assert_eq!(config.shard_id(), 0);
+    assert_eq!(config.shard_num, 3);
+    assert!(config.reshard.is_none());
+    assert_eq!(Supply::get_state_value(&state), Some(26));
+    assert_eq!(
+        ExclusiveBlock::genesis_of(0, &config).get_state_root(),
+        Blockchain::get_state_tree(&state).root()
+    );
+    assert!(ExclusiveBlock::genesis_of(1, &config).hash() != ExclusiveBlock::genesis(1).hash());
+    assert!(Checkpoint::apply(&checkpoints, &mut config).is_err());
+}
@@ -0,0 +1,75 @@
+use crate::{
+    manifoldchain::{
+        block::{
+            Info,
+            versa_block::VersaBlock,
+            exclusive_block::ExclusiveBlock,
+        },
+        blockchain::{
+            Blockchain,
+            State,
+        },
+        configuration::Configuration,
+        sharding::rotation::*,
+        transaction::*,
+    },
+    types::{
+        hash::{
+            H256,
+            Hashable,
+        },
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::Ed25519KeyPair;
+use std::collections::HashMap;
+
+#[test]
+fn rotation_test_one() {
+    //every shard keeps its size, the same beacon gives the same assignment
+    let beacon: H256 = (&[7u8; 32]).into();
+    let shards = Rotation::assign(&beacon, 12, 3);
+    assert_eq!(shards, Rotation::assign(&beacon, 12, 3));
+    for shard_id in 0..3 {
+        assert_eq!(shards.iter().filter(|x| **x == shard_id).count(), 4);
+    }
+    let moved = (1..10u8)
+        .map(|i| Rotation::assign(&(&[i; 32]).into(), 12, 3))
+        .filter(|other| *other != shards)
+        .count();
+    assert!(moved > 0);
+
+    //the copies of the configuration move together
+    let mut config = Configuration::new();
+    config.set_shard_id(1);
+    let copy = config.clone();
+    copy.role.begin_switch(2);
+    assert_eq!(config.shard_id(), 2);
+    assert!(config.role.is_syncing());
+    config.role.end_switch();
+    assert!(!copy.role.is_syncing());
+}
+
+#[test]
+fn rotation_test_two() {
+    let user: Address = (&[2u8; 20]).into();
+    let key: Ed25519KeyPair = key_pair::random();
+    let tx = Transaction::create_initial_tx((&user, &key), 10);
+    let mut state: State = HashMap::new();
+    state.insert((tx.hash(), 0), (tx.clone(), None));
+    let root = Blockchain::get_state_tree(&state).root();
+    let block = VersaBlock::ExBlock(ExclusiveBlock::genesis_with_state(1, &root));
+
+    //the snapshot holds against the state root of its block only
+    let snapshot = StateSnapshot::new(1, block.hash(), state.clone());
+    assert!(snapshot.verify(&block).is_ok());
+    assert_eq!(snapshot.get_state().len(), 1);
+    let mut forged = snapshot.clone();
+    forged.utxos[0].0.1 = 1;
+    assert!(forged.verify(&block).is_err());
+    let other = VersaBlock::ExBlock(ExclusiveBlock::genesis_with_state(0, &root));
+    let mut misplaced = snapshot.clone();
+    misplaced.block_hash = other.hash();
+    assert!(misplaced.verify(&other).is_err());
+}
@@ -0,0 +1,83 @@
+use crate::{
+    manifoldchain::{
+        configuration::Configuration,
+        sharding::*,
+        validator::Validator,
+    },
+    types::address::Address,
+};
+use rand::Rng;
+
+#[test]
+fn sharding_test_one() {
+    let addrs: Vec<Address> = (0..20u8).map(|i| (&[i; 20]).into()).collect();
+
+    //the default policy is the last bytes modulo the number of shards
+    let mut config = Configuration::new();
+    config.shard_num = 4;
+    for addr in addrs.iter() {
+        assert_eq!(Validator::get_shard_id(addr, &config), addr.get_shard_id(4));
+    }
+
+    //a new shard only takes addresses from the others
+    let consistent = ShardMap::from_spec("consistent", 4).unwrap();
+    for addr in addrs.iter() {
+        let before = consistent.get_shard_id(addr, 4);
+        let after = consistent.get_shard_id(addr, 5);
+        assert!(before < 4);
+        assert!(after == before || after == 4);
+    }
+
+    //the prefixes are left-aligned, every shard needs a range
+    let ranges = ShardMap::from_spec("ranges:0=0,08=1,1=2", 3).unwrap();
+    assert_eq!(ranges.get_shard_id(&addrs[0], 3), 0);
+    assert_eq!(ranges.get_shard_id(&addrs[8], 3), 1);
+    assert_eq!(ranges.get_shard_id(&addrs[15], 3), 1);
+    assert_eq!(ranges.get_shard_id(&addrs[16], 3), 2);
+    assert!(ShardMap::from_spec("ranges:08=1,1=0", 2).is_err());
+    assert!(ShardMap::from_spec("ranges:0=0,1=1", 3).is_err());
+    assert!(ShardMap::from_spec("ranges:0=0,1=3", 3).is_err());
+    assert!(ShardMap::from_spec("random", 3).is_err());
+}
+
+#[test]
+fn sharding_test_two() {
+    //4 groups of 5 addresses trading within the group, the modulo policy splits them
+    let addrs: Vec<Address> = (0..20u8).map(|i| (&[i; 20]).into()).collect();
+    let mut trades: Vec<(Address, Address, u64)> = vec![];
+    for group in addrs.chunks(5) {
+        for i in 0..group.len() {
+            for j in i + 1..group.len() {
+                trades.push((group[i], group[j], 10));
+            }
+        }
+    }
+    for g in 0..3 {
+        trades.push((addrs[g * 5], addrs[(g + 1) * 5], 1));
+    }
+
+    let table = TablePolicy::cluster(&trades, 2);
+    for group in addrs.chunks(5) {
+        assert!(group.iter().all(|addr| table[addr] == table[&group[0]]));
+    }
+    assert_eq!(table.values().filter(|shard_id| **shard_id == 0).count(), 10);
+
+    //the table goes through its file
+    let path = std::env::temp_dir().join(format!("sharding_test_{}.json", rand::thread_rng().gen::<u32>()));
+    let path = path.to_str().unwrap();
+    TablePolicy::save(&table, path).unwrap();
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    let modulo = ShardLoad::from_trades(&trades, &config);
+    config.shard_map = ShardMap::from_spec(&format!("table:{}", path), 2).unwrap();
+    assert!(ShardMap::from_spec(&format!("table:{}", path), 1).is_err());
+    std::fs::remove_file(path).unwrap();
+    let local = ShardLoad::from_trades(&trades, &config);
+    assert_eq!(modulo.domestic + modulo.cross, local.domestic + local.cross);
+    assert_eq!(local.cross, 1);
+    assert!(local.domestic_ratio > modulo.domestic_ratio);
+
+    //the addresses out of the table fall back to the modulo policy
+    let other: Address = (&[21u8; 20]).into();
+    assert_eq!(Validator::get_shard_id(&other, &config), other.get_shard_id(2));
+}
@@ -0,0 +1,202 @@
+use crate::{
+    manifoldchain::{
+        configuration::Configuration,
+        blockchain::{
+            Blockchain,
+            State,
+            supply::Supply,
+        },
+        testimony::Testimony,
+        transaction::*,
+    },
+    types::{
+        hash::{
+            H256,
+            Hashable,
+        },
+        sparse_merkle::SparseMerkleTree,
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::Ed25519KeyPair;
+use std::collections::HashMap;
+
+#[test]
+fn state_test_one() {
+    let leaves: Vec<(H256, H256)> = (0..20u8)
+        .map(|i| ((&[i; 32]).into(), H256::from(&[i; 32]).hash()))
+        .collect();
+    let tree = SparseMerkleTree::new(&leaves);
+    let root = tree.root();
+    assert_eq!(SparseMerkleTree::new(&[]).root(), SparseMerkleTree::empty_root());
+
+    //inclusion proofs
+    for (key, value) in leaves.iter() {
+        let proof = tree.proof(key);
+        assert!(proof.verify(&root, key, Some(value)));
+        assert!(!proof.verify(&root, key, Some(&key)));
+        assert!(!proof.verify(&root, key, None));
+    }
+    //non-inclusion proofs
+    for i in 20..40u8 {
+        let key: H256 = (&[i; 32]).into();
+        let proof = tree.proof(&key);
+        assert!(proof.verify(&root, &key, None));
+        assert!(!proof.verify(&root, &key, Some(&key)));
+        //the proof of another key does not prove the absence of the key
+        assert!(!tree.proof(&leaves[0].0).verify(&root, &key, None));
+    }
+
+    //updating the root with the proofs gives the root of the new tree
+    let mut current = leaves.clone();
+    let mut root = root;
+    let mut tree = tree;
+    let updates: Vec<(H256, Option<H256>)> = vec![
+        ((&[3u8; 32]).into(), None),
+        ((&[50u8; 32]).into(), Some((&[1u8; 32]).into())),
+        ((&[7u8; 32]).into(), Some((&[2u8; 32]).into())),
+        ((&[60u8; 32]).into(), None),
+        ((&[50u8; 32]).into(), None),
+    ];
+    for (key, value) in updates.iter() {
+        let proof = tree.proof(key);
+        assert!(proof.verify(&root, key, proof.get_value(key).as_ref()));
+        root = proof.update(key, value.as_ref()).unwrap();
+        tree.update(key, value.as_ref());
+        current.retain(|leaf| leaf.0 != *key);
+        if let Some(value) = value {
+            current.push((key.clone(), value.clone()));
+        }
+        assert_eq!(root, SparseMerkleTree::new(&current).root());
+        assert_eq!(root, tree.root());
+    }
+    //remove every leaf
+    while let Some((key, _)) = current.pop() {
+        let proof = SparseMerkleTree::new(&current)
+            .proof(&key);
+        let mut with_key = current.clone();
+        with_key.push((key.clone(), H256::default()));
+        let proof_before = SparseMerkleTree::new(&with_key).proof(&key);
+        assert!(proof.verify(&SparseMerkleTree::new(&current).root(), &key, None));
+        assert_eq!(
+            proof_before.update(&key, None),
+            Some(SparseMerkleTree::new(&current).root())
+        );
+    }
+}
+
+#[test]
+fn state_test_two() {
+    let user: Address = (&[2u8; 20]).into();
+    let key: Ed25519KeyPair = key_pair::random();
+    let tx1 = Transaction::create_initial_tx((&user, &key), 10);
+    let tx2 = Transaction::create_initial_tx((&user, &key), 20);
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+
+    //the state root follows the state built by the blockchain
+    let mut state: State = HashMap::new();
+    let txs = vec![tx1.clone(), tx2.clone()];
+    let state_root = Blockchain::get_next_state_root(&state, &txs, 0, &config);
+    for tx in txs.iter() {
+        Blockchain::apply_tx(tx, &mut state, &HashMap::new(), 0, &config);
+    }
+    assert_eq!(state.len(), 2);
+    let tree = Blockchain::get_state_tree(&state);
+    assert_eq!(tree.root(), state_root);
+
+    //the utxo of tx1 is in the state, the second output of tx1 is not
+    let utxo_key = Blockchain::get_utxo_key(&(tx1.hash(), 0));
+    let utxo_value = Blockchain::get_utxo_value(&tx1, 0);
+    assert!(tree.proof(&utxo_key).verify(&state_root, &utxo_key, Some(&utxo_value)));
+    let missing_key = Blockchain::get_utxo_key(&(tx1.hash(), 1));
+    assert!(tree.proof(&missing_key).verify(&state_root, &missing_key, None));
+
+    //the updates of the txs replayed with proofs
+    let mut root = Blockchain::get_state_tree(&HashMap::new()).root();
+    let mut leaves: Vec<(H256, H256)> = vec![];
+    for (key, value) in Blockchain::get_state_updates(&txs, 0, &config) {
+        let proof = SparseMerkleTree::new(&leaves).proof(&key);
+        root = proof.update(&key, value.as_ref()).unwrap();
+        leaves.push((key, value.unwrap()));
+    }
+    assert_eq!(root, state_root);
+}
+
+#[test]
+fn state_test_three() {
+    //user0 in shard 0, user1 in shard 1
+    let user0: Address = (&[2u8; 20]).into();
+    let key0: Ed25519KeyPair = key_pair::random();
+    let user1: Address = (&[3u8; 20]).into();
+    let key1: Ed25519KeyPair = key_pair::random();
+    let tx1 = Transaction::create_initial_tx((&user0, &key0), 10);
+    let tx2 = Transaction::create_initial_tx((&user1, &key1), 20);
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+
+    //user0 pays 6 coins to user1, user1 fails to pay 20 coins to user0
+    let pay = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![(&user0, &key0)],
+        vec![(&user1, &key1, 6), (&user0, &key0, 4)],
+        TxFlag::Input,
+    ).unwrap();
+    let mut pay_output = pay.clone();
+    pay_output.flag = TxFlag::Output;
+    let refund = Transaction::consume(
+        vec![(&tx2, 0)],
+        vec![(&user1, &key1)],
+        vec![(&user0, &key0, 20)],
+        TxFlag::Input,
+    ).unwrap();
+    let mut refund_reject = refund.clone();
+    refund_reject.flag = TxFlag::Reject;
+    let tmys: HashMap<H256, Testimony> = vec![&pay_output, &refund_reject]
+        .into_iter()
+        .map(|tx| (tx.hash(), Testimony::default()))
+        .collect();
+
+    let shard_txs = vec![
+        vec![tx1.clone(), pay.clone(), pay_output.clone()],
+        vec![tx2.clone(), pay_output.clone(), refund.clone(), refund_reject.clone()],
+    ];
+    let mut supplies: Vec<Supply> = vec![];
+    for shard_id in 0..2 {
+        let mut state: State = HashMap::new();
+        let mut supply = Supply::default();
+        for tx in shard_txs[shard_id].iter() {
+            Blockchain::apply_tx(tx, &mut state, &tmys, shard_id, &config);
+            supply.apply_tx(tx, shard_id, &config).unwrap();
+            assert!(supply.check_state(&state).is_ok());
+        }
+        supplies.push(supply);
+    }
+    assert_eq!(supplies[0], Supply { minted: 10, sent: 10, received: 4, refunded: 0, migrated: 0 });
+    assert_eq!(supplies[1], Supply { minted: 20, sent: 20, received: 6, refunded: 20, migrated: 0 });
+    assert_eq!(supplies[0].get_total(), Some(4));
+    assert_eq!(supplies[1].get_total(), Some(26));
+    assert_eq!(Supply::check_conservation(&supplies), Ok(0));
+
+    //coins still on the way, and coins which came from nowhere
+    let mut in_flight = supplies.clone();
+    in_flight[1].received = 0;
+    assert_eq!(Supply::check_conservation(&in_flight), Ok(6));
+    let mut forged = supplies.clone();
+    forged[0].received = 5;
+    assert!(Supply::check_conservation(&forged).is_err());
+    assert!(forged[0].check_state(&HashMap::new()).is_err());
+
+    //the sums of the values overflow
+    let mut overflow = tx1.clone();
+    overflow.flag = TxFlag::Domestic;
+    overflow.outputs.push(UtxoOutput {
+        value: u64::MAX,
+        ..Default::default()
+    });
+    assert_eq!(overflow.get_output_value(), None);
+    assert_eq!(tx1.get_output_value(), Some(10));
+    let mut supply = Supply { minted: u64::MAX, ..Default::default() };
+    assert!(supply.apply_tx(&tx1, 0, &config).is_err());
+}
@@ -0,0 +1,123 @@
+use crate::{
+    manifoldchain::{
+        blockchain::State,
+        configuration::Configuration,
+        swap::*,
+        transaction::{*, script::*},
+    },
+    types::{
+        hash::Hashable,
+        key_pair,
+        address::Address,
+    }
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::collections::HashMap;
+
+#[test]
+fn swap_test_one() {
+    //user_a in shard 0, user_b in shard 1
+    let user_a: Address = (&[2u8; 20]).into();
+    let user_b: Address = (&[3u8; 20]).into();
+    let key_a: Ed25519KeyPair = key_pair::random();
+    let key_b: Ed25519KeyPair = key_pair::random();
+    let pk_a = key_a.public_key().as_ref().to_vec();
+    let pk_b = key_b.public_key().as_ref().to_vec();
+    let secret = b"the secret of user_a".to_vec();
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+
+    let leg_a = SwapLeg {
+        sender: user_a.clone(),
+        sender_key: pk_a.clone(),
+        receiver: user_b.clone(),
+        receiver_key: pk_b.clone(),
+        value: 10,
+        timeout: 20,
+    };
+    let leg_b = SwapLeg {
+        sender: user_b.clone(),
+        sender_key: pk_b.clone(),
+        receiver: user_a.clone(),
+        receiver_key: pk_a.clone(),
+        value: 20,
+        timeout: 10,
+    };
+    //the leg of the responder has to time out first
+    assert!(Swap::new(Lock::hash_secret(&secret), leg_a.clone(), SwapLeg {
+        timeout: 20,
+        ..leg_b.clone()
+    }, &config).is_err());
+    //shard 1 grows twice as fast, so the leg locked in it needs twice the blocks
+    let mut fast_config = config.clone();
+    let mut slow_target = [0u8; 32];
+    slow_target[0] = 0x40;
+    let mut fast_target = [0u8; 32];
+    fast_target[0] = 0x80;
+    fast_config.shard_diffs = vec![slow_target.into(), fast_target.into()];
+    assert_eq!(scale_timeout(10, 0, 1, &fast_config), 20);
+    assert!(Swap::new(
+        Lock::hash_secret(&secret),
+        leg_a.clone(),
+        leg_b.clone(),
+        &fast_config
+    ).is_err());
+    assert!(Swap::new(
+        Lock::hash_secret(&secret),
+        SwapLeg { timeout: 40, ..leg_a.clone() },
+        leg_b.clone(),
+        &fast_config
+    ).is_ok());
+    let swap = Swap::new(Lock::hash_secret(&secret), leg_a, leg_b, &config).unwrap();
+
+    //both legs are locked in the shard of their receivers
+    let initial_a = Transaction::create_initial_tx((&user_a, &key_a), 15);
+    let initial_b = Transaction::create_initial_tx((&user_b, &key_b), 20);
+    let fund_a = swap.fund_tx(&swap.initiator, vec![(&initial_a, 0)], &key_a, &config).unwrap();
+    let fund_b = swap.fund_tx(&swap.responder, vec![(&initial_b, 0)], &key_b, &config).unwrap();
+    assert_eq!(fund_a.flag, TxFlag::Input);
+    assert_eq!(fund_a.outputs.len(), 2);
+    assert_eq!(fund_a.outputs[1].value, 5);
+    assert_eq!(fund_b.outputs.len(), 1);
+    assert!(swap.fund_tx(&swap.responder, vec![(&initial_a, 0)], &key_b, &config).is_none());
+    let locked_a = Swap::get_arrived_tx(&fund_a);
+    let locked_b = Swap::get_arrived_tx(&fund_b);
+    let mut state: State = HashMap::new();
+    state.insert((locked_b.hash(), 0), (locked_b.clone(), None));
+    state.insert((initial_a.hash(), 0), (initial_a.clone(), None));
+    assert_eq!(swap.find_funding(&swap.responder, &state), Some((locked_b.clone(), 0)));
+    assert_eq!(swap.find_funding(&swap.initiator, &state), None);
+
+    let verify = |signed_tx: &Transaction, key: &[u8], sig: &[u8]| {
+        Transaction::verify(signed_tx, key, sig)
+    };
+    let ctx = |age: u64| LockContext { height: 100, age };
+
+    //user_a claims in its shard, the claim takes the secret to the shard of user_b
+    let claim_a = swap.claim_tx(&swap.responder, (&locked_b, 0), &secret, &key_a, &config);
+    assert_eq!(claim_a.flag, TxFlag::Input);
+    assert_eq!(claim_a.outputs[1].receiver_addr, user_b);
+    assert!(Transaction::check_spend(&locked_b, &claim_a, &claim_a.inputs[0], &ctx(0), &verify));
+    let wrong_claim = swap.claim_tx(&swap.responder, (&locked_b, 0), b"wrong", &key_a, &config);
+    assert!(!Transaction::check_spend(&locked_b, &wrong_claim, &wrong_claim.inputs[0], &ctx(0), &verify));
+    let revealed = swap.find_secret(&Swap::get_arrived_tx(&claim_a)).unwrap();
+
+    //user_b claims with the revealed secret, only user_a refunds after the timeout
+    let claim_b = swap.claim_tx(&swap.initiator, (&locked_a, 0), &revealed, &key_b, &config);
+    assert!(Transaction::check_spend(&locked_a, &claim_b, &claim_b.inputs[0], &ctx(0), &verify));
+
+    //a claim seen in the mempool cannot be replayed with its witness to pay someone else
+    let mut stolen = claim_b.clone();
+    stolen.outputs[0].receiver_addr = user_a.clone();
+    stolen.outputs[0].public_key_ref = pk_a.clone();
+    assert!(!Transaction::check_spend(&locked_a, &stolen, &stolen.inputs[0], &ctx(0), &verify));
+    let mut replayed = swap.refund_tx(&swap.initiator, (&locked_a, 0), &key_a, &config);
+    replayed.inputs[0].witness = claim_b.inputs[0].witness.clone();
+    assert!(!Transaction::check_spend(&locked_a, &replayed, &replayed.inputs[0], &ctx(0), &verify));
+    let refund_a = swap.refund_tx(&swap.initiator, (&locked_a, 0), &key_a, &config);
+    assert_eq!(refund_a.flag, TxFlag::Input);
+    assert!(!Transaction::check_spend(&locked_a, &refund_a, &refund_a.inputs[0], &ctx(19), &verify));
+    assert!(Transaction::check_spend(&locked_a, &refund_a, &refund_a.inputs[0], &ctx(20), &verify));
+    let refund_b = swap.refund_tx(&swap.initiator, (&locked_a, 0), &key_b, &config);
+    assert!(!Transaction::check_spend(&locked_a, &refund_b, &refund_b.inputs[0], &ctx(20), &verify));
+}
@@ -1,10 +1,15 @@
 use rand::{self, Rng};
 use crate::{
     manifoldchain::{
-        transaction::*,
+        transaction::{
+            *,
+            script::*,
+        },
+        validator::sig_cache::SigCache,
     },
     types::{
         key_pair,
+        address::Address,
         hash::{Hashable, H256},
     }
 };
@@ -14,10 +19,10 @@ use ring::signature::{self, Ed25519KeyPair, Signature, KeyPair};
 #[test]
 
 fn transaction_test_one() {
-    let user1: H256 = (&[1u8; 32]).into();
-    let user2: H256 = (&[2u8; 32]).into();
-    let user3: H256 = (&[3u8; 32]).into();
-    let user4: H256 = (&[4u8; 32]).into();
+    let user1: Address = (&[1u8; 20]).into();
+    let user2: Address = (&[2u8; 20]).into();
+    let user3: Address = (&[3u8; 20]).into();
+    let user4: Address = (&[4u8; 20]).into();
 
     let key1: Ed25519KeyPair = key_pair::random();
     let key2: Ed25519KeyPair = key_pair::random();
@@ -89,3 +94,158 @@ fn transaction_test_one() {
     assert_eq!(tx3.outputs.len(), 3);
     assert_eq!(tx4.outputs.len(), 1);
 }
+
+#[test]
+fn transaction_test_two() {
+    let user1: Address = (&[1u8; 20]).into();
+    let user2: Address = (&[2u8; 20]).into();
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+
+    let ini_tx = Transaction::create_initial_tx((&user1, &key1), 10);
+    let tx = Transaction::consume(
+        vec![(&ini_tx, 0)],
+        vec![(&user1, &key1)],
+        vec![(&user2, &key2, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let public_key = ini_tx.outputs[0].public_key_ref.clone();
+    let sig = tx.inputs[0].sig_ref.clone();
+
+    //a valid signature is verified once, then found in the cache
+    let sig_cache = SigCache::new(16);
+    assert!(sig_cache.verify(&ini_tx, &public_key, &sig));
+    assert!(sig_cache.verify(&ini_tx, &public_key, &sig));
+    let stats = sig_cache.stats();
+    assert_eq!((stats.size, stats.hits, stats.misses), (1, 1, 1));
+
+    //an invalid one is never cached
+    let wrong_key = key2.public_key().as_ref().to_vec();
+    assert!(!sig_cache.verify(&ini_tx, &wrong_key, &sig));
+    assert!(!sig_cache.verify(&ini_tx, &wrong_key, &sig));
+    assert_eq!(sig_cache.stats().size, 1);
+}
+
+#[test]
+fn transaction_test_three() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+    let key3: Ed25519KeyPair = key_pair::random();
+    let pk = |key: &Ed25519KeyPair| key.public_key().as_ref().to_vec();
+
+    //user1 locks 10 coins with a 2-of-3 multisig, and 10 coins with a htlc to user2
+    let secret = b"swap secret".to_vec();
+    let multisig = Lock::Multisig {
+        threshold: 2,
+        keys: vec![pk(&key1), pk(&key2), pk(&key3)],
+    };
+    let htlc = Lock::htlc(&pk(&key2), Lock::hash_secret(&secret), &pk(&key1), 5);
+    let mut spent_tx = Transaction::create_initial_tx((&user1, &key1), 20);
+    spent_tx.outputs[0].value = 10;
+    spent_tx.outputs[0].lock = Some(multisig.clone());
+    spent_tx.outputs.push(UtxoOutput {
+        receiver_addr: user2.clone(),
+        value: 10,
+        public_key_ref: pk(&key2),
+        lock: Some(htlc.clone()),
+    });
+    //the lock is part of the hash of the output
+    let mut unlocked_tx = spent_tx.clone();
+    unlocked_tx.outputs[1].lock = None;
+    assert_ne!(unlocked_tx.hash(), spent_tx.hash());
+
+    //the spending tx pays the coins to user2, the witness signs it without the witness
+    let spend = |index: u32, witness: Witness| Transaction {
+        inputs: vec![UtxoInput {
+            sender_addr: spent_tx.outputs[index as usize].receiver_addr.clone(),
+            tx_hash: spent_tx.hash(),
+            value: 10,
+            index,
+            sig_ref: vec![],
+            witness: Some(witness),
+        }],
+        outputs: vec![UtxoOutput {
+            receiver_addr: user2.clone(),
+            value: 10,
+            public_key_ref: pk(&key2),
+            lock: None,
+        }],
+        flag: TxFlag::Domestic,
+    };
+    let sign = |index: u32, key: &Ed25519KeyPair| {
+        Transaction::sign(&spend(index, Witness::default()).get_unsigned(), key).as_ref().to_vec()
+    };
+    let verify = |signed_tx: &Transaction, public_key_ref: &[u8], sig_ref: &[u8]| {
+        Transaction::verify(signed_tx, public_key_ref, sig_ref)
+    };
+    let check = |tx: &Transaction, ctx: &LockContext| {
+        Transaction::check_spend(&spent_tx, tx, &tx.inputs[0], ctx, &verify)
+    };
+    let ctx = LockContext { height: 10, age: 2 };
+
+    //two signatures of the three keys, one key signing twice is not enough
+    let two_sigs = Witness { sigs: vec![sign(0, &key3), sign(0, &key1)], preimages: vec![] };
+    assert!(check(&spend(0, two_sigs), &ctx));
+    let same_sig = Witness { sigs: vec![sign(0, &key1), sign(0, &key1)], preimages: vec![] };
+    assert!(!check(&spend(0, same_sig), &ctx));
+
+    //user2 claims the htlc with the secret, user1 only takes it back after the timeout
+    let claim = Witness { sigs: vec![sign(1, &key2)], preimages: vec![secret.clone()] };
+    assert!(check(&spend(1, claim.clone()), &ctx));
+    let wrong_secret = Witness { sigs: vec![sign(1, &key2)], preimages: vec![b"guess".to_vec()] };
+    assert!(!check(&spend(1, wrong_secret), &ctx));
+    let refund = Witness { sigs: vec![sign(1, &key1)], preimages: vec![] };
+    assert!(!check(&spend(1, refund.clone()), &ctx));
+    let later = LockContext { height: 13, age: 5 };
+    assert!(check(&spend(1, refund), &later));
+
+    //the signatures are bound to the outputs of the spending tx
+    let mut redirected = spend(1, claim);
+    redirected.outputs[0].receiver_addr = user1.clone();
+    assert!(!check(&redirected, &ctx));
+
+    //an absolute timelock, and an output without lock still needs the signature
+    let after = Lock::All(vec![Lock::After(12), multisig.clone()]);
+    assert!(after.has_absolute_timelock() && !htlc.has_absolute_timelock());
+    let witness = Witness { sigs: vec![sign(0, &key1), sign(0, &key2)], preimages: vec![] };
+    let unsigned = spend(0, Witness::default()).get_unsigned();
+    let verify_unsigned = |public_key_ref: &[u8], sig_ref: &[u8]| {
+        Transaction::verify(&unsigned, public_key_ref, sig_ref)
+    };
+    assert!(!after.eval(&witness, &ctx, &verify_unsigned));
+    assert!(after.eval(&witness, &later, &verify_unsigned));
+    let mut plain = spend(0, Witness::default());
+    unlocked_tx.outputs[0].lock = None;
+    plain.inputs[0].sig_ref = Transaction::sign(&unlocked_tx, &key1).as_ref().to_vec();
+    assert!(Transaction::check_spend(&unlocked_tx, &plain, &plain.inputs[0], &ctx, &verify));
+
+    //the input names the receiver of the utxo, whose address is derived from its key
+    let mut forged_sender = plain.clone();
+    forged_sender.inputs[0].sender_addr = user2.clone();
+    assert!(!Transaction::check_spend(&unlocked_tx, &forged_sender, &forged_sender.inputs[0], &ctx, &verify));
+    let mut foreign_key = unlocked_tx.clone();
+    foreign_key.outputs[0].receiver_addr = (&[1u8; 20]).into();
+    let mut foreign_spend = plain.clone();
+    foreign_spend.inputs[0].sender_addr = foreign_key.outputs[0].receiver_addr.clone();
+    foreign_spend.inputs[0].sig_ref = Transaction::sign(&foreign_key, &key1).as_ref().to_vec();
+    assert!(!Transaction::check_spend(&foreign_key, &foreign_spend, &foreign_spend.inputs[0], &ctx, &verify));
+
+    //the size of a lock is bounded and its thresholds can be met
+    assert!(multisig.is_well_formed() && htlc.is_well_formed());
+    assert!(!Lock::Multisig { threshold: 4, keys: vec![pk(&key1); 3] }.is_well_formed());
+    assert!(!Lock::Multisig { threshold: 1, keys: vec![pk(&key1); MAX_LOCK_KEYS + 1] }.is_well_formed());
+    assert!(!Lock::Any(vec![]).is_well_formed());
+    let mut deep = Lock::Older(1);
+    for _ in 0..MAX_LOCK_DEPTH {
+        deep = Lock::All(vec![deep]);
+    }
+    assert!(!deep.is_well_formed());
+
+    //so is a witness
+    assert!(Witness { sigs: vec![sign(0, &key1); MAX_LOCK_KEYS], preimages: vec![secret.clone()] }.is_well_formed());
+    assert!(!Witness { sigs: vec![sign(0, &key1); MAX_LOCK_KEYS + 1], preimages: vec![] }.is_well_formed());
+    assert!(!Witness { sigs: vec![], preimages: vec![vec![0u8; MAX_WITNESS_PREIMAGE_LEN + 1]] }.is_well_formed());
+    assert!(!deep.eval(&Witness::default(), &later, &verify_unsigned));
+}
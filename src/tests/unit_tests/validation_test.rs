@@ -0,0 +1,67 @@
+use crate::{
+    manifoldchain::{
+        fraudproof::*,
+        transaction::Transaction,
+        validator::{
+            error::ValidationError,
+            stats::{ValidationStats, FROM_BLOCK, FROM_TRANSACTION},
+        },
+    },
+    types::hash::{H256, Hashable},
+};
+
+#[test]
+fn validation_test_one() {
+    let tx_hash: H256 = (&[1u8; 32]).into();
+    let fp = FraudProof::UtxoLost(UtxoLostProof {
+        shard_id: 0,
+        block_hash: H256::default(),
+        invalid_tx: Transaction::default(),
+        invalid_tx_merkle_proof: vec![],
+        invalid_index: 0,
+    });
+
+    //a provable fault carries the proof attached to it
+    let mut err = ValidationError::MissingInput {
+        tx_hash: tx_hash.clone(),
+        input_index: 1,
+        proof: None,
+    }.with_fraud_proof(fp.clone());
+    assert_eq!(err.fraud_proof(), Some(&fp));
+    if let Some(FraudProof::UtxoLost(proof)) = err.fraud_proof_mut() {
+        proof.invalid_index = 3;
+    }
+    assert_eq!(err.fraud_proof().unwrap().get_invalid_index(), 3);
+
+    //the others never get one
+    let known = ValidationError::KnownTx { tx_hash: tx_hash.clone() }
+        .with_fraud_proof(fp.clone());
+    assert!(known.fraud_proof().is_none());
+
+    //the cause of a wrong testimony is part of its message
+    let wrong_tmy = ValidationError::WrongTestimony {
+        tx_hash: tx_hash.clone(),
+        cause: Box::new(ValidationError::OriginNotConfirmed {
+            tx_hash: tx_hash.clone(),
+            block_hash: H256::default(),
+            shard_id: 2,
+        }),
+        proof: None,
+    };
+    assert!(wrong_tmy.to_string().contains("not confirmed in shard 2"));
+
+    let stats = ValidationStats::new();
+    stats.record(FROM_BLOCK, &err);
+    stats.record(FROM_TRANSACTION, &known);
+    stats.record(FROM_TRANSACTION, &known);
+    let report = stats.get_report();
+    let counts: Vec<(&str, &str, u64)> = report.errors
+        .iter()
+        .map(|count| (count.source, count.error, count.count))
+        .collect();
+    assert_eq!(counts, vec![("block", "missing_input", 1), ("transaction", "known_tx", 2)]);
+    assert_eq!(report.recent.len(), 3);
+    let fp_hash = err.fraud_proof().unwrap().hash();
+    assert_eq!(report.recent[0].fraud_proof, Some(fp_hash.to_string()));
+    assert_eq!(err.into_fraud_proof().unwrap().hash(), fp_hash);
+}
@@ -0,0 +1,350 @@
+use crate::{
+    manifoldchain::{
+        blockchain::Blockchain,
+        block::{exclusive_block::ExclusiveBlock, versa_block::VersaBlock},
+        configuration::Configuration,
+        confirmation::Confirmation,
+        fraudproof::*,
+        mempool::Mempool,
+        multichain::Multichain,
+        sharding::rotation::Rotation,
+        network::{
+            identity::{NodeIdentity, PeerId},
+            message::Message,
+            peer_score::{Misbehavior, PeerScores},
+            request::RequestTracker,
+            server::{self, Handle as ServerHandle},
+            stats::NetworkStats,
+            worker::{Worker, WorkerCaches},
+        },
+        transaction::{generator, Transaction, TxFlag},
+        validator::{Validator, stats::ValidationStats},
+    },
+    types::{
+        address::Address,
+        hash::{H256, Hashable},
+        key_pair,
+    },
+};
+use crossbeam::channel::Receiver;
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+//a full node with its p2p server and workers, but without miner or API
+pub(super) struct TestNode {
+    pub(super) addr: SocketAddr,
+    pub(super) identity: PeerId,
+    pub(super) multichain: Multichain,
+    pub(super) server: ServerHandle,
+    pub(super) peer_scores: PeerScores,
+    pub(super) caches: WorkerCaches,
+    pub(super) mempool: Arc<Mutex<Mempool>>,
+    _generator: Receiver<generator::ControlSignal>,
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+pub(super) fn start_node(shard_id: usize, node_id: usize) -> TestNode {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    //two nodes per shard, reassigned every block once confirmed
+    config.shard_size = 2;
+    config.rotation_epoch = 1;
+    config.set_shard_id(shard_id);
+    config.node_id = node_id;
+    start_node_with_config(config)
+}
+
+fn start_node_with_config(config: Configuration) -> TestNode {
+    let addr = free_addr();
+    let chains: Vec<Arc<Mutex<Blockchain>>> = (0..config.shard_num)
+        .map(|i| Arc::new(Mutex::new(Blockchain::new(&config, i))))
+        .collect();
+    let multichain = Multichain::create(chains.iter().collect(), &config);
+    let mempool = Arc::new(Mutex::new(Mempool::new()));
+    let confirmation = Arc::new(Mutex::new(Confirmation::new(&multichain, &config)));
+    let (generator_sender, generator_receiver) = generator::create_channel();
+    let peer_scores = PeerScores::new_in_memory(&config);
+    let identity = NodeIdentity::random();
+    let stats = NetworkStats::new(None);
+    let (msg_tx, msg_rx) = smol::channel::bounded(10000);
+    let (server_ctx, server) = server::new(
+        addr,
+        msg_tx,
+        &generator::new_handle(&generator_sender),
+        &multichain,
+        &config,
+        &peer_scores,
+        &identity,
+        &stats,
+    ).unwrap();
+    server_ctx.start().unwrap();
+    let requests = RequestTracker::new(&server, &config);
+    let caches = WorkerCaches::new(&config, &requests);
+    Worker::new(
+        1,
+        msg_rx,
+        &server,
+        &multichain,
+        &mempool,
+        &config,
+        &confirmation,
+        &requests,
+        &caches,
+        &stats,
+        &ValidationStats::new(),
+        None,
+        None,
+    ).start();
+    TestNode {
+        addr,
+        identity: identity.peer_id(),
+        multichain,
+        server,
+        peer_scores,
+        caches,
+        mempool,
+        _generator: generator_receiver,
+    }
+}
+
+//wait until the condition holds, false if it does not within the time
+pub(super) fn wait_for<F: Fn() -> bool>(cond: F) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if cond() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+//extend every shard of the node with empty blocks
+fn grow_chains(node: &TestNode, len: usize) {
+    let mut multichain = node.multichain.clone();
+    for shard_id in 0..2 {
+        for _ in 0..len {
+            let parent = multichain
+                .all_blocks_in_longest_chain_with_shard(shard_id)
+                .last()
+                .unwrap()
+                .clone();
+            let (block, _) = ExclusiveBlock::generate(
+                parent.clone(),
+                shard_id,
+                0,
+                H256::default(),
+                vec![],
+                vec![],
+                vec![parent.clone()],
+                vec![(vec![parent.clone()], shard_id)],
+            );
+            multichain
+                .insert_block_with_parent(VersaBlock::ExBlock(block), &parent, shard_id)
+                .unwrap();
+        }
+    }
+}
+
+//a proof blaming the block for a tx it does not contain
+fn forged_proof(block_hash: H256, invalid_index: u32) -> FraudProof {
+    FraudProof::UtxoLost(UtxoLostProof {
+        shard_id: 0,
+        block_hash,
+        invalid_tx: Transaction::default(),
+        invalid_tx_merkle_proof: vec![],
+        invalid_index,
+    })
+}
+
+#[test]
+fn worker_test_one() {
+    let node = start_node(0, 0);
+    let peer = start_node(0, 1);
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| !node.server.get_peers_by_shard(0).is_empty()));
+
+    //a forged proof about a known block is neither kept nor relayed
+    let genesis = node.multichain.all_blocks_in_longest_chain_with_shard(0)[0];
+    let forged = forged_proof(genesis, 0);
+    peer.server.send(node.addr, Message::FraudProofs(vec![forged.clone()]));
+    assert!(wait_for(|| !node.peer_scores.get_scores().is_empty()));
+    assert!(node.caches.get_fraud_proof(&forged.hash()).is_none());
+    assert!(node.caches.get_fraud_proof_hashes().is_empty());
+    let scores = node.peer_scores.get_scores();
+    assert_eq!(scores[0].identity, peer.identity);
+    assert_eq!(scores[0].addr, peer.addr);
+    assert_eq!(scores[0].last_misbehavior, Some(Misbehavior::InvalidFraudProof));
+    match node.multichain.get_block_by_shard(&genesis, 0) {
+        Some(VersaBlock::ExBlock(_)) => {}
+        _ => panic!("the genesis block is lost"),
+    }
+
+    //a proof about a block the node lacks is parked rather than judged
+    let unknown = forged_proof(H256::from([9u8; 32]), 0);
+    peer.server.send(node.addr, Message::FraudProofs(vec![unknown.clone()]));
+    assert!(wait_for(|| !node.caches.get_pending_fraud_proof_hashes().is_empty()));
+    assert_eq!(node.caches.get_pending_fraud_proof_hashes(), vec![unknown.hash()]);
+    assert!(node.caches.get_fraud_proof_hashes().is_empty());
+    assert_eq!(node.peer_scores.get_scores()[0].score, Misbehavior::InvalidFraudProof.penalty());
+}
+
+#[test]
+fn worker_test_two() {
+    let node = start_node(0, 0);
+    let peer = start_node(0, 1);
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| !node.server.get_peers_by_shard(0).is_empty()));
+
+    //every forged proof about a known block costs the sender, until it is banned
+    let genesis = node.multichain.all_blocks_in_longest_chain_with_shard(0)[0];
+    peer.server.send(node.addr, Message::FraudProofs(vec![forged_proof(genesis, 0)]));
+    assert!(wait_for(|| !node.peer_scores.get_scores().is_empty()));
+    assert!(!node.peer_scores.is_banned(&peer.identity));
+    peer.server.send(node.addr, Message::FraudProofs(vec![forged_proof(genesis, 1)]));
+    assert!(wait_for(|| node.peer_scores.is_banned(&peer.identity)));
+    assert!(wait_for(|| node.server.get_peers_by_shard(0).is_empty()));
+    assert!(node.caches.get_fraud_proof_hashes().is_empty());
+}
+
+#[test]
+fn worker_test_three() {
+    let node = start_node(0, 0);
+    let peer = start_node(0, 1);
+
+    //a peer stalling in the handshake holds neither the server nor the other peers
+    let silent = std::net::TcpStream::connect(node.addr).unwrap();
+    let silent_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let silent_addr = silent_listener.local_addr().unwrap();
+    let dialer = node.server.clone();
+    thread::spawn(move || dialer.connect(silent_addr));
+    let start = Instant::now();
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| node.server.get_peers_by_shard(0).len() == 1));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    drop(silent);
+}
+
+#[test]
+fn worker_test_four() {
+    let node = start_node(0, 0);
+    let peer = start_node(0, 1);
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| !node.server.get_peers_by_shard(0).is_empty()));
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    let (user, key) = loop {
+        let key = key_pair::random();
+        let user = Address::from_key_pair(&key);
+        if Validator::get_shard_id(&user, &config) == 0 {
+            break (user, key);
+        }
+    };
+
+    //a utxo missing from the chain of the node may be the node lagging behind, so
+    //neither it nor a known tx costs the peer
+    let ini_tx = Transaction::create_initial_tx((&user, &key), 10);
+    let tx = Transaction::consume(
+        vec![(&ini_tx, 0)],
+        vec![(&user, &key)],
+        vec![(&user, &key, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    peer.server.send(node.addr, Message::Transactions((vec![tx.clone()], 0)));
+    assert!(wait_for(|| node.mempool.lock().unwrap().check(&tx.hash())));
+    peer.server.send(node.addr, Message::Transactions((vec![tx.clone()], 0)));
+
+    //unequal coins are invalid on any chain
+    let mut unequal = tx.clone();
+    unequal.outputs[0].value = 11;
+    peer.server.send(node.addr, Message::Transactions((vec![unequal.clone()], 0)));
+    let score = || node.peer_scores.get_scores().iter().map(|status| status.score).sum::<u32>();
+    assert!(wait_for(|| score() > 0));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(score(), Misbehavior::InvalidTransaction.penalty());
+    assert!(!node.mempool.lock().unwrap().check(&unequal.hash()));
+}
+
+#[test]
+fn worker_test_five() {
+    let node = start_node(0, 0);
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.shard_size = 2;
+    config.rotation_epoch = 1;
+    //the first height of epoch 1 is confirmed in both shards
+    grow_chains(&node, config.k + 1);
+    let beacon = Rotation::get_beacon(&node.multichain, &config, 1).unwrap();
+    let shards = Rotation::assign(&beacon, 4, 2);
+    let stays = (1..4).find(|node_id| shards[*node_id] == 0).unwrap();
+    let moves = (1..4).find(|node_id| shards[*node_id] == 1).unwrap();
+    let peer_stays = start_node(0, stays);
+    let peer_moves = start_node(0, moves);
+    peer_stays.server.connect(node.addr).unwrap();
+    peer_moves.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| node.server.get_peers_by_shard(0).len() == 2));
+
+    //only the rotation the beacon assigns moves the peer
+    peer_stays.server.send(node.addr, Message::Rotate(1));
+    peer_moves.server.send(node.addr, Message::Rotate(1));
+    assert!(wait_for(|| node.server.get_peers_by_shard(1).len() == 1));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(node.server.get_peers_by_shard(0).len(), 1);
+    assert_eq!(node.server.get_peers_by_shard(1).len(), 1);
+}
+
+#[test]
+fn worker_test_six() {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.shard_size = 2;
+    config.rotation_epoch = 1;
+    config.max_unsolicited_txs = 2;
+    let node = start_node_with_config(config.clone());
+    let peer = start_node(0, 1);
+    peer.server.connect(node.addr).unwrap();
+    assert!(wait_for(|| !node.server.get_peers_by_shard(0).is_empty()));
+    let (user, key) = loop {
+        let key = key_pair::random();
+        let user = Address::from_key_pair(&key);
+        if Validator::get_shard_id(&user, &config) == 0 {
+            break (user, key);
+        }
+    };
+
+    //gossiping more new transactions than the limit is not a flood
+    let txs: Vec<Transaction> = (0..3)
+        .map(|value| {
+            let ini_tx = Transaction::create_initial_tx((&user, &key), value + 10);
+            Transaction::consume(
+                vec![(&ini_tx, 0)],
+                vec![(&user, &key)],
+                vec![(&user, &key, value + 10)],
+                TxFlag::Domestic,
+            ).unwrap()
+        })
+        .collect();
+    peer.server.send(node.addr, Message::Transactions((txs.clone(), 0)));
+    assert!(wait_for(|| {
+        let mempool = node.mempool.lock().unwrap();
+        txs.iter().all(|tx| mempool.check(&tx.hash()))
+    }));
+    thread::sleep(Duration::from_millis(200));
+    assert!(node.peer_scores.get_scores().is_empty());
+
+    //pushing known transactions beyond the limit is
+    peer.server.send(node.addr, Message::Transactions((txs.clone(), 0)));
+    assert!(wait_for(|| !node.peer_scores.get_scores().is_empty()));
+    let scores = node.peer_scores.get_scores();
+    assert_eq!(scores[0].identity, peer.identity);
+    assert_eq!(scores[0].last_misbehavior, Some(Misbehavior::TransactionFlood));
+    assert!(!node.peer_scores.is_banned(&peer.identity));
+}
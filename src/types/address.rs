@@ -1,9 +1,18 @@
 use serde::{Serialize, Deserialize};
 use std::convert::TryInto;
 use crate::types::hash::*;
+use ring::{
+    digest::{digest, SHA256},
+    signature::{Ed25519KeyPair, KeyPair},
+};
+
+//the prefix of the encoded addresses of the network, separated from the address
+//and its checksum by a '1'
+pub const ADDRESS_PREFIX: &str = "mfc";
+const CHECKSUM_SIZE: usize = 4;
 
 // 20-byte address
-#[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Hash, Default, Copy)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, Clone, Hash, Default, Copy)]
 pub struct Address([u8; 20]);
 
 impl std::convert::From<&[u8; 20]> for Address {
@@ -58,6 +67,73 @@ impl Address {
         let address: Address = std::convert::From::from(address_slice);
         address
     }
+
+    /// The address of the public key of a key pair
+    pub fn from_key_pair(key: &Ed25519KeyPair) -> Address {
+        let public_key: &[u8; 32] = key.public_key().as_ref().try_into().unwrap();
+        Self::from_public_key_bytes(public_key)
+    }
+
+    /// The shard of the address, given by its last bytes
+    pub fn get_shard_id(&self, shard_num: usize) -> usize {
+        //one u8 can represent 256 shards
+        let byte_size = shard_num / 256 + 1;
+        let mut value: usize = 0;
+        for i in 20-byte_size..20 {
+            value = value*256 + (self.0[i] as usize);
+        }
+        value % shard_num
+    }
+
+    /// The first bytes of the address, as used by the prefix ranges of the shard map
+    pub fn get_prefix(&self) -> u32 {
+        u32::from_be_bytes(self.0[..4].try_into().unwrap())
+    }
+
+    fn checksum(prefix: &str, bytes: &[u8; 20]) -> [u8; CHECKSUM_SIZE] {
+        let mut data = prefix.as_bytes().to_vec();
+        data.extend_from_slice(bytes);
+        digest(&SHA256, &data).as_ref()[..CHECKSUM_SIZE].try_into().unwrap()
+    }
+
+    /// The human-readable form of the address: the network prefix, then the address
+    /// and its checksum in hex
+    pub fn encode(&self) -> String {
+        format!(
+            "{}1{}{}",
+            ADDRESS_PREFIX,
+            hex::encode(self.0),
+            hex::encode(Self::checksum(ADDRESS_PREFIX, &self.0))
+        )
+    }
+}
+
+impl Hashable for Address {
+    fn hash(&self) -> H256 {
+        digest(&SHA256, &self.0).into()
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = String;
+
+    /// Parse an encoded address, checking its network prefix and its checksum
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = s
+            .strip_prefix(ADDRESS_PREFIX)
+            .and_then(|data| data.strip_prefix('1'))
+            .ok_or(format!("address {} does not start with {}1", s, ADDRESS_PREFIX))?;
+        let bytes = hex::decode(data)
+            .map_err(|e| format!("error decoding address {}: {}", s, e))?;
+        if bytes.len() != 20 + CHECKSUM_SIZE {
+            return Err(format!("address {} has a wrong length", s));
+        }
+        let address: [u8; 20] = bytes[..20].try_into().unwrap();
+        if bytes[20..] != Self::checksum(ADDRESS_PREFIX, &address) {
+            return Err(format!("address {} has a wrong checksum", s));
+        }
+        Ok(Address(address))
+    }
 }
 // DO NOT CHANGE THIS COMMENT, IT IS FOR AUTOGRADER. BEFORE TEST
 
@@ -75,6 +151,20 @@ mod test {
         // "0a0b0c0d0e0f0e0d0a0b0c0d0e0f0e0d0a0b0c0d0e0f0e0d0a0b0c0d0e0f0e0d"
         // take the last 20 bytes, we get "1851a0eae0060a132cf0f64a0ffaea248de6cba0"
     }
+
+    #[test]
+    fn encode_and_parse() {
+        let addr: Address = hex!("1851a0eae0060a132cf0f64a0ffaea248de6cba0").into();
+        let encoded = addr.encode();
+        assert!(encoded.starts_with("mfc11851a0ea"));
+        assert_eq!(encoded.parse::<Address>(), Ok(addr));
+        // a changed character breaks the checksum
+        let mut corrupted = encoded.clone().into_bytes();
+        corrupted[10] = if corrupted[10] == b'0' { b'1' } else { b'0' };
+        assert!(String::from_utf8(corrupted).unwrap().parse::<Address>().is_err());
+        assert!(encoded.replacen("mfc", "btc", 1).parse::<Address>().is_err());
+        assert!("mfc11851a0ea".parse::<Address>().is_err());
+    }
 }
 
 // DO NOT CHANGE THIS COMMENT, IT IS FOR AUTOGRADER. AFTER TEST
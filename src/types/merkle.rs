@@ -168,6 +168,35 @@ where T: Hashable + Clone,
         let con2: bool =  proof[proof_index] == *datum;
         con1 && con2
     }
+
+    /// The length of the proof of the datum at `index` in a tree of `leaf_size` leaves.
+    pub fn proof_len(index: usize, leaf_size: usize) -> usize {
+        if leaf_size <= 2 {
+            leaf_size
+        } else {
+            let mid: usize = leaf_size / 2;
+            if index < mid {
+                Self::proof_len(index, mid) + 1
+            } else {
+                Self::proof_len(index - mid, leaf_size - mid) + 1
+            }
+        }
+    }
+
+    /// Same as `verify`, but return false instead of panicking on a malformed proof,
+    /// for the proofs received from untrusted peers.
+    pub fn check_proof(
+        root: &H256,
+        datum: &H256,
+        proof: &[H256],
+        index: usize,
+        leaf_size: usize) -> bool
+    {
+        if index >= leaf_size || proof.len() != Self::proof_len(index, leaf_size) {
+            return false;
+        }
+        Self::verify(root, datum, proof, index, leaf_size)
+    }
 }
 //// DO NOT CHANGE THIS COMMENT, IT IS FOR AUTOGRADER. BEFORE TEST
 //
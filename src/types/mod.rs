@@ -1,4 +1,5 @@
 pub mod address;
 pub mod hash;
 pub mod merkle;
+pub mod sparse_merkle;
 pub mod key_pair;
@@ -0,0 +1,248 @@
+use super::hash::H256;
+use serde::{Serialize, Deserialize};
+
+/// A sparse Merkle tree over 256-bit keys. A subtree holding a single leaf is
+/// represented by the leaf itself, so the depth only grows with the number of leaves.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SparseMerkleTree {
+    //(key, value), sorted by key
+    leaves: Vec<(H256, H256)>,
+}
+
+/// The siblings on the path to a key, from the root down, and the leaf found at the
+/// end of the path, if any. The leaf is the key itself for an inclusion proof, and
+/// none or another key sharing the path for a non-inclusion proof.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
+pub struct SparseMerkleProof {
+    pub siblings: Vec<H256>,
+    pub leaf: Option<(H256, H256)>,
+    //the preimage of the last sibling of an inclusion proof, to tell whether the
+    //sibling takes the place of its parent once the key is removed
+    pub last_sibling: Option<ProofNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub enum ProofNode {
+    Leaf((H256, H256)),
+    Node((H256, H256)),
+}
+
+fn get_bit(key: &H256, depth: usize) -> bool {
+    (key.0[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+fn digest(prefix: u8, hash1: &H256, hash2: &H256) -> H256 {
+    let mut bytes: [u8; 65] = [0; 65];
+    bytes[0] = prefix;
+    bytes[1..33].copy_from_slice(&hash1.0);
+    bytes[33..65].copy_from_slice(&hash2.0);
+    ring::digest::digest(&ring::digest::SHA256, &bytes).into()
+}
+
+impl SparseMerkleTree {
+    pub fn new(leaves: &[(H256, H256)]) -> Self {
+        let mut leaves = leaves.to_vec();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        leaves.dedup_by(|a, b| a.0 == b.0);
+        SparseMerkleTree {
+            leaves
+        }
+    }
+
+    /// The root of an empty tree.
+    pub fn empty_root() -> H256 {
+        (&[0u8; 32]).into()
+    }
+
+    //leaves and inner nodes are hashed with different prefixes, so that a leaf can not
+    //be passed off as a subtree
+    pub fn leaf_hash(key: &H256, value: &H256) -> H256 {
+        digest(0, key, value)
+    }
+
+    pub fn node_hash(left: &H256, right: &H256) -> H256 {
+        digest(1, left, right)
+    }
+
+    pub fn root(&self) -> H256 {
+        Self::recursive_hash(&self.leaves, 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn get(&self, key: &H256) -> Option<H256> {
+        match self.leaves.binary_search_by(|leaf| leaf.0.cmp(key)) {
+            Ok(i) => Some(self.leaves[i].1.clone()),
+            Err(_) => None,
+        }
+    }
+
+    /// Set the key to the value, or remove the key if the value is none.
+    pub fn update(&mut self, key: &H256, value: Option<&H256>) {
+        match (self.leaves.binary_search_by(|leaf| leaf.0.cmp(key)), value) {
+            (Ok(i), Some(value)) => self.leaves[i].1 = value.clone(),
+            (Ok(i), None) => {
+                self.leaves.remove(i);
+            }
+            (Err(i), Some(value)) => self.leaves.insert(i, (key.clone(), value.clone())),
+            (Err(_), None) => {}
+        }
+    }
+
+    fn split(leaves: &[(H256, H256)], depth: usize) -> usize {
+        leaves.partition_point(|leaf| !get_bit(&leaf.0, depth))
+    }
+
+    fn recursive_hash(leaves: &[(H256, H256)], depth: usize) -> H256 {
+        match leaves.len() {
+            0 => Self::empty_root(),
+            1 => Self::leaf_hash(&leaves[0].0, &leaves[0].1),
+            _ => {
+                let mid = Self::split(leaves, depth);
+                let left = Self::recursive_hash(&leaves[..mid], depth + 1);
+                let right = Self::recursive_hash(&leaves[mid..], depth + 1);
+                Self::node_hash(&left, &right)
+            }
+        }
+    }
+
+    fn recursive_node(leaves: &[(H256, H256)], depth: usize) -> Option<ProofNode> {
+        match leaves.len() {
+            0 => None,
+            1 => Some(ProofNode::Leaf(leaves[0].clone())),
+            _ => {
+                let mid = Self::split(leaves, depth);
+                Some(ProofNode::Node((
+                    Self::recursive_hash(&leaves[..mid], depth + 1),
+                    Self::recursive_hash(&leaves[mid..], depth + 1),
+                )))
+            }
+        }
+    }
+
+    /// Returns the inclusion proof of the key if it is in the tree, and its
+    /// non-inclusion proof otherwise.
+    pub fn proof(&self, key: &H256) -> SparseMerkleProof {
+        let mut siblings: Vec<H256> = vec![];
+        let mut last_sibling: Option<ProofNode> = None;
+        let mut leaves: &[(H256, H256)] = &self.leaves;
+        let mut depth: usize = 0;
+        while leaves.len() > 1 {
+            let mid = Self::split(leaves, depth);
+            let sibling_leaves = match get_bit(key, depth) {
+                true => {
+                    let sibling_leaves = &leaves[..mid];
+                    leaves = &leaves[mid..];
+                    sibling_leaves
+                }
+                false => {
+                    let sibling_leaves = &leaves[mid..];
+                    leaves = &leaves[..mid];
+                    sibling_leaves
+                }
+            };
+            siblings.push(Self::recursive_hash(sibling_leaves, depth + 1));
+            last_sibling = Self::recursive_node(sibling_leaves, depth + 1);
+            depth += 1;
+        }
+        let leaf = leaves.first().cloned();
+        if leaf.as_ref().map(|leaf| leaf.0 != *key).unwrap_or(true) {
+            last_sibling = None;
+        }
+        SparseMerkleProof {
+            siblings,
+            leaf,
+            last_sibling,
+        }
+    }
+}
+
+impl SparseMerkleProof {
+    /// The value of the key claimed by the proof, none if it claims the key is absent.
+    pub fn get_value(&self, key: &H256) -> Option<H256> {
+        match &self.leaf {
+            Some((leaf_key, value)) if leaf_key == key => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Verify that the key holds the value in the tree of the root, or that the key is
+    /// absent if the value is none.
+    pub fn verify(&self, root: &H256, key: &H256, value: Option<&H256>) -> bool {
+        if self.siblings.len() > 256 || self.get_value(key).as_ref() != value {
+            return false;
+        }
+        //another key taking the place of the key must share its path
+        if let Some((leaf_key, _)) = &self.leaf {
+            if (0..self.siblings.len()).any(|d| get_bit(leaf_key, d) != get_bit(key, d)) {
+                return false;
+            }
+        }
+        self.compute_root(key) == *root
+    }
+
+    fn compute_root(&self, key: &H256) -> H256 {
+        let mut hash = match &self.leaf {
+            Some((leaf_key, leaf_value)) => SparseMerkleTree::leaf_hash(leaf_key, leaf_value),
+            None => SparseMerkleTree::empty_root(),
+        };
+        for depth in (0..self.siblings.len()).rev() {
+            hash = Self::combine(key, depth, &hash, &self.siblings[depth]);
+        }
+        hash
+    }
+
+    fn combine(key: &H256, depth: usize, hash: &H256, sibling: &H256) -> H256 {
+        match get_bit(key, depth) {
+            true => SparseMerkleTree::node_hash(sibling, hash),
+            false => SparseMerkleTree::node_hash(hash, sibling),
+        }
+    }
+
+    /// The root after setting the key to the value, or removing it if the value is none.
+    /// The proof must have been verified against the current root. Returns none if the
+    /// proof lacks the preimage of its last sibling to remove the key.
+    pub fn update(&self, key: &H256, value: Option<&H256>) -> Option<H256> {
+        let depth = self.siblings.len();
+        let empty_root = SparseMerkleTree::empty_root();
+        //the subtree at the end of the path after the update, and whether it is a leaf
+        let (mut hash, mut is_leaf) = match (&self.leaf, value) {
+            (Some((leaf_key, _)), None) if leaf_key == key => (empty_root.clone(), false),
+            (_, None) => return Some(self.compute_root(key)),
+            (Some((leaf_key, _)), Some(value)) if leaf_key == key => {
+                (SparseMerkleTree::leaf_hash(key, value), true)
+            }
+            (Some(leaf), Some(value)) => {
+                let tree = SparseMerkleTree::new(&[leaf.clone(), (key.clone(), value.clone())]);
+                (SparseMerkleTree::recursive_hash(&tree.leaves, depth), false)
+            }
+            (None, Some(value)) => (SparseMerkleTree::leaf_hash(key, value), true),
+        };
+        for d in (0..depth).rev() {
+            let sibling = &self.siblings[d];
+            if hash == empty_root && d + 1 == depth {
+                //the key is removed, a leaf sibling moves up
+                match &self.last_sibling {
+                    Some(ProofNode::Leaf((leaf_key, leaf_value)))
+                        if SparseMerkleTree::leaf_hash(leaf_key, leaf_value) == *sibling => {
+                        hash = sibling.clone();
+                        is_leaf = true;
+                    }
+                    Some(ProofNode::Node((left, right)))
+                        if SparseMerkleTree::node_hash(left, right) == *sibling => {
+                        hash = Self::combine(key, d, &hash, sibling);
+                    }
+                    _ => return None,
+                }
+            } else if is_leaf && *sibling == empty_root {
+                //a single leaf takes the place of its parent
+            } else {
+                hash = Self::combine(key, d, &hash, sibling);
+                is_leaf = false;
+            }
+        }
+        Some(hash)
+    }
+}